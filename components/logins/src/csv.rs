@@ -0,0 +1,377 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! # CSV import and export
+//!
+//! Browsers all offer to export saved logins as a CSV file, but they don't agree on the column
+//! names. This module knows about the common layouts:
+//!
+//! - Firefox: `url,username,password,httpRealm,formActionOrigin,guid,timeCreated,timeLastUsed,timePasswordChanged`
+//! - Chrome/Edge: `name,url,username,password,note`
+//! - Safari: `Title,URL,Username,Password,Notes,OTPAuth`
+//!
+//! Column names are matched case-insensitively and unknown columns are ignored. Only an origin
+//! column and a password column are required.
+//!
+//! The actual import, which runs the usual `LoginEntry` fixups and dupe checks, lives in
+//! `LoginDb::import_csv()`. We always export using the Firefox layout.
+
+use crate::error::*;
+use crate::login::{Login, LoginEntry};
+
+/// What happened to a single row during `LoginStore::import_csv()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CsvImportOutcome {
+    /// A new login was added.
+    Added { id: String },
+    /// The row matched an existing login with a different, older, password which was updated.
+    Merged { id: String },
+    /// The row matched an existing login which was left alone.
+    SkippedDuplicate { id: String },
+    /// The row could not be turned into a valid login.
+    Invalid { reason: String },
+}
+
+/// The outcome for a single CSV row. `row` is the 1-based line of the record in the CSV data,
+/// counting the header, so it matches what a user sees in a spreadsheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsvImportRowResult {
+    pub row: u32,
+    pub outcome: CsvImportOutcome,
+}
+
+/// Returned by `LoginStore::import_csv()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsvImportReport {
+    pub added: u32,
+    pub merged: u32,
+    pub skipped: u32,
+    pub invalid: u32,
+    pub rows: Vec<CsvImportRowResult>,
+}
+
+impl CsvImportReport {
+    pub(crate) fn push(&mut self, row: u32, outcome: CsvImportOutcome) {
+        match outcome {
+            CsvImportOutcome::Added { .. } => self.added += 1,
+            CsvImportOutcome::Merged { .. } => self.merged += 1,
+            CsvImportOutcome::SkippedDuplicate { .. } => self.skipped += 1,
+            CsvImportOutcome::Invalid { .. } => self.invalid += 1,
+        }
+        self.rows.push(CsvImportRowResult { row, outcome });
+    }
+}
+
+/// A login parsed from a CSV row, before any validation or fixup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CsvLogin {
+    pub entry: LoginEntry,
+    pub time_created: Option<i64>,
+    pub time_last_used: Option<i64>,
+    pub time_password_changed: Option<i64>,
+}
+
+/// A parsed data row: the row number (as in `CsvImportRowResult`), and either the login or the
+/// reason the row couldn't be read.
+pub(crate) type CsvRow = (u32, std::result::Result<CsvLogin, String>);
+
+const EXPORT_HEADER: &[&str] = &[
+    "url",
+    "username",
+    "password",
+    "httpRealm",
+    "formActionOrigin",
+    "guid",
+    "timeCreated",
+    "timeLastUsed",
+    "timePasswordChanged",
+];
+
+#[derive(Default)]
+struct ColumnMap {
+    origin: Option<usize>,
+    username: Option<usize>,
+    password: Option<usize>,
+    http_realm: Option<usize>,
+    form_action_origin: Option<usize>,
+    time_created: Option<usize>,
+    time_last_used: Option<usize>,
+    time_password_changed: Option<usize>,
+}
+
+impl ColumnMap {
+    fn from_header(header: &[String]) -> Result<Self> {
+        let mut map = ColumnMap::default();
+        for (index, name) in header.iter().enumerate() {
+            let slot = match name.trim().to_ascii_lowercase().as_str() {
+                "url" | "origin" | "hostname" | "login_uri" => &mut map.origin,
+                "username" | "login_username" => &mut map.username,
+                "password" | "login_password" => &mut map.password,
+                "httprealm" | "http_realm" | "realm" => &mut map.http_realm,
+                "formactionorigin" | "form_action_origin" | "formsubmiturl" => {
+                    &mut map.form_action_origin
+                }
+                "timecreated" | "time_created" => &mut map.time_created,
+                "timelastused" | "time_last_used" => &mut map.time_last_used,
+                "timepasswordchanged" | "time_password_changed" => &mut map.time_password_changed,
+                _ => continue,
+            };
+            // If a column appears twice, the first one wins.
+            slot.get_or_insert(index);
+        }
+        if map.origin.is_none() {
+            return Err(Error::InvalidCsv("missing a url/origin column".into()));
+        }
+        if map.password.is_none() {
+            return Err(Error::InvalidCsv("missing a password column".into()));
+        }
+        Ok(map)
+    }
+
+    fn login_from_record(&self, record: &[String]) -> std::result::Result<CsvLogin, String> {
+        let get = |index: Option<usize>| {
+            index
+                .and_then(|i| record.get(i))
+                .map(String::as_str)
+                .filter(|s| !s.is_empty())
+        };
+        let get_time = |index: Option<usize>| -> std::result::Result<Option<i64>, String> {
+            get(index)
+                .map(|s| {
+                    s.trim()
+                        .parse::<i64>()
+                        .map_err(|_| "invalid timestamp".to_string())
+                })
+                .transpose()
+        };
+        let http_realm = get(self.http_realm).map(str::to_string);
+        let form_action_origin = match (get(self.form_action_origin), &http_realm) {
+            (Some(form_action_origin), _) => Some(form_action_origin.to_string()),
+            // Most exports (everything other than Firefox) don't have either target column, so
+            // treat those as form logins with an unknown form action origin. This matches
+            // Desktop's CSV importer.
+            (None, None) => Some(String::new()),
+            (None, Some(_)) => None,
+        };
+        Ok(CsvLogin {
+            entry: LoginEntry {
+                origin: get(self.origin).unwrap_or_default().to_string(),
+                form_action_origin,
+                http_realm,
+                username: get(self.username).unwrap_or_default().to_string(),
+                password: get(self.password).unwrap_or_default().to_string(),
+                ..Default::default()
+            },
+            time_created: get_time(self.time_created)?,
+            time_last_used: get_time(self.time_last_used)?,
+            time_password_changed: get_time(self.time_password_changed)?,
+        })
+    }
+}
+
+/// Parse CSV data into the logins it contains. The first record must be a header.
+///
+/// Errors are only returned for problems with the data as a whole (eg, no header, or no password
+/// column). Problems with individual rows are returned in the `CsvRow`.
+pub(crate) fn parse_logins_csv(data: &str) -> Result<Vec<CsvRow>> {
+    let mut records = parse_records(data)?.into_iter();
+    let (_, header) = records
+        .next()
+        .ok_or_else(|| Error::InvalidCsv("no header row".into()))?;
+    let columns = ColumnMap::from_header(&header)?;
+    Ok(records
+        .filter(|(_, record)| !(record.len() == 1 && record[0].is_empty()))
+        .map(|(row, record)| {
+            let result = if record.len() != header.len() {
+                Err(format!(
+                    "expected {} fields, found {}",
+                    header.len(),
+                    record.len()
+                ))
+            } else {
+                columns.login_from_record(&record)
+            };
+            (row, result)
+        })
+        .collect())
+}
+
+/// Serialize logins using the Firefox CSV layout.
+pub(crate) fn logins_to_csv(logins: &[Login]) -> String {
+    let mut out = String::new();
+    write_record(&mut out, EXPORT_HEADER.iter().copied());
+    for login in logins {
+        let time_created = login.time_created.to_string();
+        let time_last_used = login.time_last_used.to_string();
+        let time_password_changed = login.time_password_changed.to_string();
+        write_record(
+            &mut out,
+            [
+                login.origin.as_str(),
+                login.username.as_str(),
+                login.password.as_str(),
+                login.http_realm.as_deref().unwrap_or_default(),
+                login.form_action_origin.as_deref().unwrap_or_default(),
+                login.id.as_str(),
+                time_created.as_str(),
+                time_last_used.as_str(),
+                time_password_changed.as_str(),
+            ],
+        );
+    }
+    out
+}
+
+fn write_record<'a>(out: &mut String, fields: impl IntoIterator<Item = &'a str>) {
+    for (i, field) in fields.into_iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if field.contains(['"', ',', '\n', '\r']) || field.starts_with(' ') {
+            out.push('"');
+            out.push_str(&field.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(field);
+        }
+    }
+    out.push_str("\r\n");
+}
+
+/// A minimal RFC 4180 reader. Returns each record along with the 1-based line it starts on.
+fn parse_records(data: &str) -> Result<Vec<(u32, Vec<String>)>> {
+    let data = data.strip_prefix('\u{feff}').unwrap_or(data);
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = data.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\n' || (c == '\r' && chars.peek() != Some(&'\n')) {
+            line += 1;
+        }
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => (),
+            '\r' | '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push((record_line, std::mem::take(&mut record)));
+                record_line = line;
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(Error::InvalidCsv(format!(
+            "unterminated quoted field starting on line {record_line}"
+        )));
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((record_line, record));
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_records() {
+        let records = parse_records(
+            "\u{feff}a,b,c\r\n\"quoted, comma\",\"with \"\"quotes\"\"\",\"multi\nline\"\nlast,,\n",
+        )
+        .unwrap();
+        assert_eq!(
+            records,
+            vec![
+                (1, strings(&["a", "b", "c"])),
+                (
+                    2,
+                    strings(&["quoted, comma", "with \"quotes\"", "multi\nline"])
+                ),
+                (4, strings(&["last", "", ""])),
+            ]
+        );
+        assert!(matches!(
+            parse_records("a,\"b\n"),
+            Err(Error::InvalidCsv(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_chrome_layout() {
+        let rows = parse_logins_csv(
+            "name,url,username,password,note\n\
+             example.com,https://example.com/login,alice,hunter2,\n\
+             bad row,https://example.com\n",
+        )
+        .unwrap();
+        assert_eq!(rows.len(), 2);
+        let (row, login) = &rows[0];
+        assert_eq!(*row, 2);
+        let login = login.as_ref().unwrap();
+        assert_eq!(login.entry.origin, "https://example.com/login");
+        assert_eq!(login.entry.username, "alice");
+        assert_eq!(login.entry.password, "hunter2");
+        assert_eq!(login.entry.form_action_origin, Some("".into()));
+        assert_eq!(login.entry.http_realm, None);
+        assert_eq!(login.time_created, None);
+        assert_eq!(rows[1].0, 3);
+        assert!(rows[1].1.is_err());
+    }
+
+    #[test]
+    fn test_missing_columns() {
+        assert!(matches!(
+            parse_logins_csv("url,username\nhttps://example.com,alice\n"),
+            Err(Error::InvalidCsv(_))
+        ));
+        assert!(matches!(parse_logins_csv(""), Err(Error::InvalidCsv(_))));
+    }
+
+    #[test]
+    fn test_export_roundtrip() {
+        let login = Login {
+            id: "guid".into(),
+            origin: "https://example.com".into(),
+            http_realm: Some("Realm, \"quoted\"".into()),
+            username: "alice".into(),
+            password: "p,w\"d".into(),
+            time_created: 1000,
+            time_last_used: 2000,
+            time_password_changed: 3000,
+            ..Default::default()
+        };
+        let csv = logins_to_csv(&[login]);
+        let rows = parse_logins_csv(&csv).unwrap();
+        let parsed = rows[0].1.as_ref().unwrap();
+        assert_eq!(parsed.entry.origin, "https://example.com");
+        assert_eq!(parsed.entry.http_realm, Some("Realm, \"quoted\"".into()));
+        assert_eq!(parsed.entry.form_action_origin, None);
+        assert_eq!(parsed.entry.password, "p,w\"d");
+        assert_eq!(parsed.time_created, Some(1000));
+        assert_eq!(parsed.time_last_used, Some(2000));
+        assert_eq!(parsed.time_password_changed, Some(3000));
+    }
+}
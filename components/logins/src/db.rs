@@ -22,9 +22,12 @@
 ///     server.
 ///   - After we sync, we move all records from loginsL to loginsM, overwriting any previous data.
 ///     loginsL will be an empty table after this.  See mark_as_synchronized() for the details.
-use crate::encryption::EncryptorDecryptor;
+use crate::csv::{self, CsvImportOutcome, CsvImportReport, CsvLogin};
+use crate::encryption::{check_encdec_canary, create_encdec_canary, EncryptorDecryptor};
 use crate::error::*;
 use crate::login::*;
+use crate::matching::{FormQuery, LoginFormMatch};
+use crate::password_health::PasswordHealthReport;
 use crate::schema;
 use crate::sync::SyncStatus;
 use crate::util;
@@ -36,6 +39,7 @@ use rusqlite::{
     Connection,
 };
 use sql_support::ConnExt;
+use std::collections::HashSet;
 use std::ops::Deref;
 use std::path::Path;
 use std::sync::Arc;
@@ -54,6 +58,21 @@ pub struct LoginsDeletionMetrics {
     pub mirror_deleted: u64,
 }
 
+/// A previous password of a login, returned by `get_password_history()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswordHistoryEntry {
+    pub password: String,
+    /// When this password was replaced, in milliseconds since the epoch.
+    pub time_changed: i64,
+}
+
+/// A login in the trash, returned by `list_deleted()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeletedLogin {
+    pub login: Login,
+    pub time_deleted: i64,
+}
+
 impl LoginDb {
     pub fn with_connection(db: Connection, encdec: Arc<dyn EncryptorDecryptor>) -> Result<Self> {
         #[cfg(test)]
@@ -245,6 +264,44 @@ impl LoginDb {
         rows.collect::<Result<_>>()
     }
 
+    /// Find the logins which could fill a form (or HTTP auth prompt if `http_realm` is given) on
+    /// `origin`, best match first. See the `matching` module for the ranking rules.
+    pub fn find_logins_for_form(
+        &self,
+        origin: &str,
+        form_action_origin: Option<&str>,
+        http_realm: Option<&str>,
+    ) -> Result<Vec<LoginFormMatch>> {
+        let Some(query) = FormQuery::new(origin, form_action_origin, http_realm)? else {
+            return Ok(vec![]);
+        };
+        // A linear scan, for the same reasons as `get_by_base_domain()`.
+        let mut scored = self
+            .get_all()?
+            .into_iter()
+            .filter_map(|login| {
+                query
+                    .score(&login)
+                    .map(|(origin_match, score)| (login, origin_match, score))
+            })
+            .collect::<Vec<_>>();
+        scored.sort_by(|(a, _, a_score), (b, _, b_score)| {
+            b_score
+                .cmp(a_score)
+                .then_with(|| b.meta.time_last_used.cmp(&a.meta.time_last_used))
+        });
+        scored
+            .into_iter()
+            .map(|(login, origin_match, score)| {
+                Ok(LoginFormMatch {
+                    login: login.decrypt(self.encdec.as_ref())?,
+                    origin_match,
+                    score,
+                })
+            })
+            .collect()
+    }
+
     pub fn get_by_id(&self, id: &str) -> Result<Option<EncryptedLogin>> {
         self.try_query_row(
             &GET_BY_GUID_SQL,
@@ -392,13 +449,31 @@ impl LoginDb {
         }
 
         // Load and decrypt all breached passwords once
+        let breached_passwords = self.get_potentially_vulnerable_passwords()?;
+
+        // Check each login against the breached passwords set
+        let mut vulnerable_guids = Vec::new();
+        for guid in guids {
+            if let Some(login) = self.get_by_id(guid)? {
+                let decrypted_login = login.decrypt(self.encdec.as_ref())?;
+                if breached_passwords.contains(&decrypted_login.password) {
+                    vulnerable_guids.push(guid.to_string());
+                }
+            }
+        }
+
+        Ok(vulnerable_guids)
+    }
+
+    /// Loads and decrypts every password in the breach database.
+    fn get_potentially_vulnerable_passwords(&self) -> Result<HashSet<String>> {
         let all_encrypted_passwords: Vec<String> = self.db.query_rows_and_then_cached(
             "SELECT encryptedPassword FROM breachesL",
             [],
             |row| row.get(0),
         )?;
 
-        let mut breached_passwords = std::collections::HashSet::new();
+        let mut breached_passwords = HashSet::new();
         for ciphertext in &all_encrypted_passwords {
             let decrypted_bytes =
                 self.encdec
@@ -419,19 +494,7 @@ impl LoginDb {
 
             breached_passwords.insert(decrypted_password.to_string());
         }
-
-        // Check each login against the breached passwords set
-        let mut vulnerable_guids = Vec::new();
-        for guid in guids {
-            if let Some(login) = self.get_by_id(guid)? {
-                let decrypted_login = login.decrypt(self.encdec.as_ref())?;
-                if breached_passwords.contains(&decrypted_login.password) {
-                    vulnerable_guids.push(guid.to_string());
-                }
-            }
-        }
-
-        Ok(vulnerable_guids)
+        Ok(breached_passwords)
     }
 
     pub fn is_potentially_vulnerable_password(&self, guid: &str) -> Result<bool> {
@@ -440,6 +503,20 @@ impl LoginDb {
         Ok(!vulnerable.is_empty())
     }
 
+    /// Checks every login's password for reuse across origins, weakness, and presence in the
+    /// breach database. See the `password_health` module for details.
+    pub fn password_health_report(&self) -> Result<PasswordHealthReport> {
+        let logins = self
+            .get_all()?
+            .into_iter()
+            // The FxA session-credentials login isn't a user credential.
+            .filter(|login| login.fields.origin != FXA_CREDENTIALS_ORIGIN)
+            .map(|login| login.decrypt(self.encdec.as_ref()))
+            .collect::<Result<Vec<Login>>>()?;
+        let vulnerable_passwords = self.get_potentially_vulnerable_passwords()?;
+        Ok(PasswordHealthReport::new(&logins, &vulnerable_passwords))
+    }
+
     pub fn reset_all_breaches(&self) -> Result<()> {
         let tx = self.unchecked_transaction()?;
         self.execute_cached("DELETE FROM breachesL", [])?;
@@ -712,6 +789,7 @@ impl LoginDb {
         let time_password_changed = if existing.password == entry.password {
             existing.time_password_changed
         } else {
+            self.record_password_history(&existing.id, &existing.password, now_ms)?;
             now_ms
         };
 
@@ -755,6 +833,119 @@ impl LoginDb {
         }
     }
 
+    /// Import logins from CSV data exported by a browser. See the `csv` module for the layouts we
+    /// understand.
+    ///
+    /// Each row goes through the same fixups and dupe-checks as `add()`. A row that matches an
+    /// existing login (same origin, target and username) is merged if its password differs and
+    /// was changed more recently than the existing one (or has no timestamp), otherwise it's
+    /// skipped. All changes are made in a single transaction.
+    pub fn import_csv(&self, data: &str) -> Result<CsvImportReport> {
+        let rows = csv::parse_logins_csv(data)?;
+        let now_ms = util::system_time_ms_i64(SystemTime::now());
+        let tx = self.unchecked_transaction()?;
+        let mut report = CsvImportReport::default();
+        for (row, parsed) in rows {
+            let outcome = match parsed {
+                Ok(csv_login) => match self.import_csv_login(csv_login, now_ms) {
+                    Ok(outcome) => outcome,
+                    Err(Error::InvalidLogin(why)) => CsvImportOutcome::Invalid {
+                        reason: why.to_string(),
+                    },
+                    Err(e) => return Err(e),
+                },
+                Err(reason) => CsvImportOutcome::Invalid { reason },
+            };
+            report.push(row, outcome);
+        }
+        tx.commit()?;
+        Ok(report)
+    }
+
+    fn import_csv_login(&self, csv_login: CsvLogin, now_ms: i64) -> Result<CsvImportOutcome> {
+        let entry = csv_login.entry.fixup()?;
+        let guid = Guid::random();
+
+        if let Some(existing_guid) = self.find_dupe(&guid, &entry)? {
+            let existing = self
+                .get_by_id(&existing_guid)?
+                .ok_or_else(|| Error::NoSuchRecord(existing_guid.to_string()))?;
+            let existing_sec_fields = existing.decrypt_fields(self.encdec.as_ref())?;
+            let is_newer = csv_login
+                .time_password_changed
+                .is_none_or(|t| t > existing.meta.time_password_changed);
+            if existing_sec_fields.password == entry.password || !is_newer {
+                return Ok(CsvImportOutcome::SkippedDuplicate {
+                    id: existing_guid.to_string(),
+                });
+            }
+
+            self.ensure_local_overlay_exists(&existing_guid)?;
+            self.mark_mirror_overridden(&existing_guid)?;
+            self.record_password_history(&existing.meta.id, &existing_sec_fields.password, now_ms)?;
+            let sec_fields = SecureLoginFields {
+                username: existing_sec_fields.username,
+                password: entry.password,
+            }
+            .encrypt(self.encdec.as_ref(), &existing.meta.id)?;
+            let merged = EncryptedLogin {
+                meta: LoginMeta {
+                    time_password_changed: csv_login.time_password_changed.unwrap_or(now_ms),
+                    ..existing.meta
+                },
+                fields: existing.fields,
+                sec_fields,
+            };
+            self.update_existing_login(&merged)?;
+            return Ok(CsvImportOutcome::Merged {
+                id: existing_guid.to_string(),
+            });
+        }
+
+        let time_created = csv_login.time_created.unwrap_or(now_ms);
+        let sec_fields = SecureLoginFields {
+            username: entry.username,
+            password: entry.password,
+        }
+        .encrypt(self.encdec.as_ref(), &guid)?;
+        let login = EncryptedLogin {
+            meta: LoginMeta {
+                id: guid.to_string(),
+                time_created,
+                time_password_changed: csv_login.time_password_changed.unwrap_or(time_created),
+                time_last_used: csv_login.time_last_used.unwrap_or(time_created),
+                times_used: 1,
+                time_last_breach_alert_dismissed: None,
+            },
+            fields: LoginFields {
+                origin: entry.origin,
+                form_action_origin: entry.form_action_origin,
+                http_realm: entry.http_realm,
+                username_field: entry.username_field,
+                password_field: entry.password_field,
+            },
+            sec_fields,
+        };
+        self.insert_new_login(&login)?;
+        Ok(CsvImportOutcome::Added {
+            id: guid.to_string(),
+        })
+    }
+
+    /// Export all logins as CSV data, using the same layout as Firefox Desktop.
+    ///
+    /// The FxA session-credentials login is never exported.
+    pub fn export_csv(&self) -> Result<String> {
+        let mut logins = self
+            .get_all()?
+            .into_iter()
+            .filter(|login| login.fields.origin != FXA_CREDENTIALS_ORIGIN)
+            .map(|login| login.decrypt(self.encdec.as_ref()))
+            .collect::<Result<Vec<Login>>>()?;
+        logins.sort_by(|a, b| a.origin.cmp(&b.origin).then(a.id.cmp(&b.id)));
+        Ok(csv::logins_to_csv(&logins))
+    }
+
     pub fn fixup_and_check_for_dupes(&self, guid: &Guid, entry: LoginEntry) -> Result<LoginEntry> {
         let entry = entry.fixup()?;
         self.check_for_dupes(guid, &entry)?;
@@ -903,8 +1094,12 @@ impl LoginDb {
 
     /// Delete the records with the specified IDs. Returns a list of Boolean values
     /// indicating whether the respective records already existed.
+    ///
+    /// If the trash is enabled, a copy of each login is kept in the trash (see `list_deleted()`),
+    /// and any logins which have been in the trash for longer than the retention window are purged.
     pub fn delete_many(&self, ids: Vec<&str>) -> Result<Vec<bool>> {
         let tx = self.unchecked_transaction_imm()?;
+        let trash_retention_ms = self.get_trash_retention()?;
         let sql = format!(
             "
             UPDATE loginsL
@@ -926,6 +1121,15 @@ impl LoginDb {
         for id in ids {
             let now_ms = util::system_time_ms_i64(SystemTime::now());
 
+            if trash_retention_ms.is_some() {
+                self.execute_cached(
+                    &TRASH_LOGIN_SQL,
+                    named_params! { ":now_ms": now_ms, ":guid": id },
+                )?;
+            } else {
+                self.delete_password_history(id)?;
+            }
+
             // For IDs that have, mark is_deleted and clear sensitive fields
             let update_result = stmt.execute(named_params! { ":now_ms": now_ms, ":guid": id })?;
 
@@ -951,11 +1155,259 @@ impl LoginDb {
             result.push(exists);
         }
 
+        self.purge_expired_deleted()?;
+
         tx.commit()?;
 
         Ok(result)
     }
 
+    /// The trash retention window in milliseconds, or `None` if the trash is disabled.
+    pub fn get_trash_retention(&self) -> Result<Option<i64>> {
+        self.get_meta(schema::TRASH_RETENTION_META_KEY)
+    }
+
+    /// Enable the trash, keeping deleted logins for `retention_ms`, or disable it with `None`.
+    /// Disabling the trash empties it.
+    pub fn set_trash_retention(&self, retention_ms: Option<i64>) -> Result<()> {
+        let tx = self.unchecked_transaction()?;
+        match retention_ms {
+            Some(retention_ms) => {
+                self.put_meta(schema::TRASH_RETENTION_META_KEY, &retention_ms)?;
+                self.purge_expired_deleted()?;
+            }
+            None => {
+                self.delete_meta(schema::TRASH_RETENTION_META_KEY)?;
+                self.purge_deleted(i64::MAX)?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    // The oldest `time_deleted` which is still within the retention window, or `None` if the
+    // trash is disabled.
+    fn trash_cutoff(&self) -> Result<Option<i64>> {
+        let now_ms = util::system_time_ms_i64(SystemTime::now());
+        Ok(self
+            .get_trash_retention()?
+            .map(|retention_ms| now_ms - retention_ms))
+    }
+
+    /// List the logins in the trash, most recently deleted first.
+    pub fn list_deleted(&self) -> Result<Vec<DeletedLogin>> {
+        let Some(cutoff) = self.trash_cutoff()? else {
+            return Ok(vec![]);
+        };
+        let rows: Vec<(EncryptedLogin, i64)> = self.query_rows_and_then(
+            &format!(
+                "SELECT {common_cols}, time_deleted FROM trashL
+                 WHERE time_deleted >= :cutoff
+                 ORDER BY time_deleted DESC, id DESC",
+                common_cols = schema::COMMON_COLS,
+            ),
+            named_params! { ":cutoff": cutoff },
+            |row| Ok::<_, Error>((EncryptedLogin::from_row(row)?, row.get("time_deleted")?)),
+        )?;
+        rows.into_iter()
+            .map(|(login, time_deleted)| {
+                Ok(DeletedLogin {
+                    login: login.decrypt(self.encdec.as_ref())?,
+                    time_deleted,
+                })
+            })
+            .collect()
+    }
+
+    /// Restore a login from the trash. Returns false if the login isn't in the trash.
+    ///
+    /// If the login's tombstone hasn't been uploaded yet, it is replaced, so it never syncs.
+    /// Otherwise, the login is re-added and will be uploaded on the next sync.
+    pub fn restore(&self, id: &str) -> Result<bool> {
+        let tx = self.unchecked_transaction_imm()?;
+        let Some(cutoff) = self.trash_cutoff()? else {
+            return Ok(false);
+        };
+        let is_mirror_only: Option<bool> = self.try_query_row(
+            "SELECT is_mirror_only FROM trashL WHERE guid = :guid AND time_deleted >= :cutoff",
+            named_params! { ":guid": id, ":cutoff": cutoff },
+            |row| row.get(0),
+            true,
+        )?;
+        let Some(is_mirror_only) = is_mirror_only else {
+            return Ok(false);
+        };
+        // A sync may have brought the login back since it was deleted, in which case that wins.
+        let restored = !self.exists(id)?;
+        if restored {
+            let has_mirror: bool = self.db.query_row(
+                "SELECT EXISTS(SELECT 1 FROM loginsM WHERE guid = :guid)",
+                named_params! { ":guid": id },
+                |row| row.get(0),
+            )?;
+            // This removes our tombstone if it hasn't been uploaded yet.
+            let had_tombstone = self.execute_cached(
+                "DELETE FROM loginsL WHERE guid = :guid",
+                named_params! { ":guid": id },
+            )? > 0;
+            if had_tombstone && has_mirror && is_mirror_only {
+                // Nothing changed locally, so we can just use the mirror again.
+                self.execute_cached(
+                    "UPDATE loginsM SET is_overridden = 0 WHERE guid = :guid",
+                    named_params! { ":guid": id },
+                )?;
+            } else {
+                let sync_status = if has_mirror {
+                    SyncStatus::Changed
+                } else {
+                    SyncStatus::New
+                };
+                self.execute_cached(
+                    &RESTORE_LOGIN_SQL,
+                    named_params! {
+                        ":guid": id,
+                        ":now_ms": util::system_time_ms_i64(SystemTime::now()),
+                        ":sync_status": sync_status as u8,
+                    },
+                )?;
+                if has_mirror {
+                    self.mark_mirror_overridden(id)?;
+                }
+            }
+        }
+        self.execute_cached(
+            "DELETE FROM trashL WHERE guid = :guid",
+            named_params! { ":guid": id },
+        )?;
+        tx.commit()?;
+        Ok(restored)
+    }
+
+    /// Permanently delete the logins which were moved to the trash before `older_than` (in
+    /// milliseconds since the epoch). Returns how many were purged.
+    pub fn purge_deleted(&self, older_than: i64) -> Result<usize> {
+        self.execute_cached(
+            "DELETE FROM passwordHistoryL
+             WHERE guid IN (SELECT guid FROM trashL WHERE time_deleted < :older_than)",
+            named_params! { ":older_than": older_than },
+        )?;
+        Ok(self.execute_cached(
+            "DELETE FROM trashL WHERE time_deleted < :older_than",
+            named_params! { ":older_than": older_than },
+        )?)
+    }
+
+    /// Purge the logins which have been in the trash for longer than the retention window.
+    pub fn purge_expired_deleted(&self) -> Result<()> {
+        if let Some(cutoff) = self.trash_cutoff()? {
+            self.purge_deleted(cutoff)?;
+        }
+        Ok(())
+    }
+
+    /// The maximum number of previous passwords kept per login, or `None` if the password history
+    /// is disabled.
+    pub fn get_password_history_limit(&self) -> Result<Option<u32>> {
+        self.get_meta(schema::PASSWORD_HISTORY_LIMIT_META_KEY)
+    }
+
+    /// Enable the password history, keeping up to `limit` previous passwords per login, or
+    /// disable it with `None`. Disabling the history, or lowering the limit, drops the excess.
+    pub fn set_password_history_limit(&self, limit: Option<u32>) -> Result<()> {
+        let tx = self.unchecked_transaction()?;
+        match limit {
+            Some(limit) => {
+                self.put_meta(schema::PASSWORD_HISTORY_LIMIT_META_KEY, &limit)?;
+                self.execute_cached(
+                    "DELETE FROM passwordHistoryL
+                     WHERE id NOT IN (
+                         SELECT id FROM (
+                             SELECT id, ROW_NUMBER() OVER (PARTITION BY guid ORDER BY id DESC) AS n
+                             FROM passwordHistoryL
+                         )
+                         WHERE n <= :limit
+                     )",
+                    named_params! { ":limit": limit },
+                )?;
+            }
+            None => {
+                self.delete_meta(schema::PASSWORD_HISTORY_LIMIT_META_KEY)?;
+                self.execute("DELETE FROM passwordHistoryL", [])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The previous passwords of a login, most recent first.
+    pub fn get_password_history(&self, id: &str) -> Result<Vec<PasswordHistoryEntry>> {
+        let rows: Vec<(String, i64)> = self.query_rows_and_then_cached(
+            "SELECT encryptedPassword, timeChanged FROM passwordHistoryL
+             WHERE guid = :guid
+             ORDER BY id DESC",
+            named_params! { ":guid": id },
+            |row| Ok::<_, Error>((row.get(0)?, row.get(1)?)),
+        )?;
+        rows.into_iter()
+            .map(|(encrypted_password, time_changed)| {
+                let password = self
+                    .encdec
+                    .decrypt(encrypted_password.as_bytes().into())
+                    .map_err(|e| {
+                        Error::DecryptionFailed(format!("{e} (decrypting password history)"))
+                    })?;
+                let password = String::from_utf8(password).map_err(|e| {
+                    Error::DecryptionFailed(format!("{e} (decrypting password history)"))
+                })?;
+                Ok(PasswordHistoryEntry {
+                    password,
+                    time_changed,
+                })
+            })
+            .collect()
+    }
+
+    // Record that `id`'s password `previous` was replaced at `now_ms`, if the history is enabled.
+    // Must be called in a transaction.
+    fn record_password_history(&self, id: &str, previous: &str, now_ms: i64) -> Result<()> {
+        let Some(limit) = self.get_password_history_limit()? else {
+            return Ok(());
+        };
+        let cipherbytes = self
+            .encdec
+            .encrypt(previous.as_bytes().into())
+            .map_err(|e| Error::EncryptionFailed(format!("{e} (encrypting password history)")))?;
+        let encrypted_password = String::from_utf8(cipherbytes).map_err(|e| {
+            Error::EncryptionFailed(format!("{e} (encrypting password history: data not utf8)"))
+        })?;
+        self.execute_cached(
+            "INSERT INTO passwordHistoryL (guid, encryptedPassword, timeChanged)
+             VALUES (:guid, :encrypted_password, :now_ms)",
+            named_params! {
+                ":guid": id,
+                ":encrypted_password": encrypted_password,
+                ":now_ms": now_ms,
+            },
+        )?;
+        self.execute_cached(
+            "DELETE FROM passwordHistoryL
+             WHERE guid = :guid
+               AND id NOT IN (
+                   SELECT id FROM passwordHistoryL WHERE guid = :guid ORDER BY id DESC LIMIT :limit
+               )",
+            named_params! { ":guid": id, ":limit": limit },
+        )?;
+        Ok(())
+    }
+
+    fn delete_password_history(&self, id: &str) -> Result<()> {
+        self.execute_cached(
+            "DELETE FROM passwordHistoryL WHERE guid = :guid",
+            named_params! { ":guid": id },
+        )?;
+        Ok(())
+    }
+
     pub fn delete_undecryptable_records_for_remote_replacement(
         &self,
     ) -> Result<LoginsDeletionMetrics> {
@@ -970,9 +1422,28 @@ impl LoginDb {
             .map(|login| login.guid_str())
             .collect::<Vec<_>>();
 
+        self.purge_undecryptable_trash()?;
         self.delete_local_records_for_remote_replacement(ids)
     }
 
+    // Logins in the trash which can't be decrypted can never be restored, and would break
+    // `list_deleted()`, so drop them.
+    fn purge_undecryptable_trash(&self) -> Result<()> {
+        let trash: Vec<(i64, String, String)> =
+            self.query_rows_and_then("SELECT id, guid, secFields FROM trashL", [], |row| {
+                Ok::<_, Error>((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+        for (id, guid, sec_fields) in trash {
+            if SecureLoginFields::decrypt(&sec_fields, self.encdec.as_ref(), &guid).is_err() {
+                self.execute_cached(
+                    "DELETE FROM trashL WHERE id = :id",
+                    named_params! { ":id": id },
+                )?;
+            }
+        }
+        Ok(())
+    }
+
     pub fn delete_local_records_for_remote_replacement(
         &self,
         ids: Vec<&str>,
@@ -990,6 +1461,13 @@ impl LoginDb {
                 rusqlite::params_from_iter(chunk),
             )?;
             local_deleted += deleted;
+            self.execute(
+                &format!(
+                    "DELETE FROM passwordHistoryL WHERE guid IN ({})",
+                    sql_support::repeat_sql_values(chunk.len())
+                ),
+                rusqlite::params_from_iter(chunk),
+            )?;
             Ok(())
         })?;
 
@@ -1047,6 +1525,129 @@ impl LoginDb {
         Ok(self.execute_cached(&CLONE_SINGLE_MIRROR_SQL, &[(":guid", &guid as &dyn ToSql)])?)
     }
 
+    /// Re-encrypt all encrypted data in the database, switching from `old` to `new`.
+    ///
+    /// The local and mirror `secFields`, the mirror's `enc_unknown_fields` and the breach
+    /// database are all re-encrypted in a single transaction. If anything fails to decrypt with
+    /// `old`, if `new` doesn't round-trip a canary, or if we are interrupted, the transaction is
+    /// rolled back and the database continues to use the old key. On success, this `LoginDb`
+    /// uses `new` from then on.
+    pub fn rekey(
+        &mut self,
+        old: Arc<dyn EncryptorDecryptor>,
+        new: Arc<dyn EncryptorDecryptor>,
+    ) -> Result<()> {
+        const CANARY_TEXT: &str = "logins rekey canary";
+        let scope = self.begin_interrupt_scope()?;
+        // Check `new` works before we touch anything.
+        let canary = create_encdec_canary(new.as_ref(), CANARY_TEXT)?;
+        if !check_encdec_canary(new.as_ref(), &canary, CANARY_TEXT) {
+            return Err(Error::EncryptionFailed(
+                "new key failed the canary check".into(),
+            ));
+        }
+
+        let tx = self.unchecked_transaction_imm()?;
+        let reencrypt = |ciphertext: &str, what: &str| -> Result<String> {
+            scope.err_if_interrupted()?;
+            let cleartext = old
+                .decrypt(ciphertext.as_bytes().into())
+                .map_err(|e| Error::DecryptionFailed(format!("{e} (rekeying {what})")))?;
+            let cipherbytes = new
+                .encrypt(cleartext)
+                .map_err(|e| Error::EncryptionFailed(format!("{e} (rekeying {what})")))?;
+            String::from_utf8(cipherbytes).map_err(|e| {
+                Error::EncryptionFailed(format!("{e} (rekeying {what}: data not utf8)"))
+            })
+        };
+
+        // Tombstones have an empty `secFields`, so skip those.
+        let local: Vec<(i64, String, String)> = self.query_rows_and_then(
+            "SELECT id, guid, secFields FROM loginsL WHERE secFields != ''",
+            [],
+            |row| Ok::<_, Error>((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        for (id, guid, sec_fields) in local {
+            self.execute_cached(
+                "UPDATE loginsL SET secFields = :sec_fields WHERE id = :id",
+                named_params! { ":id": id, ":sec_fields": reencrypt(&sec_fields, &guid)? },
+            )?;
+        }
+
+        let mirror: Vec<(i64, String, String, Option<String>)> = self.query_rows_and_then(
+            "SELECT id, guid, secFields, enc_unknown_fields FROM loginsM",
+            [],
+            |row| Ok::<_, Error>((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )?;
+        for (id, guid, sec_fields, enc_unknown_fields) in mirror {
+            let enc_unknown_fields = enc_unknown_fields
+                .map(|fields| reencrypt(&fields, &guid))
+                .transpose()?;
+            self.execute_cached(
+                "UPDATE loginsM
+                 SET secFields = :sec_fields, enc_unknown_fields = :enc_unknown_fields
+                 WHERE id = :id",
+                named_params! {
+                    ":id": id,
+                    ":sec_fields": reencrypt(&sec_fields, &guid)?,
+                    ":enc_unknown_fields": enc_unknown_fields,
+                },
+            )?;
+        }
+
+        let trash: Vec<(i64, String, String)> =
+            self.query_rows_and_then("SELECT id, guid, secFields FROM trashL", [], |row| {
+                Ok::<_, Error>((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?;
+        for (id, guid, sec_fields) in trash {
+            self.execute_cached(
+                "UPDATE trashL SET secFields = :sec_fields WHERE id = :id",
+                named_params! { ":id": id, ":sec_fields": reencrypt(&sec_fields, &guid)? },
+            )?;
+        }
+
+        let history: Vec<(i64, String, String)> = self.query_rows_and_then(
+            "SELECT id, guid, encryptedPassword FROM passwordHistoryL",
+            [],
+            |row| Ok::<_, Error>((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        for (id, guid, encrypted_password) in history {
+            self.execute_cached(
+                "UPDATE passwordHistoryL SET encryptedPassword = :encrypted_password WHERE id = :id",
+                named_params! {
+                    ":id": id,
+                    ":encrypted_password": reencrypt(&encrypted_password, &guid)?,
+                },
+            )?;
+        }
+
+        let breaches: Vec<(i64, String)> =
+            self.query_rows_and_then("SELECT id, encryptedPassword FROM breachesL", [], |row| {
+                Ok::<_, Error>((row.get(0)?, row.get(1)?))
+            })?;
+        for (id, encrypted_password) in breaches {
+            self.execute_cached(
+                "UPDATE breachesL SET encryptedPassword = :encrypted_password WHERE id = :id",
+                named_params! {
+                    ":id": id,
+                    ":encrypted_password": reencrypt(&encrypted_password, "breachesL")?,
+                },
+            )?;
+        }
+
+        // A `KeyManager` might hand out a different key on each call, so check the canary again
+        // before committing to the new key.
+        scope.err_if_interrupted()?;
+        if !check_encdec_canary(new.as_ref(), &canary, CANARY_TEXT) {
+            return Err(Error::EncryptionFailed(
+                "new key changed while rekeying".into(),
+            ));
+        }
+        tx.commit()?;
+        self.encdec = new;
+        Ok(())
+    }
+
     /// Wipe all local data, returns the number of rows deleted
     pub fn wipe_local(&self) -> Result<usize> {
         info!("Executing wipe_local on password engine!");
@@ -1056,6 +1657,8 @@ impl LoginDb {
         row_count += self.execute("DELETE FROM loginsM", [])?;
         row_count += self.execute("DELETE FROM loginsSyncMeta", [])?;
         row_count += self.execute("DELETE FROM breachesL", [])?;
+        row_count += self.execute("DELETE FROM trashL", [])?;
+        row_count += self.execute("DELETE FROM passwordHistoryL", [])?;
         tx.commit()?;
         Ok(row_count)
     }
@@ -1075,6 +1678,15 @@ impl LoginDb {
         )?;
         row_count += self.execute("DELETE FROM loginsSyncMeta", [])?;
         row_count += self.execute("DELETE FROM breachesL", [])?;
+        row_count += self.execute(
+            "DELETE FROM trashL WHERE origin != :fxa_origin",
+            named_params! { ":fxa_origin": FXA_CREDENTIALS_ORIGIN },
+        )?;
+        row_count += self.execute(
+            "DELETE FROM passwordHistoryL
+             WHERE guid NOT IN (SELECT guid FROM loginsL UNION ALL SELECT guid FROM loginsM)",
+            [],
+        )?;
         tx.commit()?;
         Ok(row_count)
     }
@@ -1137,6 +1749,18 @@ lazy_static! {
     );
     static ref CLONE_SINGLE_MIRROR_SQL: String =
         format!("{} WHERE guid = :guid", &*CLONE_ENTIRE_MIRROR_SQL,);
+    static ref TRASH_LOGIN_SQL: String = format!(
+        "INSERT OR REPLACE INTO trashL ({common_cols}, time_deleted, is_mirror_only)
+         SELECT {common_cols}, :now_ms, 0 FROM loginsL WHERE guid = :guid AND is_deleted = 0
+         UNION ALL
+         SELECT {common_cols}, :now_ms, 1 FROM loginsM WHERE guid = :guid AND is_overridden = 0",
+        common_cols = schema::COMMON_COLS,
+    );
+    static ref RESTORE_LOGIN_SQL: String = format!(
+        "INSERT INTO loginsL ({common_cols}, local_modified, is_deleted, sync_status)
+         SELECT {common_cols}, :now_ms, 0, :sync_status FROM trashL WHERE guid = :guid",
+        common_cols = schema::COMMON_COLS,
+    );
 }
 
 #[cfg(not(feature = "keydb"))]
@@ -1314,8 +1938,11 @@ pub mod test_utils {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::csv::CsvImportRowResult;
     use crate::db::test_utils::{get_local_guids, get_mirror_guids};
     use crate::encryption::test_utils::TEST_ENCDEC;
+    use crate::encryption::{create_key, ManagedEncryptorDecryptor, StaticKeyManager};
+    use crate::password_health::PasswordWeakness;
     use crate::sync::merge::LocalLogin;
     use nss_as::ensure_initialized;
     use std::{thread, time};
@@ -1885,6 +2512,80 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_find_logins_for_form() {
+        ensure_initialized();
+        let db = LoginDb::open_in_memory();
+        let add = |username: &str,
+                   origin: &str,
+                   form_action_origin: Option<&str>,
+                   http_realm: Option<&str>| {
+            db.add(LoginEntry {
+                origin: origin.into(),
+                form_action_origin: form_action_origin.map(Into::into),
+                http_realm: http_realm.map(Into::into),
+                username: username.into(),
+                password: "test".into(),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+        add("sub", "http://sub.example.com", Some(""), None);
+        add(
+            "exact",
+            "https://example.com",
+            Some("https://example.com"),
+            None,
+        );
+        add(
+            "upgrade",
+            "http://example.com",
+            Some("https://example.com"),
+            None,
+        );
+        add("wildcard", "https://example.com", Some(""), None);
+        add("auth", "https://example.com", None, Some("realm"));
+        add("other", "https://other.com", Some(""), None);
+
+        let results = |matches: Vec<LoginFormMatch>| {
+            matches
+                .into_iter()
+                .map(|m| (m.login.username, m.score))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            results(
+                db.find_logins_for_form("https://example.com", Some("https://example.com"), None)
+                    .unwrap()
+            ),
+            vec![
+                ("exact".to_string(), 440),
+                ("wildcard".to_string(), 420),
+                ("upgrade".to_string(), 240),
+                ("sub".to_string(), 120),
+            ]
+        );
+        assert_eq!(
+            results(
+                db.find_logins_for_form("https://example.com", None, Some("realm"))
+                    .unwrap()
+            ),
+            vec![("auth".to_string(), 440)]
+        );
+        // We never offer https logins to http pages.
+        assert_eq!(
+            results(
+                db.find_logins_for_form("http://example.com", None, None)
+                    .unwrap()
+            ),
+            vec![("upgrade".to_string(), 420), ("sub".to_string(), 320),]
+        );
+        assert!(db
+            .find_logins_for_form("invalid origin", None, None)
+            .unwrap()
+            .is_empty());
+    }
+
     #[test]
     fn test_add() {
         ensure_initialized();
@@ -2079,6 +2780,119 @@ mod tests {
         assert!(!db.exists(login.guid_str()).unwrap());
     }
 
+    #[test]
+    fn test_trash() {
+        ensure_initialized();
+        let db = LoginDb::open_in_memory();
+        let add = |origin: &str| {
+            db.add(LoginEntry {
+                origin: origin.into(),
+                http_realm: Some("https://www.example.com".into()),
+                username: "test_user".into(),
+                password: "test_password".into(),
+                ..Default::default()
+            })
+            .unwrap()
+        };
+
+        // The trash is disabled by default.
+        assert_eq!(db.get_trash_retention().unwrap(), None);
+        let login = add("https://a.example.com");
+        db.delete(login.guid_str()).unwrap();
+        assert!(db.list_deleted().unwrap().is_empty());
+        assert!(!db.restore(login.guid_str()).unwrap());
+
+        db.set_trash_retention(Some(24 * 60 * 60 * 1000)).unwrap();
+        let login_b = add("https://b.example.com");
+        let login_c = add("https://c.example.com");
+        db.delete_many(vec![login_b.guid_str(), login_c.guid_str()])
+            .unwrap();
+        let deleted = db.list_deleted().unwrap();
+        assert_eq!(deleted.len(), 2);
+        assert_eq!(deleted[0].login.id, login_c.guid_str());
+        assert_eq!(deleted[1].login.password, "test_password");
+
+        assert!(db.restore(login_b.guid_str()).unwrap());
+        assert!(!db.restore(login_b.guid_str()).unwrap());
+        let restored = db
+            .get_by_id(login_b.guid_str())
+            .unwrap()
+            .unwrap()
+            .decrypt(TEST_ENCDEC.as_ref())
+            .unwrap();
+        assert_eq!(restored.origin, "https://b.example.com");
+        assert_eq!(restored.password, "test_password");
+        assert_eq!(db.list_deleted().unwrap().len(), 1);
+
+        // Purging with a cutoff in the future purges everything.
+        let now_ms = util::system_time_ms_i64(SystemTime::now());
+        assert_eq!(db.purge_deleted(now_ms + 1).unwrap(), 1);
+        assert!(db.list_deleted().unwrap().is_empty());
+        assert!(!db.restore(login_c.guid_str()).unwrap());
+
+        // Disabling the trash empties it.
+        db.delete(login_b.guid_str()).unwrap();
+        assert_eq!(db.list_deleted().unwrap().len(), 1);
+        db.set_trash_retention(None).unwrap();
+        assert!(db.list_deleted().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_password_history() {
+        ensure_initialized();
+        let db = LoginDb::open_in_memory();
+        let entry = |username: &str, password: &str| LoginEntry {
+            origin: "https://www.example.com".into(),
+            http_realm: Some("https://www.example.com".into()),
+            username: username.into(),
+            password: password.into(),
+            ..Default::default()
+        };
+        let history = |id: &str| {
+            db.get_password_history(id)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.password)
+                .collect::<Vec<_>>()
+        };
+        let login = db.add(entry("user", "password-1")).unwrap();
+        let id = login.guid_str();
+
+        // The history is disabled by default.
+        db.update(id, entry("user", "password-2")).unwrap();
+        assert!(history(id).is_empty());
+
+        db.set_password_history_limit(Some(2)).unwrap();
+        db.update(id, entry("user", "password-3")).unwrap();
+        // Changing something other than the password isn't recorded.
+        db.update(id, entry("other-user", "password-3")).unwrap();
+        db.update(id, entry("other-user", "password-4")).unwrap();
+        db.update(id, entry("other-user", "password-5")).unwrap();
+        assert_eq!(history(id), vec!["password-4", "password-3"]);
+
+        // Lowering the limit drops the oldest.
+        db.set_password_history_limit(Some(1)).unwrap();
+        assert_eq!(history(id), vec!["password-4"]);
+
+        db.wipe_local().unwrap();
+        assert!(history(id).is_empty());
+
+        // Deleting a login drops its history, and disabling the history clears it.
+        let login = db.add(entry("user", "password-1")).unwrap();
+        let id = login.guid_str();
+        db.set_password_history_limit(Some(5)).unwrap();
+        db.update(id, entry("user", "password-2")).unwrap();
+        assert_eq!(history(id), vec!["password-1"]);
+        db.delete(id).unwrap();
+        assert!(history(id).is_empty());
+
+        let login = db.add(entry("user", "password-1")).unwrap();
+        let id = login.guid_str();
+        db.update(id, entry("user", "password-2")).unwrap();
+        db.set_password_history_limit(None).unwrap();
+        assert!(history(id).is_empty());
+    }
+
     #[test]
     fn test_delete_many() {
         ensure_initialized();
@@ -2268,6 +3082,226 @@ mod tests {
         assert_eq!(result.local_deleted, 1);
     }
 
+    #[test]
+    fn test_import_csv() {
+        ensure_initialized();
+        let db = LoginDb::open_in_memory();
+        let existing = db
+            .add(LoginEntry {
+                origin: "https://example.com".into(),
+                form_action_origin: Some("".into()),
+                username: "alice".into(),
+                password: "old".into(),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let report = db
+            .import_csv(
+                "url,username,password,httpRealm,formActionOrigin,timePasswordChanged\n\
+                 https://example.com,alice,new,,,\n\
+                 https://example.com,alice,new,,,\n\
+                 https://other.example.com/path,bob,pw,,,1\n\
+                 https://realm.example.com,carol,pw,My Realm,,\n\
+                 ,dave,pw,,,\n\
+                 not a url,erin,pw,,,\n",
+            )
+            .unwrap();
+
+        assert_eq!(report.added, 2);
+        assert_eq!(report.merged, 1);
+        assert_eq!(report.skipped, 1);
+        assert_eq!(report.invalid, 2);
+        assert_eq!(
+            report.rows[0].outcome,
+            CsvImportOutcome::Merged {
+                id: existing.meta.id.clone()
+            }
+        );
+        assert_eq!(
+            report.rows[1].outcome,
+            CsvImportOutcome::SkippedDuplicate {
+                id: existing.meta.id.clone()
+            }
+        );
+        assert_eq!(
+            report.rows[4],
+            CsvImportRowResult {
+                row: 6,
+                outcome: CsvImportOutcome::Invalid {
+                    reason: InvalidLogin::EmptyOrigin.to_string()
+                },
+            }
+        );
+        assert!(matches!(
+            report.rows[5].outcome,
+            CsvImportOutcome::Invalid { .. }
+        ));
+
+        let merged = db
+            .get_by_id(&existing.meta.id)
+            .unwrap()
+            .unwrap()
+            .decrypt(db.encdec.as_ref())
+            .unwrap();
+        assert_eq!(merged.password, "new");
+
+        let CsvImportOutcome::Added { id } = &report.rows[2].outcome else {
+            panic!("expected the row to be added");
+        };
+        let added = db.get_by_id(id).unwrap().unwrap();
+        // The origin was fixed up, and the timestamp preserved.
+        assert_eq!(added.fields.origin, "https://other.example.com");
+        assert_eq!(added.meta.time_password_changed, 1);
+        assert_eq!(db.count_all().unwrap(), 3);
+
+        // An older password doesn't replace a newer one.
+        let report = db
+            .import_csv(
+                "url,username,password,timePasswordChanged\n\
+                 https://example.com,alice,older,1\n",
+            )
+            .unwrap();
+        assert_eq!(report.skipped, 1);
+
+        // Exporting and re-importing is a no-op.
+        let exported = db.export_csv().unwrap();
+        let report = db.import_csv(&exported).unwrap();
+        assert_eq!(report.skipped, 3);
+        assert_eq!(db.count_all().unwrap(), 3);
+    }
+
+    fn new_test_encdec() -> Arc<dyn EncryptorDecryptor> {
+        Arc::new(ManagedEncryptorDecryptor::new(Arc::new(
+            StaticKeyManager::new(create_key().unwrap()),
+        )))
+    }
+
+    #[test]
+    fn test_rekey() {
+        ensure_initialized();
+        let mut db = LoginDb::open_in_memory();
+        let local = db
+            .add(LoginEntry {
+                origin: "https://www.example.com".into(),
+                http_realm: Some("https://www.example.com".into()),
+                username: "test".into(),
+                password: "sekret".into(),
+                ..LoginEntry::default()
+            })
+            .unwrap();
+        test_utils::insert_login(&db, "mirror_only", None, Some("mirror_password"));
+        let deleted = db
+            .add(LoginEntry {
+                origin: "https://deleted.example.com".into(),
+                http_realm: Some("https://deleted.example.com".into()),
+                username: "test".into(),
+                password: "sekret".into(),
+                ..LoginEntry::default()
+            })
+            .unwrap();
+        db.delete(deleted.guid_str()).unwrap();
+        db.record_potentially_vulnerable_passwords(vec!["sekret".into()])
+            .unwrap();
+
+        let new_encdec = new_test_encdec();
+        db.rekey(TEST_ENCDEC.clone(), new_encdec.clone()).unwrap();
+
+        // Everything decrypts with the new key, and nothing with the old.
+        for id in [local.guid_str(), "mirror_only"] {
+            let login = db.get_by_id(id).unwrap().unwrap();
+            assert!(login.decrypt_fields(new_encdec.as_ref()).is_ok());
+            assert!(login.decrypt_fields(TEST_ENCDEC.as_ref()).is_err());
+        }
+        assert_eq!(
+            db.are_potentially_vulnerable_passwords(&[local.guid_str()])
+                .unwrap(),
+            vec![local.guid_str().to_string()]
+        );
+        assert_eq!(db.get_all().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_rekey_with_wrong_old_key() {
+        ensure_initialized();
+        let mut db = LoginDb::open_in_memory();
+        let login = db
+            .add(LoginEntry {
+                origin: "https://www.example.com".into(),
+                http_realm: Some("https://www.example.com".into()),
+                username: "test".into(),
+                password: "sekret".into(),
+                ..LoginEntry::default()
+            })
+            .unwrap();
+
+        assert!(matches!(
+            db.rekey(new_test_encdec(), new_test_encdec()),
+            Err(Error::DecryptionFailed(_))
+        ));
+        // We rolled back and still use the original key.
+        let login = db.get_by_id(login.guid_str()).unwrap().unwrap();
+        assert!(login.decrypt_fields(TEST_ENCDEC.as_ref()).is_ok());
+        assert!(login.decrypt_fields(db.encdec.as_ref()).is_ok());
+    }
+
+    #[test]
+    fn test_rekey_interrupted() {
+        ensure_initialized();
+
+        // Interrupts the rekey the first time it's used to decrypt.
+        struct InterruptingEncDec {
+            inner: Arc<dyn EncryptorDecryptor>,
+            handle: Arc<SqlInterruptHandle>,
+        }
+        impl EncryptorDecryptor for InterruptingEncDec {
+            fn encrypt(&self, cleartext: Vec<u8>) -> ApiResult<Vec<u8>> {
+                self.inner.encrypt(cleartext)
+            }
+            fn decrypt(&self, ciphertext: Vec<u8>) -> ApiResult<Vec<u8>> {
+                self.handle.interrupt();
+                self.inner.decrypt(ciphertext)
+            }
+        }
+
+        let mut db = LoginDb::open_in_memory();
+        let mut ids = vec![];
+        for i in 0..2 {
+            let login = db
+                .add(LoginEntry {
+                    origin: format!("https://{i}.example.com"),
+                    http_realm: Some("https://www.example.com".into()),
+                    username: "test".into(),
+                    password: "sekret".into(),
+                    ..LoginEntry::default()
+                })
+                .unwrap();
+            ids.push(login.meta.id);
+        }
+
+        let old = Arc::new(InterruptingEncDec {
+            inner: TEST_ENCDEC.clone(),
+            handle: db.new_interrupt_handle(),
+        });
+        assert!(db.rekey(old, new_test_encdec()).is_err());
+
+        for id in ids {
+            let login = db.get_by_id(&id).unwrap().unwrap();
+            assert!(login.decrypt_fields(TEST_ENCDEC.as_ref()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_import_csv_invalid_data() {
+        ensure_initialized();
+        let db = LoginDb::open_in_memory();
+        assert!(matches!(
+            db.import_csv("url,username\nhttps://example.com,alice\n"),
+            Err(Error::InvalidCsv(_))
+        ));
+        assert_eq!(db.count_all().unwrap(), 0);
+    }
+
     mod test_find_login_to_update {
         use super::*;
 
@@ -2545,5 +3579,48 @@ mod tests {
             assert_eq!(vulnerable.len(), 1);
             assert!(vulnerable.contains(&login1.meta.id));
         }
+
+        #[test]
+        fn test_password_health_report() {
+            ensure_initialized();
+            let db = LoginDb::open_in_memory();
+            let add = |origin: &str, password: &str| {
+                db.add(LoginEntry {
+                    origin: origin.into(),
+                    http_realm: Some("realm".into()),
+                    username: "user".into(),
+                    password: password.into(),
+                    ..Default::default()
+                })
+                .unwrap()
+                .meta
+                .id
+            };
+            let reused1 = add("https://site1.com", "a-Sh4red-password");
+            let reused2 = add("https://site2.com", "a-Sh4red-password");
+            let weak = add("https://site3.com", "qwerty");
+            let breached = add("https://site4.com", "Br3ached-but-strong");
+            add(FXA_CREDENTIALS_ORIGIN, "qwerty");
+            db.record_potentially_vulnerable_passwords(vec!["Br3ached-but-strong".into()])
+                .unwrap();
+
+            let report = db.password_health_report().unwrap();
+            assert_eq!(report.logins.len(), 4);
+            assert_eq!(report.reuse_groups.len(), 1);
+            let mut group = report.reuse_groups[0].clone();
+            group.sort();
+            let mut expected = vec![reused1.clone(), reused2];
+            expected.sort();
+            assert_eq!(group, expected);
+
+            let health = |id: &str| report.logins.iter().find(|h| h.id == id).unwrap();
+            assert_eq!(health(&reused1).reuse_group, Some(0));
+            assert!(health(&reused1).weaknesses.is_empty());
+            assert!(!health(&reused1).is_potentially_vulnerable);
+            assert_eq!(health(&weak).reuse_group, None);
+            assert!(health(&weak).weaknesses.contains(&PasswordWeakness::Common));
+            assert!(health(&breached).is_potentially_vulnerable);
+            assert!(health(&breached).weaknesses.is_empty());
+        }
     }
 }
@@ -337,6 +337,26 @@ pub fn create_key() -> ApiResult<String> {
     Ok(jwcrypto::EncryptorDecryptor::create_key()?)
 }
 
+// `create_canary()` and `check_canary()` work with raw keys, but when rekeying we only have an
+// `EncryptorDecryptor`, so these are the equivalent for that.
+pub(crate) fn create_encdec_canary(encdec: &dyn EncryptorDecryptor, text: &str) -> Result<String> {
+    let cipherbytes = encdec
+        .encrypt(text.as_bytes().into())
+        .map_err(|e| Error::EncryptionFailed(format!("{e} (creating canary)")))?;
+    String::from_utf8(cipherbytes)
+        .map_err(|e| Error::EncryptionFailed(format!("{e} (creating canary: data not utf8)")))
+}
+
+pub(crate) fn check_encdec_canary(
+    encdec: &dyn EncryptorDecryptor,
+    canary: &str,
+    text: &str,
+) -> bool {
+    encdec
+        .decrypt(canary.as_bytes().into())
+        .is_ok_and(|bytes| bytes == text.as_bytes())
+}
+
 #[cfg(test)]
 pub mod test_utils {
     use super::*;
@@ -109,6 +109,9 @@ pub enum Error {
 
     #[error("IncompatibleVersion: {0}")]
     IncompatibleVersion(i64),
+
+    #[error("Invalid CSV data: {0}")]
+    InvalidCsv(String),
 }
 
 /// Error::InvalidLogin subtypes
@@ -148,6 +151,11 @@ impl GetErrorHandling for Error {
                     reason: "invalid incoming record".to_string(),
                 })
             }
+            // The CSV data as a whole couldn't be used (eg, no password column). Problems with
+            // individual rows are reported in the `CsvImportReport` instead.
+            Self::InvalidCsv(why) => ErrorHandling::convert(LoginsApiError::InvalidRecord {
+                reason: why.to_string(),
+            }),
             // Our internal "no such record" error is converted to our public "no such record" error, with no logging and no error reporting.
             Self::NoSuchRecord(guid) => ErrorHandling::convert(LoginsApiError::NoSuchRecord {
                 reason: guid.to_string(),
@@ -171,6 +179,12 @@ impl GetErrorHandling for Error {
                     })
                     .report_error("logins-db-corrupt")
                 }
+                // We were interrupted mid-statement via `LoginStore::interrupt()`.
+                rusqlite::ErrorCode::OperationInterrupted => {
+                    ErrorHandling::convert(LoginsApiError::Interrupted {
+                        reason: self.to_string(),
+                    })
+                }
                 rusqlite::ErrorCode::DiskFull => {
                     ErrorHandling::convert(LoginsApiError::UnexpectedLoginsApiError {
                         reason: self.to_string(),
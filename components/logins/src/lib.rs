@@ -9,8 +9,11 @@
 mod error;
 mod login;
 
+mod csv;
 mod db;
 pub mod encryption;
+mod matching;
+mod password_health;
 mod schema;
 mod store;
 mod sync;
@@ -24,10 +27,13 @@ uniffi::include_scaffolding!("logins");
 #[cfg(feature = "keydb")]
 pub use crate::encryption::{NSSKeyManager, PrimaryPasswordAuthenticator};
 
-pub use crate::db::{LoginDb, LoginsDeletionMetrics};
+pub use crate::csv::{CsvImportOutcome, CsvImportReport, CsvImportRowResult};
+pub use crate::db::{DeletedLogin, LoginDb, LoginsDeletionMetrics, PasswordHistoryEntry};
 use crate::encryption::{check_canary, create_canary, create_key};
 pub use crate::error::*;
 pub use crate::login::*;
+pub use crate::matching::{LoginFormMatch, LoginOriginMatch};
+pub use crate::password_health::{LoginPasswordHealth, PasswordHealthReport, PasswordWeakness};
 pub use crate::store::*;
 pub use crate::sync::{LoginsBridgedEngine, LoginsSyncEngine};
 use std::sync::Arc;
@@ -0,0 +1,349 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! # Form matching
+//!
+//! Support for `LoginStore::find_logins_for_form()`, which finds the logins that could be filled
+//! into a login form or HTTP auth prompt, ranked the way Firefox Desktop ranks them, so that all
+//! our apps autofill identically.
+//!
+//! A login can fill a page if its origin:
+//! - is exactly the page's origin, or
+//! - is the `http:` version of an `https:` page on the same host (a "scheme upgrade"; we never
+//!   fill `https:` logins into `http:` pages), or
+//! - is on a parent or child domain of the page's host (eg, `example.com` and
+//!   `login.example.com`), optionally also with a scheme upgrade. A leading `www.` is ignored, so
+//!   `www.example.com` and `login.example.com` also match. We don't have a public suffix list, so
+//!   sibling subdomains otherwise don't match.
+//!
+//! And its target is compatible:
+//! - For HTTP auth, the login's `http_realm` must equal the requested realm.
+//! - For forms, the login must be a form login, and if a form action origin is given, the login's
+//!   `form_action_origin` must match it, allowing a scheme upgrade. An empty, `javascript:` or
+//!   other host-less form action origin matches any form, on either side.
+//!
+//! Results are scored, with the origin match dominating (exact origins first, then subdomains, then
+//! scheme upgrades), then how well the target matched, and ties broken by the most recently used.
+
+use crate::error::*;
+use crate::login::{EncryptedLogin, Login, LoginEntry};
+use url::{Host, Url};
+
+/// How a login's origin matched the requested origin, from best to worst.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum LoginOriginMatch {
+    Exact,
+    Subdomain,
+    SchemeUpgrade,
+    SubdomainSchemeUpgrade,
+}
+
+impl LoginOriginMatch {
+    fn score(self) -> u32 {
+        match self {
+            Self::Exact => 400,
+            Self::Subdomain => 300,
+            Self::SchemeUpgrade => 200,
+            Self::SubdomainSchemeUpgrade => 100,
+        }
+    }
+}
+
+/// A login returned by `LoginStore::find_logins_for_form()`. Higher scores are better matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginFormMatch {
+    pub login: Login,
+    pub origin_match: LoginOriginMatch,
+    pub score: u32,
+}
+
+// How well the login's target (form action origin or realm) matched.
+const TARGET_EXACT_SCORE: u32 = 40;
+const TARGET_SCHEME_UPGRADE_SCORE: u32 = 30;
+const TARGET_WILDCARD_SCORE: u32 = 20;
+
+enum Target {
+    // A form, with the normalized form action origin if known.
+    Form(Option<Url>),
+    HttpAuth(String),
+}
+
+/// A normalized `find_logins_for_form()` query.
+pub(crate) struct FormQuery {
+    origin: Url,
+    target: Target,
+}
+
+impl FormQuery {
+    /// Returns `Ok(None)` if the origin isn't valid, since no logins could match it.
+    pub(crate) fn new(
+        origin: &str,
+        form_action_origin: Option<&str>,
+        http_realm: Option<&str>,
+    ) -> Result<Option<Self>> {
+        let origin = match LoginEntry::validate_and_fixup_origin(origin) {
+            Ok(fixed) => fixed.unwrap_or_else(|| origin.to_string()),
+            Err(e) => {
+                // don't log the input string as it's PII.
+                warn!("find_logins_for_form was passed an invalid origin: {}", e);
+                return Ok(None);
+            }
+        };
+        let Ok(origin) = Url::parse(&origin) else {
+            return Ok(None);
+        };
+        let target = match (form_action_origin, http_realm) {
+            (Some(_), Some(_)) => return Err(InvalidLogin::BothTargets.into()),
+            (None, Some(realm)) => Target::HttpAuth(realm.to_string()),
+            (Some(action), None) => Target::Form(parse_form_action_origin(action)),
+            (None, None) => Target::Form(None),
+        };
+        Ok(Some(Self { origin, target }))
+    }
+
+    /// Check whether `login` matches, returning how its origin matched and its score.
+    pub(crate) fn score(&self, login: &EncryptedLogin) -> Option<(LoginOriginMatch, u32)> {
+        let fields = &login.fields;
+        let target_score = match &self.target {
+            Target::HttpAuth(realm) => {
+                if fields.http_realm.as_ref()? != realm {
+                    return None;
+                }
+                TARGET_EXACT_SCORE
+            }
+            Target::Form(action) => {
+                let login_action = fields.form_action_origin.as_deref()?;
+                match (action, parse_form_action_origin(login_action)) {
+                    // Either side is a wildcard.
+                    (None, _) | (_, None) => TARGET_WILDCARD_SCORE,
+                    (Some(action), Some(login_action)) => {
+                        match compare_origins(action, &login_action)? {
+                            OriginRelation::Same => TARGET_EXACT_SCORE,
+                            OriginRelation::SchemeUpgrade => TARGET_SCHEME_UPGRADE_SCORE,
+                        }
+                    }
+                }
+            }
+        };
+        let origin_match = self.match_origin(&login.fields.origin)?;
+        Some((origin_match, origin_match.score() + target_score))
+    }
+
+    fn match_origin(&self, login_origin: &str) -> Option<LoginOriginMatch> {
+        let login_origin = Url::parse(login_origin).ok()?;
+        if let Some(relation) = compare_origins(&self.origin, &login_origin) {
+            return Some(match relation {
+                OriginRelation::Same => LoginOriginMatch::Exact,
+                OriginRelation::SchemeUpgrade => LoginOriginMatch::SchemeUpgrade,
+            });
+        }
+        if !is_same_site(self.origin.host()?, login_origin.host()?)
+            || self.origin.port() != login_origin.port()
+        {
+            return None;
+        }
+        if self.origin.scheme() == login_origin.scheme() {
+            Some(LoginOriginMatch::Subdomain)
+        } else if is_scheme_upgrade(&self.origin, &login_origin) {
+            Some(LoginOriginMatch::SubdomainSchemeUpgrade)
+        } else {
+            None
+        }
+    }
+}
+
+// Parses a form action origin, returning `None` if it's a wildcard. An action without a host (eg,
+// "" or "javascript:") tells us nothing about where the form is submitted.
+fn parse_form_action_origin(action: &str) -> Option<Url> {
+    Url::parse(action).ok().filter(|url| url.host().is_some())
+}
+
+enum OriginRelation {
+    Same,
+    SchemeUpgrade,
+}
+
+// Compare the origin of `wanted` against `found`, allowing `found` to be the `http:` version of an
+// `https:` `wanted`.
+fn compare_origins(wanted: &Url, found: &Url) -> Option<OriginRelation> {
+    if wanted.host() != found.host() {
+        return None;
+    }
+    if wanted.scheme() == found.scheme() && wanted.port() == found.port() {
+        Some(OriginRelation::Same)
+    } else if is_scheme_upgrade(wanted, found) && wanted.port() == found.port() {
+        Some(OriginRelation::SchemeUpgrade)
+    } else {
+        None
+    }
+}
+
+fn is_scheme_upgrade(wanted: &Url, found: &Url) -> bool {
+    wanted.scheme() == "https" && found.scheme() == "http"
+}
+
+fn is_same_site(a: Host<&str>, b: Host<&str>) -> bool {
+    match (a, b) {
+        (Host::Domain(a), Host::Domain(b)) => {
+            let a = a.strip_prefix("www.").unwrap_or(a);
+            let b = b.strip_prefix("www.").unwrap_or(b);
+            a == b || is_subdomain_of(a, b) || is_subdomain_of(b, a)
+        }
+        // IP addresses only ever match exactly, which `compare_origins()` handles.
+        _ => false,
+    }
+}
+
+fn is_subdomain_of(child: &str, parent: &str) -> bool {
+    // Require the parent to have at least 2 labels, so we never treat a TLD as a site.
+    parent.contains('.')
+        && child
+            .strip_suffix(parent)
+            .is_some_and(|prefix| prefix.ends_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::login::LoginFields;
+
+    fn form_login(origin: &str, form_action_origin: &str) -> EncryptedLogin {
+        EncryptedLogin {
+            fields: LoginFields {
+                origin: origin.into(),
+                form_action_origin: Some(form_action_origin.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn auth_login(origin: &str, realm: &str) -> EncryptedLogin {
+        EncryptedLogin {
+            fields: LoginFields {
+                origin: origin.into(),
+                http_realm: Some(realm.into()),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_origin_matching() {
+        let query = FormQuery::new("https://www.example.com", None, None)
+            .unwrap()
+            .unwrap();
+        let origin_match = |origin: &str| query.score(&form_login(origin, "")).map(|s| s.0);
+        assert_eq!(
+            origin_match("https://www.example.com"),
+            Some(LoginOriginMatch::Exact)
+        );
+        assert_eq!(
+            origin_match("http://www.example.com"),
+            Some(LoginOriginMatch::SchemeUpgrade)
+        );
+        assert_eq!(
+            origin_match("https://login.example.com"),
+            Some(LoginOriginMatch::Subdomain)
+        );
+        assert_eq!(
+            origin_match("https://example.com"),
+            Some(LoginOriginMatch::Subdomain)
+        );
+        assert_eq!(
+            origin_match("http://example.com"),
+            Some(LoginOriginMatch::SubdomainSchemeUpgrade)
+        );
+        assert_eq!(origin_match("https://www.example.com:8443"), None);
+        assert_eq!(origin_match("https://example.org"), None);
+        assert_eq!(origin_match("https://notexample.com"), None);
+
+        // Subdomains rank above scheme upgrades.
+        let score = |origin: &str| query.score(&form_login(origin, "")).unwrap().1;
+        assert!(score("https://www.example.com") > score("https://login.example.com"));
+        assert!(score("https://login.example.com") > score("http://www.example.com"));
+        assert!(score("http://www.example.com") > score("http://example.com"));
+
+        // We never downgrade.
+        let query = FormQuery::new("http://example.com", None, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(query.score(&form_login("https://example.com", "")), None);
+
+        // Sibling domains under a shared suffix aren't the same site.
+        let query = FormQuery::new("https://a.co.uk", None, None)
+            .unwrap()
+            .unwrap();
+        assert_eq!(query.score(&form_login("https://b.co.uk", "")), None);
+    }
+
+    #[test]
+    fn test_target_matching() {
+        let query = FormQuery::new("https://example.com", Some("https://example.com"), None)
+            .unwrap()
+            .unwrap();
+        let target_score = |login: EncryptedLogin| query.score(&login).map(|s| s.1 - 400);
+        assert_eq!(
+            target_score(form_login("https://example.com", "https://example.com")),
+            Some(TARGET_EXACT_SCORE)
+        );
+        assert_eq!(
+            target_score(form_login("https://example.com", "http://example.com")),
+            Some(TARGET_SCHEME_UPGRADE_SCORE)
+        );
+        assert_eq!(
+            target_score(form_login("https://example.com", "")),
+            Some(TARGET_WILDCARD_SCORE)
+        );
+        assert_eq!(
+            target_score(form_login("https://example.com", "javascript:")),
+            Some(TARGET_WILDCARD_SCORE)
+        );
+        assert_eq!(
+            target_score(form_login("https://example.com", "data:text/html,form")),
+            Some(TARGET_WILDCARD_SCORE)
+        );
+
+        // Host-less actions in the query match any login.
+        for action in ["", "javascript:"] {
+            let query = FormQuery::new("https://example.com", Some(action), None)
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                query
+                    .score(&form_login("https://example.com", "https://other.com"))
+                    .map(|s| s.1 - 400),
+                Some(TARGET_WILDCARD_SCORE)
+            );
+        }
+        assert_eq!(
+            target_score(form_login("https://example.com", "https://other.com")),
+            None
+        );
+        assert_eq!(
+            target_score(auth_login("https://example.com", "realm")),
+            None
+        );
+
+        let query = FormQuery::new("https://example.com", None, Some("realm"))
+            .unwrap()
+            .unwrap();
+        assert!(query
+            .score(&auth_login("https://example.com", "realm"))
+            .is_some());
+        assert!(query
+            .score(&auth_login("https://example.com", "other"))
+            .is_none());
+        assert!(query
+            .score(&form_login("https://example.com", ""))
+            .is_none());
+
+        assert!(matches!(
+            FormQuery::new("https://example.com", Some(""), Some("realm")),
+            Err(Error::InvalidLogin(InvalidLogin::BothTargets))
+        ));
+        assert!(FormQuery::new("not a url", None, None).unwrap().is_none());
+    }
+}
@@ -0,0 +1,258 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! # Password health
+//!
+//! Support for `LoginStore::password_health_report()`, which flags passwords that are reused
+//! across origins, weak, or recorded in the breach database (`breachesL`).
+//!
+//! Passwords are only ever decrypted in-process. The report we hand back across the FFI only
+//! contains login ids and flags - never the passwords themselves.
+//!
+//! The weakness checks are deliberately simple heuristics; they aren't intended to be a
+//! replacement for a real strength estimator, just to catch the obvious cases.
+
+use crate::login::Login;
+use std::collections::{HashMap, HashSet};
+
+/// Passwords shorter than this are considered weak.
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+/// Passwords with an estimated entropy below this many bits are considered weak.
+const MIN_ENTROPY_BITS: f64 = 40.0;
+
+/// A small list of the most commonly used passwords, all lowercase. We check against this after
+/// lowercasing and stripping trailing digits and punctuation, so eg "Password123!" matches.
+const COMMON_PASSWORDS: &[&str] = &[
+    "111111",
+    "123123",
+    "123456",
+    "1234567",
+    "12345678",
+    "123456789",
+    "1234567890",
+    "654321",
+    "666666",
+    "696969",
+    "abc123",
+    "access",
+    "admin",
+    "baseball",
+    "batman",
+    "charlie",
+    "dragon",
+    "football",
+    "freedom",
+    "hello",
+    "iloveyou",
+    "letmein",
+    "login",
+    "master",
+    "michael",
+    "monkey",
+    "mustang",
+    "passw0rd",
+    "password",
+    "princess",
+    "qazwsx",
+    "qwerty",
+    "qwertyuiop",
+    "secret",
+    "shadow",
+    "starwars",
+    "sunshine",
+    "superman",
+    "trustno1",
+    "welcome",
+    "whatever",
+    "zaq1zaq1",
+];
+
+/// Why a password is considered weak.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PasswordWeakness {
+    /// The password is shorter than 8 characters.
+    TooShort,
+    /// The password uses too few distinct characters, or too small a set of characters.
+    LowEntropy,
+    /// The password is, or is a trivial variant of, a very commonly used password.
+    Common,
+}
+
+/// The health of a single login's password.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoginPasswordHealth {
+    pub id: String,
+    /// If the password is reused across origins, the index of the group in
+    /// `PasswordHealthReport::reuse_groups` this login belongs to.
+    pub reuse_group: Option<u32>,
+    /// Empty if the password isn't considered weak.
+    pub weaknesses: Vec<PasswordWeakness>,
+    /// The password is in the breach database (see `record_potentially_vulnerable_passwords()`).
+    pub is_potentially_vulnerable: bool,
+}
+
+/// Returned by `LoginStore::password_health_report()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PasswordHealthReport {
+    pub logins: Vec<LoginPasswordHealth>,
+    /// Groups of login ids which share the same password across at least two origins.
+    pub reuse_groups: Vec<Vec<String>>,
+}
+
+impl PasswordHealthReport {
+    /// Build a report for `logins`. `vulnerable_passwords` are the decrypted contents of the breach
+    /// database.
+    pub(crate) fn new(logins: &[Login], vulnerable_passwords: &HashSet<String>) -> Self {
+        let mut by_password: HashMap<&str, Vec<&Login>> = HashMap::new();
+        for login in logins {
+            by_password.entry(&login.password).or_default().push(login);
+        }
+
+        let mut report = Self::default();
+        let mut group_for_password: HashMap<&str, u32> = HashMap::new();
+        for login in logins {
+            let sharing = &by_password[login.password.as_str()];
+            let origins: HashSet<&str> = sharing.iter().map(|l| l.origin.as_str()).collect();
+            let reuse_group = if origins.len() > 1 {
+                // Groups are numbered in the order we first see them.
+                Some(
+                    *group_for_password
+                        .entry(&login.password)
+                        .or_insert_with(|| {
+                            report
+                                .reuse_groups
+                                .push(sharing.iter().map(|l| l.id.clone()).collect());
+                            (report.reuse_groups.len() - 1) as u32
+                        }),
+                )
+            } else {
+                None
+            };
+            report.logins.push(LoginPasswordHealth {
+                id: login.id.clone(),
+                reuse_group,
+                weaknesses: password_weaknesses(&login.password),
+                is_potentially_vulnerable: vulnerable_passwords.contains(&login.password),
+            });
+        }
+        report
+    }
+}
+
+pub(crate) fn password_weaknesses(password: &str) -> Vec<PasswordWeakness> {
+    let mut weaknesses = vec![];
+    if password.chars().count() < MIN_PASSWORD_LENGTH {
+        weaknesses.push(PasswordWeakness::TooShort);
+    }
+    if estimate_entropy_bits(password) < MIN_ENTROPY_BITS {
+        weaknesses.push(PasswordWeakness::LowEntropy);
+    }
+    if is_common(password) {
+        weaknesses.push(PasswordWeakness::Common);
+    }
+    weaknesses
+}
+
+// A rough estimate: the number of distinct characters, times the bits needed to pick each one from
+// the character classes used. Only counting distinct characters means repetition like "aaaaaaaa"
+// or "abcabcabc" doesn't earn any credit.
+fn estimate_entropy_bits(password: &str) -> f64 {
+    let mut pool = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        pool += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        pool += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii_punctuation() || c == ' ')
+    {
+        pool += 33;
+    }
+    if password.chars().any(|c| !c.is_ascii()) {
+        pool += 100;
+    }
+    if pool == 0 {
+        return 0.0;
+    }
+    let distinct = password.chars().collect::<HashSet<_>>().len();
+    distinct as f64 * f64::from(pool).log2()
+}
+
+fn is_common(password: &str) -> bool {
+    let lower = password.to_lowercase();
+    let stripped = lower.trim_end_matches(|c: char| c.is_ascii_digit() || c.is_ascii_punctuation());
+    COMMON_PASSWORDS.contains(&lower.as_str())
+        || (!stripped.is_empty() && COMMON_PASSWORDS.contains(&stripped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login(id: &str, origin: &str, password: &str) -> Login {
+        Login {
+            id: id.into(),
+            origin: origin.into(),
+            password: password.into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_password_weaknesses() {
+        assert_eq!(
+            password_weaknesses("abc"),
+            vec![PasswordWeakness::TooShort, PasswordWeakness::LowEntropy]
+        );
+        assert_eq!(
+            password_weaknesses("aaaaaaaaaaaaaaaaaaaa"),
+            vec![PasswordWeakness::LowEntropy]
+        );
+        assert_eq!(
+            password_weaknesses("Password123!"),
+            vec![PasswordWeakness::Common]
+        );
+        assert_eq!(
+            password_weaknesses("123456"),
+            vec![
+                PasswordWeakness::TooShort,
+                PasswordWeakness::LowEntropy,
+                PasswordWeakness::Common
+            ]
+        );
+        assert!(password_weaknesses("correct horse battery staple").is_empty());
+        assert!(password_weaknesses("Tr0ub4dor&3x").is_empty());
+        assert!(password_weaknesses("Zß8ü!kQ2").is_empty());
+    }
+
+    #[test]
+    fn test_reuse_groups() {
+        let logins = [
+            login("a", "https://a.example.com", "shared-Passw0rd!"),
+            login("b", "https://b.example.com", "shared-Passw0rd!"),
+            // Same password, but the same origin as "c" - not reuse on its own.
+            login("c", "https://c.example.com", "other-Passw0rd!"),
+            login("d", "https://c.example.com", "other-Passw0rd!"),
+            login("e", "https://e.example.com", "unique-Passw0rd!"),
+        ];
+        let vulnerable = HashSet::from(["unique-Passw0rd!".to_string()]);
+        let report = PasswordHealthReport::new(&logins, &vulnerable);
+        assert_eq!(report.reuse_groups, vec![vec!["a", "b"]]);
+        let groups: Vec<_> = report.logins.iter().map(|h| h.reuse_group).collect();
+        assert_eq!(groups, vec![Some(0), Some(0), None, None, None]);
+        let vulnerable: Vec<_> = report
+            .logins
+            .iter()
+            .filter(|h| h.is_potentially_vulnerable)
+            .map(|h| h.id.as_str())
+            .collect();
+        assert_eq!(vulnerable, vec!["e"]);
+    }
+}
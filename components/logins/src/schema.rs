@@ -6,12 +6,15 @@
 //! ================
 //!
 //! The schema we use is a evolution of the firefox-ios logins database format.
-//! There are three tables:
+//! There are three main tables:
 //!
 //! - `loginsL`: The local table.
 //! - `loginsM`: The mirror table.
 //! - `loginsSyncMeta`: The table used to to store various sync metadata.
 //!
+//! As well as `breachesL` (see below), `trashL`, which holds recently
+//! deleted logins while the trash is enabled, and `passwordHistoryL`.
+//!
 //! ## `loginsL`
 //!
 //! This stores local login information, also known as the "overlay".
@@ -81,6 +84,34 @@
 //! Currently it is used to store the last sync timestamp, under [LAST_SYNC_META_KEY], a
 //!    `sync15::ServerTimestamp` stored in integer milliseconds.
 //!
+//! It also stores the trash retention window, under [TRASH_RETENTION_META_KEY],
+//! and the maximum number of previous passwords kept per login, under
+//! [PASSWORD_HISTORY_LIMIT_META_KEY]. Each feature is disabled when its key is
+//! absent.
+//!
+//! ## `trashL`
+//!
+//! A copy of each login at the time it was deleted, so it can be restored. It is
+//! local-only and never synced; deleting a login still writes a tombstone to
+//! `loginsL` as usual.
+//!
+//! ### `trashL` Columns
+//!
+//! Contains all fields in [COMMON_COLS], as well as:
+//!
+//! - `time_deleted`: A millisecond local timestamp of the deletion.
+//!
+//! - `is_mirror_only`: A boolean indicating whether the login only existed in
+//!   `loginsM` (ie, had no local changes) when it was deleted. Restoring such a
+//!   login before its tombstone is uploaded just drops the tombstone and uses the
+//!   mirror again, so nothing is synced at all.
+//!
+//! ## `passwordHistoryL`
+//!
+//! The previous passwords of each login, encrypted, along with when they were
+//! replaced. Rows are only added by local updates while the history is enabled,
+//! are trimmed to the configured limit, and are never synced.
+//!
 
 use crate::error::*;
 use lazy_static::lazy_static;
@@ -92,7 +123,9 @@ use sql_support::ConnExt;
 /// Version 3: addition of `timeOfLastBreach` and `timeLastBreachAlertDismissed`.
 /// Version 4: addition of `breachesL` table
 /// Version 5: removal of `timeOfLastBreach`.
-pub(super) const VERSION: i64 = 5;
+/// Version 6: addition of `trashL` table
+/// Version 7: addition of `passwordHistoryL` table
+pub(super) const VERSION: i64 = 7;
 
 /// Every column shared by both tables except for `id`
 ///
@@ -169,6 +202,14 @@ lazy_static! {
         )",
         common_sql = COMMON_SQL
     );
+    static ref CREATE_TRASH_TABLE_SQL: String = format!(
+        "CREATE TABLE IF NOT EXISTS trashL (
+            {common_sql},
+            time_deleted   INTEGER NOT NULL,
+            is_mirror_only TINYINT NOT NULL DEFAULT 0
+        )",
+        common_sql = COMMON_SQL
+    );
     static ref SET_VERSION_SQL: String =
         format!("PRAGMA user_version = {version}", version = VERSION);
 }
@@ -208,9 +249,26 @@ const CREATE_LOCAL_BREACHES_TABLE_SQL: &str = "
     )
 ";
 
+const CREATE_PASSWORD_HISTORY_TABLE_SQL: &str = "
+    CREATE TABLE IF NOT EXISTS passwordHistoryL (
+        id                INTEGER PRIMARY KEY AUTOINCREMENT,
+        guid              TEXT NOT NULL,
+        encryptedPassword TEXT NOT NULL,
+        -- Milliseconds; when this password was replaced.
+        timeChanged       INTEGER NOT NULL
+    )
+";
+
+const CREATE_PASSWORD_HISTORY_GUID_INDEX_SQL: &str = "
+    CREATE INDEX IF NOT EXISTS idx_passwordHistoryL_guid
+    ON passwordHistoryL (guid)
+";
+
 pub(crate) static LAST_SYNC_META_KEY: &str = "last_sync_time";
 pub(crate) static GLOBAL_SYNCID_META_KEY: &str = "global_sync_id";
 pub(crate) static COLLECTION_SYNCID_META_KEY: &str = "passwords_sync_id";
+pub(crate) static TRASH_RETENTION_META_KEY: &str = "trash_retention_ms";
+pub(crate) static PASSWORD_HISTORY_LIMIT_META_KEY: &str = "password_history_limit";
 
 pub(crate) fn init(db: &Connection) -> Result<()> {
     let user_version = db.conn_ext_query_one::<i64>("PRAGMA user_version")?;
@@ -272,6 +330,13 @@ fn upgrade_from(db: &Connection, from: i64) -> Result<()> {
         ALTER TABLE loginsM DROP COLUMN timeOfLastBreach;",
         )?),
 
+        5 => Ok(db.execute_batch(&CREATE_TRASH_TABLE_SQL)?),
+
+        6 => Ok(db.execute_all(&[
+            CREATE_PASSWORD_HISTORY_TABLE_SQL,
+            CREATE_PASSWORD_HISTORY_GUID_INDEX_SQL,
+        ])?),
+
         // next migration, add here
         _ => Err(Error::IncompatibleVersion(from)),
     }
@@ -286,6 +351,9 @@ pub(crate) fn create(db: &Connection) -> Result<()> {
         CREATE_DELETED_ORIGIN_INDEX_SQL,
         CREATE_META_TABLE_SQL,
         CREATE_LOCAL_BREACHES_TABLE_SQL,
+        &*CREATE_TRASH_TABLE_SQL,
+        CREATE_PASSWORD_HISTORY_TABLE_SQL,
+        CREATE_PASSWORD_HISTORY_GUID_INDEX_SQL,
         &*SET_VERSION_SQL,
     ])?;
     Ok(())
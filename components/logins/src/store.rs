@@ -1,11 +1,15 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
-use crate::db::{LoginDb, LoginsDeletionMetrics};
+use crate::csv::CsvImportReport;
+use crate::db::{DeletedLogin, LoginDb, LoginsDeletionMetrics, PasswordHistoryEntry};
 use crate::encryption::EncryptorDecryptor;
 use crate::error::*;
 use crate::login::{BulkResultEntry, EncryptedLogin, Login, LoginEntry, LoginEntryWithMeta};
+use crate::matching::LoginFormMatch;
+use crate::password_health::PasswordHealthReport;
 use crate::LoginsSyncEngine;
+use interrupt_support::SqlInterruptHandle;
 use parking_lot::Mutex;
 use sql_support::run_maintenance;
 use std::path::Path;
@@ -78,25 +82,30 @@ fn map_bulk_result_entry(
 
 pub struct LoginStore {
     pub db: Mutex<Option<LoginDb>>,
+    // Kept outside of the mutex so we can interrupt long-running operations (which are holding
+    // the lock) from another thread.
+    interrupt_handle: Arc<SqlInterruptHandle>,
 }
 
 impl LoginStore {
     #[handle_error(Error)]
     pub fn new(path: impl AsRef<Path>, encdec: Arc<dyn EncryptorDecryptor>) -> ApiResult<Self> {
-        let db = Mutex::new(Some(LoginDb::open(path, encdec)?));
-        Ok(Self { db })
+        Ok(Self::new_from_db(LoginDb::open(path, encdec)?))
     }
 
     pub fn new_from_db(db: LoginDb) -> Self {
+        let interrupt_handle = db.new_interrupt_handle();
         let db = Mutex::new(Some(db));
-        Self { db }
+        Self {
+            db,
+            interrupt_handle,
+        }
     }
 
     // Only used for tests, but it's `pub` the `sync-test` crate uses it.
     #[cfg(test)]
     pub fn new_in_memory() -> Self {
-        let db = Mutex::new(Some(LoginDb::open_in_memory()));
-        Self { db }
+        Self::new_from_db(LoginDb::open_in_memory())
     }
 
     pub fn lock_db(&self) -> Result<parking_lot::MappedMutexGuard<'_, LoginDb>> {
@@ -159,6 +168,20 @@ impl LoginStore {
         })
     }
 
+    #[handle_error(Error)]
+    pub fn find_logins_for_form(
+        &self,
+        origin: &str,
+        form_action_origin: Option<String>,
+        http_realm: Option<String>,
+    ) -> ApiResult<Vec<LoginFormMatch>> {
+        self.lock_db()?.find_logins_for_form(
+            origin,
+            form_action_origin.as_deref(),
+            http_realm.as_deref(),
+        )
+    }
+
     #[handle_error(Error)]
     pub fn has_logins_by_base_domain(&self, base_domain: &str) -> ApiResult<bool> {
         self.lock_db()?
@@ -197,6 +220,11 @@ impl LoginStore {
         db.record_potentially_vulnerable_passwords(passwords)
     }
 
+    #[handle_error(Error)]
+    pub fn password_health_report(&self) -> ApiResult<PasswordHealthReport> {
+        self.lock_db()?.password_health_report()
+    }
+
     #[handle_error(Error)]
     pub fn reset_all_breaches(&self) -> ApiResult<()> {
         self.lock_db()?.reset_all_breaches()
@@ -226,6 +254,46 @@ impl LoginStore {
         self.lock_db()?.delete_many(ids)
     }
 
+    #[handle_error(Error)]
+    pub fn get_password_history_limit(&self) -> ApiResult<Option<u32>> {
+        self.lock_db()?.get_password_history_limit()
+    }
+
+    #[handle_error(Error)]
+    pub fn set_password_history_limit(&self, limit: Option<u32>) -> ApiResult<()> {
+        self.lock_db()?.set_password_history_limit(limit)
+    }
+
+    #[handle_error(Error)]
+    pub fn get_password_history(&self, id: &str) -> ApiResult<Vec<PasswordHistoryEntry>> {
+        self.lock_db()?.get_password_history(id)
+    }
+
+    #[handle_error(Error)]
+    pub fn get_trash_retention(&self) -> ApiResult<Option<i64>> {
+        self.lock_db()?.get_trash_retention()
+    }
+
+    #[handle_error(Error)]
+    pub fn set_trash_retention(&self, retention_ms: Option<i64>) -> ApiResult<()> {
+        self.lock_db()?.set_trash_retention(retention_ms)
+    }
+
+    #[handle_error(Error)]
+    pub fn list_deleted(&self) -> ApiResult<Vec<DeletedLogin>> {
+        self.lock_db()?.list_deleted()
+    }
+
+    #[handle_error(Error)]
+    pub fn restore(&self, id: &str) -> ApiResult<bool> {
+        self.lock_db()?.restore(id)
+    }
+
+    #[handle_error(Error)]
+    pub fn purge_deleted(&self, older_than: i64) -> ApiResult<u64> {
+        Ok(self.lock_db()?.purge_deleted(older_than)? as u64)
+    }
+
     #[handle_error(Error)]
     pub fn delete_all(&self) -> ApiResult<Vec<String>> {
         self.lock_db()?.delete_all()
@@ -333,11 +401,43 @@ impl LoginStore {
             .and_then(|enc_login| enc_login.decrypt(db.encdec.as_ref()))
     }
 
+    /// Import logins from CSV data exported by a browser, returning what happened to each row.
+    #[handle_error(Error)]
+    pub fn import_csv(&self, data: &str) -> ApiResult<CsvImportReport> {
+        self.lock_db()?.import_csv(data)
+    }
+
+    #[handle_error(Error)]
+    pub fn export_csv(&self) -> ApiResult<String> {
+        self.lock_db()?.export_csv()
+    }
+
+    /// Re-encrypt all logins data with `new_encdec`, which is used from then on.
+    ///
+    /// `old_encdec` must be able to decrypt the existing data. This happens in a single
+    /// transaction, so if it fails, or is interrupted via `interrupt()`, the database is left
+    /// untouched and continues to use the old key.
+    #[handle_error(Error)]
+    pub fn rekey(
+        &self,
+        old_encdec: Arc<dyn EncryptorDecryptor>,
+        new_encdec: Arc<dyn EncryptorDecryptor>,
+    ) -> ApiResult<()> {
+        self.lock_db()?.rekey(old_encdec, new_encdec)
+    }
+
+    /// Interrupt any current long-running operation, such as `rekey()`, which will fail with
+    /// `LoginsApiError::Interrupted`.
+    pub fn interrupt(&self) {
+        self.interrupt_handle.interrupt();
+    }
+
     #[handle_error(Error)]
     pub fn run_maintenance(&self, options: Option<RunMaintenanceOptions>) -> ApiResult<()> {
         let conn = self.lock_db()?;
         let options = options.unwrap_or_default();
         run_maintenance(&conn)?;
+        conn.purge_expired_deleted()?;
         if options.delete_undecryptable_records_for_remote_replacement {
             conn.delete_undecryptable_records_for_remote_replacement()?;
         }
@@ -785,6 +785,52 @@ mod tests {
         assert!(changes["remote-readded-redeleted"].get("deleted").is_some());
     }
 
+    #[test]
+    fn test_sync_restore_from_trash() {
+        ensure_initialized();
+        let store = Arc::new(LoginStore::new_in_memory());
+        let engine = LoginsSyncEngine::new(store.clone()).unwrap();
+        {
+            let db = store.lock_db().unwrap();
+            db.set_trash_retention(Some(24 * 60 * 60 * 1000)).unwrap();
+            insert_login(&db, "mirror-only", None, Some("password"));
+            insert_login(&db, "changed", Some("new-password"), Some("password"));
+            insert_login(&db, "added", Some("password"), None);
+            insert_login(&db, "uploaded", None, Some("password"));
+            db.delete_many(vec!["mirror-only", "changed", "added", "uploaded"])
+                .unwrap();
+        }
+        // Upload the tombstone for one of them before restoring.
+        engine
+            .mark_as_synchronized(&["uploaded"], ServerTimestamp(1000))
+            .unwrap();
+        {
+            let db = store.lock_db().unwrap();
+            for id in ["mirror-only", "changed", "added", "uploaded"] {
+                assert!(db.restore(id).unwrap());
+            }
+        }
+
+        let changes: HashMap<String, serde_json::Value> = engine
+            .fetch_outgoing()
+            .unwrap()
+            .into_iter()
+            .map(|b| {
+                (
+                    b.envelope.id.to_string(),
+                    serde_json::from_str(&b.payload).unwrap(),
+                )
+            })
+            .collect();
+        // No tombstones, and the untouched mirror-only login needn't be uploaded at all.
+        assert_eq!(changes.len(), 3);
+        assert!(changes.values().all(|c| c.get("deleted").is_none()));
+        assert_eq!(changes["changed"].get("password").unwrap(), "new-password");
+        assert_eq!(changes["added"].get("password").unwrap(), "password");
+        assert_eq!(changes["uploaded"].get("password").unwrap(), "password");
+        assert!(store.get("mirror-only").unwrap().is_some());
+    }
+
     #[test]
     fn test_fetch_outgoing() {
         ensure_initialized();
@@ -99,6 +99,8 @@ fn new_test_client_internal(
         }),
         bucket_name: None,
         app_context: Some(rs_ctx),
+        dumps_directory: None,
+        attachment_cache_max_bytes: None,
     };
     let storage_dir = tmp_dir
         .path()
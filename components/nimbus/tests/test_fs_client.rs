@@ -36,6 +36,8 @@ fn test_simple() -> Result<()> {
         }),
         bucket_name: None,
         app_context: Some(RemoteSettingsContext::default()),
+        dumps_directory: None,
+        attachment_cache_max_bytes: None,
     };
     let remote_settings_service = RemoteSettingsService::new("tests".to_string(), config);
     let tmp_dir = tempfile::tempdir()?;
@@ -281,3 +281,69 @@ CREATE TABLE IF NOT EXISTS moz_places_metadata_search_queries (
     id INTEGER PRIMARY KEY,
     term TEXT NOT NULL UNIQUE
 );
+
+----------------------------------------------------------------------
+--------------------History Search------------------------------------
+----------------------------------------------------------------------
+
+-- A full-text index over page titles, URLs, and the search terms that led to
+-- each page, for `search_history()`. The rowid is the `moz_places` id. This is
+-- kept up to date by triggers in create_shared_triggers.sql.
+CREATE VIRTUAL TABLE IF NOT EXISTS moz_places_fts USING FTS5(
+    title,
+    url,
+    search_terms,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+----------------------------------------------------------------------
+--------------------Favicons------------------------------------------
+----------------------------------------------------------------------
+
+-- Pages we have icons for. These aren't tied to moz_places, as apps often
+-- learn a page's icon before the visit is recorded, but pages which are no
+-- longer in history are pruned by `run_maintenance_prune()`.
+CREATE TABLE IF NOT EXISTS moz_pages_w_icons (
+    id INTEGER PRIMARY KEY,
+    page_url TEXT NOT NULL UNIQUE,
+    page_url_hash INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS moz_pages_w_icons_urlhashindex ON moz_pages_w_icons(page_url_hash);
+
+-- A page can have one icon of each width.
+CREATE TABLE IF NOT EXISTS moz_icons (
+    id INTEGER PRIMARY KEY,
+    page_id INTEGER NOT NULL,
+    width INTEGER NOT NULL,
+    mime_type TEXT NOT NULL,
+    data BLOB NOT NULL,
+
+    FOREIGN KEY(page_id) REFERENCES moz_pages_w_icons(id) ON DELETE CASCADE,
+    UNIQUE(page_id, width)
+);
+
+----------------------------------------------------------------------
+--------------------Annotations---------------------------------------
+----------------------------------------------------------------------
+
+-- Small, named values apps attach to pages. They're removed with the page's
+-- history, and those with `expires_after_days` are also removed by
+-- `run_maintenance_prune()` that long after they were last set.
+-- `content_type` says how to read `content`; see `AnnotationValue`.
+CREATE TABLE IF NOT EXISTS moz_annos (
+    id INTEGER PRIMARY KEY,
+    place_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    content_type INTEGER NOT NULL,
+    content NOT NULL,
+    expires_after_days INTEGER,
+    exclude_from_sync INTEGER NOT NULL DEFAULT 0,
+    date_added INTEGER NOT NULL,
+    last_modified INTEGER NOT NULL,
+
+    FOREIGN KEY(place_id) REFERENCES moz_places(id) ON DELETE CASCADE,
+    UNIQUE(place_id, name)
+);
+
+CREATE INDEX IF NOT EXISTS moz_annos_nameindex ON moz_annos(name);
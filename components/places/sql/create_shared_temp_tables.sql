@@ -44,3 +44,22 @@ CREATE TEMP TABLE moz_updateoriginsupdate_temp (
     frecency_delta INTEGER NOT NULL,
     PRIMARY KEY (prefix, host)
 ) WITHOUT ROWID;
+
+-- This table collects changes to history and bookmarks for observers, via the
+-- triggers at the end of create_shared_triggers.sql. Because it's written in
+-- the same transaction as the changes themselves, a rollback forgets them too.
+-- Rows are read and removed once the transaction commits; see `changes.rs`.
+CREATE TEMP TABLE moz_places_changes (
+    id INTEGER PRIMARY KEY,
+    kind INTEGER NOT NULL,
+    url TEXT,
+    title TEXT,
+    guid TEXT,
+    parent_guid TEXT,
+    old_parent_guid TEXT,
+    position INTEGER,
+    visit_date INTEGER,
+    visit_type INTEGER,
+    is_local INTEGER,
+    frecency INTEGER
+);
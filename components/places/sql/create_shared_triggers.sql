@@ -422,3 +422,139 @@ BEGIN
         SELECT id FROM moz_places_metadata pm WHERE pm.search_query_id = OLD.search_query_id
     );
 END;
+
+-- These triggers keep `moz_places_fts` in sync with `moz_places` and the
+-- history metadata search terms.
+CREATE TEMP TRIGGER moz_places_afterinsert_trigger_fts
+AFTER INSERT ON moz_places FOR EACH ROW
+BEGIN
+    INSERT INTO moz_places_fts(rowid, title, url, search_terms)
+    VALUES (NEW.id, NEW.title, NEW.url, '');
+END;
+
+CREATE TEMP TRIGGER moz_places_afterupdate_trigger_fts
+AFTER UPDATE OF title, url ON moz_places FOR EACH ROW
+BEGIN
+    UPDATE moz_places_fts SET
+        title = NEW.title,
+        url = NEW.url
+    WHERE rowid = NEW.id;
+END;
+
+CREATE TEMP TRIGGER moz_places_afterdelete_trigger_fts
+AFTER DELETE ON moz_places FOR EACH ROW
+BEGIN
+    DELETE FROM moz_places_fts WHERE rowid = OLD.id;
+END;
+
+CREATE TEMP TRIGGER moz_places_metadata_afterinsert_trigger_fts
+AFTER INSERT ON moz_places_metadata FOR EACH ROW
+WHEN NEW.search_query_id IS NOT NULL
+BEGIN
+    {update_new_fts_search_terms};
+END;
+
+CREATE TEMP TRIGGER moz_places_metadata_afterupdate_trigger_fts
+AFTER UPDATE OF search_query_id ON moz_places_metadata FOR EACH ROW
+BEGIN
+    {update_new_fts_search_terms};
+END;
+
+CREATE TEMP TRIGGER moz_places_metadata_afterdelete_trigger_fts
+AFTER DELETE ON moz_places_metadata FOR EACH ROW
+WHEN OLD.search_query_id IS NOT NULL
+BEGIN
+    {update_old_fts_search_terms};
+END;
+
+-- These triggers record changes for observers in moz_places_changes, but only
+-- when the connection's API has an observer registered.
+CREATE TEMP TRIGGER moz_historyvisits_afterinsert_trigger_changes
+AFTER INSERT ON moz_historyvisits FOR EACH ROW
+WHEN has_change_observer()
+BEGIN
+    INSERT INTO moz_places_changes(kind, url, visit_date, visit_type, is_local)
+    SELECT {visit_added}, h.url, NEW.visit_date, NEW.visit_type, NEW.is_local
+    FROM moz_places h
+    WHERE h.id = NEW.place_id;
+END;
+
+CREATE TEMP TRIGGER moz_places_afterupdate_title_trigger_changes
+AFTER UPDATE OF title ON moz_places FOR EACH ROW
+WHEN NEW.title IS NOT OLD.title AND has_change_observer()
+BEGIN
+    INSERT INTO moz_places_changes(kind, url, title)
+    VALUES ({title_changed}, NEW.url, NEW.title);
+END;
+
+CREATE TEMP TRIGGER moz_places_afterupdate_frecency_trigger_changes
+AFTER UPDATE OF frecency ON moz_places FOR EACH ROW
+WHEN NEW.frecency != OLD.frecency AND has_change_observer()
+BEGIN
+    INSERT INTO moz_places_changes(kind, url, frecency)
+    VALUES ({frecency_changed}, NEW.url, NEW.frecency);
+END;
+
+-- The bookmark sync engine inserts new items under the root with a position
+-- of -1, and moves them into place when it applies the new structure. We
+-- report those as insertions once they're in place, below.
+CREATE TEMP TRIGGER moz_bookmarks_afterinsert_trigger_changes
+AFTER INSERT ON moz_bookmarks FOR EACH ROW
+WHEN NEW.position >= 0 AND has_change_observer()
+BEGIN
+    INSERT INTO moz_places_changes(kind, guid, parent_guid, position, url)
+    VALUES (
+        {bookmark_inserted},
+        NEW.guid,
+        (SELECT guid FROM moz_bookmarks WHERE id = NEW.parent),
+        NEW.position,
+        (SELECT url FROM moz_places WHERE id = NEW.fk)
+    );
+END;
+
+-- Only statements which set the parent are moves. Those which just set the
+-- position are shifting siblings to make room for, or close the gap left by,
+-- an insertion, removal or move, which we don't report.
+CREATE TEMP TRIGGER moz_bookmarks_afterupdate_parent_trigger_changes
+AFTER UPDATE OF parent ON moz_bookmarks FOR EACH ROW
+WHEN (NEW.parent IS NOT OLD.parent OR NEW.position != OLD.position)
+     AND OLD.position >= 0
+     AND has_change_observer()
+BEGIN
+    INSERT INTO moz_places_changes(kind, guid, old_parent_guid, parent_guid, position)
+    VALUES (
+        {bookmark_moved},
+        NEW.guid,
+        (SELECT guid FROM moz_bookmarks WHERE id = OLD.parent),
+        (SELECT guid FROM moz_bookmarks WHERE id = NEW.parent),
+        NEW.position
+    );
+END;
+
+CREATE TEMP TRIGGER moz_bookmarks_afterupdate_synced_insert_trigger_changes
+AFTER UPDATE OF parent ON moz_bookmarks FOR EACH ROW
+WHEN OLD.position < 0 AND NEW.position >= 0 AND has_change_observer()
+BEGIN
+    INSERT INTO moz_places_changes(kind, guid, parent_guid, position, url)
+    VALUES (
+        {bookmark_inserted},
+        NEW.guid,
+        (SELECT guid FROM moz_bookmarks WHERE id = NEW.parent),
+        NEW.position,
+        (SELECT url FROM moz_places WHERE id = NEW.fk)
+    );
+END;
+
+-- The parent may be gone already if it's being removed too.
+CREATE TEMP TRIGGER moz_bookmarks_afterdelete_trigger_changes
+AFTER DELETE ON moz_bookmarks FOR EACH ROW
+WHEN has_change_observer()
+BEGIN
+    INSERT INTO moz_places_changes(kind, guid, parent_guid, url)
+    VALUES (
+        {bookmark_removed},
+        OLD.guid,
+        (SELECT guid FROM moz_bookmarks WHERE id = OLD.parent),
+        (SELECT url FROM moz_places WHERE id = OLD.fk)
+    );
+END;
@@ -2,9 +2,11 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::bookmark_sync::BookmarksSyncEngine;
+use crate::bookmark_sync::{BookmarksMergePreview, BookmarksSyncEngine};
+use crate::changes::{self, PlacesChangeObserver};
 use crate::db::db::{PlacesDb, SharedPlacesDb};
 use crate::error::*;
+use crate::frecency::{self, FrecencySettings};
 use crate::history_sync::HistorySyncEngine;
 use crate::util::normalize_path;
 use error_support::handle_error;
@@ -263,6 +265,40 @@ impl PlacesApi {
     pub fn register_with_sync_manager(self: Arc<Self>) {
         *PLACES_API_FOR_SYNC_MANAGER.lock() = Arc::downgrade(&self);
     }
+
+    /// Registers an observer for changes committed by any of this API's
+    /// connections, replacing any existing one.
+    pub fn register_change_observer(&self, observer: Box<dyn PlacesChangeObserver>) {
+        changes::register_observer(self.id, observer);
+    }
+
+    pub fn unregister_change_observer(&self) {
+        changes::unregister_observer(self.id);
+    }
+
+    /// Changes the frecency settings used by all of this API's connections.
+    /// Frecencies calculated before the change are kept until
+    /// `recalculate_all_frecencies()` is called.
+    #[handle_error(crate::Error)]
+    pub fn set_frecency_settings(&self, settings: FrecencySettings) -> ApiResult<()> {
+        frecency::set_settings(self.id, settings)
+    }
+
+    /// Returns what the next bookmark sync would change, based on the records
+    /// downloaded by the last one, without changing anything. Used to
+    /// diagnose sync problems.
+    #[handle_error(crate::Error)]
+    pub fn bookmarks_preview_sync_merge(&self) -> ApiResult<BookmarksMergePreview> {
+        let engine = BookmarksSyncEngine::new(self.get_sync_connection()?)?;
+        engine.preview_merge()
+    }
+}
+
+impl Drop for PlacesApi {
+    fn drop(&mut self) {
+        changes::unregister_observer(self.id);
+        frecency::clear_settings(self.id);
+    }
 }
 
 #[cfg(test)]
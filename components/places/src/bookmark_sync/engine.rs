@@ -10,13 +10,12 @@ use super::record::{
 use super::{SyncedBookmarkKind, SyncedBookmarkValidity};
 use crate::db::{GlobalChangeCounterTracker, PlacesDb, SharedPlacesDb};
 use crate::error::*;
-use crate::frecency::{calculate_frecency, DEFAULT_FRECENCY_SETTINGS};
 use crate::storage::{
     bookmarks::{
         bookmark_sync::{create_synced_bookmark_roots, reset},
         BookmarkRootGuid,
     },
-    delete_pending_temp_tables, get_meta, put_meta,
+    delete_pending_temp_tables, get_meta, put_meta, update_stale_frecencies,
 };
 use crate::types::{BookmarkType, SyncStatus, UnknownFields};
 use dogear::{
@@ -43,12 +42,6 @@ pub const GLOBAL_SYNCID_META_KEY: &str = "bookmarks_global_sync_id";
 pub const COLLECTION_SYNCID_META_KEY: &str = "bookmarks_sync_id";
 pub const COLLECTION_NAME: &str = "bookmarks";
 
-/// The maximum number of URLs for which to recalculate frecencies at once.
-/// This is a trade-off between write efficiency and transaction time: higher
-/// maximums mean fewer write statements, but longer transactions, possibly
-/// blocking writes from other connections.
-const MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK: usize = 400;
-
 /// Adapts an interruptee to a Dogear abort signal.
 struct MergeInterruptee<'a>(&'a SqlInterruptScope);
 
@@ -976,75 +969,6 @@ fn push_synced_items(
     Ok(())
 }
 
-pub(crate) fn update_frecencies(db: &PlacesDb, scope: &SqlInterruptScope) -> Result<()> {
-    let mut tx = db.begin_transaction()?;
-
-    let mut frecencies = Vec::with_capacity(MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK);
-    loop {
-        let sql = format!(
-            "SELECT place_id FROM moz_places_stale_frecencies
-             ORDER BY stale_at DESC
-             LIMIT {}",
-            MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK
-        );
-        let mut stmt = db.prepare_maybe_cached(&sql, true)?;
-        let mut results = stmt.query([])?;
-        while let Some(row) = results.next()? {
-            let place_id = row.get("place_id")?;
-            // Frecency recalculation runs several statements, so check to
-            // make sure we aren't interrupted before each calculation.
-            scope.err_if_interrupted()?;
-            let frecency =
-                calculate_frecency(db, &DEFAULT_FRECENCY_SETTINGS, place_id, Some(false))?;
-            frecencies.push((place_id, frecency));
-        }
-        if frecencies.is_empty() {
-            break;
-        }
-
-        // Update all frecencies in one fell swoop...
-        db.execute_batch(&format!(
-            "WITH frecencies(id, frecency) AS (
-               VALUES {}
-             )
-             UPDATE moz_places SET
-               frecency = (SELECT frecency FROM frecencies f
-                           WHERE f.id = id)
-             WHERE id IN (SELECT f.id FROM frecencies f)",
-            sql_support::repeat_display(frecencies.len(), ",", |index, f| {
-                let (id, frecency) = frecencies[index];
-                write!(f, "({}, {})", id, frecency)
-            })
-        ))?;
-        tx.maybe_commit()?;
-        scope.err_if_interrupted()?;
-
-        // ...And remove them from the stale table.
-        db.execute_batch(&format!(
-            "DELETE FROM moz_places_stale_frecencies
-             WHERE place_id IN ({})",
-            sql_support::repeat_display(frecencies.len(), ",", |index, f| {
-                let (id, _) = frecencies[index];
-                write!(f, "{}", id)
-            })
-        ))?;
-        tx.maybe_commit()?;
-        scope.err_if_interrupted()?;
-
-        // If the query returned fewer URLs than the maximum, we're done.
-        // Otherwise, we might have more, so clear the ones we just
-        // recalculated and fetch the next chunk.
-        if frecencies.len() < MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK {
-            break;
-        }
-        frecencies.clear();
-    }
-
-    tx.commit()?;
-
-    Ok(())
-}
-
 // Short-lived struct that's constructed each sync
 pub struct BookmarksSyncEngine {
     db: Arc<SharedPlacesDb>,
@@ -1060,6 +984,14 @@ impl BookmarksSyncEngine {
             db,
         })
     }
+
+    /// Merges the local tree with the incoming records staged in the mirror,
+    /// and returns what the next sync would change, without changing
+    /// anything.
+    pub fn preview_merge(&self) -> Result<BookmarksMergePreview> {
+        let conn = self.db.lock();
+        Merger::preview(&conn, &self.scope)
+    }
 }
 
 impl SyncEngine for BookmarksSyncEngine {
@@ -1105,7 +1037,8 @@ impl SyncEngine for BookmarksSyncEngine {
     ) -> anyhow::Result<()> {
         let conn = self.db.lock();
         push_synced_items(&conn, &self.scope, new_timestamp, ids)?;
-        Ok(update_frecencies(&conn, &self.scope)?)
+        update_stale_frecencies(&conn, &self.scope)?;
+        Ok(())
     }
 
     fn sync_finished(&self) -> anyhow::Result<()> {
@@ -1219,6 +1152,96 @@ impl dogear::Driver for Driver {
     }
 }
 
+/// What syncing bookmarks would change, from `bookmarks_preview_sync_merge()`.
+/// This reflects the records already downloaded, so it doesn't include
+/// changes on the server since the last sync.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BookmarksMergePreview {
+    /// Remote items which would be added locally.
+    pub local_additions: Vec<SyncGuid>,
+    /// Local items which would be updated with remote changes.
+    pub local_updates: Vec<SyncGuid>,
+    /// Local items which would be moved to a different parent or position.
+    pub local_moves: Vec<SyncGuid>,
+    pub local_deletions: Vec<SyncGuid>,
+    /// Local items which would be uploaded for the first time.
+    pub remote_additions: Vec<SyncGuid>,
+    /// Items which would be uploaded with local changes, including moves.
+    pub remote_updates: Vec<SyncGuid>,
+    pub remote_deletions: Vec<SyncGuid>,
+    /// Items changed on both sides.
+    pub conflicts: Vec<BookmarkMergeConflict>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BookmarkMergeConflict {
+    pub guid: SyncGuid,
+    pub resolution: BookmarkConflictResolution,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BookmarkConflictResolution {
+    /// The local item was newer, so it would be uploaded.
+    KeptLocal,
+    /// The remote item was newer, so it would be applied locally.
+    TookRemote,
+}
+
+impl BookmarksMergePreview {
+    fn from_ops(ops: &CompletionOps<'_>) -> Self {
+        let guid = |guid: &dogear::Guid| SyncGuid::from(guid.as_str());
+        let mut preview = Self::default();
+        for op in &ops.apply_remote_items {
+            let merged_guid = guid(&op.merged_node.guid);
+            match op.merged_node.merge_state.local_node() {
+                Some(local_node) => {
+                    if local_node.needs_merge {
+                        preview.conflicts.push(BookmarkMergeConflict {
+                            guid: merged_guid.clone(),
+                            resolution: BookmarkConflictResolution::TookRemote,
+                        });
+                    }
+                    preview.local_updates.push(merged_guid);
+                }
+                None => preview.local_additions.push(merged_guid),
+            }
+        }
+        preview.local_moves = ops
+            .apply_new_local_structure
+            .iter()
+            // Remote items that don't exist locally yet are additions, not moves.
+            .filter(|op| op.merged_node.merge_state.local_node().is_some())
+            .map(|op| guid(&op.merged_node.guid))
+            .collect();
+        preview.local_deletions = ops
+            .delete_local_items
+            .iter()
+            .map(|op| guid(&op.local_node().guid))
+            .collect();
+        for op in &ops.upload_items {
+            let merged_guid = guid(&op.merged_node.guid);
+            match op.merged_node.merge_state.remote_node() {
+                Some(remote_node) => {
+                    if remote_node.needs_merge {
+                        preview.conflicts.push(BookmarkMergeConflict {
+                            guid: merged_guid.clone(),
+                            resolution: BookmarkConflictResolution::KeptLocal,
+                        });
+                    }
+                    preview.remote_updates.push(merged_guid);
+                }
+                None => preview.remote_additions.push(merged_guid),
+            }
+        }
+        preview.remote_deletions = ops
+            .upload_tombstones
+            .iter()
+            .map(|op| guid(op.guid()))
+            .collect();
+        preview
+    }
+}
+
 // The "merger", which is just a thin wrapper for dogear.
 pub(crate) struct Merger<'a> {
     db: &'a PlacesDb,
@@ -1234,6 +1257,8 @@ pub(crate) struct Merger<'a> {
     // Allows us to abort applying the result of the merge if the local tree
     // changed since we fetched it.
     global_change_tracker: GlobalChangeCounterTracker,
+    // If set, `apply()` summarizes the merge here instead of applying it.
+    preview: Option<BookmarksMergePreview>,
 }
 
 impl<'a> Merger<'a> {
@@ -1251,6 +1276,7 @@ impl<'a> Merger<'a> {
             external_transaction: false,
             telem: None,
             global_change_tracker: db.global_bookmark_change_tracker(),
+            preview: None,
         }
     }
 
@@ -1268,6 +1294,7 @@ impl<'a> Merger<'a> {
             external_transaction: false,
             telem: Some(telem),
             global_change_tracker: db.global_bookmark_change_tracker(),
+            preview: None,
         }
     }
 
@@ -1286,9 +1313,37 @@ impl<'a> Merger<'a> {
             external_transaction: false,
             telem: None,
             global_change_tracker: db.global_bookmark_change_tracker(),
+            preview: None,
         }
     }
 
+    /// Merges the local tree with the mirror and returns what syncing would
+    /// change, without changing anything.
+    pub(crate) fn preview(
+        db: &'a PlacesDb,
+        scope: &'a SqlInterruptScope,
+    ) -> Result<BookmarksMergePreview> {
+        let mut merger = Self {
+            db,
+            scope,
+            remote_time: ServerTimestamp::from_millis(
+                get_meta::<i64>(db, LAST_SYNC_META_KEY)?.unwrap_or_default(),
+            ),
+            local_time: Timestamp::now(),
+            // We manage the transaction, so that we can roll back the
+            // changes `prepare()` makes.
+            external_transaction: true,
+            telem: None,
+            global_change_tracker: db.global_bookmark_change_tracker(),
+            preview: Some(BookmarksMergePreview::default()),
+        };
+        let tx = db.begin_transaction()?;
+        let result = merger.merge();
+        tx.rollback()?;
+        result?;
+        Ok(merger.preview.unwrap_or_default())
+    }
+
     pub(crate) fn merge(&mut self) -> Result<()> {
         use dogear::Store;
         if !db_has_changes(self.db)? {
@@ -1742,6 +1797,11 @@ impl dogear::Store for Merger<'_> {
     fn apply(&mut self, root: MergedRoot<'_>) -> Result<()> {
         let ops = root.completion_ops_with_signal(&MergeInterruptee(self.scope))?;
 
+        if let Some(preview) = &mut self.preview {
+            *preview = BookmarksMergePreview::from_ops(&ops);
+            return Ok(());
+        }
+
         if ops.is_empty() {
             // If we don't have any items to apply, upload, or delete,
             // no need to open a transaction at all.
@@ -2430,7 +2490,7 @@ mod tests {
         let syncer = sync_db.lock();
         let interrupt_scope = syncer.begin_interrupt_scope().unwrap();
 
-        update_frecencies(&syncer, &interrupt_scope).expect("Should update frecencies");
+        update_stale_frecencies(&syncer, &interrupt_scope).expect("Should update frecencies");
 
         assert!(
             frecency_stale_at(&reader, &Url::parse("http://example.com").unwrap())
@@ -3314,6 +3374,89 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_apply_notifies_change_observer() {
+        use crate::changes::{PlacesChange, PlacesChangeObserver};
+        use parking_lot::Mutex;
+        use std::sync::Arc;
+
+        #[derive(Default)]
+        struct RecordingObserver {
+            changes: Arc<Mutex<Vec<PlacesChange>>>,
+        }
+
+        impl PlacesChangeObserver for RecordingObserver {
+            fn on_changes(&self, changes: Vec<PlacesChange>) {
+                self.changes.lock().extend(changes);
+            }
+        }
+
+        let api = new_mem_api();
+        let observer = RecordingObserver::default();
+        let changes = Arc::clone(&observer.changes);
+        api.register_change_observer(Box::new(observer));
+
+        apply_incoming(
+            &api,
+            ServerTimestamp(0),
+            json!([{
+                "id": "bookmarkAAAA",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "parentName": "Unfiled Bookmarks",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "A",
+                "bmkUri": "http://example.com/a",
+            }, {
+                "id": "bookmarkBBBB",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "parentName": "Unfiled Bookmarks",
+                "dateAdded": 1_381_542_355_843u64,
+                "title": "B",
+                "bmkUri": "http://example.com/b",
+            }, {
+                "id": "unfiled",
+                "type": "folder",
+                "parentid": "places",
+                "parentName": "",
+                "dateAdded": 0,
+                "title": "Unfiled Bookmarks",
+                "children": ["bookmarkAAAA", "bookmarkBBBB"],
+            }]),
+        );
+
+        // New items are reported as inserted where the remote tree put them,
+        // not under the root where the engine first inserts them.
+        let bookmark_changes: Vec<_> = changes
+            .lock()
+            .iter()
+            .filter(|c| {
+                matches!(
+                    c,
+                    PlacesChange::BookmarkInserted { .. } | PlacesChange::BookmarkMoved { .. }
+                )
+            })
+            .cloned()
+            .collect();
+        assert_eq!(bookmark_changes.len(), 2, "{:?}", bookmark_changes);
+        for (guid, position, url) in [
+            ("bookmarkAAAA", 0, "http://example.com/a"),
+            ("bookmarkBBBB", 1, "http://example.com/b"),
+        ] {
+            assert!(
+                bookmark_changes.contains(&PlacesChange::BookmarkInserted {
+                    guid: guid.into(),
+                    parent_guid: BookmarkRootGuid::Unfiled.as_guid(),
+                    position,
+                    url: Some(Url::parse(url).unwrap()),
+                }),
+                "{:?}",
+                bookmark_changes
+            );
+        }
+    }
+
     #[test]
     fn test_apply_tombstones() -> Result<()> {
         let local_modified = Timestamp::now();
@@ -4765,4 +4908,139 @@ mod tests {
         );
         Ok(())
     }
+
+    #[test]
+    fn test_preview_merge() -> Result<()> {
+        let api = new_mem_api();
+        let writer = api.open_connection(ConnectionType::ReadWrite)?;
+
+        apply_incoming(
+            &api,
+            ServerTimestamp(0),
+            json!([{
+                "id": "menu",
+                "type": "folder",
+                "parentid": "places",
+                "parentName": "",
+                "dateAdded": 0,
+                "title": "menu",
+                "children": ["bookmark1___"],
+            }, {
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "menu",
+                "parentName": "menu",
+                "dateAdded": 1_552_183_116_885u64,
+                "title": "original",
+                "bmkUri": "https://example.com/1",
+            }]),
+        );
+
+        update_bookmark(
+            &writer,
+            &"bookmark1___".into(),
+            &UpdatableBookmark {
+                title: Some("local".into()),
+                ..UpdatableBookmark::default()
+            }
+            .into(),
+        )?;
+        insert_bookmark(
+            &writer,
+            InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Menu.as_guid(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: Some("bookmarkAAAA".into()),
+                url: Url::parse("https://example.com/a").unwrap(),
+                title: Some("A".into()),
+            }
+            .into(),
+        )?;
+
+        let engine = create_sync_engine(&api);
+        let incoming = vec![
+            json!({
+                "id": "bookmark1___",
+                "type": "bookmark",
+                "parentid": "menu",
+                "parentName": "menu",
+                "dateAdded": 1_552_183_116_885u64,
+                "title": "remote",
+                "bmkUri": "https://example.com/1",
+            }),
+            json!({
+                "id": "unfiled",
+                "type": "folder",
+                "parentid": "places",
+                "parentName": "",
+                "dateAdded": 0,
+                "title": "unfiled",
+                "children": ["bookmarkBBBB"],
+            }),
+            json!({
+                "id": "bookmarkBBBB",
+                "type": "bookmark",
+                "parentid": "unfiled",
+                "parentName": "unfiled",
+                "dateAdded": 1_552_183_116_885u64,
+                "title": "B",
+                "bmkUri": "https://example.com/b",
+            }),
+        ]
+        .into_iter()
+        .map(IncomingBso::from_test_content)
+        .collect();
+        let mut telem = telemetry::Engine::new(engine.collection_name());
+        engine
+            .stage_incoming(incoming, &mut telem)
+            .expect("Should stage incoming");
+
+        let preview = engine.preview_merge()?;
+        assert!(preview.local_additions.contains(&"bookmarkBBBB".into()));
+        assert!(!preview.local_moves.contains(&"bookmarkBBBB".into()));
+        assert!(preview.remote_additions.contains(&"bookmarkAAAA".into()));
+        assert!(preview.local_deletions.is_empty());
+        assert!(preview.remote_deletions.is_empty());
+        assert_eq!(preview.conflicts.len(), 1);
+        // The remote record's age is zero, so it's at least as new as the
+        // local change, and wins.
+        assert_eq!(
+            preview.conflicts[0],
+            BookmarkMergeConflict {
+                guid: "bookmark1___".into(),
+                resolution: BookmarkConflictResolution::TookRemote,
+            }
+        );
+
+        // Nothing should have changed, so previewing again gives the same
+        // result.
+        assert_eq!(
+            get_raw_bookmark(&writer, &"bookmark1___".into())?
+                .unwrap()
+                .title
+                .as_deref(),
+            Some("local")
+        );
+        assert!(get_raw_bookmark(&writer, &"bookmarkBBBB".into())?.is_none());
+        assert_eq!(engine.preview_merge()?, preview);
+
+        // And syncing should do what the preview said.
+        let outgoing = engine_apply_incoming(&engine, vec![]);
+        let outgoing_ids = outgoing
+            .iter()
+            .map(|bso| bso.envelope.id.clone())
+            .collect::<Vec<_>>();
+        assert!(outgoing_ids.contains(&"bookmarkAAAA".into()));
+        assert!(get_raw_bookmark(&writer, &"bookmarkBBBB".into())?.is_some());
+        assert_eq!(
+            get_raw_bookmark(&writer, &"bookmark1___".into())?
+                .unwrap()
+                .title
+                .as_deref(),
+            Some("remote")
+        );
+        Ok(())
+    }
 }
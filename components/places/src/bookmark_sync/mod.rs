@@ -10,7 +10,9 @@ pub mod record;
 mod tests;
 
 use crate::error::*;
-pub use engine::BookmarksSyncEngine;
+pub use engine::{
+    BookmarkConflictResolution, BookmarkMergeConflict, BookmarksMergePreview, BookmarksSyncEngine,
+};
 use rusqlite::types::{ToSql, ToSqlOutput};
 use rusqlite::Result as RusqliteResult;
 
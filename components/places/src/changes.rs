@@ -0,0 +1,499 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Notifications of changes to history and bookmarks.
+//!
+//! While an observer is registered for a `PlacesApi`, triggers on every
+//! writable connection record changes in `temp.moz_places_changes` as they
+//! happen. Because that's in the same transaction as the changes themselves,
+//! a rollback forgets them too. Once a transaction commits, we read the
+//! changes back and hand them to the observer, so it hears about everything
+//! committed by any of the API's connections, including those made by the
+//! history and bookmark sync engines.
+
+use crate::api::places_api::ConnectionType;
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::types::VisitType;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use rusqlite::Row;
+use sql_support::ConnExt;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use sync_guid::Guid as SyncGuid;
+use types::Timestamp;
+use url::Url;
+
+/// A change to history or bookmarks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PlacesChange {
+    VisitAdded {
+        url: Url,
+        visit_date: Timestamp,
+        visit_type: VisitType,
+        is_remote: bool,
+    },
+    TitleChanged {
+        url: Url,
+        title: Option<String>,
+    },
+    FrecencyChanged {
+        url: Url,
+        frecency: i64,
+    },
+    BookmarkInserted {
+        guid: SyncGuid,
+        parent_guid: SyncGuid,
+        position: u32,
+        url: Option<Url>,
+    },
+    BookmarkMoved {
+        guid: SyncGuid,
+        old_parent_guid: SyncGuid,
+        parent_guid: SyncGuid,
+        position: u32,
+    },
+    /// `parent_guid` is `None` if the parent was removed too.
+    BookmarkRemoved {
+        guid: SyncGuid,
+        parent_guid: Option<SyncGuid>,
+        url: Option<Url>,
+    },
+    /// All history was removed. Any other history changes made in the same
+    /// transaction aren't reported.
+    HistoryCleared,
+}
+
+/// Implemented by the application to hear about changes. `on_changes` is
+/// called on the thread which committed them, while the connection which made
+/// them is still busy, so it must not synchronously call back into that
+/// connection.
+pub trait PlacesChangeObserver: Send + Sync {
+    fn on_changes(&self, changes: Vec<PlacesChange>);
+}
+
+// These are the values of `moz_places_changes.kind`.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ChangeKind {
+    VisitAdded = 1,
+    TitleChanged = 2,
+    FrecencyChanged = 3,
+    BookmarkInserted = 4,
+    BookmarkMoved = 5,
+    BookmarkRemoved = 6,
+    HistoryCleared = 7,
+}
+
+lazy_static! {
+    // Like the bookmark change counters, observers are per API, indexed by
+    // the "api id", so that they hear about changes from all its connections.
+    static ref CHANGE_OBSERVERS: RwLock<HashMap<usize, Arc<dyn PlacesChangeObserver>>> =
+        RwLock::new(HashMap::new());
+}
+
+/// Registers the observer for an API, replacing any existing one.
+pub(crate) fn register_observer(api_id: usize, observer: Box<dyn PlacesChangeObserver>) {
+    CHANGE_OBSERVERS.write().insert(api_id, Arc::from(observer));
+}
+
+pub(crate) fn unregister_observer(api_id: usize) {
+    CHANGE_OBSERVERS.write().remove(&api_id);
+}
+
+/// Used by our triggers to decide whether to record changes at all.
+pub(crate) fn has_observer(api_id: usize) -> bool {
+    CHANGE_OBSERVERS.read().contains_key(&api_id)
+}
+
+/// Records that all history was removed, replacing any history changes
+/// recorded earlier in the transaction.
+pub(crate) fn note_history_cleared(db: &PlacesDb) -> Result<()> {
+    if !has_observer(db.api_id()) {
+        return Ok(());
+    }
+    db.execute_cached(
+        "DELETE FROM temp.moz_places_changes WHERE kind IN (:visit_added, :title_changed, :frecency_changed)",
+        rusqlite::named_params! {
+            ":visit_added": ChangeKind::VisitAdded as u8,
+            ":title_changed": ChangeKind::TitleChanged as u8,
+            ":frecency_changed": ChangeKind::FrecencyChanged as u8,
+        },
+    )?;
+    db.execute_cached(
+        "INSERT INTO temp.moz_places_changes(kind) VALUES (:kind)",
+        rusqlite::named_params! { ":kind": ChangeKind::HistoryCleared as u8 },
+    )?;
+    Ok(())
+}
+
+/// Passes changes committed on this connection to the API's observer. Does
+/// nothing while a transaction is open, as its changes aren't committed yet.
+///
+/// The changes are already committed, so failing to report them isn't an
+/// error for the caller; we just log it.
+pub(crate) fn notify_observer(db: &PlacesDb) {
+    if db.conn_type() == ConnectionType::ReadOnly || !db.is_autocommit() {
+        return;
+    }
+    let changes = match take_pending_changes(db) {
+        Ok(changes) if !changes.is_empty() => changes,
+        Ok(_) => return,
+        Err(e) => {
+            warn!("Failed to read changes for the observer: {}", e);
+            return;
+        }
+    };
+    // Clone the observer so that we don't hold the lock while calling it.
+    let observer = CHANGE_OBSERVERS.read().get(&db.api_id()).cloned();
+    if let Some(observer) = observer {
+        observer.on_changes(coalesce(changes));
+    }
+}
+
+fn take_pending_changes(db: &PlacesDb) -> Result<Vec<PlacesChange>> {
+    let changes = db.query_rows_and_then_cached(
+        "SELECT kind, url, title, guid, parent_guid, old_parent_guid, position,
+                visit_date, visit_type, is_local, frecency
+         FROM temp.moz_places_changes
+         ORDER BY id",
+        [],
+        PlacesChange::from_row,
+    );
+    // Even if we couldn't read them, we don't want to try again next time.
+    db.execute_cached("DELETE FROM temp.moz_places_changes", [])?;
+    changes
+}
+
+impl PlacesChange {
+    fn from_row(row: &Row<'_>) -> Result<Self> {
+        let url = |column: &str| -> Result<Option<Url>> {
+            Ok(match row.get::<_, Option<String>>(column)? {
+                Some(url) => Some(Url::parse(&url)?),
+                None => None,
+            })
+        };
+        // Columns which are `NOT NULL` for a kind of change are unwrapped
+        // by `row.get()` failing, rather than here.
+        let page_url = || -> Result<Url> { Ok(Url::parse(&row.get::<_, String>("url")?)?) };
+        let kind: u8 = row.get("kind")?;
+        Ok(match kind {
+            k if k == ChangeKind::VisitAdded as u8 => PlacesChange::VisitAdded {
+                url: page_url()?,
+                visit_date: row.get("visit_date")?,
+                visit_type: row.get("visit_type")?,
+                is_remote: !row.get::<_, bool>("is_local")?,
+            },
+            k if k == ChangeKind::TitleChanged as u8 => PlacesChange::TitleChanged {
+                url: page_url()?,
+                title: row.get("title")?,
+            },
+            k if k == ChangeKind::FrecencyChanged as u8 => PlacesChange::FrecencyChanged {
+                url: page_url()?,
+                frecency: row.get("frecency")?,
+            },
+            k if k == ChangeKind::BookmarkInserted as u8 => PlacesChange::BookmarkInserted {
+                guid: row.get("guid")?,
+                parent_guid: row.get("parent_guid")?,
+                position: row.get("position")?,
+                url: url("url")?,
+            },
+            k if k == ChangeKind::BookmarkMoved as u8 => PlacesChange::BookmarkMoved {
+                guid: row.get("guid")?,
+                old_parent_guid: row.get("old_parent_guid")?,
+                parent_guid: row.get("parent_guid")?,
+                position: row.get("position")?,
+            },
+            k if k == ChangeKind::BookmarkRemoved as u8 => PlacesChange::BookmarkRemoved {
+                guid: row.get("guid")?,
+                parent_guid: row.get("parent_guid")?,
+                url: url("url")?,
+            },
+            k if k == ChangeKind::HistoryCleared as u8 => PlacesChange::HistoryCleared,
+            _ => {
+                return Err(rusqlite::Error::IntegralValueOutOfRange(0, kind.into()).into());
+            }
+        })
+    }
+}
+
+// Drops changes which are superseded by later ones in the same batch: only
+// the last title and frecency for a page are reported, and several moves of
+// the same bookmark become a single move from its original parent.
+fn coalesce(changes: Vec<PlacesChange>) -> Vec<PlacesChange> {
+    let mut titles = HashSet::new();
+    let mut frecencies = HashSet::new();
+    let mut moves: HashMap<SyncGuid, usize> = HashMap::new();
+    // Walking backwards means we see the last of each change first.
+    let mut coalesced = Vec::with_capacity(changes.len());
+    for change in changes.into_iter().rev() {
+        match &change {
+            PlacesChange::TitleChanged { url, .. } if !titles.insert(url.clone()) => continue,
+            PlacesChange::FrecencyChanged { url, .. } if !frecencies.insert(url.clone()) => {
+                continue
+            }
+            PlacesChange::BookmarkMoved {
+                guid,
+                old_parent_guid,
+                ..
+            } => {
+                if let Some(&index) = moves.get(guid) {
+                    if let PlacesChange::BookmarkMoved {
+                        old_parent_guid: later_old_parent_guid,
+                        ..
+                    } = &mut coalesced[index]
+                    {
+                        *later_old_parent_guid = old_parent_guid.clone();
+                    }
+                    continue;
+                }
+                moves.insert(guid.clone(), coalesced.len());
+            }
+            _ => {}
+        }
+        coalesced.push(change);
+    }
+    coalesced.reverse();
+    coalesced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_api;
+    use crate::api::places_api::PlacesApi;
+    use crate::observation::VisitObservation;
+    use crate::storage::bookmarks::{
+        delete_bookmark, insert_bookmark, update_bookmark, BookmarkPosition, BookmarkRootGuid,
+        InsertableBookmark, InsertableFolder, UpdatableBookmark, UpdatableItem, UpdateTreeLocation,
+    };
+    use crate::storage::history::{apply_observation, delete_everything};
+    use parking_lot::Mutex;
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        batches: Arc<Mutex<Vec<Vec<PlacesChange>>>>,
+    }
+
+    impl PlacesChangeObserver for RecordingObserver {
+        fn on_changes(&self, changes: Vec<PlacesChange>) {
+            self.batches.lock().push(changes);
+        }
+    }
+
+    fn observe(api: &PlacesApi) -> Arc<Mutex<Vec<Vec<PlacesChange>>>> {
+        let observer = RecordingObserver::default();
+        let batches = Arc::clone(&observer.batches);
+        api.register_change_observer(Box::new(observer));
+        batches
+    }
+
+    // Bookmarking a URL changes its frecency, too.
+    fn bookmark_changes(batches: &Mutex<Vec<Vec<PlacesChange>>>) -> Vec<PlacesChange> {
+        batches
+            .lock()
+            .pop()
+            .unwrap()
+            .into_iter()
+            .filter(|c| !matches!(c, PlacesChange::FrecencyChanged { .. }))
+            .collect()
+    }
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn visit(conn: &PlacesDb, s: &str, title: &str) {
+        apply_observation(
+            conn,
+            VisitObservation::new(url(s))
+                .with_title(title.to_string())
+                .with_visit_type(VisitType::Link),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_history_changes() {
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        let batches = observe(&api);
+
+        visit(&conn, "https://example.com/", "Example");
+        let batch = batches.lock().pop().unwrap();
+        assert!(batch.iter().any(|c| matches!(
+            c,
+            PlacesChange::VisitAdded { url: u, visit_type: VisitType::Link, is_remote: false, .. }
+                if u.as_str() == "https://example.com/"
+        )));
+        // Only the final frecency is reported.
+        assert_eq!(
+            batch
+                .iter()
+                .filter(|c| matches!(c, PlacesChange::FrecencyChanged { .. }))
+                .count(),
+            1
+        );
+
+        visit(&conn, "https://example.com/", "New title");
+        let batch = batches.lock().pop().unwrap();
+        assert!(batch.contains(&PlacesChange::TitleChanged {
+            url: url("https://example.com/"),
+            title: Some("New title".to_string()),
+        }));
+
+        delete_everything(&conn).unwrap();
+        assert_eq!(
+            batches.lock().pop().unwrap(),
+            vec![PlacesChange::HistoryCleared]
+        );
+    }
+
+    #[test]
+    fn test_bookmark_changes() {
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        let batches = observe(&api);
+
+        let folder = insert_bookmark(
+            &conn,
+            InsertableFolder {
+                parent_guid: BookmarkRootGuid::Unfiled.into(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: None,
+                title: Some("Folder".into()),
+                children: vec![],
+            }
+            .into(),
+        )
+        .unwrap();
+        let insert_at_start = |parent: &SyncGuid, s: &str| {
+            insert_bookmark(
+                &conn,
+                InsertableBookmark {
+                    parent_guid: parent.clone(),
+                    position: BookmarkPosition::Specific { pos: 0 },
+                    date_added: None,
+                    last_modified: None,
+                    guid: None,
+                    url: url(s),
+                    title: None,
+                }
+                .into(),
+            )
+            .unwrap()
+        };
+        let first = insert_at_start(&BookmarkRootGuid::Unfiled.into(), "https://example.com/");
+        batches.lock().clear();
+
+        // Inserting before `folder` shifts it along, but doesn't report it.
+        let second = insert_at_start(&BookmarkRootGuid::Unfiled.into(), "https://mozilla.org/");
+        assert_eq!(
+            bookmark_changes(&batches),
+            vec![PlacesChange::BookmarkInserted {
+                guid: second.clone(),
+                parent_guid: BookmarkRootGuid::Unfiled.into(),
+                position: 0,
+                url: Some(url("https://mozilla.org/")),
+            }]
+        );
+
+        update_bookmark(
+            &conn,
+            &first,
+            &UpdatableItem::Bookmark {
+                b: UpdatableBookmark {
+                    location: UpdateTreeLocation::Parent {
+                        guid: folder.clone(),
+                        pos: BookmarkPosition::Append,
+                    },
+                    ..Default::default()
+                },
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            bookmark_changes(&batches),
+            vec![PlacesChange::BookmarkMoved {
+                guid: first.clone(),
+                old_parent_guid: BookmarkRootGuid::Unfiled.into(),
+                parent_guid: folder.clone(),
+                position: 0,
+            }]
+        );
+
+        // Removing a folder removes its children, too.
+        delete_bookmark(&conn, &folder).unwrap();
+        let batch = bookmark_changes(&batches);
+        assert_eq!(batch.len(), 2);
+        assert!(batch.contains(&PlacesChange::BookmarkRemoved {
+            guid: folder.clone(),
+            parent_guid: Some(BookmarkRootGuid::Unfiled.into()),
+            url: None,
+        }));
+        assert!(batch.iter().any(|c| matches!(
+            c,
+            PlacesChange::BookmarkRemoved { guid, url: Some(u), .. }
+                if *guid == first && u.as_str() == "https://example.com/"
+        )));
+    }
+
+    #[test]
+    fn test_rollback_and_unregister() {
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        let batches = observe(&api);
+
+        visit(&conn, "https://example.com/", "Example");
+        batches.lock().clear();
+
+        let tx = conn.begin_transaction().unwrap();
+        conn.execute_batch(
+            "UPDATE moz_places SET title = 'Rolled back'
+             WHERE url = 'https://example.com/'",
+        )
+        .unwrap();
+        // Nothing's reported until the transaction commits...
+        notify_observer(&conn);
+        assert!(batches.lock().is_empty());
+        tx.rollback().unwrap();
+        // ...and nothing is if it doesn't.
+        notify_observer(&conn);
+        assert!(batches.lock().is_empty());
+
+        api.unregister_change_observer();
+        visit(&conn, "https://mozilla.org/", "Mozilla");
+        assert!(batches.lock().is_empty());
+        assert_eq!(
+            conn.conn_ext_query_one::<i64>("SELECT COUNT(*) FROM temp.moz_places_changes")
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_coalesce_moves() {
+        let guid = SyncGuid::from("bookmarkAAAA");
+        let moved = |from: &str, to: &str, position| PlacesChange::BookmarkMoved {
+            guid: guid.clone(),
+            old_parent_guid: from.into(),
+            parent_guid: to.into(),
+            position,
+        };
+        assert_eq!(
+            coalesce(vec![
+                moved("folderAAAAAA", "folderBBBBBB", 1),
+                PlacesChange::HistoryCleared,
+                moved("folderBBBBBB", "folderCCCCCC", 2),
+            ]),
+            vec![
+                PlacesChange::HistoryCleared,
+                moved("folderAAAAAA", "folderCCCCCC", 2)
+            ]
+        );
+    }
+}
@@ -411,6 +411,12 @@ fn define_functions(c: &Connection, api_id: usize) -> rusqlite::Result<()> {
         FunctionFlags::SQLITE_UTF8,
         move |ctx| -> rusqlite::Result<i64> { sql_fns::note_bookmarks_sync_change(ctx, api_id) },
     )?;
+    c.create_scalar_function(
+        "has_change_observer",
+        0,
+        FunctionFlags::SQLITE_UTF8,
+        move |_ctx| -> rusqlite::Result<bool> { Ok(crate::changes::has_observer(api_id)) },
+    )?;
     c.create_scalar_function("throw", 1, FunctionFlags::SQLITE_UTF8, move |ctx| {
         sql_fns::throw(ctx, api_id)
     })?;
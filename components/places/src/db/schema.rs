@@ -4,6 +4,7 @@
 
 use crate::api::places_api::ConnectionType;
 use crate::bookmark_sync::engine::LAST_SYNC_META_KEY;
+use crate::changes::ChangeKind;
 use crate::error::debug;
 use crate::storage::bookmarks::{
     bookmark_sync::create_synced_bookmark_roots, create_bookmark_roots,
@@ -14,7 +15,7 @@ use sql_support::ConnExt;
 
 use super::db::{Pragma, PragmaGuard};
 
-pub const VERSION: u32 = 20;
+pub const VERSION: u32 = 23;
 
 // Shared schema and temp tables for the read-write and Sync connections.
 const CREATE_SHARED_SCHEMA_SQL: &str = include_str!("../../sql/create_shared_schema.sql");
@@ -34,10 +35,28 @@ lazy_static::lazy_static! {
             include_str!("../../sql/create_shared_triggers.sql"),
             increase_frecency_stats = update_origin_frecency_stats("+"),
             decrease_frecency_stats = update_origin_frecency_stats("-"),
+            update_new_fts_search_terms = update_fts_search_terms("NEW"),
+            update_old_fts_search_terms = update_fts_search_terms("OLD"),
+            visit_added = ChangeKind::VisitAdded as u8,
+            title_changed = ChangeKind::TitleChanged as u8,
+            frecency_changed = ChangeKind::FrecencyChanged as u8,
+            bookmark_inserted = ChangeKind::BookmarkInserted as u8,
+            bookmark_moved = ChangeKind::BookmarkMoved as u8,
+            bookmark_removed = ChangeKind::BookmarkRemoved as u8,
         )
     };
 }
 
+// Fills `moz_places_fts` for a database which had pages before it existed.
+const POPULATE_PLACES_FTS_SQL: &str = "
+    INSERT INTO moz_places_fts(rowid, title, url, search_terms)
+    SELECT h.id, h.title, h.url, IFNULL(
+        (SELECT group_concat(q.term, ' ') FROM moz_places_metadata_search_queries q
+         WHERE q.id IN (SELECT search_query_id FROM moz_places_metadata
+                        WHERE place_id = h.id)),
+        '')
+    FROM moz_places h";
+
 // Keys in the moz_meta table.
 pub(crate) static MOZ_META_KEY_ORIGIN_FRECENCY_COUNT: &str = "origin_frecency_count";
 pub(crate) static MOZ_META_KEY_ORIGIN_FRECENCY_SUM: &str = "origin_frecency_sum";
@@ -72,6 +91,21 @@ fn update_origin_frecency_stats(op: &str) -> String {
     )
 }
 
+// Recomputes the search terms indexed for the page of a `moz_places_metadata`
+// row, which is `row` ("NEW" or "OLD") in a trigger.
+fn update_fts_search_terms(row: &str) -> String {
+    format!(
+        "
+        UPDATE moz_places_fts SET search_terms = IFNULL(
+            (SELECT group_concat(q.term, ' ') FROM moz_places_metadata_search_queries q
+             WHERE q.id IN (SELECT search_query_id FROM moz_places_metadata
+                            WHERE place_id = {row}.place_id)),
+            '')
+        WHERE rowid = {row}.place_id",
+        row = row,
+    )
+}
+
 pub fn init(conn: &Connection) -> rusqlite::Result<()> {
     debug!("Initializing schema");
     conn.execute_batch(CREATE_SHARED_SCHEMA_SQL)?;
@@ -341,6 +375,19 @@ pub fn upgrade_from(db: &Connection, from: u32) -> rusqlite::Result<()> {
             db.execute("ANALYZE moz_places", [])?;
             db.execute("ANALYZE moz_historyvisits", [])?;
         }
+        20 => {
+            // Create `moz_places_fts` and index the existing pages.
+            db.execute_batch(CREATE_SHARED_SCHEMA_SQL)?;
+            db.execute_batch(POPULATE_PLACES_FTS_SQL)?;
+        }
+        21 => {
+            // Create the favicon tables.
+            db.execute_batch(CREATE_SHARED_SCHEMA_SQL)?;
+        }
+        22 => {
+            // Create `moz_annos`.
+            db.execute_batch(CREATE_SHARED_SCHEMA_SQL)?;
+        }
         // Add more migrations here...
 
         // Any other from value indicates that something very wrong happened
@@ -1173,6 +1220,40 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_upgrade_schema_20_21() {
+        let db_file = MigratedDatabaseFile::new(PlacesInitializer::new_for_test(), CREATE_V17_DB);
+
+        db_file.upgrade_to(20);
+        let db = db_file.open();
+        db.execute_batch(
+            "INSERT INTO moz_places(id, guid, url, title)
+             VALUES (2, 'page_guid_2_', 'https://www.mozilla.org/', 'Internet for people');
+             INSERT INTO moz_places_metadata_search_queries(id, term) VALUES (1, 'firefox');
+             INSERT INTO moz_places_metadata(place_id, search_query_id) VALUES (2, 1);",
+        )
+        .unwrap();
+        drop(db);
+
+        db_file.upgrade_to(21);
+        let db = db_file.open();
+        // Both the page from the v17 database and the one we added are indexed.
+        for (query, id) in [
+            ("example", 1),
+            ("people", 2),
+            ("mozilla", 2),
+            ("firefox", 2),
+        ] {
+            assert_eq!(
+                db.conn_ext_query_one::<i64>(&format!(
+                    "SELECT rowid FROM moz_places_fts WHERE moz_places_fts MATCH '{query}'"
+                ))
+                .unwrap(),
+                id
+            );
+        }
+    }
+
     #[test]
     fn test_all_upgrades() {
         // Test the migration process in general: open a fresh DB and a DB that's gone through the migration
@@ -1210,6 +1291,10 @@ mod tests {
             "moz_keywords",
             "moz_places_metadata",
             "moz_places_metadata_search_queries",
+            "moz_places_fts",
+            "moz_pages_w_icons",
+            "moz_icons",
+            "moz_annos",
         ];
         #[derive(Debug, Ord, PartialOrd, Eq, PartialEq)]
         struct ColumnInfo {
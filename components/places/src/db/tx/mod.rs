@@ -12,7 +12,10 @@ use sql_support::{ConnExt, UncheckedTransaction};
 
 /// High level transaction type which "does the right thing" for you.
 /// Construct one with `PlacesDb::begin_transaction()`.
-pub struct PlacesTransaction<'conn>(PlacesTransactionRepr<'conn>);
+pub struct PlacesTransaction<'conn> {
+    repr: PlacesTransactionRepr<'conn>,
+    db: &'conn super::PlacesDb,
+}
 
 /// Only separated from PlacesTransaction so that the internals of the former
 /// are private (so that it can't be `matched` on, for example)
@@ -29,7 +32,7 @@ impl PlacesTransaction<'_> {
     /// earliest opportunity.
     #[inline]
     pub fn should_commit(&self) -> bool {
-        match &self.0 {
+        match &self.repr {
             PlacesTransactionRepr::ChunkedWrite(tx) => tx.should_commit(),
             _ => true,
         }
@@ -42,7 +45,7 @@ impl PlacesTransaction<'_> {
     ///   warning and does nothing.
     #[inline]
     pub fn maybe_commit(&mut self) -> Result<()> {
-        if let PlacesTransactionRepr::ChunkedWrite(tx) = &mut self.0 {
+        if let PlacesTransactionRepr::ChunkedWrite(tx) = &mut self.repr {
             tx.maybe_commit()?;
         } else {
             error_support::report_error!(
@@ -56,13 +59,15 @@ impl PlacesTransaction<'_> {
         Ok(())
     }
 
-    /// Consumes and commits a PlacesTransaction transaction.
+    /// Consumes and commits a PlacesTransaction transaction, then tells the
+    /// change observer, if any, what was committed.
     pub fn commit(self) -> Result<()> {
-        match self.0 {
+        match self.repr {
             PlacesTransactionRepr::ChunkedWrite(t) => t.commit()?,
             PlacesTransactionRepr::UnchunkedWrite(t) => t.commit()?,
             PlacesTransactionRepr::ReadOnly(t) => t.commit()?,
         };
+        crate::changes::notify_observer(self.db);
         Ok(())
     }
 
@@ -70,7 +75,7 @@ impl PlacesTransaction<'_> {
     /// maybe_commit has been called, this may only roll back as far as that
     /// call.
     pub fn rollback(self) -> Result<()> {
-        match self.0 {
+        match self.repr {
             PlacesTransactionRepr::ChunkedWrite(t) => t.rollback()?,
             PlacesTransactionRepr::UnchunkedWrite(t) => t.rollback()?,
             PlacesTransactionRepr::ReadOnly(t) => t.rollback()?,
@@ -86,7 +91,7 @@ impl super::PlacesDb {
     /// - for ReadWrite connections, begins a normal coop transaction
     /// - for ReadOnly connections, begins an unchecked transaction.
     pub fn begin_transaction(&self) -> Result<PlacesTransaction<'_>> {
-        Ok(PlacesTransaction(match self.conn_type() {
+        let repr = match self.conn_type() {
             ConnectionType::Sync => {
                 PlacesTransactionRepr::ChunkedWrite(self.chunked_coop_trransaction()?)
             }
@@ -97,7 +102,8 @@ impl super::PlacesDb {
                 // Use an unchecked transaction with no locking.
                 PlacesTransactionRepr::ReadOnly(self.unchecked_transaction()?)
             }
-        }))
+        };
+        Ok(PlacesTransaction { repr, db: self })
     }
 }
 
@@ -105,7 +111,7 @@ impl std::ops::Deref for PlacesTransaction<'_> {
     type Target = Connection;
 
     fn deref(&self) -> &Connection {
-        match &self.0 {
+        match &self.repr {
             PlacesTransactionRepr::ChunkedWrite(t) => t,
             PlacesTransactionRepr::UnchunkedWrite(t) => t,
             PlacesTransactionRepr::ReadOnly(t) => t,
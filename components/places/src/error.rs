@@ -113,6 +113,18 @@ pub enum Error {
 
     #[error("Invalid metadata observation: {0}")]
     InvalidMetadataObservation(#[from] InvalidMetadataObservation),
+
+    #[error("Invalid bookmarks backup: {0}")]
+    InvalidBookmarksBackup(String),
+
+    #[error("Invalid favicon: {0}")]
+    InvalidFavicon(#[from] InvalidFavicon),
+
+    #[error("Invalid annotation: {0}")]
+    InvalidAnnotation(#[from] InvalidAnnotation),
+
+    #[error("Invalid frecency settings: {0}")]
+    InvalidFrecencySettings(String),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -179,6 +191,26 @@ pub enum InvalidMetadataObservation {
     ViewTimeTooLong,
 }
 
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidFavicon {
+    #[error("The icon data is empty")]
+    Empty,
+    #[error("The icon data is too large ({0} bytes)")]
+    TooLarge(usize),
+    #[error("The MIME type {0:?} isn't an image type")]
+    NotAnImage(String),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum InvalidAnnotation {
+    #[error("The name is empty")]
+    EmptyName,
+    #[error("The name is too long ({0} bytes)")]
+    NameTooLong(usize),
+    #[error("The value is too long ({0} bytes)")]
+    ValueTooLong(usize),
+}
+
 // Define how our internal errors are handled and converted to external errors
 // See `support/error/README.md` for how this works, especially the warning about PII.
 impl GetErrorHandling for Error {
@@ -265,6 +297,30 @@ impl GetErrorHandling for Error {
                 })
                 .log_warning()
             }
+            Error::InvalidFavicon(_) => {
+                ErrorHandling::convert(PlacesApiError::UnexpectedPlacesException {
+                    reason: self.to_string(),
+                })
+                .log_warning()
+            }
+            Error::InvalidAnnotation(_) => {
+                ErrorHandling::convert(PlacesApiError::UnexpectedPlacesException {
+                    reason: self.to_string(),
+                })
+                .log_warning()
+            }
+            Error::InvalidFrecencySettings(_) => {
+                ErrorHandling::convert(PlacesApiError::UnexpectedPlacesException {
+                    reason: self.to_string(),
+                })
+                .log_warning()
+            }
+            Error::InvalidBookmarksBackup(_) => {
+                ErrorHandling::convert(PlacesApiError::UnexpectedPlacesException {
+                    reason: self.to_string(),
+                })
+                .log_warning()
+            }
             _ => ErrorHandling::convert(PlacesApiError::UnexpectedPlacesException {
                 reason: self.to_string(),
             })
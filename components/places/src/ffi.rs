@@ -6,22 +6,37 @@
 
 use crate::api::matcher::{self, search_frecent, SearchParams};
 pub use crate::api::places_api::places_api_new;
+pub use crate::bookmark_sync::{
+    BookmarkConflictResolution, BookmarkMergeConflict, BookmarksMergePreview,
+};
+pub use crate::changes::{PlacesChange, PlacesChangeObserver};
 pub use crate::error::{warn, Result};
 pub use crate::error::{ApiResult, PlacesApiError};
+pub use crate::frecency::FrecencySettings;
 #[cfg(all(feature = "glean-sym", any(target_os = "android", target_os = "ios")))]
 use crate::glean_metrics::places_manager;
 pub use crate::import::common::HistoryMigrationResult;
-use crate::import::import_ios_history;
+pub use crate::import::BookmarksImportResult;
+use crate::import::{
+    backup_bookmarks_json, export_bookmarks_html, import_bookmarks_html, import_chromium_history,
+    import_ios_history, restore_bookmarks_json,
+};
 use crate::storage;
+pub use crate::storage::annotations::{Annotation, AnnotationExpiry, AnnotationValue};
 use crate::storage::bookmarks;
 pub use crate::storage::bookmarks::BookmarkPosition;
+pub use crate::storage::favicons::Favicon;
+pub use crate::storage::history_groups::{HistoryGroup, HistoryGroupKind};
 pub use crate::storage::history_metadata::{
     DocumentType, HistoryHighlight, HistoryHighlightWeights, HistoryMetadata,
     HistoryMetadataObservation, HistoryMetadataPageMissingBehavior,
     NoteHistoryMetadataObservationOptions,
 };
+pub use crate::storage::history_search::{HistorySearchResult, HistoryTimeRange};
 pub use crate::storage::RunMaintenanceMetrics;
-use crate::storage::{history, history_metadata};
+use crate::storage::{
+    annotations, favicons, history, history_groups, history_metadata, history_search,
+};
 use crate::types::VisitTransitionSet;
 use crate::ConnectionType;
 use crate::VisitObservation;
@@ -128,7 +143,11 @@ impl PlacesConnection {
         F: FnOnce(&PlacesDb) -> crate::error::Result<T>,
     {
         let conn = self.db.lock();
-        f(&conn)
+        let result = f(&conn);
+        // Most changes are reported when their transaction commits, but this
+        // catches any made outside of one.
+        crate::changes::notify_observer(&conn);
+        result
     }
 
     // pass the SqlInterruptHandle as an object through Uniffi
@@ -185,6 +204,11 @@ impl PlacesConnection {
         self.with_conn(|conn| history_metadata::query(conn, query.as_str(), limit))
     }
 
+    #[handle_error(crate::Error)]
+    pub fn get_history_groups(&self, range: HistoryTimeRange) -> ApiResult<Vec<HistoryGroup>> {
+        self.with_conn(|conn| history_groups::get_history_groups(conn, range))
+    }
+
     #[handle_error(crate::Error)]
     pub fn get_history_highlights(
         &self,
@@ -194,6 +218,16 @@ impl PlacesConnection {
         self.with_conn(|conn| history_metadata::get_highlights(conn, weights, limit))
     }
 
+    #[handle_error(crate::Error)]
+    pub fn search_history(
+        &self,
+        query: String,
+        limit: i32,
+        time_range: Option<HistoryTimeRange>,
+    ) -> ApiResult<Vec<HistorySearchResult>> {
+        self.with_conn(|conn| history_search::search_history(conn, &query, limit, time_range))
+    }
+
     #[handle_error(crate::Error)]
     pub fn note_history_metadata_observation(
         &self,
@@ -231,6 +265,60 @@ impl PlacesConnection {
         self.with_conn(history_metadata::delete_all_metadata_for_search)
     }
 
+    #[handle_error(crate::Error)]
+    pub fn set_favicon_for_page(
+        &self,
+        url: Url,
+        data: Vec<u8>,
+        mime_type: String,
+        width: u32,
+    ) -> ApiResult<()> {
+        self.with_conn(|conn| favicons::set_favicon_for_page(conn, &url, &data, &mime_type, width))
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn get_favicon_for_page(
+        &self,
+        url: Url,
+        preferred_width: u32,
+    ) -> ApiResult<Option<Favicon>> {
+        self.with_conn(|conn| favicons::get_favicon_for_page(conn, &url, preferred_width))
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn set_annotation(
+        &self,
+        url: Url,
+        name: String,
+        value: AnnotationValue,
+        expiry: AnnotationExpiry,
+        exclude_from_sync: bool,
+    ) -> ApiResult<()> {
+        self.with_conn(|conn| {
+            annotations::set_annotation(conn, &url, &name, value, expiry, exclude_from_sync)
+        })
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn get_annotation(&self, url: Url, name: String) -> ApiResult<Option<Annotation>> {
+        self.with_conn(|conn| annotations::get_annotation(conn, &url, &name))
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn get_annotations_for_url(&self, url: Url) -> ApiResult<Vec<Annotation>> {
+        self.with_conn(|conn| annotations::get_annotations_for_url(conn, &url))
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn get_annotations_by_name(&self, name: String) -> ApiResult<Vec<Annotation>> {
+        self.with_conn(|conn| annotations::get_annotations_by_name(conn, &name))
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn remove_annotation(&self, url: Url, name: String) -> ApiResult<bool> {
+        self.with_conn(|conn| annotations::remove_annotation(conn, &url, &name))
+    }
+
     /// Add an observation to the database.
     #[handle_error(crate::Error)]
     pub fn apply_observation(&self, visit: VisitObservation) -> ApiResult<()> {
@@ -400,6 +488,11 @@ impl PlacesConnection {
         res
     }
 
+    #[handle_error(crate::Error)]
+    pub fn recalculate_all_frecencies(&self) -> ApiResult<u32> {
+        self.with_conn(|conn| Ok(storage::recalculate_all_frecencies(conn)? as u32))
+    }
+
     #[handle_error(crate::Error)]
     pub fn run_maintenance_vacuum(&self) -> ApiResult<()> {
         #[cfg(all(feature = "glean-sym", any(target_os = "android", target_os = "ios")))]
@@ -568,6 +661,34 @@ impl PlacesConnection {
     ) -> ApiResult<HistoryMigrationResult> {
         self.with_conn(|conn| import_ios_history(conn, &db_path, last_sync_timestamp))
     }
+
+    #[handle_error(crate::Error)]
+    pub fn places_history_import_from_chromium(
+        &self,
+        db_path: String,
+    ) -> ApiResult<HistoryMigrationResult> {
+        self.with_conn(|conn| import_chromium_history(conn, &db_path))
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn bookmarks_import_html(&self, html: String) -> ApiResult<BookmarksImportResult> {
+        self.with_conn(|conn| import_bookmarks_html(conn, &html))
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn bookmarks_export_html(&self) -> ApiResult<String> {
+        self.with_conn(export_bookmarks_html)
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn bookmarks_backup_json(&self, compress: bool) -> ApiResult<Vec<u8>> {
+        self.with_conn(|conn| backup_bookmarks_json(conn, compress))
+    }
+
+    #[handle_error(crate::Error)]
+    pub fn bookmarks_restore_json(&self, data: Vec<u8>) -> ApiResult<BookmarksImportResult> {
+        self.with_conn(|conn| restore_bookmarks_json(conn, &data))
+    }
 }
 
 impl AsRef<SqlInterruptHandle> for PlacesConnection {
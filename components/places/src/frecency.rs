@@ -2,10 +2,22 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+//! Frecency calculation, ported from Desktop.
+//!
+//! The settings used can be changed at runtime with
+//! `PlacesApi::set_frecency_settings()`, for example to experiment with
+//! ranking. New settings apply to frecencies calculated from then on; existing
+//! ones are updated by `recalculate_all_frecencies()`.
+
 use crate::error::*;
 use crate::types::VisitType;
 use error_support::trace_error;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
 use rusqlite::Connection;
+use serde_derive::*;
+use std::collections::HashMap;
+use std::sync::Arc;
 use types::Timestamp;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -15,7 +27,7 @@ enum RedirectBonus {
     Normal,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct FrecencySettings {
     // TODO: These probably should not all be i32s...
     pub num_visits: i32,                     // from "places.frecency.numVisits"
@@ -77,7 +89,58 @@ impl Default for FrecencySettings {
     }
 }
 
+lazy_static! {
+    // Like change observers, settings are per API, indexed by the "api id",
+    // so that all its connections use the same ones. APIs which haven't set
+    // any use the defaults.
+    static ref FRECENCY_SETTINGS: RwLock<HashMap<usize, Arc<FrecencySettings>>> =
+        RwLock::new(HashMap::new());
+}
+
+pub(crate) fn set_settings(api_id: usize, settings: FrecencySettings) -> Result<()> {
+    settings.validate()?;
+    FRECENCY_SETTINGS.write().insert(api_id, Arc::new(settings));
+    Ok(())
+}
+
+pub(crate) fn clear_settings(api_id: usize) {
+    FRECENCY_SETTINGS.write().remove(&api_id);
+}
+
+/// Returns the settings to use for an API's connections.
+pub(crate) fn settings_for(api_id: usize) -> Arc<FrecencySettings> {
+    FRECENCY_SETTINGS
+        .read()
+        .get(&api_id)
+        .cloned()
+        .unwrap_or_else(|| Arc::new(DEFAULT_FRECENCY_SETTINGS))
+}
+
 impl FrecencySettings {
+    fn validate(&self) -> Result<()> {
+        // `num_visits` is used as a `LIMIT`, where negative numbers mean
+        // "no limit".
+        if self.num_visits <= 0 {
+            return Err(Error::InvalidFrecencySettings(format!(
+                "num_visits must be positive, not {}",
+                self.num_visits
+            )));
+        }
+        let cutoffs = [
+            self.first_bucket_cutoff_days,
+            self.second_bucket_cutoff_days,
+            self.third_bucket_cutoff_days,
+            self.fourth_bucket_cutoff_days,
+        ];
+        if cutoffs[0] < 0 || cutoffs.windows(2).any(|pair| pair[0] > pair[1]) {
+            return Err(Error::InvalidFrecencySettings(format!(
+                "bucket cutoffs must be non-negative and in order, not {:?}",
+                cutoffs
+            )));
+        }
+        Ok(())
+    }
+
     // Note: in Places, `redirect` defaults to false.
     pub fn get_transition_bonus(
         &self,
@@ -2,12 +2,13 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use super::record::{HistoryRecord, HistoryRecordVisit};
+use super::record::{HistoryRecord, HistoryRecordAnno, HistoryRecordVisit};
 use super::{MAX_OUTGOING_PLACES, MAX_VISITS};
 use crate::api::history::can_add_url;
 use crate::db::PlacesDb;
 use crate::error::*;
 use crate::storage::{
+    annotations::fetch_synced_annotations,
     delete_pending_temp_tables,
     history::history_sync::{
         apply_synced_deletion, apply_synced_reconciliation, apply_synced_visits, fetch_outgoing,
@@ -56,6 +57,7 @@ pub enum IncomingPlan {
         url: Url,
         new_title: Option<String>,
         visits: Vec<HistoryRecordVisit>,
+        annos: Option<Vec<HistoryRecordAnno>>,
         unknown_fields: UnknownFields,
     },
     /// Entry exists locally and it's the same as the incoming record. This is
@@ -99,6 +101,21 @@ fn plan_incoming_record(conn: &PlacesDb, record: HistoryRecord, max_visits: usiz
             Some((p, v)) => (Some(p), v),
         };
 
+    // Records from clients which don't know about annotations don't have
+    // any, and we keep ours.
+    let annos_changed = match (&record.annos, &existing_page) {
+        (None, _) => false,
+        (Some(incoming), Some(page)) => match fetch_synced_annotations(conn, page.row_id) {
+            Ok(existing) => {
+                let mut incoming = incoming.clone();
+                incoming.sort_by(|a, b| a.name.cmp(&b.name));
+                incoming != existing
+            }
+            Err(e) => return IncomingPlan::Failed(e),
+        },
+        (Some(incoming), None) => !incoming.is_empty(),
+    };
+
     let guid_changed = match existing_page {
         Some(p) => p.guid != record.id,
         None => false,
@@ -164,12 +181,14 @@ fn plan_incoming_record(conn: &PlacesDb, record: HistoryRecord, max_visits: usiz
     // Now we need to check the other attributes.
     // Check if we should update title? For now, assume yes. It appears
     // as though desktop always updates it.
-    if guid_changed || !to_apply.is_empty() {
+    if guid_changed || !to_apply.is_empty() || annos_changed {
         let new_title = Some(record.title);
         IncomingPlan::Apply {
             url,
             new_title,
             visits: to_apply,
+            // Re-applying unchanged annotations would restart their expiry.
+            annos: if annos_changed { record.annos } else { None },
             unknown_fields: record.unknown_fields,
         }
     } else {
@@ -239,12 +258,13 @@ pub fn apply_plan(
                 url,
                 new_title,
                 visits,
+                annos,
                 unknown_fields,
             } => {
                 trace!(
-                    "incoming: will apply {guid:?}: url={url:?}, title={new_title:?}, to_add={visits:?}, unknown_fields={unknown_fields:?}"
+                    "incoming: will apply {guid:?}: url={url:?}, title={new_title:?}, to_add={visits:?}, annos={annos:?}, unknown_fields={unknown_fields:?}"
                 );
-                apply_synced_visits(db, &guid, url, new_title, visits, unknown_fields)?;
+                apply_synced_visits(db, &guid, url, new_title, visits, annos, unknown_fields)?;
                 telem.applied(1);
             }
             IncomingPlan::Reconciled => {
@@ -364,6 +384,7 @@ mod tests {
             title: "title".into(),
             hist_uri: "http://example.com".into(),
             visits: vec![],
+            annos: None,
             unknown_fields: UnknownFields::new(),
         };
 
@@ -383,6 +404,7 @@ mod tests {
             title: "title".into(),
             hist_uri: "invalid".into(),
             visits: vec![],
+            annos: None,
             unknown_fields: UnknownFields::new(),
         };
 
@@ -407,6 +429,7 @@ mod tests {
             title: "title".into(),
             hist_uri: "https://example.com".into(),
             visits,
+            annos: None,
             unknown_fields: UnknownFields::new(),
         };
 
@@ -444,6 +467,7 @@ mod tests {
             title: "title".into(),
             hist_uri: "https://example.com".into(),
             visits,
+            annos: None,
             unknown_fields: UnknownFields::new(),
         };
         // We should have reconciled it.
@@ -453,6 +477,82 @@ mod tests {
         ));
     }
 
+    #[test]
+    fn test_plan_annos() {
+        use crate::storage::annotations::{
+            get_annotation, set_annotation, AnnotationExpiry, AnnotationValue,
+        };
+        error_support::init_for_tests();
+        let conn = PlacesDb::open_in_memory(ConnectionType::Sync).expect("no memory db");
+        let now = SystemTime::now();
+        let url = Url::parse("https://example.com").expect("is valid");
+        let obs = VisitObservation::new(url.clone())
+            .with_visit_type(VisitType::Link)
+            .with_at(Some(now.into()));
+        apply_observation(&conn, obs).expect("should apply");
+        set_annotation(
+            &conn,
+            &url,
+            "reader-mode",
+            AnnotationValue::Boolean { value: true },
+            AnnotationExpiry::WithHistory,
+            false,
+        )
+        .expect("should set");
+        let guid = get_existing_guid(&conn, &url);
+
+        let record = |annos| HistoryRecord {
+            id: guid.clone(),
+            title: "title".into(),
+            hist_uri: "https://example.com".into(),
+            visits: vec![HistoryRecordVisit {
+                date: now.into(),
+                transition: 1,
+                unknown_fields: UnknownFields::new(),
+            }],
+            annos,
+            unknown_fields: UnknownFields::new(),
+        };
+        let anno = |value| HistoryRecordAnno {
+            name: "reader-mode".into(),
+            value: AnnotationValue::Boolean { value },
+            expires_after_days: None,
+        };
+
+        // Records from older clients, and those with the same annotations,
+        // don't change anything.
+        assert!(matches!(
+            plan_incoming_record(&conn, record(None), 10),
+            IncomingPlan::Reconciled
+        ));
+        assert!(matches!(
+            plan_incoming_record(&conn, record(Some(vec![anno(true)])), 10),
+            IncomingPlan::Reconciled
+        ));
+
+        // But a changed annotation is applied.
+        assert!(matches!(
+            plan_incoming_record(&conn, record(Some(vec![anno(false)])), 10),
+            IncomingPlan::Apply { annos: Some(annos), .. } if annos == [anno(false)]
+        ));
+        let incoming = IncomingBso::from_test_content(record(Some(vec![anno(false)])));
+        apply_and_get_outgoing(&conn, vec![incoming]);
+        assert_eq!(
+            get_annotation(&conn, &url, "reader-mode")
+                .expect("should work")
+                .map(|a| a.value),
+            Some(AnnotationValue::Boolean { value: false })
+        );
+
+        // And so is one that was removed.
+        let incoming = IncomingBso::from_test_content(record(Some(vec![])));
+        apply_and_get_outgoing(&conn, vec![incoming]);
+        assert_eq!(
+            get_annotation(&conn, &url, "reader-mode").expect("should work"),
+            None
+        );
+    }
+
     #[test]
     fn test_plan_dupe_visit_different_guid_no_visits() {
         error_support::init_for_tests();
@@ -473,6 +573,7 @@ mod tests {
             title: "title".into(),
             hist_uri: "https://example.com".into(),
             visits: vec![],
+            annos: None,
             unknown_fields: UnknownFields::new(),
         };
         // Even though there are no visits we should record that it will be
@@ -703,6 +804,7 @@ mod tests {
             title: "title".into(),
             hist_uri: "http://example.com".into(),
             visits,
+            annos: None,
             unknown_fields: UnknownFields::new(),
         };
         let plan = plan_incoming_record(&db, record, 10);
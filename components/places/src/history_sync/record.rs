@@ -2,6 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use crate::storage::annotations::AnnotationValue;
 use crate::{history_sync::ServerVisitTimestamp, types::UnknownFields};
 use serde::Deserialize;
 use serde_derive::*;
@@ -33,10 +34,26 @@ pub struct HistoryRecord {
 
     pub visits: Vec<HistoryRecordVisit>,
 
+    /// The page's synced annotations. This is `None` for records from
+    /// clients which don't know about annotations, so that we keep ours.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub annos: Option<Vec<HistoryRecordAnno>>,
+
     #[serde(flatten)]
     pub unknown_fields: UnknownFields,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRecordAnno {
+    pub name: String,
+    pub value: AnnotationValue,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_after_days: Option<u32>,
+}
+
 fn deserialize_nonull_string<'de, D>(deserializer: D) -> Result<String, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -0,0 +1,843 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Import and export of bookmarks in the "Netscape bookmark file" HTML format, which is what
+//! every browser produces when asked to export bookmarks.
+//!
+//! The format is a loose, unclosed HTML dialect:
+//!
+//! ```html
+//! <DL><p>
+//!     <DT><H3 ADD_DATE="1600000000">A folder</H3>
+//!     <DL><p>
+//!         <DT><A HREF="https://example.com" ADD_DATE="1600000000" TAGS="a,b">A bookmark</A>
+//!         <HR>
+//!     </DL><p>
+//! </DL><p>
+//! ```
+//!
+//! so we parse it with a small, tolerant tokenizer rather than a real HTML parser. As on Desktop,
+//! items at the top level are imported into the menu, while folders marked with
+//! `PERSONAL_TOOLBAR_FOLDER` or `UNFILED_BOOKMARKS_FOLDER` have their children imported into the
+//! toolbar and "Other Bookmarks" roots. Items are always appended, so importing never touches
+//! existing bookmarks.
+
+use crate::db::PlacesDb;
+use crate::error::{info, warn, Result};
+use crate::import::common::{add_imported_tags_and_keyword, BookmarksImportResult};
+use crate::storage::bookmarks::json_tree::{fetch_tree, BookmarkTreeNode, FetchDepth};
+use crate::storage::bookmarks::{
+    bookmarks_get_keyword_for_url, insert_bookmark_in_tx, BookmarkPosition, BookmarkRootGuid,
+    InsertableBookmark, InsertableFolder, InsertableItem, InsertableSeparator,
+};
+use crate::storage::tags::{get_tags_for_url, validate_tag};
+use crate::storage::{delete_pending_temp_tables, URL_LENGTH_MAX};
+use sync_guid::Guid as SyncGuid;
+use types::Timestamp;
+use url::Url;
+
+/// Imports a bookmarks HTML file into the menu, toolbar and unfiled roots.
+///
+/// The import happens in a single transaction, so either everything that
+/// could be imported is, or nothing is.
+pub fn import(db: &PlacesDb, html: &str) -> Result<BookmarksImportResult> {
+    let scope = db.begin_interrupt_scope()?;
+    let parsed = parse(html);
+    let mut result = BookmarksImportResult::default();
+    let mut annotations = Vec::new();
+    let roots = [
+        (BookmarkRootGuid::Menu, parsed.menu),
+        (BookmarkRootGuid::Toolbar, parsed.toolbar),
+        (BookmarkRootGuid::Unfiled, parsed.unfiled),
+    ];
+
+    let tx = db.begin_transaction()?;
+    let inserted = (|| -> Result<()> {
+        for (root, items) in roots {
+            for item in items {
+                let Some(item) = to_insertable(item, root.as_guid(), &mut result, &mut annotations)
+                else {
+                    continue;
+                };
+                insert_bookmark_in_tx(db, item)?;
+                scope.err_if_interrupted()?;
+            }
+        }
+        for (url, tags, keyword) in &annotations {
+            add_imported_tags_and_keyword(db, url, tags, keyword.as_deref())?;
+        }
+        Ok(())
+    })();
+    delete_pending_temp_tables(db)?;
+    match inserted {
+        Ok(()) => tx.commit()?,
+        Err(e) => {
+            tx.rollback()?;
+            return Err(e);
+        }
+    }
+    info!("Imported bookmarks HTML: {:?}", result);
+    Ok(result)
+}
+
+/// Exports all bookmarks as a bookmarks HTML file, in the same layout as
+/// Desktop: the menu's children at the top level, followed by the toolbar,
+/// "Other Bookmarks" and, if it has anything in it, mobile folders.
+pub fn export(db: &PlacesDb) -> Result<String> {
+    let mut out = String::from(EXPORT_HEADER);
+    out.push_str("<DL><p>\n");
+    let mut exporter = Exporter { db, out };
+    if let Some(BookmarkTreeNode::Folder { f }) = fetch_root(db, BookmarkRootGuid::Menu)? {
+        exporter.write_children(&f.children, 1)?;
+    }
+    for (root, title, attr) in [
+        (
+            BookmarkRootGuid::Toolbar,
+            "Bookmarks Toolbar",
+            Some("PERSONAL_TOOLBAR_FOLDER"),
+        ),
+        (
+            BookmarkRootGuid::Unfiled,
+            "Other Bookmarks",
+            Some("UNFILED_BOOKMARKS_FOLDER"),
+        ),
+        (BookmarkRootGuid::Mobile, "Mobile Bookmarks", None),
+    ] {
+        let Some(BookmarkTreeNode::Folder { f }) = fetch_root(db, root)? else {
+            continue;
+        };
+        if attr.is_none() && f.children.is_empty() {
+            continue;
+        }
+        exporter.write_folder_header(title, f.date_added, f.last_modified, attr, 1);
+        exporter.write_children(&f.children, 2)?;
+        exporter.out.push_str("    </DL><p>\n");
+    }
+    exporter.out.push_str("</DL>\n");
+    Ok(exporter.out)
+}
+
+const EXPORT_HEADER: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<meta http-equiv="Content-Security-Policy" content="default-src 'self'; script-src 'none'; img-src data: *; object-src 'none'"></meta>
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks Menu</H1>
+
+"#;
+
+fn fetch_root(db: &PlacesDb, root: BookmarkRootGuid) -> Result<Option<BookmarkTreeNode>> {
+    Ok(fetch_tree(db, root.guid(), &FetchDepth::Deepest)?.map(|(node, _, _)| node))
+}
+
+struct Exporter<'a> {
+    db: &'a PlacesDb,
+    out: String,
+}
+
+impl Exporter<'_> {
+    fn write_children(&mut self, children: &[BookmarkTreeNode], depth: usize) -> Result<()> {
+        let indent = "    ".repeat(depth);
+        for child in children {
+            match child {
+                BookmarkTreeNode::Bookmark { b } => {
+                    self.out.push_str(&indent);
+                    self.out.push_str("<DT><A HREF=\"");
+                    self.out.push_str(&escape(b.url.as_str()));
+                    self.out.push('"');
+                    self.write_dates(b.date_added, b.last_modified);
+                    let tags = get_tags_for_url(self.db, &b.url)?;
+                    if !tags.is_empty() {
+                        self.write_attr("TAGS", &tags.join(","));
+                    }
+                    if let Some(keyword) = bookmarks_get_keyword_for_url(self.db, &b.url)? {
+                        self.write_attr("SHORTCUTURL", &keyword);
+                    }
+                    self.out.push('>');
+                    self.out
+                        .push_str(&escape(b.title.as_deref().unwrap_or_default()));
+                    self.out.push_str("</A>\n");
+                }
+                BookmarkTreeNode::Separator { .. } => {
+                    self.out.push_str(&indent);
+                    self.out.push_str("<HR>\n");
+                }
+                BookmarkTreeNode::Folder { f } => {
+                    self.write_folder_header(
+                        f.title.as_deref().unwrap_or_default(),
+                        f.date_added,
+                        f.last_modified,
+                        None,
+                        depth,
+                    );
+                    self.write_children(&f.children, depth + 1)?;
+                    self.out.push_str(&indent);
+                    self.out.push_str("</DL><p>\n");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_folder_header(
+        &mut self,
+        title: &str,
+        date_added: Option<Timestamp>,
+        last_modified: Option<Timestamp>,
+        root_attr: Option<&str>,
+        depth: usize,
+    ) {
+        let indent = "    ".repeat(depth);
+        self.out.push_str(&indent);
+        self.out.push_str("<DT><H3");
+        self.write_dates(date_added, last_modified);
+        if let Some(attr) = root_attr {
+            self.write_attr(attr, "true");
+        }
+        self.out.push('>');
+        self.out.push_str(&escape(title));
+        self.out.push_str("</H3>\n");
+        self.out.push_str(&indent);
+        self.out.push_str("<DL><p>\n");
+    }
+
+    fn write_dates(&mut self, date_added: Option<Timestamp>, last_modified: Option<Timestamp>) {
+        if let Some(ts) = date_added {
+            self.write_attr("ADD_DATE", &(ts.as_millis() / 1000).to_string());
+        }
+        if let Some(ts) = last_modified {
+            self.write_attr("LAST_MODIFIED", &(ts.as_millis() / 1000).to_string());
+        }
+    }
+
+    fn write_attr(&mut self, name: &str, value: &str) {
+        self.out.push(' ');
+        self.out.push_str(name);
+        self.out.push_str("=\"");
+        self.out.push_str(&escape(value));
+        self.out.push('"');
+    }
+}
+
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// The tags and keyword for an imported bookmark, which we can only add once
+// the bookmark (and therefore its place) exists.
+type Annotations = (Url, Vec<String>, Option<String>);
+
+fn to_insertable(
+    item: ParsedItem,
+    parent_guid: SyncGuid,
+    result: &mut BookmarksImportResult,
+    annotations: &mut Vec<Annotations>,
+) -> Option<InsertableItem> {
+    Some(match item {
+        ParsedItem::Bookmark(b) => {
+            let url = match Url::parse(b.href.trim()) {
+                Ok(url) if url.as_str().len() <= URL_LENGTH_MAX && url.scheme() != "place" => url,
+                _ => {
+                    // The URL comes from the user's bookmarks file, so keep it out of the logs.
+                    warn!("Skipping a bookmark with an invalid or unsupported URL");
+                    result.num_skipped += 1;
+                    return None;
+                }
+            };
+            let tags = b
+                .tags
+                .split(',')
+                .filter_map(|tag| validate_tag(tag).ensure_valid().ok())
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            let keyword = b
+                .keyword
+                .map(|keyword| keyword.trim().to_lowercase())
+                .filter(|keyword| !keyword.is_empty());
+            if !tags.is_empty() || keyword.is_some() {
+                annotations.push((url.clone(), tags, keyword));
+            }
+            result.num_bookmarks += 1;
+            InsertableBookmark {
+                parent_guid,
+                position: BookmarkPosition::Append,
+                date_added: b.date_added,
+                last_modified: b.last_modified,
+                guid: None,
+                url,
+                title: non_empty(b.title),
+            }
+            .into()
+        }
+        ParsedItem::Separator => {
+            result.num_separators += 1;
+            InsertableSeparator {
+                parent_guid,
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: None,
+            }
+            .into()
+        }
+        ParsedItem::Folder(f) => {
+            result.num_folders += 1;
+            let children = f
+                .children
+                .into_iter()
+                .filter_map(|child| {
+                    // An empty parent guid means "this folder".
+                    to_insertable(child, SyncGuid::empty(), result, annotations)
+                })
+                .collect();
+            InsertableFolder {
+                parent_guid,
+                position: BookmarkPosition::Append,
+                date_added: f.date_added,
+                last_modified: f.last_modified,
+                guid: None,
+                title: non_empty(f.title),
+                children,
+            }
+            .into()
+        }
+    })
+}
+
+fn non_empty(title: String) -> Option<String> {
+    let title = title.trim();
+    (!title.is_empty()).then(|| title.to_string())
+}
+
+#[derive(Debug, Default)]
+struct ParsedBookmark {
+    href: String,
+    title: String,
+    date_added: Option<Timestamp>,
+    last_modified: Option<Timestamp>,
+    tags: String,
+    keyword: Option<String>,
+}
+
+#[derive(Debug, Default)]
+struct ParsedFolder {
+    title: String,
+    date_added: Option<Timestamp>,
+    last_modified: Option<Timestamp>,
+    // Set for the folders Desktop uses to mark its toolbar and unfiled roots.
+    root: Option<BookmarkRootGuid>,
+    children: Vec<ParsedItem>,
+}
+
+#[derive(Debug)]
+enum ParsedItem {
+    Bookmark(ParsedBookmark),
+    Separator,
+    Folder(ParsedFolder),
+}
+
+#[derive(Debug, Default)]
+struct ParsedBookmarks {
+    menu: Vec<ParsedItem>,
+    toolbar: Vec<ParsedItem>,
+    unfiled: Vec<ParsedItem>,
+}
+
+#[derive(Debug, Default)]
+struct Parser {
+    bookmarks: ParsedBookmarks,
+    // One entry per open `<DL>`; `None` for lists that don't belong to a
+    // folder, like the outermost one.
+    lists: Vec<Option<ParsedFolder>>,
+    // A folder whose `<H3>` we've seen, but not (yet) its `<DL>`.
+    pending_folder: Option<ParsedFolder>,
+    // The bookmark whose `<A>` we're in.
+    pending_bookmark: Option<ParsedBookmark>,
+    in_folder_title: bool,
+}
+
+impl Parser {
+    fn start_tag(&mut self, name: &str, attrs: Vec<(String, String)>) {
+        match name {
+            "dl" => {
+                let folder = self.pending_folder.take();
+                self.lists.push(folder);
+            }
+            "h3" => {
+                self.finish_pending();
+                let mut folder = ParsedFolder::default();
+                for (name, value) in attrs {
+                    match name.as_str() {
+                        "add_date" => folder.date_added = parse_date(&value),
+                        "last_modified" => folder.last_modified = parse_date(&value),
+                        "personal_toolbar_folder" if value.eq_ignore_ascii_case("true") => {
+                            folder.root = Some(BookmarkRootGuid::Toolbar)
+                        }
+                        "unfiled_bookmarks_folder" if value.eq_ignore_ascii_case("true") => {
+                            folder.root = Some(BookmarkRootGuid::Unfiled)
+                        }
+                        _ => {}
+                    }
+                }
+                self.pending_folder = Some(folder);
+                self.in_folder_title = true;
+            }
+            "a" => {
+                self.finish_pending();
+                let mut bookmark = ParsedBookmark::default();
+                for (name, value) in attrs {
+                    match name.as_str() {
+                        "href" => bookmark.href = value,
+                        "add_date" => bookmark.date_added = parse_date(&value),
+                        "last_modified" => bookmark.last_modified = parse_date(&value),
+                        "tags" => bookmark.tags = value,
+                        "shortcuturl" => bookmark.keyword = Some(value),
+                        _ => {}
+                    }
+                }
+                self.pending_bookmark = Some(bookmark);
+            }
+            "hr" => {
+                self.finish_pending();
+                self.add_item(ParsedItem::Separator);
+            }
+            "dt" | "dd" => self.finish_pending(),
+            _ => {}
+        }
+    }
+
+    fn end_tag(&mut self, name: &str) {
+        match name {
+            "dl" => {
+                self.finish_pending();
+                if let Some(Some(folder)) = self.lists.pop() {
+                    self.add_folder(folder);
+                }
+            }
+            "h3" => self.in_folder_title = false,
+            "a" => {
+                if let Some(bookmark) = self.pending_bookmark.take() {
+                    self.add_item(ParsedItem::Bookmark(bookmark));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some(bookmark) = &mut self.pending_bookmark {
+            bookmark.title.push_str(text);
+        } else if self.in_folder_title {
+            if let Some(folder) = &mut self.pending_folder {
+                folder.title.push_str(text);
+            }
+        }
+    }
+
+    // Finishes an unclosed `<A>`, or a folder without a `<DL>`, which we
+    // treat as empty.
+    fn finish_pending(&mut self) {
+        self.in_folder_title = false;
+        if let Some(bookmark) = self.pending_bookmark.take() {
+            self.add_item(ParsedItem::Bookmark(bookmark));
+        }
+        if let Some(folder) = self.pending_folder.take() {
+            self.add_folder(folder);
+        }
+    }
+
+    fn add_folder(&mut self, folder: ParsedFolder) {
+        match folder.root {
+            Some(BookmarkRootGuid::Toolbar) => self.bookmarks.toolbar.extend(folder.children),
+            Some(BookmarkRootGuid::Unfiled) => self.bookmarks.unfiled.extend(folder.children),
+            _ => self.add_item(ParsedItem::Folder(folder)),
+        }
+    }
+
+    fn add_item(&mut self, item: ParsedItem) {
+        match self.lists.iter_mut().rev().find_map(Option::as_mut) {
+            Some(folder) => folder.children.push(item),
+            None => self.bookmarks.menu.push(item),
+        }
+    }
+
+    fn finish(mut self) -> ParsedBookmarks {
+        // Close anything left open by a truncated file.
+        self.finish_pending();
+        while !self.lists.is_empty() {
+            self.end_tag("dl");
+        }
+        self.bookmarks
+    }
+}
+
+fn parse(html: &str) -> ParsedBookmarks {
+    let mut parser = Parser::default();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(lt) = rest.find('<') else {
+            parser.text(&decode_entities(rest));
+            break;
+        };
+        if lt > 0 {
+            parser.text(&decode_entities(&rest[..lt]));
+        }
+        rest = &rest[lt..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = find_tag_end(rest) else {
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        if let Some(name) = tag.strip_prefix('/') {
+            parser.end_tag(&name.trim().to_ascii_lowercase());
+        } else if !tag.starts_with('!') {
+            let (name, attrs) = parse_tag(tag);
+            parser.start_tag(&name, attrs);
+        }
+    }
+    parser.finish()
+}
+
+// Finds the `>` that ends the tag at the start of `s`, skipping any inside
+// quoted attribute values.
+fn find_tag_end(s: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Splits the inside of a start tag into its lowercased name and attributes,
+// with attribute names lowercased and values decoded.
+fn parse_tag(tag: &str) -> (String, Vec<(String, String)>) {
+    let name_end = tag
+        .find(|c: char| c == '/' || c.is_ascii_whitespace())
+        .unwrap_or(tag.len());
+    let name = tag[..name_end].to_ascii_lowercase();
+    let mut attrs = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_ascii_whitespace())
+            .unwrap_or(rest.len());
+        let attr_name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+        let value = if let Some(after_eq) = rest.strip_prefix('=') {
+            let after_eq = after_eq.trim_start();
+            let (value, remaining) = match after_eq.chars().next() {
+                Some(q @ ('"' | '\'')) => {
+                    let quoted = &after_eq[1..];
+                    let end = quoted.find(q).unwrap_or(quoted.len());
+                    (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+                }
+                _ => {
+                    let end = after_eq
+                        .find(|c: char| c.is_ascii_whitespace())
+                        .unwrap_or(after_eq.len());
+                    (&after_eq[..end], &after_eq[end..])
+                }
+            };
+            rest = remaining.trim_start();
+            decode_entities(value)
+        } else {
+            String::new()
+        };
+        // A `/` here is from a self-closing tag, like `<HR/>`.
+        if !attr_name.is_empty() && attr_name != "/" {
+            attrs.push((attr_name, value));
+        }
+    }
+    (name, attrs)
+}
+
+fn decode_entities(s: &str) -> String {
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        decoded.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| Some((decode_entity(&rest[1..=end])?, end + 2)));
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    let code = match entity {
+        "amp" => return Some('&'),
+        "lt" => return Some('<'),
+        "gt" => return Some('>'),
+        "quot" => return Some('"'),
+        "apos" => return Some('\''),
+        "nbsp" => return Some('\u{a0}'),
+        _ => entity.strip_prefix('#')?,
+    };
+    let code = match code.strip_prefix(['x', 'X']) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => code.parse().ok()?,
+    };
+    char::from_u32(code)
+}
+
+// Dates are meant to be in seconds, but some exporters write milli- or
+// microseconds. Anything we can't make sense of, or in the future, is ignored,
+// so the item gets the current time instead.
+fn parse_date(value: &str) -> Option<Timestamp> {
+    let value = value.trim().parse::<u64>().ok()?;
+    let millis = match value {
+        0 => return None,
+        v if v < 100_000_000_000 => v.checked_mul(1000)?,
+        v if v < 100_000_000_000_000 => v,
+        v => v / 1000,
+    };
+    let ts = Timestamp(millis);
+    (ts <= Timestamp::now()).then_some(ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::bookmarks::{bookmarks_get_url_for_keyword, insert_bookmark};
+    use crate::storage::tags::tag_url;
+
+    const FIREFOX_EXPORT: &str = r#"<!DOCTYPE NETSCAPE-Bookmark-file-1>
+<!-- This is an automatically generated file.
+     It will be read and overwritten.
+     DO NOT EDIT! -->
+<META HTTP-EQUIV="Content-Type" CONTENT="text/html; charset=UTF-8">
+<TITLE>Bookmarks</TITLE>
+<H1>Bookmarks Menu</H1>
+
+<DL><p>
+    <DT><A HREF="https://www.mozilla.org/" ADD_DATE="1600000000" LAST_MODIFIED="1600000100" TAGS="moz,foundation">Mozilla &amp; friends</A>
+    <HR>
+    <DT><H3 ADD_DATE="1600000000">Folder</H3>
+    <DL><p>
+        <DT><A HREF="https://example.com/search?q=%s" SHORTCUTURL="Ex">Search</A>
+        <DT><A HREF="place:sort=8&maxResults=10">Most Visited</A>
+        <DT><A HREF="not a url">Broken</A>
+        <DT><H3>Empty</H3>
+        <DL><p>
+        </DL><p>
+    </DL><p>
+    <DT><H3 PERSONAL_TOOLBAR_FOLDER="true">Bookmarks Toolbar</H3>
+    <DL><p>
+        <DT><A HREF="https://toolbar.example.com/">On the toolbar</A>
+    </DL><p>
+    <DT><H3 UNFILED_BOOKMARKS_FOLDER="true">Other Bookmarks</H3>
+    <DL><p>
+        <DT><A HREF="https://unfiled.example.com/">Unfiled</A>
+    </DL><p>
+</DL>
+"#;
+
+    fn root_children(conn: &PlacesDb, root: BookmarkRootGuid) -> Vec<BookmarkTreeNode> {
+        match fetch_root(conn, root).unwrap() {
+            Some(BookmarkTreeNode::Folder { f }) => f.children,
+            _ => panic!("expected a folder"),
+        }
+    }
+
+    #[test]
+    fn test_import() -> Result<()> {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let result = import(&conn, FIREFOX_EXPORT)?;
+        assert_eq!(
+            result,
+            BookmarksImportResult {
+                num_bookmarks: 4,
+                num_folders: 2,
+                num_separators: 1,
+                num_skipped: 2,
+            }
+        );
+
+        let menu = root_children(&conn, BookmarkRootGuid::Menu);
+        assert_eq!(menu.len(), 3);
+        let BookmarkTreeNode::Bookmark { b } = &menu[0] else {
+            panic!("expected a bookmark");
+        };
+        assert_eq!(b.title.as_deref(), Some("Mozilla & friends"));
+        assert_eq!(b.date_added, Some(Timestamp(1_600_000_000_000)));
+        assert_eq!(b.last_modified, Some(Timestamp(1_600_000_100_000)));
+        let mut tags = get_tags_for_url(&conn, &b.url)?;
+        tags.sort();
+        assert_eq!(tags, vec!["foundation", "moz"]);
+
+        assert!(matches!(menu[1], BookmarkTreeNode::Separator { .. }));
+
+        let BookmarkTreeNode::Folder { f } = &menu[2] else {
+            panic!("expected a folder");
+        };
+        assert_eq!(f.title.as_deref(), Some("Folder"));
+        assert_eq!(f.children.len(), 2);
+        assert!(matches!(
+            &f.children[0],
+            BookmarkTreeNode::Bookmark { b } if b.title.as_deref() == Some("Search")
+        ));
+        assert!(matches!(
+            &f.children[1],
+            BookmarkTreeNode::Folder { f } if f.title.as_deref() == Some("Empty") && f.children.is_empty()
+        ));
+        assert_eq!(
+            bookmarks_get_url_for_keyword(&conn, "ex")?.map(String::from),
+            Some("https://example.com/search?q=%s".to_string())
+        );
+
+        assert_eq!(root_children(&conn, BookmarkRootGuid::Toolbar).len(), 1);
+        assert_eq!(root_children(&conn, BookmarkRootGuid::Unfiled).len(), 1);
+        assert!(root_children(&conn, BookmarkRootGuid::Mobile).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_appends() -> Result<()> {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        insert_bookmark(
+            &conn,
+            InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Menu.as_guid(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: Some(SyncGuid::from("bookmarkAAAA")),
+                url: Url::parse("https://existing.example.com/")?,
+                title: None,
+            }
+            .into(),
+        )?;
+        import(&conn, FIREFOX_EXPORT)?;
+        let menu = root_children(&conn, BookmarkRootGuid::Menu);
+        assert_eq!(menu.len(), 4);
+        assert!(matches!(
+            &menu[0],
+            BookmarkTreeNode::Bookmark { b } if b.guid.as_ref().unwrap() == "bookmarkAAAA"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_tolerance() {
+        // Lowercase tags, unquoted attributes, missing `</A>` and `</DL>`,
+        // and no outer `<DL>`.
+        let parsed = parse(
+            "<dt><a href=https://example.com/ add_date='1600000000'>One\n\
+             <dt><h3>Folder</h3><dl><p><dt><a href=\"https://example.com/2\">Two",
+        );
+        assert_eq!(parsed.menu.len(), 2);
+        let ParsedItem::Bookmark(one) = &parsed.menu[0] else {
+            panic!("expected a bookmark");
+        };
+        assert_eq!(one.href, "https://example.com/");
+        assert_eq!(one.title.trim(), "One");
+        assert_eq!(one.date_added, Some(Timestamp(1_600_000_000_000)));
+        let ParsedItem::Folder(folder) = &parsed.menu[1] else {
+            panic!("expected a folder");
+        };
+        assert_eq!(folder.title, "Folder");
+        assert_eq!(folder.children.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(parse_date("1600000000"), Some(Timestamp(1_600_000_000_000)));
+        assert_eq!(
+            parse_date("1600000000123"),
+            Some(Timestamp(1_600_000_000_123))
+        );
+        assert_eq!(
+            parse_date("1600000000123456"),
+            Some(Timestamp(1_600_000_000_123))
+        );
+        assert_eq!(parse_date("0"), None);
+        assert_eq!(parse_date("-1"), None);
+        assert_eq!(parse_date("nope"), None);
+        assert_eq!(parse_date("99999999999"), None);
+    }
+
+    #[test]
+    fn test_decode_entities() {
+        assert_eq!(decode_entities("a &amp; b"), "a & b");
+        assert_eq!(decode_entities("&lt;&#62;&#x22;&apos;"), "<>\"'");
+        assert_eq!(decode_entities("AT&T & co"), "AT&T & co");
+        assert_eq!(decode_entities("&bogus;"), "&bogus;");
+    }
+
+    #[test]
+    fn test_export_round_trip() -> Result<()> {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        import(&conn, FIREFOX_EXPORT)?;
+        let url = Url::parse("https://toolbar.example.com/")?;
+        tag_url(&conn, &url, "a \"quoted\" <tag>")?;
+
+        let html = export(&conn)?;
+        assert!(html.starts_with("<!DOCTYPE NETSCAPE-Bookmark-file-1>"));
+        assert!(html.contains(
+            r#"<DT><A HREF="https://www.mozilla.org/" ADD_DATE="1600000000" LAST_MODIFIED="1600000100""#
+        ));
+        assert!(html.contains(r#"SHORTCUTURL="ex">Search</A>"#));
+        assert!(html.contains(">Mozilla &amp; friends</A>"));
+        assert!(html.contains(r#"TAGS="a &quot;quoted&quot; &lt;tag&gt;""#));
+        assert!(html.contains("PERSONAL_TOOLBAR_FOLDER=\"true\">Bookmarks Toolbar</H3>"));
+        // Mobile is empty, so isn't exported.
+        assert!(!html.contains("Mobile Bookmarks"));
+
+        // Importing the export into a new database gives us the same bookmarks.
+        let other = new_mem_connection();
+        let result = import(&other, &html)?;
+        assert_eq!(
+            result,
+            BookmarksImportResult {
+                num_bookmarks: 4,
+                num_folders: 2,
+                num_separators: 1,
+                num_skipped: 0,
+            }
+        );
+        let exported = export(&other)?;
+        for line in html.lines().filter(|line| line.contains("<A HREF")) {
+            assert!(exported.contains(line), "missing {}", line);
+        }
+        Ok(())
+    }
+}
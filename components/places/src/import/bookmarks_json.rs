@@ -0,0 +1,694 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Backing up and restoring bookmarks in Desktop's JSON backup format - the contents of the
+//! `bookmarkbackups/bookmarks-*.jsonlz4` files, which can also be written uncompressed.
+//!
+//! A backup is the whole tree, starting at the root:
+//!
+//! ```json
+//! {"guid": "root________", "typeCode": 2, "type": "text/x-moz-place-container",
+//!  "root": "placesRoot", "dateAdded": 1600000000000000, ..., "children": [
+//!     {"guid": "menu________", "root": "bookmarksMenuFolder", ..., "children": [
+//!         {"guid": "bookmarkAAAA", "typeCode": 1, "type": "text/x-moz-place",
+//!          "title": "Example", "uri": "https://example.com/", "tags": "a,b",
+//!          "keyword": "ex", ...}
+//!     ]},
+//!     ...
+//! ]}
+//! ```
+//!
+//! with times in microseconds. Restoring replaces all bookmarks and keywords with the backup,
+//! keeping GUIDs, so the result looks like what `bookmarks_get_tree()` returned when the backup was
+//! made.
+//!
+//! Restoring also leaves Sync with a coherent tree to upload: every restored item and root is
+//! marked as changed, items that were on the server but aren't in the backup are tombstoned, and
+//! items which are in both keep their "synced" status, so they're uploaded as updates rather than
+//! deleted and recreated.
+
+use super::common::{add_imported_tags_and_keyword, BookmarksImportResult};
+use super::mozlz4;
+use crate::db::PlacesDb;
+use crate::error::{info, warn, Corruption, Error, Result};
+use crate::storage::bookmarks::json_tree::{fetch_tree, BookmarkTreeNode, FetchDepth};
+use crate::storage::bookmarks::{
+    bookmarks_get_keyword_for_url, insert_bookmark_in_tx, BookmarkPosition, BookmarkRootGuid,
+    InsertableBookmark, InsertableFolder, InsertableItem, InsertableSeparator, USER_CONTENT_ROOTS,
+};
+use crate::storage::tags::get_tags_for_url;
+use crate::storage::{delete_pending_temp_tables, URL_LENGTH_MAX};
+use crate::types::{BookmarkType, SyncStatus};
+use serde_derive::*;
+use sql_support::ConnExt;
+use std::collections::HashSet;
+use sync_guid::Guid as SyncGuid;
+use types::Timestamp;
+use url::Url;
+
+const TYPE_BOOKMARK: &str = "text/x-moz-place";
+const TYPE_FOLDER: &str = "text/x-moz-place-container";
+const TYPE_SEPARATOR: &str = "text/x-moz-place-separator";
+
+// A node in a Desktop backup, with a union of the fields for each type.
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+struct BackupItem {
+    guid: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    index: u32,
+    date_added: Option<u64>,
+    last_modified: Option<u64>,
+    type_code: u8,
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    root: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    uri: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tags: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keyword: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    children: Vec<BackupItem>,
+}
+
+impl BackupItem {
+    fn bookmark_type(&self) -> BookmarkType {
+        match self.kind.as_str() {
+            TYPE_BOOKMARK => BookmarkType::Bookmark,
+            TYPE_FOLDER => BookmarkType::Folder,
+            TYPE_SEPARATOR => BookmarkType::Separator,
+            _ => BookmarkType::from_u8_with_valid_url(self.type_code, || self.uri.is_some()),
+        }
+    }
+}
+
+// The name Desktop gives each root in the `root` property.
+fn root_name(root: BookmarkRootGuid) -> &'static str {
+    match root {
+        BookmarkRootGuid::Root => "placesRoot",
+        BookmarkRootGuid::Menu => "bookmarksMenuFolder",
+        BookmarkRootGuid::Toolbar => "toolbarFolder",
+        BookmarkRootGuid::Unfiled => "unfiledBookmarksFolder",
+        BookmarkRootGuid::Mobile => "mobileFolder",
+    }
+}
+
+fn root_for_item(item: &BackupItem) -> Option<BookmarkRootGuid> {
+    if let Some(root) = item.guid.as_deref().and_then(BookmarkRootGuid::well_known) {
+        return Some(root);
+    }
+    let name = item.root.as_deref()?;
+    [
+        BookmarkRootGuid::Root,
+        BookmarkRootGuid::Menu,
+        BookmarkRootGuid::Toolbar,
+        BookmarkRootGuid::Unfiled,
+        BookmarkRootGuid::Mobile,
+    ]
+    .into_iter()
+    .find(|&root| root_name(root) == name)
+}
+
+/// Backs up all bookmarks in Desktop's JSON format, compressed as a
+/// `.jsonlz4` file if `compress` is true.
+pub fn backup(db: &PlacesDb, compress: bool) -> Result<Vec<u8>> {
+    let Some((root, _, _)) = fetch_tree(db, BookmarkRootGuid::Root.guid(), &FetchDepth::Deepest)?
+    else {
+        return Err(Corruption::InvalidLocalRoots.into());
+    };
+    let backup = to_backup_item(db, root, 0)?;
+    let json = serde_json::to_vec(&backup)?;
+    Ok(if compress {
+        mozlz4::compress(&json)
+    } else {
+        json
+    })
+}
+
+fn to_backup_item(db: &PlacesDb, node: BookmarkTreeNode, index: u32) -> Result<BackupItem> {
+    let micros = |ts: Option<Timestamp>| ts.map(|ts| ts.as_millis() * 1000);
+    Ok(match node {
+        BookmarkTreeNode::Bookmark { b } => BackupItem {
+            guid: b.guid.map(String::from),
+            title: b.title,
+            index,
+            date_added: micros(b.date_added),
+            last_modified: micros(b.last_modified),
+            type_code: BookmarkType::Bookmark as u8,
+            kind: TYPE_BOOKMARK.into(),
+            tags: Some(get_tags_for_url(db, &b.url)?.join(",")).filter(|tags| !tags.is_empty()),
+            keyword: bookmarks_get_keyword_for_url(db, &b.url)?,
+            uri: Some(b.url.into()),
+            ..Default::default()
+        },
+        BookmarkTreeNode::Separator { s } => BackupItem {
+            guid: s.guid.map(String::from),
+            index,
+            date_added: micros(s.date_added),
+            last_modified: micros(s.last_modified),
+            type_code: BookmarkType::Separator as u8,
+            kind: TYPE_SEPARATOR.into(),
+            ..Default::default()
+        },
+        BookmarkTreeNode::Folder { f } => {
+            let root = f.guid.as_ref().and_then(BookmarkRootGuid::from_guid);
+            BackupItem {
+                guid: f.guid.map(String::from),
+                // Desktop always writes a title for folders, even the root.
+                title: Some(f.title.unwrap_or_default()),
+                index,
+                date_added: micros(f.date_added),
+                last_modified: micros(f.last_modified),
+                type_code: BookmarkType::Folder as u8,
+                kind: TYPE_FOLDER.into(),
+                root: root.map(|root| root_name(root).into()),
+                children: f
+                    .children
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, child)| to_backup_item(db, child, i as u32))
+                    .collect::<Result<_>>()?,
+                ..Default::default()
+            }
+        }
+    })
+}
+
+/// Replaces all bookmarks with those in a Desktop JSON backup, which may be
+/// compressed as a `.jsonlz4` file.
+pub fn restore(db: &PlacesDb, data: &[u8]) -> Result<BookmarksImportResult> {
+    let scope = db.begin_interrupt_scope()?;
+    let json = if mozlz4::is_mozlz4(data) {
+        mozlz4::decompress(data)
+            .ok_or_else(|| Error::InvalidBookmarksBackup("invalid jsonlz4 data".into()))?
+    } else {
+        data.to_vec()
+    };
+    let backup: BackupItem =
+        serde_json::from_slice(&json).map_err(|e| Error::InvalidBookmarksBackup(e.to_string()))?;
+    if root_for_item(&backup) != Some(BookmarkRootGuid::Root) {
+        return Err(Error::InvalidBookmarksBackup(
+            "the backup doesn't start at the bookmarks root".into(),
+        ));
+    }
+
+    let mut restorer = Restorer::default();
+    let mut roots = Vec::new();
+    for child in backup.children {
+        match root_for_item(&child) {
+            Some(root) if USER_CONTENT_ROOTS.contains(&root) => {
+                let children = child
+                    .children
+                    .into_iter()
+                    .filter_map(|child| restorer.convert_item(child, root.as_guid()))
+                    .collect::<Vec<_>>();
+                roots.push(children);
+            }
+            // Older Desktop backups include the tags folder, but tags are
+            // also on each bookmark, so we don't need it.
+            _ => info!("Skipping unknown bookmarks root {:?}", child.guid),
+        }
+    }
+
+    let tx = db.begin_transaction()?;
+    let restored = (|| -> Result<()> {
+        // Remember which items are already on the server, since deleting
+        // them tombstones them, and reinserting them makes them "new".
+        let synced_guids: Vec<SyncGuid> = db.query_rows_and_then(
+            "SELECT guid FROM moz_bookmarks WHERE syncStatus = :normal",
+            rusqlite::named_params! { ":normal": SyncStatus::Normal as u8 },
+            |row| row.get(0),
+        )?;
+        // Tags are only kept for URLs in the backup, so clear them too. This
+        // leaves `moz_tags` alone, as synced items refer to it.
+        db.execute_batch(&format!(
+            "DELETE FROM moz_bookmarks
+             WHERE guid NOT IN ('{}', '{}', '{}', '{}', '{}');
+             DELETE FROM moz_keywords;
+             DELETE FROM moz_tags_relation;",
+            BookmarkRootGuid::Root.as_str(),
+            BookmarkRootGuid::Menu.as_str(),
+            BookmarkRootGuid::Mobile.as_str(),
+            BookmarkRootGuid::Toolbar.as_str(),
+            BookmarkRootGuid::Unfiled.as_str(),
+        ))?;
+        for item in roots.into_iter().flatten() {
+            insert_bookmark_in_tx(db, item)?;
+            scope.err_if_interrupted()?;
+        }
+        for (url, tags, keyword) in &restorer.annotations {
+            add_imported_tags_and_keyword(db, url, tags, keyword.as_deref())?;
+        }
+        // Restored items which were already on the server are now "new".
+        // Make them synced items with changes instead.
+        sql_support::each_chunk(&synced_guids, |chunk, _| -> Result<()> {
+            db.execute(
+                &format!(
+                    "UPDATE moz_bookmarks SET
+                       syncStatus = {},
+                       syncChangeCounter = syncChangeCounter + 1
+                     WHERE guid IN ({})",
+                    SyncStatus::Normal as u8,
+                    sql_support::repeat_sql_vars(chunk.len()),
+                ),
+                rusqlite::params_from_iter(chunk),
+            )?;
+            Ok(())
+        })?;
+        db.execute_batch(&format!(
+            "DELETE FROM moz_bookmarks_deleted
+             WHERE guid IN (SELECT guid FROM moz_bookmarks);
+             UPDATE moz_bookmarks SET syncChangeCounter = syncChangeCounter + 1
+             WHERE guid IN ('{}', '{}', '{}', '{}');",
+            BookmarkRootGuid::Menu.as_str(),
+            BookmarkRootGuid::Mobile.as_str(),
+            BookmarkRootGuid::Toolbar.as_str(),
+            BookmarkRootGuid::Unfiled.as_str(),
+        ))?;
+        Ok(())
+    })();
+    delete_pending_temp_tables(db)?;
+    match restored {
+        Ok(()) => tx.commit()?,
+        Err(e) => {
+            tx.rollback()?;
+            return Err(e);
+        }
+    }
+    info!("Restored bookmarks backup: {:?}", restorer.result);
+    Ok(restorer.result)
+}
+
+#[derive(Default)]
+struct Restorer {
+    result: BookmarksImportResult,
+    // The tags and keyword for each restored bookmark that has them.
+    annotations: Vec<(Url, Vec<String>, Option<String>)>,
+    seen_guids: HashSet<SyncGuid>,
+}
+
+impl Restorer {
+    fn convert_item(&mut self, item: BackupItem, parent_guid: SyncGuid) -> Option<InsertableItem> {
+        let bookmark_type = item.bookmark_type();
+        let guid = self.guid_for(&item);
+        let date_added = sanitize_timestamp(item.date_added);
+        let last_modified = sanitize_timestamp(item.last_modified);
+        Some(match bookmark_type {
+            BookmarkType::Bookmark => {
+                let url = match item.uri.as_deref().map(Url::parse) {
+                    Some(Ok(url)) if url.as_str().len() <= URL_LENGTH_MAX => url,
+                    _ => {
+                        warn!("Skipping bookmark {:?} with an invalid URL", item.guid);
+                        self.result.num_skipped += 1;
+                        return None;
+                    }
+                };
+                let tags = item
+                    .tags
+                    .iter()
+                    .flat_map(|tags| tags.split(','))
+                    .map(str::trim)
+                    .filter(|tag| !tag.is_empty())
+                    .map(str::to_string)
+                    .collect::<Vec<_>>();
+                let keyword = item
+                    .keyword
+                    .map(|keyword| keyword.trim().to_lowercase())
+                    .filter(|keyword| !keyword.is_empty());
+                if !tags.is_empty() || keyword.is_some() {
+                    self.annotations.push((url.clone(), tags, keyword));
+                }
+                self.result.num_bookmarks += 1;
+                InsertableBookmark {
+                    parent_guid,
+                    position: BookmarkPosition::Append,
+                    date_added,
+                    last_modified,
+                    guid,
+                    url,
+                    title: item.title,
+                }
+                .into()
+            }
+            BookmarkType::Separator => {
+                self.result.num_separators += 1;
+                InsertableSeparator {
+                    parent_guid,
+                    position: BookmarkPosition::Append,
+                    date_added,
+                    last_modified,
+                    guid,
+                }
+                .into()
+            }
+            BookmarkType::Folder => {
+                self.result.num_folders += 1;
+                let children = item
+                    .children
+                    .into_iter()
+                    // An empty parent guid means "this folder".
+                    .filter_map(|child| self.convert_item(child, SyncGuid::empty()))
+                    .collect();
+                InsertableFolder {
+                    parent_guid,
+                    position: BookmarkPosition::Append,
+                    date_added,
+                    last_modified,
+                    guid,
+                    title: item.title,
+                    children,
+                }
+                .into()
+            }
+        })
+    }
+
+    // Keeps the item's guid, unless it's invalid or a duplicate, in which
+    // case the item gets a new one.
+    fn guid_for(&mut self, item: &BackupItem) -> Option<SyncGuid> {
+        let guid = SyncGuid::from(item.guid.as_deref()?);
+        let valid = guid.is_valid_for_places()
+            && guid.is_valid_for_sync_server()
+            && BookmarkRootGuid::from_guid(&guid).is_none();
+        if valid && self.seen_guids.insert(guid.clone()) {
+            Some(guid)
+        } else {
+            warn!("Replacing invalid or duplicate guid {:?}", item.guid);
+            None
+        }
+    }
+}
+
+// Backup times are in microseconds.
+fn sanitize_timestamp(micros: Option<u64>) -> Option<Timestamp> {
+    let ts = Timestamp(micros? / 1000);
+    (Timestamp::EARLIEST <= ts && ts <= Timestamp::now()).then_some(ts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::bookmarks::{bookmarks_get_url_for_keyword, insert_bookmark};
+    use crate::storage::tags::tag_url;
+
+    fn insert_test_tree(conn: &PlacesDb) -> Result<()> {
+        insert_bookmark(
+            conn,
+            InsertableFolder {
+                parent_guid: BookmarkRootGuid::Menu.as_guid(),
+                position: BookmarkPosition::Append,
+                date_added: Some(Timestamp(1_600_000_000_000)),
+                last_modified: Some(Timestamp(1_600_000_001_000)),
+                guid: Some(SyncGuid::from("folderAAAAAA")),
+                title: Some("Folder".into()),
+                children: vec![
+                    InsertableBookmark {
+                        parent_guid: SyncGuid::empty(),
+                        position: BookmarkPosition::Append,
+                        date_added: None,
+                        last_modified: None,
+                        guid: Some(SyncGuid::from("bookmarkAAAA")),
+                        url: Url::parse("https://example.com/a")?,
+                        title: Some("A".into()),
+                    }
+                    .into(),
+                    InsertableSeparator {
+                        parent_guid: SyncGuid::empty(),
+                        position: BookmarkPosition::Append,
+                        date_added: None,
+                        last_modified: None,
+                        guid: Some(SyncGuid::from("separatorAAA")),
+                    }
+                    .into(),
+                ],
+            }
+            .into(),
+        )?;
+        insert_bookmark(
+            conn,
+            InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Toolbar.as_guid(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: Some(SyncGuid::from("bookmarkBBBB")),
+                url: Url::parse("https://example.com/b")?,
+                title: Some("B".into()),
+            }
+            .into(),
+        )?;
+        tag_url(conn, &Url::parse("https://example.com/a")?, "tag")?;
+        conn.execute(
+            "INSERT INTO moz_keywords(place_id, keyword)
+             SELECT id, 'kw' FROM moz_places WHERE url = 'https://example.com/b'",
+            [],
+        )?;
+        Ok(())
+    }
+
+    fn fetch_root(conn: &PlacesDb) -> BookmarkTreeNode {
+        fetch_tree(conn, BookmarkRootGuid::Root.guid(), &FetchDepth::Deepest)
+            .unwrap()
+            .unwrap()
+            .0
+    }
+
+    // The children of each root. We don't compare the roots themselves, as
+    // their timestamps are from when the database was created.
+    fn user_content(conn: &PlacesDb) -> Vec<Vec<BookmarkTreeNode>> {
+        let BookmarkTreeNode::Folder { f: root } = fetch_root(conn) else {
+            panic!("root should be a folder");
+        };
+        root.children
+            .into_iter()
+            .map(|child| match child {
+                BookmarkTreeNode::Folder { f } => f.children,
+                _ => panic!("roots should be folders"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_backup_format() -> Result<()> {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        insert_test_tree(&conn)?;
+        let backup: serde_json::Value = serde_json::from_slice(&backup(&conn, false)?)?;
+        assert_eq!(backup["guid"], "root________");
+        assert_eq!(backup["root"], "placesRoot");
+        assert_eq!(backup["type"], TYPE_FOLDER);
+        let menu = &backup["children"][0];
+        assert_eq!(menu["root"], "bookmarksMenuFolder");
+        let folder = &menu["children"][0];
+        assert_eq!(folder["guid"], "folderAAAAAA");
+        assert_eq!(folder["dateAdded"], 1_600_000_000_000_000u64);
+        assert_eq!(folder["lastModified"], 1_600_000_001_000_000u64);
+        let bookmark = &folder["children"][0];
+        assert_eq!(bookmark["typeCode"], 1);
+        assert_eq!(bookmark["type"], TYPE_BOOKMARK);
+        assert_eq!(bookmark["uri"], "https://example.com/a");
+        assert_eq!(bookmark["tags"], "tag");
+        assert_eq!(folder["children"][1]["index"], 1);
+        assert_eq!(folder["children"][1]["type"], TYPE_SEPARATOR);
+        let toolbar = &backup["children"][1];
+        assert_eq!(toolbar["root"], "toolbarFolder");
+        assert_eq!(toolbar["children"][0]["keyword"], "kw");
+        Ok(())
+    }
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        insert_test_tree(&conn)?;
+        let original = user_content(&conn);
+        for compress in [false, true] {
+            let data = backup(&conn, compress)?;
+            assert_eq!(mozlz4::is_mozlz4(&data), compress);
+
+            let other = new_mem_connection();
+            insert_bookmark(
+                &other,
+                InsertableBookmark {
+                    parent_guid: BookmarkRootGuid::Unfiled.as_guid(),
+                    position: BookmarkPosition::Append,
+                    date_added: None,
+                    last_modified: None,
+                    guid: Some(SyncGuid::from("bookmarkCCCC")),
+                    url: Url::parse("https://example.com/c")?,
+                    title: None,
+                }
+                .into(),
+            )?;
+            tag_url(&other, &Url::parse("https://example.com/c")?, "old-tag")?;
+            let result = restore(&other, &data)?;
+            assert_eq!(
+                result,
+                BookmarksImportResult {
+                    num_bookmarks: 2,
+                    num_folders: 1,
+                    num_separators: 1,
+                    num_skipped: 0,
+                }
+            );
+            // The existing bookmark and its tags were replaced, and
+            // everything else (including timestamps and guids) matches.
+            assert_eq!(user_content(&other), original);
+            assert_eq!(
+                get_tags_for_url(&other, &Url::parse("https://example.com/a")?)?,
+                vec!["tag"]
+            );
+            assert!(get_tags_for_url(&other, &Url::parse("https://example.com/c")?)?.is_empty());
+            assert_eq!(
+                bookmarks_get_url_for_keyword(&other, "kw")?.map(String::from),
+                Some("https://example.com/b".to_string())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_sync_state() -> Result<()> {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        insert_test_tree(&conn)?;
+        let data = backup(&conn, true)?;
+
+        // Pretend everything has been synced, then change the tree.
+        conn.execute(
+            &format!(
+                "UPDATE moz_bookmarks SET syncChangeCounter = 0, syncStatus = {}",
+                SyncStatus::Normal as u8
+            ),
+            [],
+        )?;
+        conn.execute_batch(
+            "DELETE FROM moz_bookmarks WHERE guid = 'bookmarkAAAA';
+             DELETE FROM moz_bookmarks_deleted;",
+        )?;
+        insert_bookmark(
+            &conn,
+            InsertableBookmark {
+                parent_guid: BookmarkRootGuid::Toolbar.as_guid(),
+                position: BookmarkPosition::Append,
+                date_added: None,
+                last_modified: None,
+                guid: Some(SyncGuid::from("bookmarkCCCC")),
+                url: Url::parse("https://example.com/c")?,
+                title: None,
+            }
+            .into(),
+        )?;
+        conn.execute(
+            "UPDATE moz_bookmarks SET syncChangeCounter = 0, syncStatus = 2",
+            [],
+        )?;
+
+        restore(&conn, &data)?;
+
+        // bookmarkCCCC isn't in the backup, so is tombstoned.
+        let tombstones: Vec<String> =
+            conn.query_rows_and_then("SELECT guid FROM moz_bookmarks_deleted", [], |row| {
+                row.get(0)
+            })?;
+        assert_eq!(tombstones, vec!["bookmarkCCCC"]);
+
+        // Everything else is changed, and only bookmarkAAAA, which was
+        // deleted since the backup, is new.
+        let rows: Vec<(String, u8, u32)> = conn.query_rows_and_then(
+            "SELECT guid, syncStatus, syncChangeCounter FROM moz_bookmarks
+             WHERE guid <> 'root________'
+             ORDER BY guid",
+            [],
+            |row| -> rusqlite::Result<_> { Ok((row.get(0)?, row.get(1)?, row.get(2)?)) },
+        )?;
+        let normal = SyncStatus::Normal as u8;
+        let new = SyncStatus::New as u8;
+        for (guid, status, counter) in rows {
+            assert!(counter > 0, "{} should be changed", guid);
+            let expected = if guid == "bookmarkAAAA" { new } else { normal };
+            assert_eq!(status, expected, "wrong sync status for {}", guid);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_desktop_backup() -> Result<()> {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        // A trimmed down backup from Desktop, with an old-style tags root,
+        // a query, a bookmark with an invalid URL and a duplicate guid.
+        let desktop = r#"{
+            "guid": "root________", "title": "", "index": 0, "dateAdded": 1600000000000000,
+            "lastModified": 1600000000000000, "id": 1, "typeCode": 2,
+            "type": "text/x-moz-place-container", "root": "placesRoot",
+            "children": [
+                {"guid": "menu________", "title": "menu", "index": 0, "id": 2, "typeCode": 2,
+                 "type": "text/x-moz-place-container", "root": "bookmarksMenuFolder",
+                 "children": [
+                    {"guid": "bookmarkAAAA", "title": "Most Visited", "index": 0, "id": 10,
+                     "typeCode": 1, "type": "text/x-moz-place",
+                     "uri": "place:sort=8&maxResults=10"},
+                    {"guid": "bookmarkBBBB", "title": "Broken", "index": 1, "id": 11,
+                     "typeCode": 1, "type": "text/x-moz-place", "uri": "not a url"},
+                    {"guid": "bookmarkAAAA", "title": "Dupe", "index": 2, "id": 12,
+                     "typeCode": 1, "type": "text/x-moz-place",
+                     "uri": "https://example.com/", "dateAdded": 1600000000000000}
+                 ]},
+                {"guid": "tags________", "title": "tags", "index": 1, "id": 4, "typeCode": 2,
+                 "type": "text/x-moz-place-container", "root": "tagsFolder",
+                 "children": [{"title": "tag", "typeCode": 2, "children": []}]}
+            ]
+        }"#;
+        let result = restore(&conn, desktop.as_bytes())?;
+        assert_eq!(
+            result,
+            BookmarksImportResult {
+                num_bookmarks: 2,
+                num_folders: 0,
+                num_separators: 0,
+                num_skipped: 1,
+            }
+        );
+        let BookmarkTreeNode::Folder { f: root } = fetch_root(&conn) else {
+            panic!("root should be a folder");
+        };
+        let BookmarkTreeNode::Folder { f: menu } = &root.children[0] else {
+            panic!("menu should be a folder");
+        };
+        assert_eq!(menu.children.len(), 2);
+        assert_eq!(menu.children[0].guid(), "bookmarkAAAA");
+        assert_ne!(menu.children[1].guid(), "bookmarkAAAA");
+        assert_eq!(
+            menu.children[1].created_modified().0,
+            Timestamp(1_600_000_000_000)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_restore_invalid() -> Result<()> {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        insert_test_tree(&conn)?;
+        let original = user_content(&conn);
+        for data in [
+            &b"not json"[..],
+            &br#"{"guid": "menu________", "children": []}"#[..],
+            &b"mozLz40\0garbage"[..],
+        ] {
+            assert!(matches!(
+                restore(&conn, data),
+                Err(Error::InvalidBookmarksBackup(_))
+            ));
+        }
+        assert_eq!(user_content(&conn), original);
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::time::Instant;
+
+use crate::error::{info, Result};
+use crate::import::common::{
+    attached_database, define_history_migration_functions, select_count, HistoryMigrationResult,
+};
+use crate::storage::update_all_frecencies_at_once;
+use crate::types::VisitType;
+use crate::PlacesDb;
+use types::Timestamp;
+use url::Url;
+
+/// This import is used for users migrating their history from Chrome, or
+/// another Chromium-based browser, by way of the `History` SQLite database in
+/// their profile directory. The browser keeps that file locked while it's
+/// running, so callers will usually want to import a copy of it.
+///
+/// ### Basic process
+///
+/// - Attach the Chromium database.
+/// - Slurp visits into a temp table "chromiumVisitsStaging", normalizing
+///   (punycoding) the URLs, converting the timestamps and mapping the
+///   transition types onto our visit types.
+/// - Fill in titles for existing pages which don't have one.
+/// - Add any entries to moz_places that are needed.
+/// - Insert the visits, skipping any we already have.
+/// - Update frecency for the pages we touched.
+/// - Cleanup (detach the Chromium database, etc).
+pub fn import(
+    conn: &PlacesDb,
+    path: impl AsRef<std::path::Path>,
+) -> Result<HistoryMigrationResult> {
+    let url = crate::util::ensure_url_path(path)?;
+    do_import(conn, url)
+}
+
+fn do_import(conn: &PlacesDb, chromium_db_file_url: Url) -> Result<HistoryMigrationResult> {
+    let scope = conn.begin_interrupt_scope()?;
+    define_history_migration_functions(conn)?;
+
+    let import_start = Instant::now();
+    info!("Attaching database {}", chromium_db_file_url);
+    let auto_detach = attached_database(conn, &chromium_db_file_url, "chromium")?;
+    let tx = conn.begin_transaction()?;
+    let num_total = select_count(conn, COUNT_CHROMIUM_HISTORY_VISITS)?;
+    info!("The number of visits is: {:?}", num_total);
+
+    info!("Creating and populating staging table");
+    tx.execute_batch(CREATE_STAGING_TABLE)?;
+    tx.execute_batch(&FILL_STAGING)?;
+    scope.err_if_interrupted()?;
+
+    info!("Filling in missing titles");
+    tx.execute_batch(UPDATE_PLACES_TITLES)?;
+    scope.err_if_interrupted()?;
+
+    info!("Populating missing entries in moz_places");
+    tx.execute_batch(FILL_MOZ_PLACES)?;
+    scope.err_if_interrupted()?;
+
+    info!("Inserting the history visits");
+    tx.execute_batch(INSERT_HISTORY_VISITS)?;
+    scope.err_if_interrupted()?;
+
+    info!("Marking frecencies of imported pages as stale");
+    let now = Timestamp::now().as_millis();
+    tx.execute(ADD_TO_STALE_FRECENCIES, &[(":now", &now)])?;
+    scope.err_if_interrupted()?;
+
+    // Visits we skipped because we already had them count as successes, so
+    // this only counts those we couldn't import (for example, because their
+    // URL was invalid).
+    let num_succeeded = select_count(conn, COUNT_IMPORTED_VISITS)?;
+    let num_failed = num_total.saturating_sub(num_succeeded);
+
+    tx.execute_batch(DROP_STAGING_TABLE)?;
+    tx.commit()?;
+    info!("Successfully imported history visits!");
+
+    // As with the iOS import, we update the frecencies in their own
+    // transaction, so that readers don't have to wait for them to see the
+    // imported history.
+    info!("Updating all frecencies");
+    update_all_frecencies_at_once(conn, &scope)?;
+    info!("Frecencies updated!");
+    auto_detach.execute_now()?;
+
+    Ok(HistoryMigrationResult {
+        num_total,
+        num_succeeded,
+        num_failed,
+        total_duration: import_start.elapsed().as_millis() as u64,
+    })
+}
+
+// Chromium stores times as microseconds since 1601-01-01 UTC (the "WebKit" or
+// Windows FILETIME epoch). This is the number of milliseconds between that and
+// the Unix epoch.
+const WEBKIT_EPOCH_OFFSET_MS: i64 = 11_644_473_600_000;
+
+// The low byte of a Chromium `transition` is the core transition type, and the
+// high bits are qualifiers. Visits with a core type of `AUTO_SUBFRAME` (3) are
+// content loaded in a frame which the user didn't navigate; Chromium hides
+// them from its own history UI, and we don't import them at all.
+const COUNT_CHROMIUM_HISTORY_VISITS: &str = "
+    SELECT COUNT(*) FROM chromium.visits v
+    JOIN chromium.urls u ON v.url = u.id
+    WHERE (v.transition & 0xFF) != 3";
+
+// We use a staging table so that we can normalize the URLs (and specifically,
+// punycode them) and convert everything else in one place.
+const CREATE_STAGING_TABLE: &str = "
+    CREATE TEMP TABLE IF NOT EXISTS temp.chromiumVisitsStaging(
+        id INTEGER PRIMARY KEY,
+        url TEXT NOT NULL,
+        url_hash INTEGER NOT NULL,
+        title TEXT,
+        visit_date INTEGER NOT NULL,
+        visit_type INTEGER NOT NULL
+    );";
+
+const DROP_STAGING_TABLE: &str = "DROP TABLE temp.chromiumVisitsStaging;";
+
+lazy_static::lazy_static! {
+    // Chromium's transition types map onto ours as follows:
+    //
+    // | Chromium                               | Places             |
+    // |----------------------------------------|--------------------|
+    // | CLIENT_REDIRECT or SERVER_REDIRECT     | RedirectTemporary  |
+    // | TYPED, GENERATED, KEYWORD, KEYWORD_... | Typed              |
+    // | AUTO_BOOKMARK                          | Bookmark           |
+    // | MANUAL_SUBFRAME                        | FramedLink         |
+    // | RELOAD                                 | Reload             |
+    // | Everything else                        | Link               |
+    //
+    // Chromium doesn't record whether a server redirect was permanent, so all
+    // redirects are treated as temporary, which is the lower-frecency choice.
+    // The redirect qualifiers are the top two bits of a 32-bit value, which
+    // may have been stored sign-extended, hence the mask rather than a
+    // comparison.
+    static ref FILL_STAGING: String = format!("
+        INSERT OR IGNORE INTO temp.chromiumVisitsStaging(id, url, url_hash, title, visit_date, visit_type)
+            SELECT
+                v.id,
+                validate_url(u.url),
+                hash(validate_url(u.url)),
+                sanitize_utf8(u.title),
+                sanitize_timestamp(v.visit_time / 1000 - {epoch_offset}),
+                CASE
+                    WHEN (v.transition & 0xC0000000) != 0 THEN {redirect}
+                    ELSE CASE v.transition & 0xFF
+                        WHEN 1 THEN {typed}
+                        WHEN 2 THEN {bookmark}
+                        WHEN 4 THEN {framed_link}
+                        WHEN 5 THEN {typed}
+                        WHEN 8 THEN {reload}
+                        WHEN 9 THEN {typed}
+                        WHEN 10 THEN {typed}
+                        ELSE {link}
+                    END
+                END
+            FROM chromium.visits v
+            JOIN chromium.urls u ON v.url = u.id
+            WHERE (v.transition & 0xFF) != 3",
+        epoch_offset = WEBKIT_EPOCH_OFFSET_MS,
+        redirect = VisitType::RedirectTemporary as u8,
+        typed = VisitType::Typed as u8,
+        bookmark = VisitType::Bookmark as u8,
+        framed_link = VisitType::FramedLink as u8,
+        reload = VisitType::Reload as u8,
+        link = VisitType::Link as u8,
+    );
+}
+
+// Unlike the iOS import, we keep titles the user already has, as they're
+// likely to be at least as fresh as Chromium's.
+const UPDATE_PLACES_TITLES: &str = "
+    UPDATE main.moz_places
+        SET title = (SELECT t.title
+                     FROM temp.chromiumVisitsStaging t
+                     WHERE t.url_hash = main.moz_places.url_hash AND t.url = main.moz_places.url
+                       AND t.title IS NOT NULL AND t.title != ''
+                     LIMIT 1)
+        WHERE IFNULL(title, '') = ''
+          AND EXISTS (SELECT 1 FROM temp.chromiumVisitsStaging t
+                      WHERE t.url_hash = main.moz_places.url_hash AND t.url = main.moz_places.url
+                        AND t.title IS NOT NULL AND t.title != '')";
+
+// Insert any missing entries into moz_places that we'll need for this.
+const FILL_MOZ_PLACES: &str = "
+    INSERT OR IGNORE INTO main.moz_places(guid, url, url_hash, title, frecency, sync_change_counter)
+        SELECT
+            generate_guid(),
+            t.url,
+            t.url_hash,
+            t.title,
+            -1,
+            1
+        FROM temp.chromiumVisitsStaging t
+        WHERE NOT EXISTS (SELECT 1 FROM main.moz_places p
+                          WHERE p.url_hash = t.url_hash AND p.url = t.url)
+        GROUP BY t.url";
+
+// Insert the visits we don't already have. Chromium's timestamps are more
+// precise than ours, so two of its visits can become one of ours, hence the
+// GROUP BY.
+const INSERT_HISTORY_VISITS: &str = "
+    INSERT INTO main.moz_historyvisits(from_visit, place_id, visit_date, visit_type, is_local)
+        SELECT
+            NULL, -- Chromium's redirect chains refer to visits we may not import.
+            p.id,
+            t.visit_date,
+            MIN(t.visit_type),
+            1
+        FROM temp.chromiumVisitsStaging t
+        JOIN main.moz_places p ON p.url_hash = t.url_hash AND p.url = t.url
+        WHERE NOT EXISTS (SELECT 1 FROM main.moz_historyvisits v
+                          WHERE v.place_id = p.id AND v.visit_date = t.visit_date)
+        GROUP BY p.id, t.visit_date";
+
+// Counts the staged visits which are now in moz_historyvisits, whether we
+// just inserted them or already had them.
+const COUNT_IMPORTED_VISITS: &str = "
+    SELECT COUNT(*) FROM temp.chromiumVisitsStaging t
+    JOIN main.moz_places p ON p.url_hash = t.url_hash AND p.url = t.url
+    WHERE EXISTS (SELECT 1 FROM main.moz_historyvisits v
+                  WHERE v.place_id = p.id AND v.visit_date = t.visit_date)";
+
+// Adds every page we imported visits for into the stale frecencies table,
+// including pages which already existed.
+const ADD_TO_STALE_FRECENCIES: &str = "
+    INSERT OR IGNORE INTO main.moz_places_stale_frecencies(place_id, stale_at)
+    SELECT DISTINCT
+        p.id,
+        :now
+    FROM temp.chromiumVisitsStaging t
+    JOIN main.moz_places p ON p.url_hash = t.url_hash AND p.url = t.url";
@@ -171,6 +171,43 @@ pub struct HistoryMigrationResult {
     pub total_duration: u64,
 }
 
+/// A summary of a bookmarks import or restore.
+#[derive(Serialize, PartialEq, Eq, Debug, Clone, Default)]
+pub struct BookmarksImportResult {
+    pub num_bookmarks: u32,
+    pub num_folders: u32,
+    pub num_separators: u32,
+    /// Bookmarks which weren't imported because their URL was invalid or
+    /// unsupported.
+    pub num_skipped: u32,
+}
+
+/// Adds the tags and keyword from an imported bookmark. The bookmark must
+/// already have been inserted, and the caller must have a transaction open.
+pub fn add_imported_tags_and_keyword(
+    conn: &PlacesDb,
+    url: &Url,
+    tags: &[String],
+    keyword: Option<&str>,
+) -> Result<()> {
+    for tag in tags {
+        crate::storage::tags::tag_url_in_tx(conn, url, tag)?;
+    }
+    if let Some(keyword) = keyword {
+        // Keywords are unique, so if one is already in use we keep it.
+        conn.execute_cached(
+            "INSERT OR IGNORE INTO moz_keywords(place_id, keyword)
+             SELECT id, :keyword FROM moz_places
+             WHERE url_hash = hash(:url) AND url = :url",
+            named_params! {
+                ":keyword": keyword,
+                ":url": url.as_str(),
+            },
+        )?;
+    }
+    Ok(())
+}
+
 pub fn define_history_migration_functions(c: &Connection) -> Result<()> {
     use rusqlite::functions::FunctionFlags;
     c.create_scalar_function(
@@ -2,6 +2,14 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+pub mod bookmarks_html;
+pub mod bookmarks_json;
+pub mod chromium;
 pub mod common;
 pub mod ios;
+mod mozlz4;
+pub use bookmarks_html::{export as export_bookmarks_html, import as import_bookmarks_html};
+pub use bookmarks_json::{backup as backup_bookmarks_json, restore as restore_bookmarks_json};
+pub use chromium::import_history as import_chromium_history;
+pub use common::BookmarksImportResult;
 pub use ios::import_history as import_ios_history;
@@ -0,0 +1,196 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Reading and writing Desktop's "mozLz4" files, like `bookmarks-*.jsonlz4` backups.
+//!
+//! These are an 8 byte magic number, the decompressed size as a little-endian `u32`, and then a
+//! single LZ4 block (not an LZ4 frame). We only need this for the occasional backup, so rather
+//! than taking a dependency we implement the block format here. Compression is a simple greedy
+//! matcher, which is plenty for JSON.
+
+const MAGIC: &[u8] = b"mozLz40\0";
+const HEADER_LEN: usize = MAGIC.len() + 4;
+
+const MIN_MATCH: usize = 4;
+// The block format requires the last 5 bytes to be literals, and the last
+// match to start at least 12 bytes before the end.
+const LAST_LITERALS: usize = 5;
+const MF_LIMIT: usize = 12;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 16;
+
+/// Returns true if `data` looks like a mozLz4 file.
+pub fn is_mozlz4(data: &[u8]) -> bool {
+    data.starts_with(MAGIC)
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + data.len() / 2);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    compress_block(data, &mut out);
+    out
+}
+
+/// Returns `None` if `data` isn't a valid mozLz4 file.
+pub fn decompress(data: &[u8]) -> Option<Vec<u8>> {
+    let rest = data.strip_prefix(MAGIC)?;
+    let size = u32::from_le_bytes(rest.get(..4)?.try_into().ok()?) as usize;
+    let out = decompress_block(&rest[4..], size)?;
+    (out.len() == size).then_some(out)
+}
+
+fn compress_block(input: &[u8], out: &mut Vec<u8>) {
+    let read_u32 = |pos: usize| u32::from_le_bytes(input[pos..pos + 4].try_into().unwrap());
+    let hash = |seq: u32| (seq.wrapping_mul(2_654_435_761) >> (32 - HASH_BITS)) as usize;
+    // Positions are stored plus one, so zero means "empty".
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut pos = 0;
+    while pos + MF_LIMIT <= input.len() {
+        let seq = read_u32(pos);
+        let slot = &mut table[hash(seq)];
+        let candidate = slot.checked_sub(1);
+        *slot = pos + 1;
+        let Some(candidate) = candidate.filter(|&c| pos - c <= MAX_OFFSET && read_u32(c) == seq)
+        else {
+            pos += 1;
+            continue;
+        };
+        let max_len = input.len() - LAST_LITERALS - pos;
+        let mut len = MIN_MATCH;
+        while len < max_len && input[candidate + len] == input[pos + len] {
+            len += 1;
+        }
+        write_sequence(out, &input[anchor..pos], Some((pos - candidate, len)));
+        pos += len;
+        anchor = pos;
+    }
+    write_sequence(out, &input[anchor..], None);
+}
+
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], matched: Option<(usize, usize)>) {
+    let match_len = matched.map_or(0, |(_, len)| len - MIN_MATCH);
+    let token = ((literals.len().min(15) as u8) << 4) | match_len.min(15) as u8;
+    out.push(token);
+    write_length(out, literals.len());
+    out.extend_from_slice(literals);
+    if let Some((offset, _)) = matched {
+        out.extend_from_slice(&(offset as u16).to_le_bytes());
+        write_length(out, match_len);
+    }
+}
+
+// Lengths of 15 or more spill out of the token into extra bytes.
+fn write_length(out: &mut Vec<u8>, len: usize) {
+    if len < 15 {
+        return;
+    }
+    let mut rest = len - 15;
+    while rest >= 255 {
+        out.push(255);
+        rest -= 255;
+    }
+    out.push(rest as u8);
+}
+
+fn decompress_block(input: &[u8], size: usize) -> Option<Vec<u8>> {
+    // Don't trust the size for the allocation, as LZ4 can't compress by more
+    // than a factor of 255.
+    let mut out = Vec::with_capacity(size.min(input.len().saturating_mul(255)));
+    let mut pos = 0;
+    loop {
+        let token = *input.get(pos)?;
+        pos += 1;
+        let literal_len = read_length(input, &mut pos, (token >> 4) as usize)?;
+        let literals = input.get(pos..pos.checked_add(literal_len)?)?;
+        out.extend_from_slice(literals);
+        pos += literal_len;
+        if pos == input.len() {
+            // The last sequence has no match.
+            return Some(out);
+        }
+        let offset = u16::from_le_bytes(input.get(pos..pos + 2)?.try_into().ok()?) as usize;
+        pos += 2;
+        if offset == 0 || offset > out.len() {
+            return None;
+        }
+        let match_len = read_length(input, &mut pos, (token & 0xf) as usize)? + MIN_MATCH;
+        if out.len() + match_len > size {
+            return None;
+        }
+        // The match can overlap what it's writing, so copy a byte at a time.
+        let start = out.len() - offset;
+        for i in 0..match_len {
+            out.push(out[start + i]);
+        }
+    }
+}
+
+fn read_length(input: &[u8], pos: &mut usize, mut len: usize) -> Option<usize> {
+    if len == 15 {
+        loop {
+            let byte = *input.get(*pos)?;
+            *pos += 1;
+            len = len.checked_add(byte as usize)?;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Some(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let long = "bookmarks ".repeat(1000);
+        let inputs: [&[u8]; 5] = [
+            b"",
+            b"short",
+            b"exactly twelve bytes",
+            long.as_bytes(),
+            &(0..=255u8).cycle().take(100_000).collect::<Vec<_>>(),
+        ];
+        for input in inputs {
+            let compressed = compress(input);
+            assert!(is_mozlz4(&compressed));
+            assert_eq!(decompress(&compressed).as_deref(), Some(input));
+        }
+        assert!(compress(long.as_bytes()).len() < 100);
+    }
+
+    #[test]
+    fn test_known_block() {
+        // A hand-assembled block: "abc", then a 13 byte match at offset 3,
+        // then the literals "bcabc".
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&21u32.to_le_bytes());
+        data.extend_from_slice(b"\x39abc\x03\x00\x50bcabc");
+        assert_eq!(
+            decompress(&data).as_deref(),
+            Some(&b"abcabcabcabcabcabcabc"[..])
+        );
+        // The wrong size is rejected.
+        data[MAGIC.len()] = 22;
+        assert_eq!(decompress(&data), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert_eq!(decompress(b""), None);
+        assert_eq!(decompress(b"{\"guid\": \"root________\"}"), None);
+        let mut data = MAGIC.to_vec();
+        data.extend_from_slice(&10u32.to_le_bytes());
+        // A match pointing before the start of the output.
+        data.extend_from_slice(b"\x14\x61\x05\x00");
+        assert_eq!(decompress(&data), None);
+        // Truncated.
+        let compressed = compress(b"some bookmarks, some bookmarks, some bookmarks");
+        assert_eq!(decompress(&compressed[..compressed.len() - 3]), None);
+    }
+}
@@ -10,6 +10,7 @@ pub mod error;
 pub mod types;
 // Making these all pub for now while we flesh out the API.
 pub mod bookmark_sync;
+pub mod changes;
 pub mod db;
 pub mod ffi;
 pub mod frecency;
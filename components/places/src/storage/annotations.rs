@@ -0,0 +1,735 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Page annotations: small, named values apps attach to a URL, like reader
+//! mode state or a scroll position.
+//!
+//! Annotations live as long as the page's history does, so they're removed
+//! by `delete_visits_for()` and `delete_everything()`, and when the page
+//! itself is removed. Those set with `AnnotationExpiry::AfterDays` are also
+//! removed by `run_maintenance_prune()` once they haven't been set for that
+//! long, and are ignored before then.
+//!
+//! Unless they're excluded, annotations are synced as part of the page's
+//! history record.
+
+use super::{fetch_page_info, new_page_info, RowId, URL_LENGTH_MAX};
+use crate::db::PlacesDb;
+use crate::error::*;
+use crate::history_sync::record::HistoryRecordAnno;
+use rusqlite::{named_params, Row};
+use serde_derive::*;
+use sql_support::ConnExt;
+use types::Timestamp;
+use url::Url;
+
+pub const ANNOTATION_NAME_LENGTH_MAX: usize = 256;
+pub const ANNOTATION_TEXT_LENGTH_MAX: usize = 4096;
+
+const MS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+// Synced as `{"type": "integer", "value": 1}`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AnnotationValue {
+    Integer { value: i64 },
+    Text { value: String },
+    Boolean { value: bool },
+}
+
+// These are the values of `moz_annos.content_type`.
+const CONTENT_TYPE_INTEGER: u8 = 1;
+const CONTENT_TYPE_TEXT: u8 = 2;
+const CONTENT_TYPE_BOOLEAN: u8 = 3;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationExpiry {
+    /// Kept until the page's history is removed.
+    WithHistory,
+    /// Also removed this many days after it was last set.
+    AfterDays { days: u32 },
+}
+
+impl AnnotationExpiry {
+    fn from_days(days: Option<u32>) -> Self {
+        match days {
+            Some(days) => AnnotationExpiry::AfterDays { days },
+            None => AnnotationExpiry::WithHistory,
+        }
+    }
+
+    fn days(self) -> Option<u32> {
+        match self {
+            AnnotationExpiry::WithHistory => None,
+            AnnotationExpiry::AfterDays { days } => Some(days),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub url: Url,
+    pub name: String,
+    pub value: AnnotationValue,
+    pub expiry: AnnotationExpiry,
+    pub exclude_from_sync: bool,
+    pub date_added: Timestamp,
+    pub last_modified: Timestamp,
+}
+
+impl Annotation {
+    fn from_row(row: &Row<'_>) -> Result<Self> {
+        Ok(Self {
+            url: Url::parse(&row.get::<_, String>("url")?)?,
+            name: row.get("name")?,
+            value: value_from_row(row)?,
+            expiry: AnnotationExpiry::from_days(row.get("expires_after_days")?),
+            exclude_from_sync: row.get("exclude_from_sync")?,
+            date_added: row.get("date_added")?,
+            last_modified: row.get("last_modified")?,
+        })
+    }
+}
+
+fn value_from_row(row: &Row<'_>) -> Result<AnnotationValue> {
+    let content_type: u8 = row.get("content_type")?;
+    Ok(match content_type {
+        CONTENT_TYPE_INTEGER => AnnotationValue::Integer {
+            value: row.get("content")?,
+        },
+        CONTENT_TYPE_TEXT => AnnotationValue::Text {
+            value: row.get("content")?,
+        },
+        CONTENT_TYPE_BOOLEAN => AnnotationValue::Boolean {
+            value: row.get("content")?,
+        },
+        _ => return Err(rusqlite::Error::IntegralValueOutOfRange(0, content_type.into()).into()),
+    })
+}
+
+fn validate(name: &str, value: &AnnotationValue) -> Result<()> {
+    if name.is_empty() {
+        return Err(InvalidAnnotation::EmptyName.into());
+    }
+    if name.len() > ANNOTATION_NAME_LENGTH_MAX {
+        return Err(InvalidAnnotation::NameTooLong(name.len()).into());
+    }
+    if let AnnotationValue::Text { value: text } = value {
+        if text.len() > ANNOTATION_TEXT_LENGTH_MAX {
+            return Err(InvalidAnnotation::ValueTooLong(text.len()).into());
+        }
+    }
+    Ok(())
+}
+
+// Excludes annotations which have expired, but haven't been pruned yet.
+const NOT_EXPIRED: &str =
+    "(a.expires_after_days IS NULL OR a.last_modified + a.expires_after_days * :ms_per_day > :now)";
+
+/// Sets an annotation on a page, replacing any existing annotation with the
+/// same name.
+pub fn set_annotation(
+    db: &PlacesDb,
+    url: &Url,
+    name: &str,
+    value: AnnotationValue,
+    expiry: AnnotationExpiry,
+    exclude_from_sync: bool,
+) -> Result<()> {
+    validate(name, &value)?;
+    if url.as_str().len() > URL_LENGTH_MAX {
+        return Err(InvalidPlaceInfo::UrlTooLong.into());
+    }
+    let tx = db.begin_transaction()?;
+    let place_id = match fetch_page_info(db, url)? {
+        Some(info) => info.page.row_id,
+        None => new_page_info(db, url, None)?.row_id,
+    };
+    let was_synced = db.try_query_one::<bool, _>(
+        "SELECT NOT exclude_from_sync FROM moz_annos
+         WHERE place_id = :place_id AND name = :name",
+        named_params! { ":place_id": place_id, ":name": name },
+        true,
+    )?;
+    insert_or_update(db, place_id, name, &value, expiry.days(), exclude_from_sync)?;
+    if !exclude_from_sync || was_synced == Some(true) {
+        bump_sync_change_counter(db, place_id)?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+fn insert_or_update(
+    db: &PlacesDb,
+    place_id: RowId,
+    name: &str,
+    value: &AnnotationValue,
+    expires_after_days: Option<u32>,
+    exclude_from_sync: bool,
+) -> Result<()> {
+    let (content_type, content): (u8, &dyn rusqlite::ToSql) = match value {
+        AnnotationValue::Integer { value } => (CONTENT_TYPE_INTEGER, value),
+        AnnotationValue::Text { value } => (CONTENT_TYPE_TEXT, value),
+        AnnotationValue::Boolean { value } => (CONTENT_TYPE_BOOLEAN, value),
+    };
+    db.execute_cached(
+        "INSERT INTO moz_annos(place_id, name, content_type, content, expires_after_days,
+                               exclude_from_sync, date_added, last_modified)
+         VALUES (:place_id, :name, :content_type, :content, :expires_after_days,
+                 :exclude_from_sync, :now, :now)
+         ON CONFLICT(place_id, name) DO UPDATE SET
+             content_type = excluded.content_type,
+             content = excluded.content,
+             expires_after_days = excluded.expires_after_days,
+             exclude_from_sync = excluded.exclude_from_sync,
+             last_modified = excluded.last_modified",
+        named_params! {
+            ":place_id": place_id,
+            ":name": name,
+            ":content_type": content_type,
+            ":content": content,
+            ":expires_after_days": expires_after_days,
+            ":exclude_from_sync": exclude_from_sync,
+            ":now": Timestamp::now(),
+        },
+    )?;
+    Ok(())
+}
+
+fn bump_sync_change_counter(db: &PlacesDb, place_id: RowId) -> Result<()> {
+    db.execute_cached(
+        "UPDATE moz_places SET sync_change_counter = sync_change_counter + 1
+         WHERE id = :place_id",
+        named_params! { ":place_id": place_id },
+    )?;
+    Ok(())
+}
+
+pub fn get_annotation(db: &PlacesDb, url: &Url, name: &str) -> Result<Option<Annotation>> {
+    db.try_query_row(
+        &format!(
+            "SELECT h.url, a.*
+             FROM moz_annos a
+             JOIN moz_places h ON h.id = a.place_id
+             WHERE h.url_hash = hash(:url) AND h.url = :url
+               AND a.name = :name
+               AND {NOT_EXPIRED}"
+        ),
+        named_params! {
+            ":url": url.as_str(),
+            ":name": name,
+            ":ms_per_day": MS_PER_DAY,
+            ":now": Timestamp::now(),
+        },
+        Annotation::from_row,
+        true,
+    )
+}
+
+/// Returns all of a page's annotations, ordered by name.
+pub fn get_annotations_for_url(db: &PlacesDb, url: &Url) -> Result<Vec<Annotation>> {
+    db.query_rows_and_then_cached(
+        &format!(
+            "SELECT h.url, a.*
+             FROM moz_annos a
+             JOIN moz_places h ON h.id = a.place_id
+             WHERE h.url_hash = hash(:url) AND h.url = :url
+               AND {NOT_EXPIRED}
+             ORDER BY a.name"
+        ),
+        named_params! {
+            ":url": url.as_str(),
+            ":ms_per_day": MS_PER_DAY,
+            ":now": Timestamp::now(),
+        },
+        Annotation::from_row,
+    )
+}
+
+/// Returns the annotations with this name for every page, most recently set
+/// first.
+pub fn get_annotations_by_name(db: &PlacesDb, name: &str) -> Result<Vec<Annotation>> {
+    db.query_rows_and_then_cached(
+        &format!(
+            "SELECT h.url, a.*
+             FROM moz_annos a
+             JOIN moz_places h ON h.id = a.place_id
+             WHERE a.name = :name
+               AND {NOT_EXPIRED}
+             ORDER BY a.last_modified DESC"
+        ),
+        named_params! {
+            ":name": name,
+            ":ms_per_day": MS_PER_DAY,
+            ":now": Timestamp::now(),
+        },
+        Annotation::from_row,
+    )
+}
+
+/// Removes an annotation from a page. Returns whether it existed.
+pub fn remove_annotation(db: &PlacesDb, url: &Url, name: &str) -> Result<bool> {
+    let tx = db.begin_transaction()?;
+    let removed = db.try_query_row(
+        "DELETE FROM moz_annos
+         WHERE place_id = (SELECT id FROM moz_places
+                           WHERE url_hash = hash(:url) AND url = :url)
+           AND name = :name
+         RETURNING place_id, exclude_from_sync",
+        named_params! { ":url": url.as_str(), ":name": name },
+        |row| -> Result<(RowId, bool)> { Ok((row.get(0)?, row.get(1)?)) },
+        false,
+    )?;
+    if let Some((place_id, false)) = removed {
+        bump_sync_change_counter(db, place_id)?;
+    }
+    tx.commit()?;
+    Ok(removed.is_some())
+}
+
+pub(crate) fn delete_all_annotations_for_page(db: &PlacesDb, place_id: RowId) -> Result<()> {
+    db.execute_cached(
+        "DELETE FROM moz_annos WHERE place_id = :place_id",
+        named_params! { ":place_id": place_id },
+    )?;
+    Ok(())
+}
+
+/// Deletes annotations which have expired. Returns how many there were.
+pub(crate) fn delete_expired_annotations(db: &PlacesDb) -> Result<usize> {
+    Ok(db.execute_cached(
+        "DELETE FROM moz_annos
+         WHERE expires_after_days IS NOT NULL
+           AND last_modified + expires_after_days * :ms_per_day <= :now",
+        named_params! {
+            ":ms_per_day": MS_PER_DAY,
+            ":now": Timestamp::now(),
+        },
+    )?)
+}
+
+/// Returns a page's annotations which should be synced, ordered by name.
+pub(crate) fn fetch_synced_annotations(
+    db: &PlacesDb,
+    place_id: RowId,
+) -> Result<Vec<HistoryRecordAnno>> {
+    db.query_rows_and_then_cached(
+        &format!(
+            "SELECT a.name, a.content_type, a.content, a.expires_after_days
+             FROM moz_annos a
+             WHERE a.place_id = :place_id
+               AND NOT a.exclude_from_sync
+               AND {NOT_EXPIRED}
+             ORDER BY a.name"
+        ),
+        named_params! {
+            ":place_id": place_id,
+            ":ms_per_day": MS_PER_DAY,
+            ":now": Timestamp::now(),
+        },
+        |row| -> Result<_> {
+            Ok(HistoryRecordAnno {
+                name: row.get("name")?,
+                value: value_from_row(row)?,
+                expires_after_days: row.get("expires_after_days")?,
+            })
+        },
+    )
+}
+
+/// Replaces a page's synced annotations with those from an incoming record.
+/// Annotations which are excluded from sync locally are left alone, even if
+/// the record has one with the same name.
+pub(crate) fn apply_synced_annotations(
+    db: &PlacesDb,
+    place_id: RowId,
+    annos: &[HistoryRecordAnno],
+) -> Result<()> {
+    db.execute_cached(
+        "DELETE FROM moz_annos WHERE place_id = :place_id AND NOT exclude_from_sync",
+        named_params! { ":place_id": place_id },
+    )?;
+    for anno in annos {
+        if let Err(e) = validate(&anno.name, &anno.value) {
+            warn!("Ignoring invalid incoming annotation: {}", e);
+            continue;
+        }
+        let excluded_locally = db.exists(
+            "SELECT 1 FROM moz_annos WHERE place_id = :place_id AND name = :name",
+            named_params! { ":place_id": place_id, ":name": anno.name },
+        )?;
+        if !excluded_locally {
+            insert_or_update(
+                db,
+                place_id,
+                &anno.name,
+                &anno.value,
+                anno.expires_after_days,
+                false,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::observation::VisitObservation;
+    use crate::storage::history::{
+        apply_observation, delete_everything, delete_visits_for, url_to_guid,
+    };
+    use crate::storage::run_maintenance_prune;
+    use crate::types::VisitType;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn visit(conn: &PlacesDb, s: &str) {
+        apply_observation(
+            conn,
+            VisitObservation::new(url(s)).with_visit_type(VisitType::Link),
+        )
+        .unwrap();
+    }
+
+    fn set(conn: &PlacesDb, s: &str, name: &str, value: AnnotationValue) {
+        set_annotation(
+            conn,
+            &url(s),
+            name,
+            value,
+            AnnotationExpiry::WithHistory,
+            false,
+        )
+        .unwrap();
+    }
+
+    fn value_of(conn: &PlacesDb, s: &str, name: &str) -> Option<AnnotationValue> {
+        get_annotation(conn, &url(s), name)
+            .unwrap()
+            .map(|anno| anno.value)
+    }
+
+    fn sync_change_counter(conn: &PlacesDb, s: &str) -> i64 {
+        conn.try_query_one(
+            "SELECT sync_change_counter FROM moz_places WHERE url = :url",
+            named_params! { ":url": s },
+            false,
+        )
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_set_get_remove() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let page = "https://example.com/";
+        assert_eq!(value_of(&conn, page, "pinned"), None);
+
+        set(
+            &conn,
+            page,
+            "pinned",
+            AnnotationValue::Boolean { value: true },
+        );
+        set(
+            &conn,
+            page,
+            "scroll",
+            AnnotationValue::Integer { value: 120 },
+        );
+        set(
+            &conn,
+            page,
+            "reader",
+            AnnotationValue::Text { value: "{}".into() },
+        );
+        assert_eq!(
+            value_of(&conn, page, "pinned"),
+            Some(AnnotationValue::Boolean { value: true })
+        );
+        assert_eq!(
+            value_of(&conn, page, "scroll"),
+            Some(AnnotationValue::Integer { value: 120 })
+        );
+
+        // Setting it again replaces it, even with a different type.
+        set(
+            &conn,
+            page,
+            "scroll",
+            AnnotationValue::Text {
+                value: "top".into(),
+            },
+        );
+        assert_eq!(
+            value_of(&conn, page, "scroll"),
+            Some(AnnotationValue::Text {
+                value: "top".into()
+            })
+        );
+        assert_eq!(
+            get_annotations_for_url(&conn, &url(page))
+                .unwrap()
+                .into_iter()
+                .map(|anno| anno.name)
+                .collect::<Vec<_>>(),
+            vec!["pinned", "reader", "scroll"]
+        );
+
+        assert!(remove_annotation(&conn, &url(page), "pinned").unwrap());
+        assert!(!remove_annotation(&conn, &url(page), "pinned").unwrap());
+        assert_eq!(value_of(&conn, page, "pinned"), None);
+    }
+
+    #[test]
+    fn test_invalid() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let page = url("https://example.com/");
+        let set = |name: &str, value| {
+            set_annotation(
+                &conn,
+                &page,
+                name,
+                value,
+                AnnotationExpiry::WithHistory,
+                false,
+            )
+        };
+        assert!(matches!(
+            set("", AnnotationValue::Boolean { value: true }),
+            Err(Error::InvalidAnnotation(InvalidAnnotation::EmptyName))
+        ));
+        assert!(matches!(
+            set(
+                &"x".repeat(ANNOTATION_NAME_LENGTH_MAX + 1),
+                AnnotationValue::Boolean { value: true }
+            ),
+            Err(Error::InvalidAnnotation(InvalidAnnotation::NameTooLong(_)))
+        ));
+        assert!(matches!(
+            set(
+                "reader",
+                AnnotationValue::Text {
+                    value: "x".repeat(ANNOTATION_TEXT_LENGTH_MAX + 1)
+                }
+            ),
+            Err(Error::InvalidAnnotation(InvalidAnnotation::ValueTooLong(_)))
+        ));
+    }
+
+    #[test]
+    fn test_by_name() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        set(
+            &conn,
+            "https://example.com/",
+            "pinned",
+            AnnotationValue::Boolean { value: true },
+        );
+        set(
+            &conn,
+            "https://mozilla.org/",
+            "pinned",
+            AnnotationValue::Boolean { value: false },
+        );
+        set(
+            &conn,
+            "https://example.com/",
+            "scroll",
+            AnnotationValue::Integer { value: 1 },
+        );
+        let pinned = get_annotations_by_name(&conn, "pinned").unwrap();
+        assert_eq!(pinned.len(), 2);
+        assert!(pinned.iter().all(|anno| anno.name == "pinned"));
+        assert!(pinned
+            .iter()
+            .any(|anno| anno.url.as_str() == "https://mozilla.org/"
+                && anno.value == AnnotationValue::Boolean { value: false }));
+    }
+
+    #[test]
+    fn test_expiry() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let page = "https://example.com/";
+        set_annotation(
+            &conn,
+            &url(page),
+            "reader",
+            AnnotationValue::Text { value: "{}".into() },
+            AnnotationExpiry::AfterDays { days: 7 },
+            false,
+        )
+        .unwrap();
+        set(
+            &conn,
+            page,
+            "pinned",
+            AnnotationValue::Boolean { value: true },
+        );
+        assert_eq!(
+            get_annotation(&conn, &url(page), "reader")
+                .unwrap()
+                .unwrap()
+                .expiry,
+            AnnotationExpiry::AfterDays { days: 7 }
+        );
+
+        // Pretend it was set 8 days ago.
+        conn.execute(
+            "UPDATE moz_annos SET last_modified = last_modified - 8 * :ms_per_day
+             WHERE name = 'reader'",
+            named_params! { ":ms_per_day": MS_PER_DAY },
+        )
+        .unwrap();
+        // It's ignored straight away...
+        assert_eq!(value_of(&conn, page, "reader"), None);
+        // ...and removed by maintenance.
+        run_maintenance_prune(&conn, 0, 0).unwrap();
+        assert_eq!(
+            conn.conn_ext_query_one::<i64>("SELECT COUNT(*) FROM moz_annos")
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            value_of(&conn, page, "pinned"),
+            Some(AnnotationValue::Boolean { value: true })
+        );
+    }
+
+    #[test]
+    fn test_removed_with_history() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        visit(&conn, "https://example.com/");
+        visit(&conn, "https://mozilla.org/");
+        set(
+            &conn,
+            "https://example.com/",
+            "pinned",
+            AnnotationValue::Boolean { value: true },
+        );
+        set(
+            &conn,
+            "https://mozilla.org/",
+            "pinned",
+            AnnotationValue::Boolean { value: true },
+        );
+
+        let guid = url_to_guid(&conn, &url("https://example.com/"))
+            .unwrap()
+            .unwrap();
+        delete_visits_for(&conn, &guid).unwrap();
+        assert_eq!(value_of(&conn, "https://example.com/", "pinned"), None);
+        assert_eq!(
+            value_of(&conn, "https://mozilla.org/", "pinned"),
+            Some(AnnotationValue::Boolean { value: true })
+        );
+
+        delete_everything(&conn).unwrap();
+        assert_eq!(
+            conn.conn_ext_query_one::<i64>("SELECT COUNT(*) FROM moz_annos")
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_sync() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let page = "https://example.com/";
+        visit(&conn, page);
+        let counter = sync_change_counter(&conn, page);
+
+        // Synced annotations bump the page's change counter...
+        set(
+            &conn,
+            page,
+            "pinned",
+            AnnotationValue::Boolean { value: true },
+        );
+        assert_eq!(sync_change_counter(&conn, page), counter + 1);
+        // ...but excluded ones don't.
+        set_annotation(
+            &conn,
+            &url(page),
+            "scroll",
+            AnnotationValue::Integer { value: 10 },
+            AnnotationExpiry::WithHistory,
+            true,
+        )
+        .unwrap();
+        assert_eq!(sync_change_counter(&conn, page), counter + 1);
+
+        let place_id = fetch_page_info(&conn, &url(page))
+            .unwrap()
+            .unwrap()
+            .page
+            .row_id;
+        assert_eq!(
+            fetch_synced_annotations(&conn, place_id).unwrap(),
+            vec![HistoryRecordAnno {
+                name: "pinned".into(),
+                value: AnnotationValue::Boolean { value: true },
+                expires_after_days: None,
+            }]
+        );
+
+        // Incoming annotations replace our synced ones, but not those we
+        // exclude.
+        apply_synced_annotations(
+            &conn,
+            place_id,
+            &[
+                HistoryRecordAnno {
+                    name: "reader".into(),
+                    value: AnnotationValue::Text { value: "{}".into() },
+                    expires_after_days: Some(7),
+                },
+                HistoryRecordAnno {
+                    name: "scroll".into(),
+                    value: AnnotationValue::Integer { value: 99 },
+                    expires_after_days: None,
+                },
+            ],
+        )
+        .unwrap();
+        assert_eq!(value_of(&conn, page, "pinned"), None);
+        assert_eq!(
+            value_of(&conn, page, "reader"),
+            Some(AnnotationValue::Text { value: "{}".into() })
+        );
+        assert_eq!(
+            value_of(&conn, page, "scroll"),
+            Some(AnnotationValue::Integer { value: 10 })
+        );
+    }
+
+    #[test]
+    fn test_record_serialization() {
+        let anno = HistoryRecordAnno {
+            name: "pinned".into(),
+            value: AnnotationValue::Boolean { value: true },
+            expires_after_days: Some(7),
+        };
+        let json = serde_json::json!({
+            "name": "pinned",
+            "value": { "type": "boolean", "value": true },
+            "expiresAfterDays": 7,
+        });
+        assert_eq!(serde_json::to_value(&anno).unwrap(), json);
+        assert_eq!(
+            serde_json::from_value::<HistoryRecordAnno>(json).unwrap(),
+            anno
+        );
+    }
+}
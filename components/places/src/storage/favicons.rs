@@ -0,0 +1,329 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Favicon storage.
+//!
+//! Each page can have one icon per width. When a page has no icons of its
+//! own, we fall back to those of its origin's root page (for example,
+//! `https://example.com/` for `https://example.com/a/b`), treating the `www.`
+//! and bare hosts as the same site, like Desktop does for `/favicon.ico`.
+//!
+//! Icons for pages which are no longer in history are removed by
+//! `run_maintenance_prune()`.
+
+use super::URL_LENGTH_MAX;
+use crate::db::PlacesDb;
+use crate::error::*;
+use rusqlite::named_params;
+use sql_support::ConnExt;
+use url::Url;
+
+/// The largest icon we'll store. This is plenty for the "rich" icons sites
+/// provide for home screens, and keeps a broken caller from bloating the
+/// database.
+pub const MAX_FAVICON_DATA_SIZE: usize = 256 * 1024;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Favicon {
+    pub data: Vec<u8>,
+    pub mime_type: String,
+    pub width: u32,
+}
+
+/// Stores an icon for a page, replacing any existing icon of the same width.
+pub fn set_favicon_for_page(
+    db: &PlacesDb,
+    url: &Url,
+    data: &[u8],
+    mime_type: &str,
+    width: u32,
+) -> Result<()> {
+    if data.is_empty() {
+        return Err(InvalidFavicon::Empty.into());
+    }
+    if data.len() > MAX_FAVICON_DATA_SIZE {
+        return Err(InvalidFavicon::TooLarge(data.len()).into());
+    }
+    if !mime_type.starts_with("image/") {
+        return Err(InvalidFavicon::NotAnImage(mime_type.to_owned()).into());
+    }
+    if url.as_str().len() > URL_LENGTH_MAX {
+        return Err(InvalidPlaceInfo::UrlTooLong.into());
+    }
+    let tx = db.begin_transaction()?;
+    db.execute_cached(
+        "INSERT INTO moz_pages_w_icons(page_url, page_url_hash)
+         VALUES (:url, hash(:url))
+         ON CONFLICT(page_url) DO NOTHING",
+        named_params! { ":url": url.as_str() },
+    )?;
+    db.execute_cached(
+        "INSERT INTO moz_icons(page_id, width, mime_type, data)
+         VALUES ((SELECT id FROM moz_pages_w_icons
+                  WHERE page_url_hash = hash(:url) AND page_url = :url),
+                 :width, :mime_type, :data)
+         ON CONFLICT(page_id, width) DO UPDATE SET
+             mime_type = excluded.mime_type,
+             data = excluded.data",
+        named_params! {
+            ":url": url.as_str(),
+            ":width": width,
+            ":mime_type": mime_type,
+            ":data": data,
+        },
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Returns the page's icon which best fits `preferred_width`, falling back
+/// to the icons of the page's root. The best fit is the smallest icon at
+/// least as wide as `preferred_width`, or if there are none, the widest. A
+/// `preferred_width` of 0 means the widest.
+pub fn get_favicon_for_page(
+    db: &PlacesDb,
+    url: &Url,
+    preferred_width: u32,
+) -> Result<Option<Favicon>> {
+    for page_url in std::iter::once(url.to_string()).chain(root_urls(url)) {
+        let icon = db.try_query_row(
+            "SELECT i.data, i.mime_type, i.width
+             FROM moz_icons i
+             JOIN moz_pages_w_icons p ON p.id = i.page_id
+             WHERE p.page_url_hash = hash(:url) AND p.page_url = :url
+             ORDER BY
+                 (:preferred_width > 0 AND i.width >= :preferred_width) DESC,
+                 CASE WHEN :preferred_width > 0 AND i.width >= :preferred_width
+                      THEN i.width
+                      ELSE -i.width
+                 END
+             LIMIT 1",
+            named_params! {
+                ":url": page_url,
+                ":preferred_width": preferred_width,
+            },
+            |row| -> Result<_> {
+                Ok(Favicon {
+                    data: row.get("data")?,
+                    mime_type: row.get("mime_type")?,
+                    width: row.get("width")?,
+                })
+            },
+            true,
+        )?;
+        if icon.is_some() {
+            return Ok(icon);
+        }
+    }
+    Ok(None)
+}
+
+// The root pages whose icons `url` can fall back to, for both the `www.` and
+// the bare host. Doesn't include `url` itself.
+fn root_urls(url: &Url) -> Vec<String> {
+    let Some(host) = url.host_str() else {
+        return Vec::new();
+    };
+    let port = url.port().map(|p| format!(":{}", p)).unwrap_or_default();
+    let other_host = match host.strip_prefix("www.") {
+        Some(bare) => bare.to_owned(),
+        None => format!("www.{}", host),
+    };
+    [host, other_host.as_str()]
+        .into_iter()
+        .map(|host| format!("{}://{}{}/", url.scheme(), host, port))
+        .filter(|root| root != url.as_str())
+        .collect()
+}
+
+/// Deletes icons for pages which are no longer in history. A root page's
+/// icons are kept while we have any page from its origin, as they're the
+/// fallback for those pages. Returns the number of pages whose icons were
+/// removed.
+pub(crate) fn delete_orphaned_favicons(db: &PlacesDb) -> Result<usize> {
+    // Deleting from moz_pages_w_icons cascades to moz_icons.
+    Ok(db.execute_cached(
+        "DELETE FROM moz_pages_w_icons
+         WHERE NOT EXISTS (SELECT 1 FROM moz_places h
+                           WHERE h.url_hash = moz_pages_w_icons.page_url_hash
+                             AND h.url = moz_pages_w_icons.page_url)
+           AND NOT EXISTS (SELECT 1 FROM moz_origins o
+                           WHERE moz_pages_w_icons.page_url IN (
+                               o.prefix || o.host || '/',
+                               o.prefix || 'www.' || o.host || '/',
+                               CASE WHEN o.host LIKE 'www.%'
+                                    THEN o.prefix || substr(o.host, 5) || '/'
+                               END))",
+        [],
+    )?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::observation::VisitObservation;
+    use crate::storage::history::{apply_observation, delete_everything, delete_visits_for};
+    use crate::storage::run_maintenance_prune;
+    use crate::types::VisitType;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    fn visit(conn: &PlacesDb, s: &str) {
+        apply_observation(
+            conn,
+            VisitObservation::new(url(s)).with_visit_type(VisitType::Link),
+        )
+        .unwrap();
+    }
+
+    fn set(conn: &PlacesDb, s: &str, width: u32) {
+        set_favicon_for_page(conn, &url(s), &width.to_le_bytes(), "image/png", width).unwrap();
+    }
+
+    fn width_for(conn: &PlacesDb, s: &str, preferred_width: u32) -> Option<u32> {
+        get_favicon_for_page(conn, &url(s), preferred_width)
+            .unwrap()
+            .map(|icon| icon.width)
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let page = url("https://example.com/page");
+        assert_eq!(get_favicon_for_page(&conn, &page, 16).unwrap(), None);
+
+        set_favicon_for_page(&conn, &page, b"icon", "image/png", 16).unwrap();
+        assert_eq!(
+            get_favicon_for_page(&conn, &page, 16).unwrap(),
+            Some(Favicon {
+                data: b"icon".to_vec(),
+                mime_type: "image/png".to_string(),
+                width: 16,
+            })
+        );
+
+        // Setting an icon of the same width replaces it.
+        set_favicon_for_page(&conn, &page, b"<svg/>", "image/svg+xml", 16).unwrap();
+        let icon = get_favicon_for_page(&conn, &page, 16).unwrap().unwrap();
+        assert_eq!(icon.data, b"<svg/>");
+        assert_eq!(icon.mime_type, "image/svg+xml");
+        assert_eq!(
+            conn.conn_ext_query_one::<i64>("SELECT COUNT(*) FROM moz_icons")
+                .unwrap(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let page = url("https://example.com/");
+        assert!(matches!(
+            set_favicon_for_page(&conn, &page, b"", "image/png", 16),
+            Err(Error::InvalidFavicon(InvalidFavicon::Empty))
+        ));
+        assert!(matches!(
+            set_favicon_for_page(
+                &conn,
+                &page,
+                &vec![0; MAX_FAVICON_DATA_SIZE + 1],
+                "image/png",
+                16
+            ),
+            Err(Error::InvalidFavicon(InvalidFavicon::TooLarge(_)))
+        ));
+        assert!(matches!(
+            set_favicon_for_page(&conn, &page, b"icon", "text/html", 16),
+            Err(Error::InvalidFavicon(InvalidFavicon::NotAnImage(_)))
+        ));
+    }
+
+    #[test]
+    fn test_preferred_width() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let page = "https://example.com/";
+        for width in [16, 32, 64] {
+            set(&conn, page, width);
+        }
+        assert_eq!(width_for(&conn, page, 16), Some(16));
+        assert_eq!(width_for(&conn, page, 20), Some(32));
+        assert_eq!(width_for(&conn, page, 64), Some(64));
+        // Nothing's big enough, so we get the biggest.
+        assert_eq!(width_for(&conn, page, 128), Some(64));
+        assert_eq!(width_for(&conn, page, 0), Some(64));
+    }
+
+    #[test]
+    fn test_root_fallback() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        set(&conn, "https://www.example.com/", 16);
+        set(&conn, "https://example.com/page", 32);
+        // The page's own icon wins...
+        assert_eq!(width_for(&conn, "https://example.com/page", 16), Some(32));
+        // ...but other pages fall back to the root, with or without `www.`.
+        assert_eq!(width_for(&conn, "https://example.com/other", 16), Some(16));
+        assert_eq!(
+            width_for(&conn, "https://www.example.com/a/b?c", 16),
+            Some(16)
+        );
+        // Different schemes, ports and hosts are different sites.
+        assert_eq!(width_for(&conn, "http://example.com/other", 16), None);
+        assert_eq!(width_for(&conn, "https://example.com:8080/", 16), None);
+        assert_eq!(width_for(&conn, "https://sub.example.com/", 16), None);
+
+        assert_eq!(
+            root_urls(&url("https://example.com:8080/a")),
+            vec!["https://example.com:8080/", "https://www.example.com:8080/"]
+        );
+        assert_eq!(
+            root_urls(&url("https://www.example.com/")),
+            vec!["https://example.com/"]
+        );
+        assert!(root_urls(&url("data:text/plain,hi")).is_empty());
+    }
+
+    #[test]
+    fn test_prune() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        visit(&conn, "https://example.com/page");
+        visit(&conn, "https://mozilla.org/page");
+        set(&conn, "https://example.com/page", 16);
+        set(&conn, "https://www.example.com/", 16);
+        set(&conn, "https://mozilla.org/page", 16);
+        set(&conn, "https://never-visited.com/", 16);
+
+        run_maintenance_prune(&conn, 0, 0).unwrap();
+        // Visited pages keep their icons, and so does the root we fall back
+        // to for them.
+        assert_eq!(width_for(&conn, "https://example.com/page", 16), Some(16));
+        assert_eq!(width_for(&conn, "https://www.example.com/", 16), Some(16));
+        assert_eq!(width_for(&conn, "https://mozilla.org/page", 16), Some(16));
+        assert_eq!(width_for(&conn, "https://never-visited.com/", 16), None);
+
+        let guid = crate::storage::history::url_to_guid(&conn, &url("https://example.com/page"))
+            .unwrap()
+            .unwrap();
+        delete_visits_for(&conn, &guid).unwrap();
+        run_maintenance_prune(&conn, 0, 0).unwrap();
+        assert_eq!(width_for(&conn, "https://example.com/page", 16), None);
+        assert_eq!(width_for(&conn, "https://www.example.com/", 16), None);
+        assert_eq!(width_for(&conn, "https://mozilla.org/page", 16), Some(16));
+
+        // Deleting all history removes them straight away.
+        delete_everything(&conn).unwrap();
+        assert_eq!(
+            conn.conn_ext_query_one::<i64>("SELECT COUNT(*) FROM moz_icons")
+                .unwrap(),
+            0
+        );
+    }
+}
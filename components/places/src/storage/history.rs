@@ -15,7 +15,7 @@ use crate::history_sync::engine::{
 };
 use crate::observation::VisitObservation;
 use crate::storage::{
-    delete_meta, delete_pending_temp_tables, get_meta, history_metadata, put_meta,
+    annotations, delete_meta, delete_pending_temp_tables, get_meta, history_metadata, put_meta,
 };
 use crate::types::{
     serialize_unknown_fields, SyncStatus, UnknownFields, VisitTransitionSet, VisitType,
@@ -158,7 +158,7 @@ pub fn apply_observation_direct(
 pub fn update_frecency(db: &PlacesDb, id: RowId, redirect_boost: Option<bool>) -> Result<()> {
     let score = frecency::calculate_frecency(
         db.conn(),
-        &frecency::DEFAULT_FRECENCY_SETTINGS,
+        &frecency::settings_for(db.api_id()),
         id.0, // TODO: calculate_frecency should take a RowId here.
         redirect_boost,
     )?;
@@ -285,6 +285,7 @@ fn delete_visits_for_in_tx(db: &PlacesDb, guid: &SyncGuid) -> Result<()> {
             insert_tombstones_for_all_page_visits(db, id)?;
             delete_all_visits_for_page(db, id)?;
             history_metadata::delete_all_metadata_for_page(db, id)?;
+            annotations::delete_all_annotations_for_page(db, id)?;
         }
         Some(PageToClean {
             id,
@@ -307,8 +308,9 @@ fn delete_visits_for_in_tx(db: &PlacesDb, guid: &SyncGuid) -> Result<()> {
             // we still can't delete it; we must delete its visits. But we
             // don't need to write any tombstones for those deleted visits.
             delete_all_visits_for_page(db, id)?;
-            // and we need to delete all history metadata.
+            // and we need to delete all history metadata and annotations.
             history_metadata::delete_all_metadata_for_page(db, id)?;
+            annotations::delete_all_annotations_for_page(db, id)?;
         }
         Some(PageToClean {
             id,
@@ -492,11 +494,12 @@ fn find_exotic_visits_to_prune(
 }
 
 fn wipe_local_in_tx(db: &PlacesDb) -> Result<()> {
-    use crate::frecency::DEFAULT_FRECENCY_SETTINGS;
+    let settings = frecency::settings_for(db.api_id());
     db.execute_all(&[
         "DELETE FROM moz_places WHERE foreign_count == 0",
         "DELETE FROM moz_places_metadata",
         "DELETE FROM moz_places_metadata_search_queries",
+        "DELETE FROM moz_annos",
         "DELETE FROM moz_historyvisits",
         "DELETE FROM moz_places_tombstones",
         "DELETE FROM moz_inputhistory AS i WHERE NOT EXISTS(
@@ -513,9 +516,10 @@ fn wipe_local_in_tx(db: &PlacesDb) -> Result<()> {
                                  ELSE {unvisited_bookmark_frec}
                             END),
                 sync_change_counter = 0"#,
-            unvisited_bookmark_frec = DEFAULT_FRECENCY_SETTINGS.unvisited_bookmark_bonus
+            unvisited_bookmark_frec = settings.unvisited_bookmark_bonus
         ),
     ])?;
+    super::favicons::delete_orphaned_favicons(db)?;
 
     let need_frecency_update =
         db.query_rows_and_then("SELECT id FROM moz_places", [], |r| r.get::<_, RowId>(0))?;
@@ -547,6 +551,7 @@ pub fn delete_everything(db: &PlacesDb) -> Result<()> {
     put_meta(db, DELETION_HIGH_WATER_MARK_META_KEY, &new_mark)?;
 
     wipe_local_in_tx(db)?;
+    crate::changes::note_history_cleared(db)?;
 
     // Remove Sync metadata, too.
     reset_in_tx(db, &EngineSyncAssociation::Disconnected)?;
@@ -774,7 +779,7 @@ pub mod history_sync {
     use sync15::bso::OutgoingEnvelope;
 
     use super::*;
-    use crate::history_sync::record::{HistoryRecord, HistoryRecordVisit};
+    use crate::history_sync::record::{HistoryRecord, HistoryRecordAnno, HistoryRecordVisit};
     use crate::history_sync::HISTORY_TTL;
     use std::collections::HashSet;
 
@@ -866,6 +871,7 @@ pub mod history_sync {
         url: &Url,
         title: &Option<String>,
         visits: &[HistoryRecordVisit],
+        annos: &Option<Vec<HistoryRecordAnno>>,
         unknown_fields: &UnknownFields,
     ) -> Result<()> {
         // At some point we may have done a local wipe of all visits. We skip applying
@@ -969,6 +975,9 @@ pub mod history_sync {
                 visits_to_skip.insert(timestamp);
             }
         }
+        if let Some(annos) = annos {
+            annotations::apply_synced_annotations(db, page_info.row_id, annos)?;
+        }
         // XXX - we really need a better story for frecency-boost than
         // Option<bool> - None vs Some(false) is confusing. We should use an enum.
         update_frecency(db, page_info.row_id, None)?;
@@ -1169,6 +1178,7 @@ pub mod history_sync {
                 title: page.title,
                 hist_uri: page.url.to_string(),
                 visits,
+                annos: Some(annotations::fetch_synced_annotations(db, page.row_id)?),
                 unknown_fields: page.unknown_fields,
             };
 
@@ -2602,6 +2612,7 @@ mod tests {
                     unknown_fields: UnknownFields::new(),
                 })
                 .collect::<Vec<_>>(),
+            &None,
             &UnknownFields::new(),
         )
         .unwrap();
@@ -2868,6 +2879,7 @@ mod tests {
                     unknown_fields: UnknownFields::new(),
                 },
             ],
+            &None,
             &UnknownFields::new(),
         )
         .unwrap();
@@ -2894,6 +2906,7 @@ mod tests {
                 transition: VisitType::Link as u8,
                 unknown_fields: UnknownFields::new(),
             }],
+            &None,
             &UnknownFields::new(),
         )
         .unwrap();
@@ -0,0 +1,389 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Groups history metadata into the clusters shown in "Jump back in" and
+//! grouped history views.
+//!
+//! Entries are clustered in two passes:
+//!
+//! - Entries which share a search term (ignoring case), or which are linked
+//!   by their referrers (one entry's `referrer_url` is another's `url`), are
+//!   grouped together. A group containing any search term is titled with the most
+//!   recent one; otherwise, it's titled after the page which started the
+//!   referrer chain.
+//! - Entries which weren't grouped with anything in the first pass are then
+//!   grouped by host, ignoring any `www.` prefix.
+//!
+//! Whatever's left is returned as a group of one page, so that the groups
+//! cover all the metadata in the range.
+
+use super::history_metadata::{self, HistoryMetadata};
+use super::history_search::HistoryTimeRange;
+use crate::db::PlacesDb;
+use crate::error::*;
+use std::collections::{HashMap, HashSet};
+use url::Url;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryGroupKind {
+    /// Pages found by, or reached from, the same search.
+    SearchTerm,
+    /// Pages reached by following links from the first page in the group.
+    Referrer,
+    /// Pages from the same site.
+    Host,
+    /// A page which wasn't grouped with any others.
+    Page,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HistoryGroup {
+    pub title: String,
+    pub kind: HistoryGroupKind,
+    /// Most recently updated first.
+    pub items: Vec<HistoryMetadata>,
+    pub total_view_time: i64,
+    pub last_updated_at: i64,
+}
+
+/// Returns groups of the history metadata updated in `range`, most recently
+/// updated first.
+pub fn get_history_groups(db: &PlacesDb, range: HistoryTimeRange) -> Result<Vec<HistoryGroup>> {
+    let entries =
+        history_metadata::get_between(db, range.start.as_millis_i64(), range.end.as_millis_i64())?;
+    Ok(group_entries(entries))
+}
+
+// A minimal union-find over entry indices.
+struct Clusters(Vec<usize>);
+
+impl Clusters {
+    fn new(len: usize) -> Self {
+        Self((0..len).collect())
+    }
+
+    fn root(&mut self, mut index: usize) -> usize {
+        while self.0[index] != index {
+            self.0[index] = self.0[self.0[index]];
+            index = self.0[index];
+        }
+        index
+    }
+
+    fn join(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.root(a), self.root(b));
+        // Keep the lower index, which is the more recently updated entry, as
+        // the root, so that groups come out in order.
+        self.0[a.max(b)] = a.min(b);
+    }
+}
+
+fn normalize_search_term(term: &str) -> Option<String> {
+    let term = term.trim();
+    (!term.is_empty()).then(|| term.to_lowercase())
+}
+
+fn host_key(url: &str) -> Option<String> {
+    let url = Url::parse(url).ok()?;
+    let host = url.host_str()?;
+    Some(host.strip_prefix("www.").unwrap_or(host).to_owned())
+}
+
+// `entries` are ordered by `updated_at`, most recent first.
+fn group_entries(entries: Vec<HistoryMetadata>) -> Vec<HistoryGroup> {
+    let mut clusters = Clusters::new(entries.len());
+
+    // First pass: search terms and referrers.
+    let mut first_by_search_term: HashMap<String, usize> = HashMap::new();
+    let mut first_by_url: HashMap<&str, usize> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if let Some(term) = entry.search_term.as_deref().and_then(normalize_search_term) {
+            let first = *first_by_search_term.entry(term).or_insert(index);
+            clusters.join(first, index);
+        }
+        first_by_url.entry(entry.url.as_str()).or_insert(index);
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        if let Some(&referrer) = entry
+            .referrer_url
+            .as_deref()
+            .and_then(|url| first_by_url.get(url))
+        {
+            clusters.join(referrer, index);
+        }
+    }
+
+    // Second pass: hosts, for everything that's still on its own.
+    let mut sizes: HashMap<usize, usize> = HashMap::new();
+    for index in 0..entries.len() {
+        *sizes.entry(clusters.root(index)).or_default() += 1;
+    }
+    let mut host_groups: HashSet<usize> = HashSet::new();
+    let mut first_by_host: HashMap<String, usize> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if sizes[&clusters.root(index)] != 1 {
+            continue;
+        }
+        if let Some(host) = host_key(&entry.url) {
+            let first = *first_by_host.entry(host).or_insert(index);
+            if first != index {
+                clusters.join(first, index);
+                host_groups.insert(first);
+            }
+        }
+    }
+
+    // Collect the clusters, in the order of their most recent entries.
+    let mut members: Vec<Vec<usize>> = Vec::new();
+    let mut slot_by_root: HashMap<usize, usize> = HashMap::new();
+    for index in 0..entries.len() {
+        let root = clusters.root(index);
+        let slot = *slot_by_root.entry(root).or_insert_with(|| {
+            members.push(Vec::new());
+            members.len() - 1
+        });
+        members[slot].push(index);
+    }
+
+    members
+        .into_iter()
+        .map(|indices| {
+            let items: Vec<&HistoryMetadata> = indices.iter().map(|&i| &entries[i]).collect();
+            let (kind, title) = if items.len() == 1 {
+                (HistoryGroupKind::Page, page_title(items[0]))
+            } else if host_groups.contains(&indices[0]) {
+                let host = host_key(&items[0].url).unwrap_or_default();
+                (HistoryGroupKind::Host, host)
+            } else if let Some(term) = items.iter().find_map(|item| {
+                item.search_term
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|term| !term.is_empty())
+            }) {
+                (HistoryGroupKind::SearchTerm, term.to_owned())
+            } else {
+                // The page which started the chain is the one whose referrer
+                // isn't in the group. If there's a loop, use the oldest.
+                let start = items
+                    .iter()
+                    .rev()
+                    .find(|item| {
+                        !item
+                            .referrer_url
+                            .as_deref()
+                            .is_some_and(|url| items.iter().any(|other| other.url == url))
+                    })
+                    .unwrap_or_else(|| items.last().expect("groups aren't empty"));
+                (HistoryGroupKind::Referrer, page_title(start))
+            };
+            HistoryGroup {
+                title,
+                kind,
+                total_view_time: items.iter().map(|item| item.total_view_time as i64).sum(),
+                last_updated_at: items[0].updated_at,
+                items: items.into_iter().cloned().collect(),
+            }
+        })
+        .collect()
+}
+
+fn page_title(entry: &HistoryMetadata) -> String {
+    match entry.title.as_deref().map(str::trim) {
+        Some(title) if !title.is_empty() => title.to_owned(),
+        _ => entry.url.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::storage::history_metadata::{
+        apply_metadata_observation, HistoryMetadataObservation, HistoryMetadataPageMissingBehavior,
+        NoteHistoryMetadataObservationOptions,
+    };
+    use std::{thread, time};
+    use types::Timestamp;
+
+    fn observe(
+        conn: &PlacesDb,
+        url: &str,
+        view_time: i32,
+        search_term: Option<&str>,
+        referrer_url: Option<&str>,
+        title: Option<&str>,
+    ) {
+        apply_metadata_observation(
+            conn,
+            HistoryMetadataObservation {
+                url: url.to_string(),
+                view_time: Some(view_time),
+                search_term: search_term.map(str::to_string),
+                document_type: None,
+                referrer_url: referrer_url.map(str::to_string),
+                title: title.map(str::to_string),
+            },
+            NoteHistoryMetadataObservationOptions::new()
+                .if_page_missing(HistoryMetadataPageMissingBehavior::InsertPage),
+        )
+        .unwrap();
+        // `updated_at` has millisecond precision, so make sure each
+        // observation gets its own.
+        thread::sleep(time::Duration::from_millis(10));
+    }
+
+    fn summarize(groups: &[HistoryGroup]) -> Vec<(HistoryGroupKind, &str, Vec<&str>)> {
+        groups
+            .iter()
+            .map(|group| {
+                (
+                    group.kind,
+                    group.title.as_str(),
+                    group.items.iter().map(|item| item.url.as_str()).collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_get_history_groups() {
+        let conn = new_mem_connection();
+        let start = Timestamp::now();
+        observe(&conn, "https://other.org/", 1, None, None, Some("Other"));
+        observe(&conn, "https://www.news.com/a", 2, None, None, None);
+        observe(&conn, "https://blog.com/", 4, None, None, Some("  "));
+        observe(
+            &conn,
+            "https://blog.com/1",
+            8,
+            None,
+            Some("https://blog.com/"),
+            None,
+        );
+        observe(&conn, "https://cats.com/", 16, Some("Cats"), None, None);
+        observe(&conn, "https://news.com/b", 32, None, None, None);
+        observe(
+            &conn,
+            "https://cats.com/more",
+            64,
+            None,
+            Some("https://cats.com/"),
+            None,
+        );
+        observe(
+            &conn,
+            "https://kitten.org/",
+            128,
+            Some(" cats "),
+            None,
+            None,
+        );
+        let end = Timestamp::now();
+
+        let groups = get_history_groups(&conn, HistoryTimeRange { start, end }).unwrap();
+        assert_eq!(
+            summarize(&groups),
+            vec![
+                (
+                    HistoryGroupKind::SearchTerm,
+                    "cats",
+                    vec![
+                        "https://kitten.org/",
+                        "https://cats.com/more",
+                        "https://cats.com/"
+                    ]
+                ),
+                (
+                    HistoryGroupKind::Host,
+                    "news.com",
+                    vec!["https://news.com/b", "https://www.news.com/a"]
+                ),
+                (
+                    HistoryGroupKind::Referrer,
+                    // The first page doesn't have a title, so we use its URL.
+                    "https://blog.com/",
+                    vec!["https://blog.com/1", "https://blog.com/"]
+                ),
+                (HistoryGroupKind::Page, "Other", vec!["https://other.org/"]),
+            ]
+        );
+        assert_eq!(groups[0].total_view_time, 128 + 64 + 16);
+        assert_eq!(groups[0].last_updated_at, groups[0].items[0].updated_at);
+        assert_eq!(groups[1].total_view_time, 32 + 2);
+
+        // Only metadata in the range is grouped, so without the pages that
+        // linked them, these are all on their own.
+        let groups = get_history_groups(
+            &conn,
+            HistoryTimeRange {
+                start: Timestamp(groups[1].last_updated_at as u64),
+                end,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            summarize(&groups),
+            vec![
+                (
+                    HistoryGroupKind::Page,
+                    "https://kitten.org/",
+                    vec!["https://kitten.org/"]
+                ),
+                (
+                    HistoryGroupKind::Page,
+                    "https://cats.com/more",
+                    vec!["https://cats.com/more"]
+                ),
+                (
+                    HistoryGroupKind::Page,
+                    "https://news.com/b",
+                    vec!["https://news.com/b"]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_referrer_chain() {
+        let conn = new_mem_connection();
+        let start = Timestamp::now();
+        observe(&conn, "https://example.com/", 1, None, None, Some("Start"));
+        observe(
+            &conn,
+            "https://a.com/",
+            1,
+            None,
+            Some("https://example.com/"),
+            Some("A"),
+        );
+        observe(
+            &conn,
+            "https://b.com/",
+            1,
+            None,
+            Some("https://a.com/"),
+            Some("B"),
+        );
+        // A different page with the same host doesn't join the chain.
+        observe(&conn, "https://example.com/unrelated", 1, None, None, None);
+        let end = Timestamp::now();
+
+        let groups = get_history_groups(&conn, HistoryTimeRange { start, end }).unwrap();
+        assert_eq!(
+            summarize(&groups),
+            vec![
+                (
+                    HistoryGroupKind::Page,
+                    "https://example.com/unrelated",
+                    vec!["https://example.com/unrelated"]
+                ),
+                (
+                    HistoryGroupKind::Referrer,
+                    "Start",
+                    vec!["https://b.com/", "https://a.com/", "https://example.com/"]
+                ),
+            ]
+        );
+    }
+}
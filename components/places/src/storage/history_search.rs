@@ -0,0 +1,275 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Ranked full-text search over history, using the `moz_places_fts` index.
+//!
+//! The index covers page titles and URLs, and the search terms recorded in
+//! history metadata which led to each page. It's maintained by triggers, so
+//! there's nothing to do here but query it.
+
+use crate::db::PlacesDb;
+use crate::error::*;
+use sql_support::ConnExt;
+use types::Timestamp;
+use url::Url;
+
+/// A range of visit dates, inclusive at both ends.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HistoryTimeRange {
+    pub start: Timestamp,
+    pub end: Timestamp,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct HistorySearchResult {
+    pub url: Url,
+    pub title: Option<String>,
+    pub frecency: i64,
+    pub last_visit_date: Timestamp,
+    /// Higher is better. Only meaningful relative to other results for the
+    /// same query.
+    pub score: f64,
+}
+
+impl HistorySearchResult {
+    pub(crate) fn from_row(row: &rusqlite::Row<'_>) -> Result<Self> {
+        let url: String = row.get("url")?;
+        Ok(Self {
+            url: Url::parse(&url)?,
+            title: row.get("title")?,
+            frecency: row.get("frecency")?,
+            last_visit_date: row.get("last_visit_date")?,
+            score: row.get("score")?,
+        })
+    }
+}
+
+// The bm25 weights are for the title, url and search_terms columns: a match in
+// the title counts most, then a search term, then the URL. The frecency boost
+// is at most a factor of 2, so a much better text match still wins over a
+// frequently visited page.
+const SEARCH_SQL: &str = "
+    SELECT h.url, h.title, h.frecency,
+           MAX(h.last_visit_date_local, h.last_visit_date_remote) AS last_visit_date,
+           -bm25(moz_places_fts, 10.0, 1.0, 5.0)
+             * (1.0 + MAX(h.frecency, 0) / (MAX(h.frecency, 0) + 100.0)) AS score
+    FROM moz_places_fts
+    JOIN moz_places h ON h.id = moz_places_fts.rowid
+    WHERE moz_places_fts MATCH :query
+      AND h.hidden = 0
+      AND MAX(h.last_visit_date_local, h.last_visit_date_remote) BETWEEN :start AND :end
+    ORDER BY score DESC, last_visit_date DESC
+    LIMIT :limit";
+
+/// Searches history for pages matching all the words in `query`, ranked by a
+/// combination of how well they match and their frecency. Each word matches
+/// as a prefix, case- and diacritic-insensitively.
+///
+/// Only pages which have been visited are returned; `time_range` further
+/// restricts the results to pages last visited within it.
+pub fn search_history(
+    db: &PlacesDb,
+    query: &str,
+    limit: i32,
+    time_range: Option<HistoryTimeRange>,
+) -> Result<Vec<HistorySearchResult>> {
+    let Some(query) = to_fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    // A last visit date of 0 means the page has never been visited.
+    let (start, end) = match time_range {
+        Some(range) => (range.start.max(Timestamp(1)), range.end),
+        None => (Timestamp(1), Timestamp(i64::MAX as u64)),
+    };
+    db.query_rows_and_then_cached(
+        SEARCH_SQL,
+        rusqlite::named_params! {
+            ":query": query,
+            ":start": start,
+            ":end": end,
+            ":limit": limit,
+        },
+        HistorySearchResult::from_row,
+    )
+}
+
+// Turns user input into an FTS5 query. Each word is quoted, so that characters
+// which are FTS5 syntax (like `-`, `:` or `"`) are matched literally, and the
+// words are implicitly ANDed together. Returns `None` if there are no words.
+fn to_fts_query(query: &str) -> Option<String> {
+    let terms = query
+        .split_whitespace()
+        .map(|word| format!("\"{}\"*", word.replace('"', "\"\"")))
+        .collect::<Vec<_>>();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::places_api::test::new_mem_connection;
+    use crate::observation::VisitObservation;
+    use crate::storage::history::{apply_observation, delete_visits_for};
+    use crate::storage::history_metadata::{
+        apply_metadata_observation, delete_all_metadata_for_search, HistoryMetadataObservation,
+        NoteHistoryMetadataObservationOptions,
+    };
+    use crate::types::VisitType;
+
+    fn visit(conn: &PlacesDb, url: &str, title: &str, at: Timestamp) {
+        apply_observation(
+            conn,
+            VisitObservation::new(Url::parse(url).unwrap())
+                .with_title(title.to_string())
+                .with_visit_type(VisitType::Link)
+                .with_at(at),
+        )
+        .unwrap();
+    }
+
+    fn search(conn: &PlacesDb, query: &str) -> Vec<String> {
+        search_history(conn, query, 10, None)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.url.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_to_fts_query() {
+        assert_eq!(to_fts_query(""), None);
+        assert_eq!(to_fts_query("  \t "), None);
+        assert_eq!(to_fts_query("foo"), Some("\"foo\"*".to_string()));
+        assert_eq!(
+            to_fts_query(" foo  -bar\"baz "),
+            Some("\"foo\"* \"-bar\"\"baz\"*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_search_titles_and_urls() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let now = Timestamp::now();
+        visit(
+            &conn,
+            "https://www.mozilla.org/",
+            "Internet for people",
+            now,
+        );
+        visit(&conn, "https://example.com/crème", "Café brûlée", now);
+        visit(&conn, "https://example.com/other", "Unrelated", now);
+
+        assert_eq!(search(&conn, "people"), vec!["https://www.mozilla.org/"]);
+        // Words are prefixes, and all of them must match.
+        assert_eq!(
+            search(&conn, "intern peo"),
+            vec!["https://www.mozilla.org/"]
+        );
+        assert!(search(&conn, "internet cafe").is_empty());
+        // URLs are indexed too.
+        assert_eq!(search(&conn, "mozilla"), vec!["https://www.mozilla.org/"]);
+        // Case and diacritics are ignored.
+        assert_eq!(
+            search(&conn, "CAFE BRULEE"),
+            vec!["https://example.com/cr%C3%A8me"]
+        );
+        // FTS5 syntax is matched literally rather than being an error.
+        assert_eq!(search(&conn, "\"people"), vec!["https://www.mozilla.org/"]);
+        assert!(search(&conn, "title:people OR NEAR(").is_empty());
+        assert!(search(&conn, "").is_empty());
+
+        // Title changes update the index.
+        visit(&conn, "https://www.mozilla.org/", "Firefox browser", now);
+        assert!(search(&conn, "people").is_empty());
+        assert_eq!(search(&conn, "firefox"), vec!["https://www.mozilla.org/"]);
+
+        // As does removing the page.
+        delete_visits_for(&conn, &url_to_guid(&conn, "https://www.mozilla.org/")).unwrap();
+        assert!(search(&conn, "firefox").is_empty());
+    }
+
+    #[test]
+    fn test_search_terms() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let now = Timestamp::now();
+        visit(&conn, "https://www.mozilla.org/", "Mozilla", now);
+        apply_metadata_observation(
+            &conn,
+            HistoryMetadataObservation {
+                url: "https://www.mozilla.org/".into(),
+                view_time: None,
+                search_term: Some("privacy browser".into()),
+                document_type: None,
+                referrer_url: None,
+                title: None,
+            },
+            NoteHistoryMetadataObservationOptions::new(),
+        )
+        .unwrap();
+        assert_eq!(search(&conn, "privacy"), vec!["https://www.mozilla.org/"]);
+
+        // Deleting search terms removes them from the index.
+        delete_all_metadata_for_search(&conn).unwrap();
+        assert!(search(&conn, "privacy").is_empty());
+        assert_eq!(search(&conn, "mozilla"), vec!["https://www.mozilla.org/"]);
+    }
+
+    #[test]
+    fn test_ranking() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let now = Timestamp::now();
+        // A title match beats a URL match.
+        visit(&conn, "https://example.com/firefox", "Something else", now);
+        visit(&conn, "https://example.com/a", "Get Firefox", now);
+        assert_eq!(
+            search(&conn, "firefox"),
+            vec!["https://example.com/a", "https://example.com/firefox"]
+        );
+
+        // Between equally good matches, frecency wins.
+        visit(&conn, "https://example.com/b", "Firefox", now);
+        visit(&conn, "https://example.com/c", "Firefox", now);
+        for _ in 0..5 {
+            visit(&conn, "https://example.com/c", "Firefox", now);
+        }
+        let results = search_history(&conn, "firefox", 2, None).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].url.as_str(), "https://example.com/c");
+        assert!(results[0].score > results[1].score);
+    }
+
+    #[test]
+    fn test_time_range() {
+        error_support::init_for_tests();
+        let conn = new_mem_connection();
+        let now = Timestamp::now();
+        let then = Timestamp(now.as_millis() - 1000 * 60 * 60 * 24 * 7);
+        visit(&conn, "https://example.com/old", "Firefox old", then);
+        visit(&conn, "https://example.com/new", "Firefox new", now);
+
+        let in_range = |start: Timestamp, end: Timestamp| {
+            search_history(&conn, "firefox", 10, Some(HistoryTimeRange { start, end }))
+                .unwrap()
+                .into_iter()
+                .map(|r| r.url.to_string())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(in_range(then, then), vec!["https://example.com/old"]);
+        assert_eq!(
+            in_range(Timestamp(then.as_millis() + 1), now),
+            vec!["https://example.com/new"]
+        );
+        assert_eq!(in_range(then, now).len(), 2);
+        assert!(in_range(Timestamp(0), Timestamp(1000)).is_empty());
+    }
+
+    fn url_to_guid(conn: &PlacesDb, url: &str) -> sync_guid::Guid {
+        crate::storage::history::url_to_guid(conn, &Url::parse(url).unwrap())
+            .unwrap()
+            .unwrap()
+    }
+}
@@ -5,16 +5,20 @@
 // A "storage" module - this module is intended to be the layer between the
 // API and the database.
 
+pub mod annotations;
 pub mod bookmarks;
+pub mod favicons;
 pub mod history;
+pub mod history_groups;
 pub mod history_metadata;
+pub mod history_search;
 pub mod tags;
 
 use crate::db::PlacesDb;
 use crate::error::{warn, Error, InvalidPlaceInfo, Result};
 use crate::ffi::HistoryVisitInfo;
 use crate::ffi::TopFrecentSiteInfo;
-use crate::frecency::{calculate_frecency, DEFAULT_FRECENCY_SETTINGS};
+use crate::frecency::{self, calculate_frecency};
 use crate::types::{SyncStatus, UnknownFields, VisitType};
 use interrupt_support::SqlInterruptScope;
 use rusqlite::types::{FromSql, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
@@ -247,6 +251,8 @@ pub struct RunMaintenanceMetrics {
 /// than this, some older visits will be deleted to free up space.  Pass in a 0 to skip this.
 ///
 /// prune_limit is the maximum number of visits to prune if the database is over db_size_limit
+///
+/// This also deletes favicons for pages which are no longer in history.
 pub fn run_maintenance_prune(
     conn: &PlacesDb,
     db_size_limit: u32,
@@ -257,6 +263,8 @@ pub fn run_maintenance_prune(
     if should_prune {
         history::prune_older_visits(conn, prune_limit)?;
     }
+    favicons::delete_orphaned_favicons(conn)?;
+    annotations::delete_expired_annotations(conn)?;
     let db_size_after = conn.get_db_size()?;
     Ok(RunMaintenanceMetrics {
         pruned_visits: should_prune,
@@ -312,6 +320,7 @@ pub fn run_maintenance_checkpoint(conn: &PlacesDb) -> Result<()> {
 }
 
 pub fn update_all_frecencies_at_once(db: &PlacesDb, scope: &SqlInterruptScope) -> Result<()> {
+    let settings = frecency::settings_for(db.api_id());
     let tx = db.begin_transaction()?;
 
     let need_frecency_update = tx.query_rows_and_then(
@@ -326,7 +335,7 @@ pub fn update_all_frecencies_at_once(db: &PlacesDb, scope: &SqlInterruptScope) -
             scope.err_if_interrupted()?;
             Ok((
                 *places_id,
-                calculate_frecency(db, &DEFAULT_FRECENCY_SETTINGS, *places_id, Some(false))?,
+                calculate_frecency(db, &settings, *places_id, Some(false))?,
             ))
         })
         .collect::<Result<Vec<(i64, i32)>>>()?;
@@ -365,6 +374,117 @@ pub fn update_all_frecencies_at_once(db: &PlacesDb, scope: &SqlInterruptScope) -
     Ok(())
 }
 
+/// The maximum number of URLs for which to recalculate frecencies at once.
+/// This is a trade-off between write efficiency and transaction time: higher
+/// maximums mean fewer write statements, but longer transactions, possibly
+/// blocking writes from other connections.
+const MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK: usize = 400;
+
+/// Recalculates the frecencies in `moz_places_stale_frecencies`, most
+/// recently marked first. Each chunk is committed in its own transaction, so
+/// if it's interrupted, the frecencies it already recalculated are kept, and
+/// the next call picks up where it left off. Returns the number of pages
+/// recalculated.
+///
+/// This commits explicitly instead of using `maybe_commit()`, so that it can
+/// run on any writable connection, not just the sync connection.
+pub(crate) fn update_stale_frecencies(db: &PlacesDb, scope: &SqlInterruptScope) -> Result<usize> {
+    let settings = frecency::settings_for(db.api_id());
+
+    let mut num_updated = 0;
+
+    let mut frecencies = Vec::with_capacity(MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK);
+    loop {
+        let tx = db.begin_transaction()?;
+        let sql = format!(
+            "SELECT place_id FROM moz_places_stale_frecencies
+             ORDER BY stale_at DESC
+             LIMIT {}",
+            MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK
+        );
+        {
+            let mut stmt = db.prepare_maybe_cached(&sql, true)?;
+            let mut results = stmt.query([])?;
+            while let Some(row) = results.next()? {
+                let place_id = row.get("place_id")?;
+                // Frecency recalculation runs several statements, so check to
+                // make sure we aren't interrupted before each calculation.
+                scope.err_if_interrupted()?;
+                let frecency = calculate_frecency(db, &settings, place_id, Some(false))?;
+                frecencies.push((place_id, frecency));
+            }
+        }
+        if frecencies.is_empty() {
+            tx.commit()?;
+            break;
+        }
+
+        // Update all frecencies in one fell swoop...
+        db.execute_batch(&format!(
+            "WITH frecencies(id, frecency) AS (
+               VALUES {}
+             )
+             UPDATE moz_places SET
+               frecency = (SELECT frecency FROM frecencies f
+                           WHERE f.id = id)
+             WHERE id IN (SELECT f.id FROM frecencies f)",
+            sql_support::repeat_display(frecencies.len(), ",", |index, f| {
+                let (id, frecency) = frecencies[index];
+                write!(f, "({}, {})", id, frecency)
+            })
+        ))?;
+
+        // ...And remove them from the stale table.
+        db.execute_batch(&format!(
+            "DELETE FROM moz_places_stale_frecencies
+             WHERE place_id IN ({})",
+            sql_support::repeat_display(frecencies.len(), ",", |index, f| {
+                let (id, _) = frecencies[index];
+                write!(f, "{}", id)
+            })
+        ))?;
+        tx.commit()?;
+        scope.err_if_interrupted()?;
+        num_updated += frecencies.len();
+
+        // If the query returned fewer URLs than the maximum, we're done.
+        // Otherwise, we might have more, so clear the ones we just
+        // recalculated and fetch the next chunk.
+        if frecencies.len() < MAX_FRECENCIES_TO_RECALCULATE_PER_CHUNK {
+            break;
+        }
+        frecencies.clear();
+    }
+
+    Ok(num_updated)
+}
+
+// The settings the last full recalculation was for, as JSON.
+const FRECENCY_SETTINGS_META_KEY: &str = "frecency_settings";
+
+/// Recalculates the frecency of every page after the API's frecency settings
+/// change, as well as any which are already stale. Like
+/// `update_stale_frecencies()`, this runs in chunks, and can be interrupted
+/// and called again to carry on. Returns the number of pages recalculated.
+pub fn recalculate_all_frecencies(db: &PlacesDb) -> Result<usize> {
+    let scope = db.begin_interrupt_scope()?;
+    let settings = serde_json::to_string(&*frecency::settings_for(db.api_id()))?;
+    let last_settings = get_meta::<String>(db, FRECENCY_SETTINGS_META_KEY)?;
+    if last_settings.as_ref() != Some(&settings) {
+        // Queue everything up first, so that if we're interrupted, we still
+        // know which pages are left.
+        let tx = db.begin_transaction()?;
+        db.execute_cached(
+            "INSERT OR IGNORE INTO moz_places_stale_frecencies(place_id, stale_at)
+             SELECT id, :now FROM moz_places",
+            rusqlite::named_params! { ":now": Timestamp::now() },
+        )?;
+        put_meta(db, FRECENCY_SETTINGS_META_KEY, &settings)?;
+        tx.commit()?;
+    }
+    update_stale_frecencies(db, &scope)
+}
+
 pub(crate) fn put_meta(conn: &Connection, key: &str, value: &dyn ToSql) -> Result<()> {
     conn.execute_cached(
         "REPLACE INTO moz_meta (key, value) VALUES (:key, :value)",
@@ -610,4 +730,55 @@ mod tests {
             0
         );
     }
+
+    #[test]
+    fn test_recalculate_all_frecencies() {
+        use crate::api::places_api::{test::new_mem_api, ConnectionType};
+        use crate::frecency::FrecencySettings;
+
+        let api = new_mem_api();
+        let conn = api.open_connection(ConnectionType::ReadWrite).unwrap();
+        let typed = Url::parse("https://example.com/typed").unwrap();
+        let link = Url::parse("https://example.com/link").unwrap();
+        for (url, visit_type) in [(&typed, VisitType::Typed), (&link, VisitType::Link)] {
+            apply_observation(
+                &conn,
+                VisitObservation::new(url.clone()).with_visit_type(visit_type),
+            )
+            .unwrap();
+        }
+        let frecency = |url: &Url| fetch_page_info(&conn, url).unwrap().unwrap().page.frecency;
+        assert!(frecency(&typed) > frecency(&link));
+        // Nothing's changed since the visits were added, except that this is
+        // the first time we've recalculated.
+        assert_eq!(recalculate_all_frecencies(&conn).unwrap(), 2);
+        assert_eq!(recalculate_all_frecencies(&conn).unwrap(), 0);
+
+        api.set_frecency_settings(FrecencySettings {
+            typed_visit_bonus: 10,
+            link_visit_bonus: 1000,
+            ..FrecencySettings::default()
+        })
+        .unwrap();
+        // New settings don't change the existing frecencies...
+        assert!(frecency(&typed) > frecency(&link));
+        // ...until we recalculate them.
+        assert_eq!(recalculate_all_frecencies(&conn).unwrap(), 2);
+        assert!(frecency(&typed) < frecency(&link));
+        assert_eq!(recalculate_all_frecencies(&conn).unwrap(), 0);
+
+        // Invalid settings are rejected.
+        assert!(api
+            .set_frecency_settings(FrecencySettings {
+                num_visits: 0,
+                ..FrecencySettings::default()
+            })
+            .is_err());
+        assert!(api
+            .set_frecency_settings(FrecencySettings {
+                second_bucket_cutoff_days: 100,
+                ..FrecencySettings::default()
+            })
+            .is_err());
+    }
 }
@@ -62,6 +62,16 @@ impl ToSql for VisitType {
     }
 }
 
+impl FromSql for VisitType {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let v = value.as_i64()?;
+        if v < 0 || v > i64::from(u8::MAX) {
+            return Err(FromSqlError::OutOfRange(v));
+        }
+        VisitType::from_primitive(v as u8).ok_or(FromSqlError::OutOfRange(v))
+    }
+}
+
 impl VisitType {
     pub fn from_primitive(p: u8) -> Option<Self> {
         match p {
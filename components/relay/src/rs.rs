@@ -308,6 +308,8 @@ mod tests {
             }),
             bucket_name: None,
             app_context: None,
+            dumps_directory: None,
+            attachment_cache_max_bytes: None,
         };
         Arc::new(RemoteSettingsService::new(String::from(":memory:"), config))
     }
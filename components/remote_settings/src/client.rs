@@ -3,16 +3,21 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::config::BaseUrl;
-use crate::error::{breadcrumb, debug, trace, Error, Result};
+use crate::error::{breadcrumb, debug, trace, warn, Error, Result};
 use crate::jexl_filter::JexlFilter;
+use crate::listener::{ChangeListeners, RemoteSettingsChangeListener, RemoteSettingsRecordChanges};
+use crate::record_filter::RecordFilter;
 #[cfg(feature = "signatures")]
 use crate::signatures;
 use crate::storage::Storage;
 use crate::RemoteSettingsContext;
 use crate::{packaged_attachments, packaged_collections, RemoteSettingsServer};
+use camino::Utf8PathBuf;
 use parking_lot::{Mutex, MutexGuard};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::sync::Arc;
 use std::time::{Duration, Instant};
 use url::Url;
 use viaduct::{Request, Response};
@@ -70,18 +75,25 @@ pub struct RemoteSettingsClient<C = ViaductApiClient> {
     // Config that we got from `update_config`.  This should be applied to
     // `RemoteSettingsClientInner` the next time it's used.
     pending_config: Mutex<Option<RemoteSettingsClientConfig>>,
+    // This is separate from `inner` so that listeners are called without holding that lock.
+    change_listeners: Mutex<ChangeListeners>,
 }
 
 struct RemoteSettingsClientInner<C> {
     storage: Storage,
     api_client: C,
     jexl_filter: JexlFilter,
+    /// Directory with dumps for the client's bucket, which take precedence over the compiled-in
+    /// dumps.  See [crate::RemoteSettingsConfig::dumps_directory].
+    dumps_dir: Option<Utf8PathBuf>,
 }
 
 struct RemoteSettingsClientConfig {
     server_url: BaseUrl,
     bucket_name: String,
     context: Option<RemoteSettingsContext>,
+    dumps_dir: Option<Utf8PathBuf>,
+    attachment_cache_max_bytes: Option<u64>,
 }
 
 // To initially download the dump (and attachments, if any), run:
@@ -209,11 +221,20 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
                 storage,
                 api_client,
                 jexl_filter,
+                dumps_dir: None,
             }),
             pending_config: Mutex::new(None),
+            change_listeners: Mutex::new(ChangeListeners::default()),
         }
     }
 
+    /// Use dumps from a directory, laid out like the compiled-in ones, before the compiled-in
+    /// dumps.  `dumps_dir` is the directory for the client's bucket.
+    pub fn with_dumps_dir(self, dumps_dir: Option<Utf8PathBuf>) -> Self {
+        self.inner.lock().dumps_dir = dumps_dir;
+        self
+    }
+
     /// Lock the `RemoteSettingsClientInner` field
     ///
     /// This also applies the pending config if set.
@@ -224,6 +245,10 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
             inner.api_client =
                 C::create(config.server_url, config.bucket_name, &self.collection_name);
             inner.jexl_filter = JexlFilter::new(config.context);
+            inner.dumps_dir = config.dumps_dir;
+            inner
+                .storage
+                .set_attachment_cache_max_bytes(config.attachment_cache_max_bytes);
             inner.storage.empty()?;
         }
         Ok(inner)
@@ -255,6 +280,81 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
         Self::get_packaged_attachment(&self.collection_name, filename)
     }
 
+    /// Returns the path of a file for this collection in the dumps directory, if it exists.
+    fn dumps_dir_file(
+        &self,
+        inner: &RemoteSettingsClientInner<C>,
+        relative_path: &str,
+    ) -> Option<Utf8PathBuf> {
+        let path = inner.dumps_dir.as_ref()?.join(relative_path);
+        path.is_file().then_some(path)
+    }
+
+    /// Reads a file from the dumps directory.  Errors are logged and treated like a missing
+    /// file, so that a broken dumps directory falls back to the other data sources.
+    fn read_dumps_dir_file(
+        &self,
+        inner: &RemoteSettingsClientInner<C>,
+        relative_path: &str,
+    ) -> Option<Vec<u8>> {
+        let path = self.dumps_dir_file(inner, relative_path)?;
+        match std::fs::read(&path) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                warn!("{0}: error reading {path}: {e}", self.collection_name);
+                None
+            }
+        }
+    }
+
+    /// Returns the timestamp from the `.timestamp` file in the dumps directory, if there is one.
+    fn load_dumps_dir_timestamp(&self, inner: &RemoteSettingsClientInner<C>) -> Option<u64> {
+        let data =
+            self.read_dumps_dir_file(inner, &format!("{}.timestamp", self.collection_name))?;
+        match String::from_utf8_lossy(&data).trim().parse() {
+            Ok(timestamp) => Some(timestamp),
+            Err(e) => {
+                warn!(
+                    "{0}: invalid dumps directory timestamp: {e}",
+                    self.collection_name
+                );
+                None
+            }
+        }
+    }
+
+    fn load_dumps_dir_data(&self, inner: &RemoteSettingsClientInner<C>) -> Option<CollectionData> {
+        let data = self.read_dumps_dir_file(inner, &format!("{}.json", self.collection_name))?;
+        match serde_json::from_slice(&data) {
+            Ok(data) => Some(data),
+            Err(e) => {
+                warn!(
+                    "{0}: invalid dumps directory data: {e}",
+                    self.collection_name
+                );
+                None
+            }
+        }
+    }
+
+    fn load_dumps_dir_attachment(
+        &self,
+        inner: &RemoteSettingsClientInner<C>,
+        filename: &str,
+    ) -> Option<(Vec<u8>, String)> {
+        let path = format!("attachments/{}/{filename}", self.collection_name);
+        let data = self.read_dumps_dir_file(inner, &path)?;
+        let manifest = self.read_dumps_dir_file(inner, &format!("{path}.meta.json"))?;
+        Some((data, String::from_utf8_lossy(&manifest).into_owned()))
+    }
+
+    /// Returns whether the dumps directory has a dump for this collection.  If so, it's used
+    /// instead of the compiled-in dump.
+    fn has_dumps_dir_data(&self, inner: &RemoteSettingsClientInner<C>) -> bool {
+        self.dumps_dir_file(inner, &format!("{}.json", self.collection_name))
+            .is_some()
+    }
+
     /// Filters records based on the presence and evaluation of `filter_expression`.
     fn filter_records(
         &self,
@@ -274,24 +374,73 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
 
     /// Returns the parsed packaged data, but only if it's newer than the data we have
     /// in storage. This avoids parsing the packaged data if we won't use it.
+    ///
+    /// The packaged data comes from the dumps directory if it has this collection, and from the
+    /// compiled-in dumps otherwise.  Compiled-in dumps are only used with the production server,
+    /// but dumps directory dumps are used with any server, since they're configured explicitly.
     fn get_packaged_data_if_newer(
         &self,
-        storage: &mut Storage,
+        inner: &mut RemoteSettingsClientInner<C>,
         collection_url: &str,
     ) -> Result<Option<CollectionData>> {
-        let packaged_ts = self.load_packaged_timestamp();
-        let storage_ts = storage.get_last_modified_timestamp(collection_url)?;
-        let packaged_is_newer = match (packaged_ts, storage_ts) {
-            (Some(packaged_ts), Some(storage_ts)) => packaged_ts > storage_ts,
-            (Some(_), None) => true, // no storage data
-            (None, _) => false,      // no packaged data
+        let storage_ts = inner.storage.get_last_modified_timestamp(collection_url)?;
+        let is_newer = |packaged_ts: u64| match storage_ts {
+            Some(storage_ts) => packaged_ts > storage_ts,
+            None => true, // no storage data
         };
 
-        if packaged_is_newer {
-            Ok(self.load_packaged_data())
-        } else {
-            Ok(None)
+        if self.has_dumps_dir_data(inner) {
+            // Like the compiled-in dumps, prefer the separate timestamp file to avoid parsing the
+            // full JSON, but fall back to the JSON's timestamp if there isn't one.
+            let timestamp = self.load_dumps_dir_timestamp(inner);
+            if timestamp.is_some_and(|ts| !is_newer(ts)) {
+                return Ok(None);
+            }
+            if let Some(data) = self.load_dumps_dir_data(inner) {
+                let packaged_ts = timestamp.unwrap_or(data.timestamp);
+                return Ok(is_newer(packaged_ts).then_some(data));
+            }
+            // The dump couldn't be loaded, so fall back to the compiled-in dump.
         }
+
+        if !inner.api_client.is_prod_server()? {
+            return Ok(None);
+        }
+        match self.load_packaged_timestamp() {
+            Some(packaged_ts) if is_newer(packaged_ts) => Ok(self.load_packaged_data()),
+            _ => Ok(None), // no packaged data, or it's not newer
+        }
+    }
+
+    /// Replaces the cached records with the packaged data if it's newer, and returns the
+    /// packaged records and the changes to the cached records if so.
+    ///
+    /// This happens when there's no cached data or when we get new packaged data because of a
+    /// product update.  The caller should pass the changes to the listeners once it's released
+    /// the lock on `inner`.
+    fn store_packaged_data_if_newer(
+        &self,
+        inner: &mut RemoteSettingsClientInner<C>,
+    ) -> Result<Option<(Vec<RemoteSettingsRecord>, RemoteSettingsRecordChanges)>> {
+        let collection_url = inner.api_client.collection_url();
+        let Some(packaged_data) = self.get_packaged_data_if_newer(inner, &collection_url)? else {
+            return Ok(None);
+        };
+        let ids_before = inner.storage.get_record_ids(&collection_url)?;
+        // Remove previously cached data (packaged data does not have tombstones like diff responses do).
+        inner.storage.empty()?;
+        // Insert new packaged data.
+        inner.storage.insert_collection_content(
+            &collection_url,
+            &packaged_data.data,
+            packaged_data.timestamp,
+            CollectionMetadata::default(),
+        )?;
+        let ids_after = inner.storage.get_record_ids(&collection_url)?;
+        Ok(Some((
+            packaged_data.data,
+            RemoteSettingsRecordChanges::between(&ids_before, &ids_after),
+        )))
     }
 
     /// Get the current set of records.
@@ -303,24 +452,11 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
         let collection_url = inner.api_client.collection_url();
 
         // Case 1: The packaged data is more recent than the cache
-        //
-        // This happens when there's no cached data or when we get new packaged data because of a
-        // product update
-        if inner.api_client.is_prod_server()? {
-            if let Some(packaged_data) =
-                self.get_packaged_data_if_newer(&mut inner.storage, &collection_url)?
-            {
-                // Remove previously cached data (packaged data does not have tombstones like diff responses do).
-                inner.storage.empty()?;
-                // Insert new packaged data.
-                inner.storage.insert_collection_content(
-                    &collection_url,
-                    &packaged_data.data,
-                    packaged_data.timestamp,
-                    CollectionMetadata::default(),
-                )?;
-                return Ok(Some(self.filter_records(packaged_data.data, &inner)));
-            }
+        if let Some((packaged_records, changes)) = self.store_packaged_data_if_newer(&mut inner)? {
+            let records = self.filter_records(packaged_records, &inner);
+            drop(inner);
+            self.notify_change_listeners(changes);
+            return Ok(Some(records));
         }
 
         let cached_records = inner.storage.get_records(&collection_url)?;
@@ -349,6 +485,59 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
         }
     }
 
+    /// Get the current records that match a filter.
+    ///
+    /// This works like [Self::get_records], except that the filter's predicates are evaluated
+    /// against the cached records in SQLite, so records that don't match are never loaded.
+    pub fn get_records_matching(
+        &self,
+        filter: &RecordFilter,
+        sync_if_empty: bool,
+    ) -> Result<Option<Vec<RemoteSettingsRecord>>> {
+        let mut inner = self.lock_inner()?;
+        let collection_url = inner.api_client.collection_url();
+        if let Some((_, changes)) = self.store_packaged_data_if_newer(&mut inner)? {
+            // Listeners may read the records, so release the lock first.
+            drop(inner);
+            self.notify_change_listeners(changes);
+            inner = self.lock_inner()?;
+        }
+
+        let mut records = inner
+            .storage
+            .get_records_matching(&collection_url, &filter.predicates)?;
+        if records.is_none() && sync_if_empty {
+            // `sync()` takes the lock, release it first.
+            drop(inner);
+            self.sync()?;
+            inner = self.lock_inner()?;
+            records = inner
+                .storage
+                .get_records_matching(&collection_url, &filter.predicates)?;
+        }
+        let Some(records) = records else {
+            return Ok(None);
+        };
+
+        let records = self.filter_records(records, &inner);
+        Ok(Some(match &filter.jexl_expression {
+            Some(expr) => {
+                let mut matching = Vec::with_capacity(records.len());
+                for record in records {
+                    let matches = inner
+                        .jexl_filter
+                        .evaluate_for_record(expr, &record.fields)
+                        .map_err(|e| Error::InvalidRecordFilter(e.to_string()))?;
+                    if matches {
+                        matching.push(record);
+                    }
+                }
+                matching
+            }
+            None => records,
+        }))
+    }
+
     /// Returns the last modified timestamp for the collection.
     pub fn get_last_modified_timestamp(&self) -> Result<Option<u64>> {
         let mut inner = self.lock_inner()?;
@@ -356,11 +545,40 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
         inner.storage.get_last_modified_timestamp(&collection_url)
     }
 
+    /// Register a listener for changes to the stored records.
+    ///
+    /// Listeners hear about each sync that passes signature verification, and about each time the
+    /// stored records are replaced with packaged data, including after a sync fails.
+    ///
+    /// Returns an id to pass to [Self::remove_change_listener].
+    pub fn add_change_listener(&self, listener: Arc<dyn RemoteSettingsChangeListener>) -> u64 {
+        self.change_listeners.lock().add(listener)
+    }
+
+    pub fn remove_change_listener(&self, listener_id: u64) {
+        self.change_listeners.lock().remove(listener_id)
+    }
+
+    /// Passes `changes` to the listeners.  This must be called without holding the `inner` lock.
+    fn notify_change_listeners(&self, changes: RemoteSettingsRecordChanges) {
+        ChangeListeners::notify(&self.change_listeners, &self.collection_name, changes);
+    }
+
+    /// Returns the ids of the stored records
+    fn get_record_ids(&self) -> Result<HashSet<String>> {
+        let mut inner = self.lock_inner()?;
+        let collection_url = inner.api_client.collection_url();
+        inner.storage.get_record_ids(&collection_url)
+    }
+
     /// Synchronizes the local collection with the remote server by performing the following steps:
     /// 1. Fetches the last modified timestamp of the collection from local storage.
     /// 2. Fetches the changeset from the remote server based on the last modified timestamp.
     /// 3. Inserts the fetched changeset into local storage.
-    fn perform_sync_operation(&self) -> Result<()> {
+    ///
+    /// Returns the ids of the records that were stored before the sync, and the changes that the
+    /// changeset made to them.
+    fn perform_sync_operation(&self) -> Result<(HashSet<String>, RemoteSettingsRecordChanges)> {
         let mut inner = self.lock_inner()?;
         let collection_url = inner.api_client.collection_url();
         let timestamp = inner.storage.get_last_modified_timestamp(&collection_url)?;
@@ -370,33 +588,54 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
             self.collection_name,
             changeset.changes.len()
         );
+        let existing_ids = inner.storage.get_record_ids(&collection_url)?;
+        let changes =
+            RemoteSettingsRecordChanges::from_changeset(&existing_ids, &changeset.changes);
         inner.storage.insert_collection_content(
             &collection_url,
             &changeset.changes,
             changeset.timestamp,
             changeset.metadata,
-        )
+        )?;
+        Ok((existing_ids, changes))
     }
 
     pub fn sync(&self) -> Result<()> {
         // First attempt
-        self.perform_sync_operation()?;
+        let (ids_before, changes) = self.perform_sync_operation()?;
         // Verify that inserted data has valid signature
-        if self.verify_signature().is_err() {
-            debug!(
-                "{0}: signature verification failed. Reset and retry.",
-                self.collection_name
-            );
-            // Retry with packaged dataset as base
-            self.reset_storage()?;
-            self.perform_sync_operation()?;
-            // Verify signature again
-            self.verify_signature().inspect_err(|_| {
-                // And reset with packaged data if it fails again.
-                self.reset_storage()
-                    .expect("Failed to reset storage after verification failure");
-            })?;
+        if self.verify_signature().is_ok() {
+            trace!("{0}: sync done.", self.collection_name);
+            self.notify_change_listeners(changes);
+            return Ok(());
         }
+        debug!(
+            "{0}: signature verification failed. Reset and retry.",
+            self.collection_name
+        );
+        let result = self.retry_sync();
+        // Whether or not the retry worked, the stored records were replaced.  The retry's changes
+        // are relative to the packaged data, not to what was stored before the sync, so compare
+        // the stored records instead.
+        let ids_after = self.get_record_ids()?;
+        self.notify_change_listeners(RemoteSettingsRecordChanges::between(
+            &ids_before,
+            &ids_after,
+        ));
+        result
+    }
+
+    /// Retries a sync that failed signature verification, starting from the packaged data.
+    fn retry_sync(&self) -> Result<()> {
+        // Retry with packaged dataset as base
+        self.replace_with_packaged_data()?;
+        self.perform_sync_operation()?;
+        // Verify signature again
+        self.verify_signature().inspect_err(|_| {
+            // And reset with packaged data if it fails again.
+            self.replace_with_packaged_data()
+                .expect("Failed to reset storage after verification failure");
+        })?;
         trace!("{0}: sync done.", self.collection_name);
         Ok(())
     }
@@ -407,22 +646,38 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
     }
 
     pub fn reset_storage(&self) -> Result<()> {
+        let ids_before = self.get_record_ids()?;
+        self.replace_with_packaged_data()?;
+        let ids_after = self.get_record_ids()?;
+        self.notify_change_listeners(RemoteSettingsRecordChanges::between(
+            &ids_before,
+            &ids_after,
+        ));
+        Ok(())
+    }
+
+    /// Replaces the stored records with the packaged data, if any, without notifying the
+    /// listeners.
+    fn replace_with_packaged_data(&self) -> Result<()> {
         trace!("{0}: reset local storage.", self.collection_name);
         let mut inner = self.lock_inner()?;
         let collection_url = inner.api_client.collection_url();
         // Clear existing storage
         inner.storage.empty()?;
-        // Load packaged data only for production
-        if inner.api_client.is_prod_server()? {
-            if let Some(packaged_data) = self.load_packaged_data() {
-                trace!("{0}: restore packaged dump.", self.collection_name);
-                inner.storage.insert_collection_content(
-                    &collection_url,
-                    &packaged_data.data,
-                    packaged_data.timestamp,
-                    CollectionMetadata::default(),
-                )?;
-            }
+        // Load packaged data from the dumps directory, or compiled-in data only for production
+        let packaged_data = match self.load_dumps_dir_data(&inner) {
+            Some(data) => Some(data),
+            None if inner.api_client.is_prod_server()? => self.load_packaged_data(),
+            None => None,
+        };
+        if let Some(packaged_data) = packaged_data {
+            trace!("{0}: restore packaged dump.", self.collection_name);
+            inner.storage.insert_collection_content(
+                &collection_url,
+                &packaged_data.data,
+                packaged_data.timestamp,
+                CollectionMetadata::default(),
+            )?;
         }
         Ok(())
     }
@@ -527,7 +782,8 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
         let mut inner = self.lock_inner()?;
         let collection_url = inner.api_client.collection_url();
 
-        // First try storage - it will only return data that matches our metadata
+        // First try storage - it will only return data that matches our metadata.  Corrupt or
+        // outdated data is deleted, and replaced below.
         if let Some(data) = inner
             .storage
             .get_attachment(&collection_url, metadata.clone())?
@@ -535,19 +791,24 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
             return Ok(data);
         }
 
-        // Then try packaged data if we're in prod
-        if inner.api_client.is_prod_server()? {
-            if let Some((data, manifest)) = self.load_packaged_attachment(&record.id) {
-                if let Ok(manifest_data) = serde_json::from_str::<serde_json::Value>(manifest) {
-                    if metadata.hash == manifest_data["hash"].as_str().unwrap_or_default()
-                        && metadata.size == manifest_data["size"].as_u64().unwrap_or_default()
-                    {
-                        // Store valid packaged data in storage because it was either empty or outdated
-                        inner
-                            .storage
-                            .set_attachment(&collection_url, &metadata.location, data)?;
-                        return Ok(data.to_vec());
-                    }
+        // Then try packaged data from the dumps directory, or compiled-in data if we're in prod
+        let packaged_attachment = match self.load_dumps_dir_attachment(&inner, &record.id) {
+            Some(attachment) => Some(attachment),
+            None if inner.api_client.is_prod_server()? => self
+                .load_packaged_attachment(&record.id)
+                .map(|(data, manifest)| (data.to_vec(), manifest.to_string())),
+            None => None,
+        };
+        if let Some((data, manifest)) = packaged_attachment {
+            if let Ok(manifest_data) = serde_json::from_str::<serde_json::Value>(&manifest) {
+                if metadata.hash == manifest_data["hash"].as_str().unwrap_or_default()
+                    && metadata.size == manifest_data["size"].as_u64().unwrap_or_default()
+                {
+                    // Store valid packaged data in storage because it was either empty or outdated
+                    inner
+                        .storage
+                        .set_attachment(&collection_url, &metadata.location, &data)?;
+                    return Ok(data);
                 }
             }
         }
@@ -575,17 +836,28 @@ impl<C: ApiClient> RemoteSettingsClient<C> {
         Ok(attachment)
     }
 
+    /// Get the total size of the cached attachments for this collection, in bytes
+    pub fn get_attachment_cache_size(&self) -> Result<u64> {
+        let mut inner = self.lock_inner()?;
+        let collection_url = inner.api_client.collection_url();
+        inner.storage.get_attachment_cache_size(&collection_url)
+    }
+
     pub fn update_config(
         &self,
         server_url: BaseUrl,
         bucket_name: String,
         context: Option<RemoteSettingsContext>,
+        dumps_dir: Option<Utf8PathBuf>,
+        attachment_cache_max_bytes: Option<u64>,
     ) {
         let mut pending_config = self.pending_config.lock();
         *pending_config = Some(RemoteSettingsClientConfig {
             server_url,
             bucket_name,
             context,
+            dumps_dir,
+            attachment_cache_max_bytes,
         })
     }
 }
@@ -596,12 +868,14 @@ impl RemoteSettingsClient<ViaductApiClient> {
         bucket_name: String,
         collection_name: String,
         context: Option<RemoteSettingsContext>,
+        dumps_dir: Option<Utf8PathBuf>,
         storage: Storage,
     ) -> Self {
         let api_client = ViaductApiClient::new(server_url, &bucket_name, &collection_name);
         let jexl_filter = JexlFilter::new(context);
 
         Self::new_from_parts(collection_name, storage, jexl_filter, api_client)
+            .with_dumps_dir(dumps_dir)
     }
 }
 
@@ -1215,6 +1489,8 @@ mod jexl_tests {
                     BaseUrl::parse("https://example.com/").unwrap(),
                     "test-collection".to_string(),
                     None,
+                    None,
+                    None,
                 );
             "http://rs.example.com/v2/buckets/main/collections/test-collection".into()
         });
@@ -1241,6 +1517,110 @@ mod jexl_tests {
     }
 }
 
+#[cfg(test)]
+mod test_change_listeners {
+    use super::*;
+
+    #[derive(Default)]
+    pub(super) struct RecordingListener {
+        pub(super) calls: Mutex<Vec<(String, RemoteSettingsRecordChanges)>>,
+    }
+
+    impl RemoteSettingsChangeListener for RecordingListener {
+        fn on_records_changed(
+            &self,
+            collection_name: String,
+            changes: RemoteSettingsRecordChanges,
+        ) {
+            self.calls.lock().push((collection_name, changes));
+        }
+    }
+
+    #[cfg(not(feature = "signatures"))]
+    fn record(id: &str, last_modified: u64, deleted: bool) -> RemoteSettingsRecord {
+        RemoteSettingsRecord {
+            id: id.into(),
+            last_modified,
+            deleted,
+            attachment: None,
+            fields: serde_json::Map::new(),
+        }
+    }
+
+    #[cfg(not(feature = "signatures"))]
+    #[test]
+    fn test_change_listener_notified_after_sync() {
+        let collection_url = "http://rs.example.com/v2/buckets/main/collections/test-collection";
+        let mut api_client = MockApiClient::new();
+        api_client
+            .expect_collection_url()
+            .returning(move || collection_url.into());
+        api_client.expect_is_prod_server().returning(|| Ok(false));
+        api_client.expect_fetch_changeset().returning(|timestamp| {
+            Ok(match timestamp {
+                // The second sync has nothing new
+                Some(200) => ChangesetResponse {
+                    changes: vec![],
+                    timestamp: 200,
+                    metadata: CollectionMetadata::default(),
+                },
+                _ => ChangesetResponse {
+                    changes: vec![
+                        record("record-0001", 200, false),
+                        record("record-0002", 200, true),
+                        record("record-0003", 200, false),
+                    ],
+                    timestamp: 200,
+                    metadata: CollectionMetadata::default(),
+                },
+            })
+        });
+
+        let mut storage = Storage::new(":memory:".into());
+        storage
+            .insert_collection_content(
+                collection_url,
+                &[
+                    record("record-0001", 100, false),
+                    record("record-0002", 100, false),
+                ],
+                100,
+                CollectionMetadata::default(),
+            )
+            .unwrap();
+
+        let rs_client = RemoteSettingsClient::new_from_parts(
+            "test-collection".into(),
+            storage,
+            JexlFilter::new(None),
+            api_client,
+        );
+        let listener = Arc::new(RecordingListener::default());
+        let removed_listener = Arc::new(RecordingListener::default());
+        rs_client.add_change_listener(listener.clone());
+        let removed_id = rs_client.add_change_listener(removed_listener.clone());
+        rs_client.remove_change_listener(removed_id);
+
+        rs_client.sync().unwrap();
+        assert_eq!(
+            *listener.calls.lock(),
+            vec![(
+                "test-collection".to_string(),
+                RemoteSettingsRecordChanges {
+                    created: vec!["record-0003".into()],
+                    updated: vec!["record-0001".into()],
+                    deleted: vec!["record-0002".into()],
+                }
+            )]
+        );
+        assert_eq!(*removed_listener.calls.lock(), vec![]);
+
+        // Syncs that don't change anything don't notify the listeners.
+        rs_client.sync().unwrap();
+        assert_eq!(listener.calls.lock().len(), 1);
+    }
+}
+
 #[cfg(feature = "signatures")]
 #[cfg(test)]
 mod test_signatures {
@@ -1523,6 +1903,99 @@ IKdcFKAt3fFrpyMhlfIKkLfmm0iDjmfmIXbDGBJw9SE=
         Ok(())
     }
 
+    // Puts a record in the storage, which isn't in the signed (empty) collection, so that the
+    // first attempt to sync fails verification.
+    fn store_unsigned_record(rs_client: &RemoteSettingsClient<MockApiClient>) {
+        let mut inner = rs_client.inner.lock();
+        let collection_url = inner.api_client.collection_url();
+        inner
+            .storage
+            .insert_collection_content(
+                &collection_url,
+                &[RemoteSettingsRecord {
+                    id: "unsigned-record".to_string(),
+                    last_modified: 100,
+                    deleted: false,
+                    attachment: None,
+                    fields: serde_json::Map::new(),
+                }],
+                100,
+                CollectionMetadata::default(),
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn test_change_listener_notified_after_retry() {
+        use super::test_change_listeners::RecordingListener;
+
+        ensure_initialized();
+        let rs_client = build_client(
+            &[],
+            &[],
+            VALID_CERTIFICATE,
+            &[CollectionSignature {
+                signature: VALID_SIGNATURE.to_string(),
+                x5u: "http://mocked".into(),
+                mode: "p384ecdsa".into(),
+            }],
+            VALID_CERT_EPOCH_SECONDS,
+            "main",
+        );
+        store_unsigned_record(&rs_client);
+        let listener = Arc::new(RecordingListener::default());
+        rs_client.add_change_listener(listener.clone());
+
+        // The retry starts from scratch, so the unsigned record is deleted.
+        rs_client.sync().expect("Valid signature after retry");
+        assert_eq!(
+            *listener.calls.lock(),
+            vec![(
+                "pioneer-study-addons".to_string(),
+                RemoteSettingsRecordChanges {
+                    deleted: vec!["unsigned-record".into()],
+                    ..Default::default()
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_change_listener_notified_after_failed_retry() {
+        use super::test_change_listeners::RecordingListener;
+
+        ensure_initialized();
+        let rs_client = build_client(
+            &[],
+            &[],
+            VALID_CERTIFICATE,
+            &[CollectionSignature {
+                signature: "invalid signature".to_string(),
+                x5u: "http://mocked".into(),
+                mode: "p384ecdsa".into(),
+            }],
+            VALID_CERT_EPOCH_SECONDS,
+            "main",
+        );
+        store_unsigned_record(&rs_client);
+        let listener = Arc::new(RecordingListener::default());
+        rs_client.add_change_listener(listener.clone());
+
+        // The storage is reset to the (empty) packaged data, and the listener hears about that
+        // even though the sync fails.
+        assert!(rs_client.sync().is_err());
+        assert_eq!(
+            *listener.calls.lock(),
+            vec![(
+                "pioneer-study-addons".to_string(),
+                RemoteSettingsRecordChanges {
+                    deleted: vec!["unsigned-record".into()],
+                    ..Default::default()
+                }
+            )]
+        );
+    }
+
     #[test]
     fn test_invalid_signature_value() -> Result<()> {
         ensure_initialized();
@@ -1839,3 +2312,230 @@ mod test_reset_storage {
         );
     }
 }
+
+#[cfg(test)]
+mod test_dumps_directory {
+    use super::*;
+    use camino::Utf8Path;
+
+    const COLLECTION_URL: &str =
+        "http://rs.example.com/v2/buckets/main/collections/test-collection";
+
+    fn dump_record(attachment_data: &[u8]) -> RemoteSettingsRecord {
+        RemoteSettingsRecord {
+            id: "dumped-record".into(),
+            last_modified: 500,
+            deleted: false,
+            attachment: Some(Attachment {
+                filename: "dumped-record".into(),
+                mimetype: "application/octet-stream".into(),
+                location: "main/test-collection/dumped-record".into(),
+                hash: format!("{:x}", Sha256::digest(attachment_data)),
+                size: attachment_data.len() as u64,
+            }),
+            fields: serde_json::json!({"key": "dumped-value"})
+                .as_object()
+                .unwrap()
+                .clone(),
+        }
+    }
+
+    /// Writes a dump of `test-collection` with one record and its attachment
+    fn write_dump(dir: &Utf8Path, attachment_data: &[u8]) -> RemoteSettingsRecord {
+        let record = dump_record(attachment_data);
+        let bucket_dir = dir.join("main");
+        let attachments_dir = bucket_dir.join("attachments/test-collection");
+        std::fs::create_dir_all(&attachments_dir).unwrap();
+        std::fs::write(
+            bucket_dir.join("test-collection.json"),
+            serde_json::json!({"data": [record], "timestamp": 500}).to_string(),
+        )
+        .unwrap();
+        std::fs::write(attachments_dir.join("dumped-record"), attachment_data).unwrap();
+        let attachment = record.attachment.as_ref().unwrap();
+        std::fs::write(
+            attachments_dir.join("dumped-record.meta.json"),
+            serde_json::json!({"hash": attachment.hash, "size": attachment.size}).to_string(),
+        )
+        .unwrap();
+        record
+    }
+
+    fn make_client(storage: Storage, dumps_dir: &Utf8Path) -> RemoteSettingsClient<MockApiClient> {
+        let mut api_client = MockApiClient::new();
+        api_client
+            .expect_collection_url()
+            .returning(|| COLLECTION_URL.into());
+        // Dumps directory data should be used even when not in prod.
+        api_client.expect_is_prod_server().returning(|| Ok(false));
+        RemoteSettingsClient::new_from_parts(
+            "test-collection".into(),
+            storage,
+            JexlFilter::new(None),
+            api_client,
+        )
+        .with_dumps_dir(Some(dumps_dir.join("main")))
+    }
+
+    fn cached_record(last_modified: u64) -> RemoteSettingsRecord {
+        RemoteSettingsRecord {
+            id: "cached-record".into(),
+            last_modified,
+            deleted: false,
+            attachment: None,
+            fields: serde_json::json!({"key": "cached-value"})
+                .as_object()
+                .unwrap()
+                .clone(),
+        }
+    }
+
+    #[test]
+    fn test_dumps_directory_used_when_newer() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dumps_dir = Utf8Path::from_path(tempdir.path()).unwrap();
+        let dumped_record = write_dump(dumps_dir, b"attachment-data");
+
+        // Storage is older than the dump
+        let mut storage = Storage::new(":memory:".into());
+        storage
+            .insert_collection_content(
+                COLLECTION_URL,
+                &[cached_record(100)],
+                100,
+                CollectionMetadata::default(),
+            )
+            .unwrap();
+
+        let rs_client = make_client(storage, dumps_dir);
+        assert_eq!(
+            rs_client.get_records(false).unwrap(),
+            Some(vec![dumped_record.clone()])
+        );
+        assert_eq!(rs_client.get_last_modified_timestamp().unwrap(), Some(500));
+        // The attachment is read from the dumps directory, without a network request.
+        assert_eq!(
+            rs_client.get_attachment(&dumped_record).unwrap(),
+            b"attachment-data"
+        );
+    }
+
+    #[test]
+    fn test_dumps_directory_notifies_change_listeners() {
+        use super::test_change_listeners::RecordingListener;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let dumps_dir = Utf8Path::from_path(tempdir.path()).unwrap();
+        write_dump(dumps_dir, b"attachment-data");
+
+        let mut storage = Storage::new(":memory:".into());
+        storage
+            .insert_collection_content(
+                COLLECTION_URL,
+                &[cached_record(100)],
+                100,
+                CollectionMetadata::default(),
+            )
+            .unwrap();
+
+        let rs_client = make_client(storage, dumps_dir);
+        let listener = Arc::new(RecordingListener::default());
+        rs_client.add_change_listener(listener.clone());
+        rs_client.get_records(false).unwrap();
+        // The newer dump replaces the cached records.
+        assert_eq!(
+            *listener.calls.lock(),
+            vec![(
+                "test-collection".to_string(),
+                RemoteSettingsRecordChanges {
+                    created: vec!["dumped-record".into()],
+                    deleted: vec!["cached-record".into()],
+                    ..Default::default()
+                }
+            )]
+        );
+        // The dump is only loaded once.
+        rs_client.get_records(false).unwrap();
+        assert_eq!(listener.calls.lock().len(), 1);
+    }
+
+    #[test]
+    fn test_dumps_directory_ignored_when_older() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dumps_dir = Utf8Path::from_path(tempdir.path()).unwrap();
+        write_dump(dumps_dir, b"attachment-data");
+
+        // Storage is newer than the dump
+        let mut storage = Storage::new(":memory:".into());
+        storage
+            .insert_collection_content(
+                COLLECTION_URL,
+                &[cached_record(1000)],
+                1000,
+                CollectionMetadata::default(),
+            )
+            .unwrap();
+
+        let rs_client = make_client(storage, dumps_dir);
+        assert_eq!(
+            rs_client.get_records(false).unwrap(),
+            Some(vec![cached_record(1000)])
+        );
+    }
+
+    #[test]
+    fn test_dumps_directory_timestamp_file() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dumps_dir = Utf8Path::from_path(tempdir.path()).unwrap();
+        write_dump(dumps_dir, b"attachment-data");
+        // A separate timestamp file takes precedence over the JSON's timestamp, like with the
+        // compiled-in dumps.
+        std::fs::write(dumps_dir.join("main/test-collection.timestamp"), "50\n").unwrap();
+
+        let mut storage = Storage::new(":memory:".into());
+        storage
+            .insert_collection_content(
+                COLLECTION_URL,
+                &[cached_record(100)],
+                100,
+                CollectionMetadata::default(),
+            )
+            .unwrap();
+
+        let rs_client = make_client(storage, dumps_dir);
+        assert_eq!(
+            rs_client.get_records(false).unwrap(),
+            Some(vec![cached_record(100)])
+        );
+    }
+
+    #[test]
+    fn test_dumps_directory_invalid_dump() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let dumps_dir = Utf8Path::from_path(tempdir.path()).unwrap();
+        let dumped_record = write_dump(dumps_dir, b"attachment-data");
+        // A malformed dump is ignored, and the cached records are returned instead of an error.
+        std::fs::write(dumps_dir.join("main/test-collection.json"), "{\"data\": [").unwrap();
+
+        let mut storage = Storage::new(":memory:".into());
+        storage
+            .insert_collection_content(
+                COLLECTION_URL,
+                &[cached_record(100)],
+                100,
+                CollectionMetadata::default(),
+            )
+            .unwrap();
+
+        let rs_client = make_client(storage, dumps_dir);
+        assert_eq!(
+            rs_client.get_records(false).unwrap(),
+            Some(vec![cached_record(100)])
+        );
+        // The attachment is still valid, so it's read from the dumps directory.
+        assert_eq!(
+            rs_client.get_attachment(&dumped_record).unwrap(),
+            b"attachment-data"
+        );
+    }
+}
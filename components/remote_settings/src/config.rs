@@ -25,6 +25,22 @@ pub struct RemoteSettingsConfig {
     /// App context to use for JEXL filtering (when the `jexl` feature is present).
     #[uniffi(default = None)]
     pub app_context: Option<RemoteSettingsContext>,
+    /// Directory to load packaged dumps from, in addition to the ones compiled into the library.
+    ///
+    /// This has the same layout as the `dumps` directory in this crate:
+    /// `{bucket}/{collection}.json`, an optional `{bucket}/{collection}.timestamp`, and
+    /// attachments in `{bucket}/attachments/{collection}/{filename}` with a
+    /// `{filename}.meta.json` next to each one.  A dump in this directory is used instead of the
+    /// compiled-in dump for the same collection, with any server.
+    #[uniffi(default = None)]
+    pub dumps_directory: Option<String>,
+    /// Maximum size, in bytes, of each collection's attachment cache.
+    ///
+    /// When storing an attachment puts the cache over this size, the least recently used
+    /// attachments are evicted.  Attachments larger than this aren't cached at all.  Defaults to
+    /// no limit.
+    #[uniffi(default = None)]
+    pub attachment_cache_max_bytes: Option<u64>,
 }
 
 /// The Remote Settings server that the client should use.
@@ -57,6 +57,8 @@ pub enum Error {
     DatabaseClosed,
     #[error("No attachment in given record: {0}")]
     RecordAttachmentMismatchError(String),
+    #[error("Invalid record filter: {0}")]
+    InvalidRecordFilter(String),
     #[error("Incomplete signature data: {0}")]
     IncompleteSignatureDataError(String),
     #[cfg(feature = "signatures")]
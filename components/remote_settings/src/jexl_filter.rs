@@ -2,7 +2,7 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use crate::RemoteSettingsContext;
+use crate::{RemoteSettingsContext, RsJsonObject};
 use firefox_versioning::compare::version_compare;
 use jexl_eval::Evaluator;
 use serde_json::{json, Value};
@@ -56,4 +56,24 @@ impl JexlFilter {
 
         result.as_bool().ok_or(ParseError::InvalidResultType)
     }
+
+    /// Evaluates the given filter expression in the provided context, with the record's fields
+    /// available as `record`.
+    pub(crate) fn evaluate_for_record(
+        &self,
+        filter_expr: &str,
+        record: &RsJsonObject,
+    ) -> Result<bool, ParseError> {
+        let mut context = self.context.clone();
+        context["record"] = Value::Object(record.clone());
+
+        let result = self
+            .evaluator
+            .eval_in_context(filter_expr, &context)
+            .map_err(|e| {
+                ParseError::EvaluationError(format!("Failed to evaluate '{}': {}", filter_expr, e))
+            })?;
+
+        result.as_bool().ok_or(ParseError::InvalidResultType)
+    }
 }
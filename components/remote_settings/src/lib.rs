@@ -4,12 +4,16 @@
 
 use std::{collections::HashMap, sync::Arc};
 
+use camino::Utf8PathBuf;
+
 use error_support::{convert_log_report_error, handle_error};
 
 pub mod client;
 pub mod config;
 pub mod context;
 pub mod error;
+pub mod listener;
+pub mod record_filter;
 pub mod schema;
 pub mod service;
 #[cfg(feature = "signatures")]
@@ -24,6 +28,8 @@ pub use client::{Attachment, RemoteSettingsRecord, RsJsonObject};
 pub use config::{BaseUrl, RemoteSettingsConfig, RemoteSettingsServer};
 pub use context::RemoteSettingsContext;
 pub use error::{trace, ApiResult, RemoteSettingsError, Result};
+pub use listener::{RemoteSettingsChangeListener, RemoteSettingsRecordChanges};
+pub use record_filter::{RecordFieldValue, RecordFilter, RecordPredicate};
 pub use telemetry::{RemoteSettingsTelemetry, SyncStatus, UptakeEventExtras};
 
 use error::Error;
@@ -165,6 +171,31 @@ impl RemoteSettingsClient {
             .map(|records| records.into_iter().map(|r| (r.id.clone(), r)).collect())
     }
 
+    /// Get the current records that match a filter.
+    ///
+    /// The filter's predicates are evaluated against the local cache in SQLite, so this is much
+    /// cheaper than filtering the result of [Self::get_records] for large collections.  Records
+    /// are also filtered by their `filter_expression` field, like [Self::get_records] does.
+    ///
+    /// See [Self::get_records] for an explanation of when this makes network requests, error
+    /// handling, and how the `sync_if_empty` param works.  An invalid filter is handled like any
+    /// other error: it's reported and this returns None.
+    #[uniffi::method(default(sync_if_empty = false))]
+    pub fn get_records_matching(
+        &self,
+        filter: RecordFilter,
+        sync_if_empty: bool,
+    ) -> Option<Vec<RemoteSettingsRecord>> {
+        match self.internal.get_records_matching(&filter, sync_if_empty) {
+            Ok(records) => records,
+            Err(e) => {
+                trace!("get_records_matching error: {e}");
+                convert_log_report_error(e);
+                None
+            }
+        }
+    }
+
     /// Returns the last_modified value for the collection as an unsigned int64.
     #[uniffi::method()]
     pub fn get_last_modified_timestamp(&self) -> Option<u64> {
@@ -187,11 +218,36 @@ impl RemoteSettingsClient {
         self.internal.get_attachment(record)
     }
 
+    /// Get the total size of the attachments cached for this collection, in bytes.
+    ///
+    /// See [RemoteSettingsConfig::attachment_cache_max_bytes] to limit it.
+    #[handle_error(Error)]
+    pub fn get_attachment_cache_size(&self) -> ApiResult<u64> {
+        self.internal.get_attachment_cache_size()
+    }
+
     #[handle_error(Error)]
     pub fn sync(&self) -> ApiResult<()> {
         self.internal.sync()
     }
 
+    /// Register a listener for record changes in this collection.
+    ///
+    /// Whenever the stored records change, the listener gets the ids of the created, updated, and
+    /// deleted records.  This includes syncs started by [RemoteSettingsService::sync], and the
+    /// records being replaced with packaged data, even when a sync fails.  See
+    /// [RemoteSettingsChangeListener].
+    ///
+    /// Returns an id to pass to [Self::remove_change_listener].
+    pub fn add_change_listener(&self, listener: Arc<dyn RemoteSettingsChangeListener>) -> u64 {
+        self.internal.add_change_listener(listener)
+    }
+
+    /// Remove a listener registered with [Self::add_change_listener].
+    pub fn remove_change_listener(&self, listener_id: u64) {
+        self.internal.remove_change_listener(listener_id)
+    }
+
     #[handle_error(Error)]
     pub fn reset_storage(&self) -> ApiResult<()> {
         self.internal.reset_storage()
@@ -211,6 +267,7 @@ impl RemoteSettingsClient {
         bucket_name: String,
         collection_name: String,
         #[allow(unused)] context: Option<RemoteSettingsContext>,
+        dumps_dir: Option<Utf8PathBuf>,
         storage: Storage,
     ) -> Self {
         Self {
@@ -219,6 +276,7 @@ impl RemoteSettingsClient {
                 bucket_name,
                 collection_name,
                 context,
+                dumps_dir,
                 storage,
             ),
         }
@@ -0,0 +1,181 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::{collections::HashSet, sync::Arc};
+
+use parking_lot::Mutex;
+
+use crate::RemoteSettingsRecord;
+
+/// Ids of the records that a sync changed in a collection
+#[derive(Clone, Debug, Default, PartialEq, Eq, uniffi::Record)]
+pub struct RemoteSettingsRecordChanges {
+    /// Records that weren't stored before the sync
+    pub created: Vec<String>,
+    /// Records that were stored before the sync and were replaced with a new version
+    pub updated: Vec<String>,
+    /// Records that were stored before the sync and were deleted
+    pub deleted: Vec<String>,
+}
+
+impl RemoteSettingsRecordChanges {
+    /// Computes the changes that applying `changeset` makes to a collection whose stored record
+    /// ids are `existing_ids`.
+    pub(crate) fn from_changeset(
+        existing_ids: &HashSet<String>,
+        changeset: &[RemoteSettingsRecord],
+    ) -> Self {
+        let mut changes = Self::default();
+        for record in changeset {
+            let exists = existing_ids.contains(&record.id);
+            match (record.deleted, exists) {
+                (true, true) => changes.deleted.push(record.id.clone()),
+                // A tombstone for a record that we never stored
+                (true, false) => (),
+                (false, true) => changes.updated.push(record.id.clone()),
+                (false, false) => changes.created.push(record.id.clone()),
+            }
+        }
+        changes.sort();
+        changes
+    }
+
+    /// Computes the changes between two sets of stored record ids, when the changeset that led
+    /// from one to the other isn't known.  Records in both sets are reported as updated, since
+    /// they may have been.
+    pub(crate) fn between(ids_before: &HashSet<String>, ids_after: &HashSet<String>) -> Self {
+        let mut changes = Self {
+            created: ids_after.difference(ids_before).cloned().collect(),
+            updated: ids_after.intersection(ids_before).cloned().collect(),
+            deleted: ids_before.difference(ids_after).cloned().collect(),
+        };
+        changes.sort();
+        changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty() && self.updated.is_empty() && self.deleted.is_empty()
+    }
+
+    fn sort(&mut self) {
+        self.created.sort();
+        self.updated.sort();
+        self.deleted.sort();
+    }
+}
+
+/// Trait implemented by consumers to hear about record changes in a collection.
+///
+/// Register it with [crate::RemoteSettingsClient::add_change_listener].  Whenever the stored
+/// records change, the listener gets the ids of the created, updated, and deleted records, so
+/// consumers can update just those instead of re-reading the whole collection.  The stored records
+/// change after each sync that passes signature verification, and whenever they're replaced with
+/// packaged data: when the packaged data is newer than the stored records, when the storage is
+/// reset, and when a sync fails signature verification.
+#[uniffi::export(with_foreign)]
+pub trait RemoteSettingsChangeListener: Send + Sync {
+    /// Called after the stored records in `collection_name` changed.
+    ///
+    /// This is called on the thread that changed them, after the changes have been stored, so
+    /// [crate::RemoteSettingsClient::get_records] returns the new records.  This is called even
+    /// if the sync that changed them returns an error.
+    fn on_records_changed(&self, collection_name: String, changes: RemoteSettingsRecordChanges);
+}
+
+/// Listeners registered on a client, with the ids used to remove them.
+#[derive(Default)]
+pub(crate) struct ChangeListeners {
+    next_id: u64,
+    listeners: Vec<(u64, Arc<dyn RemoteSettingsChangeListener>)>,
+}
+
+impl ChangeListeners {
+    pub fn add(&mut self, listener: Arc<dyn RemoteSettingsChangeListener>) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.listeners.push((id, listener));
+        id
+    }
+
+    pub fn remove(&mut self, id: u64) {
+        self.listeners.retain(|(listener_id, _)| *listener_id != id);
+    }
+
+    /// Notifies all the listeners in `listeners` of `changes`, if there are any.
+    ///
+    /// This doesn't hold the lock while calling the listeners, so they can add or remove
+    /// listeners themselves.
+    pub fn notify(
+        listeners: &Mutex<Self>,
+        collection_name: &str,
+        changes: RemoteSettingsRecordChanges,
+    ) {
+        if changes.is_empty() {
+            return;
+        }
+        let listeners: Vec<_> = listeners
+            .lock()
+            .listeners
+            .iter()
+            .map(|(_, listener)| listener.clone())
+            .collect();
+        for listener in listeners {
+            listener.on_records_changed(collection_name.to_string(), changes.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, deleted: bool) -> RemoteSettingsRecord {
+        RemoteSettingsRecord {
+            id: id.into(),
+            last_modified: 100,
+            deleted,
+            attachment: None,
+            fields: serde_json::Map::new(),
+        }
+    }
+
+    fn ids(ids: &[&str]) -> HashSet<String> {
+        ids.iter().map(|id| id.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_changeset() {
+        let changes = RemoteSettingsRecordChanges::from_changeset(
+            &ids(&["a", "b", "c"]),
+            &[
+                record("d", false),
+                record("b", false),
+                record("c", true),
+                record("e", true),
+            ],
+        );
+        assert_eq!(
+            changes,
+            RemoteSettingsRecordChanges {
+                created: vec!["d".into()],
+                updated: vec!["b".into()],
+                deleted: vec!["c".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_between() {
+        let changes =
+            RemoteSettingsRecordChanges::between(&ids(&["a", "b", "c"]), &ids(&["b", "c", "d"]));
+        assert_eq!(
+            changes,
+            RemoteSettingsRecordChanges {
+                created: vec!["d".into()],
+                updated: vec!["b".into(), "c".into()],
+                deleted: vec!["a".into()],
+            }
+        );
+    }
+}
@@ -0,0 +1,231 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use rusqlite::types::Value as SqlValue;
+
+use crate::{Error, Result};
+
+/// A query over a collection's records, used by
+/// [crate::RemoteSettingsClient::get_records_matching].
+///
+/// The predicates are evaluated by SQLite against the cached records, so only matching records
+/// are deserialized.
+#[derive(Clone, Debug, Default, uniffi::Record)]
+pub struct RecordFilter {
+    /// Predicates that a record must all match.
+    pub predicates: Vec<RecordPredicate>,
+    /// A JEXL expression that a record must also match.
+    ///
+    /// This is evaluated in Rust after the predicates, in the same context as the records'
+    /// `filter_expression` fields, with the record's fields available as `record`.  For example:
+    /// `record.minVersion|versionCompare(env.version) <= 0`.
+    #[uniffi(default = None)]
+    pub jexl_expression: Option<String>,
+}
+
+/// A predicate on a record field.
+///
+/// `field` is the name of a top-level field, or a dotted path to a nested one, like
+/// `attachment.filename`.
+#[derive(Clone, Debug, uniffi::Enum)]
+pub enum RecordPredicate {
+    /// The field is equal to `value`.  Numbers only equal numbers, strings only equal strings,
+    /// and so on.
+    Equals {
+        field: String,
+        value: RecordFieldValue,
+    },
+    /// The field is a number or string between `min` and `max`, inclusive.  Either bound can be
+    /// omitted.
+    Range {
+        field: String,
+        min: Option<RecordFieldValue>,
+        max: Option<RecordFieldValue>,
+    },
+    /// The field is an array with an element equal to `value`, or a string containing `value` as
+    /// a substring.
+    Contains {
+        field: String,
+        value: RecordFieldValue,
+    },
+}
+
+/// A JSON scalar to compare record fields against.
+#[derive(Clone, Debug, PartialEq, uniffi::Enum)]
+pub enum RecordFieldValue {
+    Null,
+    Bool { value: bool },
+    Integer { value: i64 },
+    Float { value: f64 },
+    String { value: String },
+}
+
+impl RecordFieldValue {
+    /// Returns an SQL expression that's true if the JSON value with type `type_expr` and value
+    /// `value_expr` equals this value, and pushes its parameters onto `params`.
+    fn sql_equals(&self, type_expr: &str, value_expr: &str, params: &mut Vec<SqlValue>) -> String {
+        match self {
+            Self::Null => format!("{type_expr} = 'null'"),
+            Self::Bool { value: true } => format!("{type_expr} = 'true'"),
+            Self::Bool { value: false } => format!("{type_expr} = 'false'"),
+            Self::Integer { value } => {
+                params.push(SqlValue::Integer(*value));
+                format!("{type_expr} IN ('integer', 'real') AND {value_expr} = ?")
+            }
+            Self::Float { value } => {
+                params.push(SqlValue::Real(*value));
+                format!("{type_expr} IN ('integer', 'real') AND {value_expr} = ?")
+            }
+            Self::String { value } => {
+                params.push(SqlValue::Text(value.clone()));
+                format!("{type_expr} = 'text' AND {value_expr} = ?")
+            }
+        }
+    }
+
+    /// Returns the JSON types this value can be ordered against, and the value as an SQL
+    /// parameter, if it can be used as a range bound.
+    fn range_bound(&self) -> Option<(&'static str, SqlValue)> {
+        match self {
+            Self::Integer { value } => Some(("'integer', 'real'", SqlValue::Integer(*value))),
+            Self::Float { value } => Some(("'integer', 'real'", SqlValue::Real(*value))),
+            Self::String { value } => Some(("'text'", SqlValue::Text(value.clone()))),
+            Self::Null | Self::Bool { .. } => None,
+        }
+    }
+}
+
+impl RecordPredicate {
+    /// Returns an SQL expression over the `data` column of the `records` table that's true if
+    /// the record matches this predicate, and pushes its parameters onto `params`.
+    fn to_sql(&self, params: &mut Vec<SqlValue>) -> Result<String> {
+        Ok(match self {
+            Self::Equals { field, value } => {
+                let path = json_path(field)?;
+                let type_expr = format!("json_type(data, '{path}')");
+                let value_expr = format!("json_extract(data, '{path}')");
+                format!("({})", value.sql_equals(&type_expr, &value_expr, params))
+            }
+            Self::Range { field, min, max } => {
+                let path = json_path(field)?;
+                let mut types = None;
+                let mut clauses = vec![];
+                for (bound, op) in [(min, ">="), (max, "<=")] {
+                    let Some(bound) = bound else {
+                        continue;
+                    };
+                    let (bound_types, param) = bound.range_bound().ok_or_else(|| {
+                        Error::InvalidRecordFilter(format!(
+                            "Range bounds for `{field}` must be numbers or strings"
+                        ))
+                    })?;
+                    if types.is_some_and(|types| types != bound_types) {
+                        return Err(Error::InvalidRecordFilter(format!(
+                            "Range bounds for `{field}` must have the same type"
+                        )));
+                    }
+                    types = Some(bound_types);
+                    params.push(param);
+                    clauses.push(format!("json_extract(data, '{path}') {op} ?"));
+                }
+                match types {
+                    Some(types) => format!(
+                        "(json_type(data, '{path}') IN ({types}) AND {})",
+                        clauses.join(" AND ")
+                    ),
+                    None => format!("(json_type(data, '{path}') IS NOT NULL)"),
+                }
+            }
+            Self::Contains { field, value } => {
+                let path = json_path(field)?;
+                let element_equals = value.sql_equals("e.type", "e.value", params);
+                let mut sql = format!(
+                    "(json_type(data, '{path}') = 'array' AND EXISTS (
+                        SELECT 1 FROM json_each(data, '{path}') e WHERE {element_equals}
+                    ))"
+                );
+                if let RecordFieldValue::String { value } = value {
+                    params.push(SqlValue::Text(value.clone()));
+                    sql = format!(
+                        "({sql} OR (json_type(data, '{path}') = 'text'
+                            AND instr(json_extract(data, '{path}'), ?) > 0))"
+                    );
+                }
+                sql
+            }
+        })
+    }
+}
+
+/// Returns an SQL expression over the `data` column of the `records` table that's true if the
+/// record matches all the predicates, along with its parameters.
+pub(crate) fn predicates_to_sql(predicates: &[RecordPredicate]) -> Result<(String, Vec<SqlValue>)> {
+    let mut params = vec![];
+    let clauses = predicates
+        .iter()
+        .map(|predicate| predicate.to_sql(&mut params))
+        .collect::<Result<Vec<_>>>()?;
+    let sql = if clauses.is_empty() {
+        "1".to_string()
+    } else {
+        clauses.join(" AND ")
+    };
+    Ok((sql, params))
+}
+
+/// Converts a dotted field path to an SQLite JSON path, quoting each key so that keys don't need
+/// escaping.
+fn json_path(field: &str) -> Result<String> {
+    if field.is_empty() || field.split('.').any(|key| key.is_empty()) {
+        return Err(Error::InvalidRecordFilter(format!(
+            "Invalid field path: `{field}`"
+        )));
+    }
+    // Paths are inlined into the SQL, so keys can't contain characters that would end the
+    // quoted key or the SQL string.
+    if field.contains(['"', '\'', '\\']) {
+        return Err(Error::InvalidRecordFilter(format!(
+            "Field paths can't contain quotes or backslashes: `{field}`"
+        )));
+    }
+    Ok(std::iter::once("$".to_string())
+        .chain(field.split('.').map(|key| format!("\"{key}\"")))
+        .collect::<Vec<_>>()
+        .join("."))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_path() {
+        assert_eq!(json_path("type").unwrap(), r#"$."type""#);
+        assert_eq!(
+            json_path("attachment.filename").unwrap(),
+            r#"$."attachment"."filename""#
+        );
+        assert!(json_path("").is_err());
+        assert!(json_path("a..b").is_err());
+        assert!(json_path("a'b").is_err());
+        assert!(json_path("a\"b").is_err());
+    }
+
+    #[test]
+    fn test_invalid_range_bounds() {
+        let predicate = RecordPredicate::Range {
+            field: "enabled".into(),
+            min: Some(RecordFieldValue::Bool { value: true }),
+            max: None,
+        };
+        assert!(predicates_to_sql(&[predicate]).is_err());
+
+        let predicate = RecordPredicate::Range {
+            field: "version".into(),
+            min: Some(RecordFieldValue::Integer { value: 1 }),
+            max: Some(RecordFieldValue::String { value: "2".into() }),
+        };
+        assert!(predicates_to_sql(&[predicate]).is_err());
+    }
+}
@@ -24,7 +24,8 @@ CREATE TABLE IF NOT EXISTS records (
 CREATE TABLE IF NOT EXISTS attachments (
     id TEXT PRIMARY KEY,
     collection_url TEXT NOT NULL,
-    data BLOB NOT NULL);
+    data BLOB NOT NULL,
+    last_accessed INTEGER NOT NULL DEFAULT 0);
 CREATE TABLE IF NOT EXISTS collection_metadata (
     collection_url TEXT PRIMARY KEY,
     last_modified INTEGER, bucket TEXT, signatures TEXT);
@@ -37,7 +38,7 @@ pub struct RemoteSettingsConnectionInitializer;
 
 impl ConnectionInitializer for RemoteSettingsConnectionInitializer {
     const NAME: &'static str = "remote_settings";
-    const END_VERSION: u32 = 4;
+    const END_VERSION: u32 = 5;
 
     fn prepare(&self, conn: &Connection, _db_empty: bool) -> open_database::Result<()> {
         let initial_pragmas = "
@@ -113,6 +114,15 @@ impl ConnectionInitializer for RemoteSettingsConnectionInitializer {
                 )?;
                 Ok(())
             }
+            4 => {
+                // Track when attachments were last used, so that the least recently used ones can
+                // be evicted when the cache goes over its size limit.
+                tx.execute(
+                    "ALTER TABLE attachments ADD COLUMN last_accessed INTEGER NOT NULL DEFAULT 0",
+                    (),
+                )?;
+                Ok(())
+            }
             _ => Err(open_database::Error::IncompatibleVersion(version)),
         }
     }
@@ -241,6 +251,7 @@ PRAGMA user_version=0;
         drop(conn);
 
         db_file.upgrade_to(4);
+        db_file.run_all_upgrades();
         db_file.assert_schema_matches_new_database();
 
         let conn = db_file.open();
@@ -263,4 +274,30 @@ PRAGMA user_version=0;
             "The referenced attachment should survive the migration"
         );
     }
+
+    #[test]
+    fn test_4_to_5_attachments_last_accessed() {
+        let db_file = MigratedDatabaseFile::new(RemoteSettingsConnectionInitializer, V0_SCHEMA);
+        db_file.upgrade_to(4);
+        let conn = db_file.open();
+        conn.execute(
+            "INSERT INTO attachments (id, collection_url, data) VALUES (?, ?, ?)",
+            rusqlite::params!["a.json", "https://example.com/api", b"attachment data"],
+        )
+        .unwrap();
+        drop(conn);
+
+        db_file.upgrade_to(5);
+        db_file.assert_schema_matches_new_database();
+
+        let conn = db_file.open();
+        let last_accessed: i64 = conn
+            .query_row(
+                "SELECT last_accessed FROM attachments WHERE id = 'a.json'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(last_accessed, 0);
+    }
 }
@@ -42,6 +42,9 @@ struct RemoteSettingsServiceConfig {
     base_url: BaseUrl,
     bucket_name: String,
     app_context: Option<RemoteSettingsContext>,
+    /// Dumps directory for `bucket_name`
+    dumps_dir: Option<Utf8PathBuf>,
+    attachment_cache_max_bytes: Option<u64>,
 }
 
 /// Current config and client list
@@ -76,6 +79,7 @@ impl RemoteSettingsService {
             .unwrap_or(RemoteSettingsServer::Prod)
             .get_base_url_with_prod_fallback();
         let bucket_name = config.bucket_name.unwrap_or_else(|| String::from("main"));
+        let dumps_dir = bucket_dumps_dir(config.dumps_directory, &bucket_name);
 
         Self {
             storage_dir,
@@ -85,6 +89,8 @@ impl RemoteSettingsService {
                     base_url,
                     bucket_name,
                     app_context: config.app_context,
+                    dumps_dir,
+                    attachment_cache_max_bytes: config.attachment_cache_max_bytes,
                 },
             }),
             sync_client: Mutex::new(SyncClient {
@@ -187,17 +193,19 @@ impl ClientState {
         collection_name: String,
     ) -> Arc<RemoteSettingsClient> {
         // Allow using in-memory databases for testing of external crates.
-        let storage = if storage_dir == ":memory:" {
+        let mut storage = if storage_dir == ":memory:" {
             Storage::new(storage_dir.to_path_buf())
         } else {
             Storage::new(storage_dir.join(format!("{collection_name}.sql")))
         };
+        storage.set_attachment_cache_max_bytes(self.config.attachment_cache_max_bytes);
 
         let client = Arc::new(RemoteSettingsClient::new(
             self.config.base_url.clone(),
             self.config.bucket_name.clone(),
             collection_name.clone(),
             self.config.app_context.clone(),
+            self.config.dumps_dir.clone(),
             storage,
         ));
         self.clients.push(Arc::downgrade(&client));
@@ -214,17 +222,22 @@ impl ClientState {
             .unwrap_or(RemoteSettingsServer::Prod)
             .get_base_url()?;
         let bucket_name = config.bucket_name.unwrap_or_else(|| String::from("main"));
+        let dumps_dir = bucket_dumps_dir(config.dumps_directory, &bucket_name);
         for client in self.active_clients() {
             client.internal.update_config(
                 base_url.clone(),
                 bucket_name.clone(),
                 config.app_context.clone(),
+                dumps_dir.clone(),
+                config.attachment_cache_max_bytes,
             );
         }
         self.config = RemoteSettingsServiceConfig {
             base_url,
             bucket_name,
             app_context: config.app_context,
+            dumps_dir,
+            attachment_cache_max_bytes: config.attachment_cache_max_bytes,
         };
         Ok(())
     }
@@ -292,6 +305,11 @@ impl SyncClient {
     }
 }
 
+/// Get the dumps directory for a bucket from [RemoteSettingsConfig::dumps_directory]
+fn bucket_dumps_dir(dumps_directory: Option<String>, bucket_name: &str) -> Option<Utf8PathBuf> {
+    dumps_directory.map(|dir| Utf8PathBuf::from(dir).join(bucket_name))
+}
+
 /// Data from the changes endpoint
 ///
 /// https://remote-settings.readthedocs.io/en/latest/client-specifications.html#endpoints
@@ -3,14 +3,17 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use crate::{
-    client::CollectionMetadata, client::CollectionSignature,
-    schema::RemoteSettingsConnectionInitializer, Attachment, Error, RemoteSettingsRecord, Result,
+    client::CollectionMetadata,
+    client::CollectionSignature,
+    record_filter::{predicates_to_sql, RecordPredicate},
+    schema::RemoteSettingsConnectionInitializer,
+    Attachment, Error, RemoteSettingsRecord, Result,
 };
 use camino::Utf8PathBuf;
-use rusqlite::{params, Connection, OpenFlags, OptionalExtension, Transaction};
+use rusqlite::{params, params_from_iter, Connection, OpenFlags, OptionalExtension, Transaction};
 use serde_json;
 use sha2::{Digest, Sha256};
-use std::io;
+use std::{collections::HashSet, io};
 
 use sql_support::{open_database::open_database_with_flags, run_maintenance, ConnExt};
 
@@ -30,6 +33,9 @@ use sql_support::{open_database::open_database_with_flags, run_maintenance, Conn
 pub struct Storage {
     path: Utf8PathBuf,
     conn: ConnectionCell,
+    /// Maximum total size of the cached attachments.  See
+    /// [crate::RemoteSettingsConfig::attachment_cache_max_bytes].
+    attachment_cache_max_bytes: Option<u64>,
 }
 
 impl Storage {
@@ -37,9 +43,17 @@ impl Storage {
         Self {
             path,
             conn: ConnectionCell::Uninitialized,
+            attachment_cache_max_bytes: None,
         }
     }
 
+    /// Limit the total size of the cached attachments
+    ///
+    /// The limit is enforced the next time an attachment is stored.
+    pub fn set_attachment_cache_max_bytes(&mut self, max_bytes: Option<u64>) {
+        self.attachment_cache_max_bytes = max_bytes;
+    }
+
     fn transaction(&mut self) -> Result<Transaction<'_>> {
         match &self.conn {
             ConnectionCell::Uninitialized => {
@@ -126,6 +140,52 @@ impl Storage {
         result
     }
 
+    /// Get the ids of the cached records for this collection
+    pub fn get_record_ids(&mut self, collection_url: &str) -> Result<HashSet<String>> {
+        let tx = self.transaction()?;
+        let ids = tx
+            .prepare("SELECT id FROM records WHERE collection_url = ?")?
+            .query_map(params![collection_url], |row| row.get(0))?
+            .collect::<rusqlite::Result<HashSet<String>>>()?;
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    /// Get cached records for this collection that match all the predicates
+    ///
+    /// The predicates are evaluated by SQLite, so records that don't match aren't deserialized.
+    /// Like [Self::get_records], this returns None if no records are stored for `collection_url`.
+    pub fn get_records_matching(
+        &mut self,
+        collection_url: &str,
+        predicates: &[RecordPredicate],
+    ) -> Result<Option<Vec<RemoteSettingsRecord>>> {
+        let (where_sql, mut params) = predicates_to_sql(predicates)?;
+        let tx = self.transaction()?;
+
+        let fetched = tx.exists(
+            "SELECT 1 FROM collection_metadata WHERE collection_url = ?",
+            (collection_url,),
+        )?;
+        let result = if fetched {
+            params.insert(0, collection_url.to_string().into());
+            let records = tx
+                .prepare(&format!(
+                    "SELECT data FROM records WHERE collection_url = ? AND {where_sql}"
+                ))?
+                .query_and_then(params_from_iter(params), |row| -> Result<_> {
+                    Ok(serde_json::from_slice(&row.get::<_, Vec<u8>>(0)?)?)
+                })?
+                .collect::<Result<Vec<RemoteSettingsRecord>>>()?;
+            Some(records)
+        } else {
+            None
+        };
+
+        tx.commit()?;
+        Ok(result)
+    }
+
     /// Get cached metadata for this collection
     ///
     /// Returns None if no data is stored or if `collection_url` does not match the `collection_url` passed
@@ -183,33 +243,48 @@ impl Storage {
     ///
     /// Returns None if no attachment data is stored or if `collection_url` does not match the `collection_url`
     /// passed to `set_attachment`.
+    ///
+    /// The data is checked against the size and hash in `metadata`.  If it doesn't match, then the
+    /// cached data is corrupt or outdated, so it's deleted and this returns None.
     pub fn get_attachment(
         &mut self,
         collection_url: &str,
         metadata: Attachment,
     ) -> Result<Option<Vec<u8>>> {
         let tx = self.transaction()?;
-        let mut stmt =
-            tx.prepare("SELECT data FROM attachments WHERE id = ? AND collection_url = ?")?;
-
-        if let Some(data) = stmt
-            .query_row((metadata.location, collection_url), |row| {
+        let data = tx
+            .prepare("SELECT data FROM attachments WHERE id = ? AND collection_url = ?")?
+            .query_row((&metadata.location, collection_url), |row| {
                 row.get::<_, Vec<u8>>(0)
             })
-            .optional()?
-        {
-            // Return None if data doesn't match expected metadata
-            if data.len() as u64 != metadata.size {
-                return Ok(None);
-            }
-            let hash = format!("{:x}", Sha256::digest(&data));
-            if hash != metadata.hash {
-                return Ok(None);
-            }
-            Ok(Some(data))
+            .optional()?;
+        let Some(data) = data else {
+            return Ok(None);
+        };
+
+        let is_valid = data.len() as u64 == metadata.size
+            && format!("{:x}", Sha256::digest(&data)) == metadata.hash;
+        if is_valid {
+            Self::mark_attachment_accessed(&tx, &metadata.location)?;
         } else {
-            Ok(None)
+            tx.execute(
+                "DELETE FROM attachments WHERE id = ?",
+                params![metadata.location],
+            )?;
         }
+        tx.commit()?;
+        Ok(is_valid.then_some(data))
+    }
+
+    /// Get the total size of the cached attachments for this collection, in bytes
+    pub fn get_attachment_cache_size(&mut self, collection_url: &str) -> Result<u64> {
+        let tx = self.transaction()?;
+        let size = tx.query_row(
+            "SELECT COALESCE(SUM(length(data)), 0) FROM attachments WHERE collection_url = ?",
+            params![collection_url],
+            |row| row.get(0),
+        )?;
+        Ok(size)
     }
 
     /// Set cached content for this collection.
@@ -296,12 +371,17 @@ impl Storage {
     }
 
     /// Set the attachment data stored in the database, clearing out any previously stored data
+    ///
+    /// If this puts the cache over [Self::set_attachment_cache_max_bytes], then the least recently
+    /// used attachments are evicted.  Attachments that are larger than the limit by themselves
+    /// aren't stored.
     pub fn set_attachment(
         &mut self,
         collection_url: &str,
         location: &str,
         attachment: &[u8],
     ) -> Result<()> {
+        let max_bytes = self.attachment_cache_max_bytes;
         let tx = self.transaction()?;
 
         // Delete ALL existing attachments for every collection_url
@@ -310,18 +390,64 @@ impl Storage {
             params![collection_url],
         )?;
 
-        tx.execute(
-            "INSERT OR REPLACE INTO ATTACHMENTS \
-            (id, collection_url, data) \
-            VALUES (?, ?, ?)",
-            params![location, collection_url, attachment,],
-        )?;
+        if max_bytes.is_some_and(|max_bytes| attachment.len() as u64 > max_bytes) {
+            // Remove any stale data for this location, since we're not replacing it.
+            tx.execute("DELETE FROM attachments WHERE id = ?", params![location])?;
+        } else {
+            tx.execute(
+                "INSERT OR REPLACE INTO ATTACHMENTS \
+                (id, collection_url, data) \
+                VALUES (?, ?, ?)",
+                params![location, collection_url, attachment,],
+            )?;
+            Self::mark_attachment_accessed(&tx, location)?;
+        }
+        if let Some(max_bytes) = max_bytes {
+            Self::evict_attachments(&tx, max_bytes)?;
+        }
 
         tx.commit()?;
 
         Ok(())
     }
 
+    /// Move an attachment to the end of the eviction order
+    ///
+    /// `last_accessed` is a counter rather than a timestamp, so that accesses are strictly
+    /// ordered.
+    fn mark_attachment_accessed(tx: &Transaction<'_>, location: &str) -> Result<()> {
+        tx.execute(
+            "UPDATE attachments
+             SET last_accessed = (SELECT COALESCE(MAX(last_accessed), 0) + 1 FROM attachments)
+             WHERE id = ?",
+            params![location],
+        )?;
+        Ok(())
+    }
+
+    /// Evict the least recently used attachments until their total size is at most `max_bytes`
+    fn evict_attachments(tx: &Transaction<'_>, max_bytes: u64) -> Result<()> {
+        // Keep the most recently used attachments whose running total fits in the limit, and
+        // delete the rest.
+        tx.execute(
+            "DELETE FROM attachments
+             WHERE id IN (
+                 SELECT id FROM (
+                     SELECT
+                         id,
+                         SUM(length(data)) OVER (
+                             ORDER BY last_accessed DESC, id
+                             ROWS UNBOUNDED PRECEDING
+                         ) AS total_size
+                     FROM attachments
+                 )
+                 WHERE total_size > ?
+             )",
+            params![max_bytes],
+        )?;
+        Ok(())
+    }
+
     /// Empty out all cached values and start from scratch.  This is called when
     /// RemoteSettingsService::update_config() is called, since that could change the remote
     /// settings server which would invalidate all cached data.
@@ -371,8 +497,8 @@ enum ConnectionCell {
 mod tests {
     use super::Storage;
     use crate::{
-        client::CollectionMetadata, client::CollectionSignature, Attachment, RemoteSettingsRecord,
-        Result, RsJsonObject,
+        client::CollectionMetadata, client::CollectionSignature, Attachment, RecordFieldValue,
+        RecordPredicate, RemoteSettingsRecord, Result, RsJsonObject,
     };
     use sha2::{Digest, Sha256};
 
@@ -470,6 +596,191 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_storage_get_records_matching() -> Result<()> {
+        let mut storage = Storage::new(":memory:".into());
+
+        let collection_url = "https://example.com/api";
+        let make_record = |id: &str, fields: serde_json::Value| RemoteSettingsRecord {
+            id: id.to_string(),
+            last_modified: 100,
+            deleted: false,
+            attachment: None,
+            fields: fields.as_object().unwrap().clone(),
+        };
+        let records = vec![
+            make_record(
+                "1",
+                serde_json::json!({
+                    "type": "icon",
+                    "version": 1,
+                    "tags": ["a", "b"],
+                    "name": "first icon",
+                    "meta": {"enabled": true},
+                }),
+            ),
+            make_record(
+                "2",
+                serde_json::json!({
+                    "type": "icon",
+                    "version": 2.5,
+                    "tags": ["b", "c"],
+                    "name": "second",
+                    "meta": {"enabled": false},
+                }),
+            ),
+            make_record(
+                "3",
+                serde_json::json!({
+                    "type": "config",
+                    "version": "3",
+                    "tags": [],
+                    "name": null,
+                }),
+            ),
+        ];
+
+        assert_eq!(
+            storage.get_records_matching(
+                collection_url,
+                &[RecordPredicate::Equals {
+                    field: "type".into(),
+                    value: RecordFieldValue::String {
+                        value: "icon".into()
+                    },
+                }]
+            )?,
+            None
+        );
+
+        storage.insert_collection_content(
+            collection_url,
+            &records,
+            300,
+            CollectionMetadata::default(),
+        )?;
+
+        let matching_ids = |storage: &mut Storage, predicates: &[RecordPredicate]| {
+            let mut ids: Vec<String> = storage
+                .get_records_matching(collection_url, predicates)
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .map(|r| r.id)
+                .collect();
+            ids.sort();
+            ids
+        };
+
+        assert_eq!(matching_ids(&mut storage, &[]), vec!["1", "2", "3"]);
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[RecordPredicate::Equals {
+                    field: "type".into(),
+                    value: RecordFieldValue::String {
+                        value: "icon".into()
+                    },
+                }]
+            ),
+            vec!["1", "2"]
+        );
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[RecordPredicate::Equals {
+                    field: "meta.enabled".into(),
+                    value: RecordFieldValue::Bool { value: true },
+                }]
+            ),
+            vec!["1"]
+        );
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[RecordPredicate::Equals {
+                    field: "name".into(),
+                    value: RecordFieldValue::Null,
+                }]
+            ),
+            vec!["3"]
+        );
+        // Numbers don't equal strings.
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[RecordPredicate::Equals {
+                    field: "version".into(),
+                    value: RecordFieldValue::Integer { value: 3 },
+                }]
+            ),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[RecordPredicate::Range {
+                    field: "version".into(),
+                    min: Some(RecordFieldValue::Float { value: 1.5 }),
+                    max: None,
+                }]
+            ),
+            vec!["2"]
+        );
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[RecordPredicate::Range {
+                    field: "version".into(),
+                    min: Some(RecordFieldValue::Integer { value: 1 }),
+                    max: Some(RecordFieldValue::Integer { value: 2 }),
+                }]
+            ),
+            vec!["1"]
+        );
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[RecordPredicate::Contains {
+                    field: "tags".into(),
+                    value: RecordFieldValue::String { value: "b".into() },
+                }]
+            ),
+            vec!["1", "2"]
+        );
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[RecordPredicate::Contains {
+                    field: "name".into(),
+                    value: RecordFieldValue::String {
+                        value: "icon".into()
+                    },
+                }]
+            ),
+            vec!["1"]
+        );
+        // Predicates are combined with AND.
+        assert_eq!(
+            matching_ids(
+                &mut storage,
+                &[
+                    RecordPredicate::Contains {
+                        field: "tags".into(),
+                        value: RecordFieldValue::String { value: "b".into() },
+                    },
+                    RecordPredicate::Contains {
+                        field: "tags".into(),
+                        value: RecordFieldValue::String { value: "c".into() },
+                    },
+                ]
+            ),
+            vec!["2"]
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn test_storage_set_and_get_attachment() -> Result<()> {
         let mut storage = Storage::new(":memory:".into());
@@ -596,6 +907,68 @@ mod tests {
         Ok(())
     }
 
+    fn attachment_metadata(location: &str, data: &[u8]) -> Attachment {
+        Attachment {
+            filename: location.to_string(),
+            mimetype: "application/json".to_string(),
+            location: location.to_string(),
+            hash: format!("{:x}", Sha256::digest(data)),
+            size: data.len() as u64,
+        }
+    }
+
+    #[test]
+    fn test_storage_attachment_cache_eviction() -> Result<()> {
+        let mut storage = Storage::new(":memory:".into());
+        storage.set_attachment_cache_max_bytes(Some(10));
+        let collection_url = "https://example.com/api";
+
+        storage.set_attachment(collection_url, "a", &[1; 4])?;
+        storage.set_attachment(collection_url, "b", &[2; 4])?;
+        assert_eq!(storage.get_attachment_cache_size(collection_url)?, 8);
+
+        // Reading `a` makes `b` the least recently used attachment, so `b` should be evicted to
+        // make room for `c`.
+        assert!(storage
+            .get_attachment(collection_url, attachment_metadata("a", &[1; 4]))?
+            .is_some());
+        storage.set_attachment(collection_url, "c", &[3; 4])?;
+        assert_eq!(storage.get_attachment_cache_size(collection_url)?, 8);
+        assert!(storage
+            .get_attachment(collection_url, attachment_metadata("a", &[1; 4]))?
+            .is_some());
+        assert!(storage
+            .get_attachment(collection_url, attachment_metadata("b", &[2; 4]))?
+            .is_none());
+        assert!(storage
+            .get_attachment(collection_url, attachment_metadata("c", &[3; 4]))?
+            .is_some());
+
+        // Attachments larger than the limit aren't stored, and don't evict anything.
+        storage.set_attachment(collection_url, "d", &[4; 11])?;
+        assert!(storage
+            .get_attachment(collection_url, attachment_metadata("d", &[4; 11]))?
+            .is_none());
+        assert_eq!(storage.get_attachment_cache_size(collection_url)?, 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_storage_corrupt_attachment_deleted() -> Result<()> {
+        let mut storage = Storage::new(":memory:".into());
+        let collection_url = "https://example.com/api";
+
+        storage.set_attachment(collection_url, "a", &[1; 4])?;
+        // Same size, different hash
+        assert!(storage
+            .get_attachment(collection_url, attachment_metadata("a", &[2; 4]))?
+            .is_none());
+        assert_eq!(storage.get_attachment_cache_size(collection_url)?, 0);
+
+        Ok(())
+    }
+
     /// Test that orphaned attachments are cleaned up when a record's attachment location changes.
     /// This reproduces the 1.1GB bloat observed in production. The `quicksuggest-amp` collection
     /// has records like:
@@ -876,6 +876,8 @@ mod tests {
             }),
             bucket_name: Some(String::from("main")),
             app_context: Some(RemoteSettingsContext::default()),
+            dumps_directory: None,
+            attachment_cache_max_bytes: None,
         };
         let service = Arc::new(RemoteSettingsService::new(String::from(":memory:"), config));
 
@@ -33,6 +33,8 @@ impl RemoteSettingsBenchmarkClient {
                 server: None,
                 bucket_name: None,
                 app_context: Some(RemoteSettingsContext::default()),
+                dumps_directory: None,
+                attachment_cache_max_bytes: None,
             },
         );
         let client = service.make_client(collection.name().to_string());
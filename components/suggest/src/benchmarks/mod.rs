@@ -92,6 +92,8 @@ fn new_store() -> SuggestStore {
             bucket_name: None,
             server: None,
             app_context: Some(RemoteSettingsContext::default()),
+            dumps_directory: None,
+            attachment_cache_max_bytes: None,
         };
         let remote_settings_service = Arc::new(RemoteSettingsService::new(
             remote_settings_dir.to_string_lossy().to_string(),
@@ -110,6 +112,8 @@ fn new_store() -> SuggestStore {
         bucket_name: None,
         server: None,
         app_context: Some(RemoteSettingsContext::default()),
+        dumps_directory: None,
+        attachment_cache_max_bytes: None,
     };
     let remote_settings_service = Arc::new(RemoteSettingsService::new("".to_string(), rs_config));
     std::fs::copy(starter_db_path, &db_path).expect("Error copying starter DB file");
@@ -5,18 +5,43 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::rs::DownloadedGlobalConfig;
+use crate::{
+    frequency_cap::SuggestFrequencyCap, provider::SuggestionProvider, rs::DownloadedGlobalConfig,
+};
 
 /// Global Suggest configuration data.
 #[derive(Clone, Default, Debug, Deserialize, Serialize, PartialEq, Eq, uniffi::Record)]
 pub struct SuggestGlobalConfig {
     pub show_less_frequently_cap: i32,
+    /// Impression and click caps for providers that have them.
+    #[serde(default)]
+    pub frequency_caps: Vec<SuggestFrequencyCap>,
+}
+
+impl SuggestGlobalConfig {
+    /// Returns the frequency cap for a provider, if it has one.
+    pub(crate) fn frequency_cap(
+        &self,
+        provider: SuggestionProvider,
+    ) -> Option<&SuggestFrequencyCap> {
+        self.frequency_caps
+            .iter()
+            .find(|cap| cap.provider == provider)
+    }
 }
 
 impl From<&DownloadedGlobalConfig> for SuggestGlobalConfig {
     fn from(config: &DownloadedGlobalConfig) -> Self {
         Self {
             show_less_frequently_cap: config.configuration.show_less_frequently_cap,
+            // Skip caps we can't parse, like ones for providers this version
+            // doesn't know about, rather than dropping the whole config.
+            frequency_caps: config
+                .configuration
+                .frequency_caps
+                .iter()
+                .filter_map(|cap| serde_json::from_value(cap.clone()).ok())
+                .collect(),
         }
     }
 }
@@ -3,7 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/.
  */
 
-use std::{cell::OnceCell, path::Path, sync::Arc};
+use std::{borrow::Cow, cell::OnceCell, path::Path, sync::Arc};
 
 use interrupt_support::{SqlInterruptHandle, SqlInterruptScope};
 use parking_lot::{Mutex, MutexGuard};
@@ -17,9 +17,15 @@ use sql_support::{open_database, repeat_sql_vars, ConnExt};
 use crate::{
     config::{SuggestGlobalConfig, SuggestProviderConfig},
     error::RusqliteResultExt,
+    frequency_cap::{
+        InteractionKind, SuggestFrequencyCap, SuggestInteractionCounts, DAY_SECONDS, HOUR_SECONDS,
+    },
     geoname::GeonameCache,
     provider::{AmpMatchingStrategy, SuggestionProvider},
-    query::{full_keywords_to_fts_content, FtsQuery},
+    query::{
+        full_keywords_to_fts_content, typo_candidates, FtsQuery, SuggestionExplanation,
+        SuggestionMatchType,
+    },
     rs::{
         DownloadedAmoSuggestion, DownloadedAmpSuggestion, DownloadedDynamicRecord,
         DownloadedDynamicSuggestion, DownloadedMdnSuggestion, DownloadedWikipediaSuggestion,
@@ -27,7 +33,7 @@ use crate::{
     },
     schema::{clear_database, SuggestConnectionInitializer},
     suggestion::{cook_raw_suggestion_url, FtsMatchInfo, Suggestion},
-    util::{full_keyword, i18n_transform, split_keyword},
+    util::{full_keyword, i18n_transform, split_keyword, KeywordNormalization},
     weather::WeatherCache,
     Result, SuggestionQuery,
 };
@@ -39,6 +45,11 @@ pub const GLOBAL_CONFIG_META_KEY: &str = "global_config";
 /// `SuggestProviderConfig`, which contains per-provider configuration data. The
 /// full key is this prefix plus the `SuggestionProvider` value as a u8.
 pub const PROVIDER_CONFIG_META_KEY_PREFIX: &str = "provider_config_";
+/// Prefix of metadata keys whose values are JSON strings encoding the
+/// `KeywordNormalization` that a provider's keywords were ingested with. The
+/// full key is this prefix plus the `SuggestionProvider` value as a u8.
+/// Providers without a key use `KeywordNormalization::Lowercase`.
+pub const KEYWORD_NORMALIZATION_META_KEY_PREFIX: &str = "keyword_normalization_";
 
 // Default value when Suggestion does not have a value for score
 pub const DEFAULT_SUGGESTION_SCORE: f64 = 0.2;
@@ -170,6 +181,9 @@ pub(crate) struct SuggestDao<'a> {
     pub scope: &'a SqlInterruptScope,
     pub weather_cache: OnceCell<WeatherCache>,
     pub geoname_cache: OnceCell<GeonameCache>,
+    /// Whether fetches should return dismissed suggestions. This is only set
+    /// by [Self::including_dismissed].
+    include_dismissed: bool,
 }
 
 impl<'a> SuggestDao<'a> {
@@ -179,6 +193,27 @@ impl<'a> SuggestDao<'a> {
             scope,
             weather_cache: std::cell::OnceCell::new(),
             geoname_cache: std::cell::OnceCell::new(),
+            include_dismissed: false,
+        }
+    }
+
+    /// Returns a DAO on the same connection whose fetches don't filter out
+    /// dismissed suggestions. This is used to explain which suggestions a
+    /// query filtered out without changing the dismissals.
+    pub fn including_dismissed(&self) -> SuggestDao<'a> {
+        SuggestDao {
+            include_dismissed: true,
+            ..SuggestDao::new(self.conn, self.scope)
+        }
+    }
+
+    /// Returns `filter`, an SQL condition that excludes dismissed suggestions,
+    /// or an empty condition if this DAO includes them.
+    fn dismissal_filter<'f>(&self, filter: &'f str) -> &'f str {
+        if self.include_dismissed {
+            ""
+        } else {
+            filter
         }
     }
 
@@ -252,6 +287,23 @@ impl<'a> SuggestDao<'a> {
             .conn_ext_query_one::<bool>("SELECT NOT EXISTS (SELECT 1 FROM suggestions)")?)
     }
 
+    /// Fetches a provider's suggestions that match the given query
+    pub fn fetch_provider_suggestions(
+        &self,
+        provider: SuggestionProvider,
+        query: &SuggestionQuery,
+    ) -> Result<Vec<Suggestion>> {
+        match provider {
+            SuggestionProvider::Amp => self.fetch_amp_suggestions(query),
+            SuggestionProvider::Wikipedia => self.fetch_wikipedia_suggestions(query),
+            SuggestionProvider::Amo => self.fetch_amo_suggestions(query),
+            SuggestionProvider::Yelp => self.fetch_yelp_suggestions(query),
+            SuggestionProvider::Mdn => self.fetch_mdn_suggestions(query),
+            SuggestionProvider::Weather => self.fetch_weather_suggestions(query),
+            SuggestionProvider::Dynamic => self.fetch_dynamic_suggestions(query),
+        }
+    }
+
     /// Fetches Suggestions of type Amp provider that match the given query
     pub fn fetch_amp_suggestions(&self, query: &SuggestionQuery) -> Result<Vec<Suggestion>> {
         let strategy = query
@@ -277,12 +329,20 @@ impl<'a> SuggestDao<'a> {
         query: &SuggestionQuery,
         allow_keyword_expansion: bool,
     ) -> Result<Vec<Suggestion>> {
-        let keyword_lowercased = &query.keyword.to_lowercase();
+        let normalized_keyword = &self.normalize_query_keyword(SuggestionProvider::Amp, query)?;
         let where_extra = if allow_keyword_expansion {
             ""
         } else {
             "AND INSTR(CONCAT(fk.full_keyword, ' '), k.keyword) != 0"
         };
+        let dismissal_filter = self.dismissal_filter(
+            "AND NOT EXISTS (
+               -- For AMP suggestions dismissed with the deprecated URL-based dismissal API,
+               -- `dismissed_suggestions.url` will be the suggestion URL. With the new
+               -- `Suggestion`-based API, it will be the full keyword.
+               SELECT 1 FROM dismissed_suggestions WHERE url IN (fk.full_keyword, s.url)
+             )",
+        );
         let suggestions = self.conn.query_rows_and_then_cached(
             &format!(
                 r#"
@@ -306,16 +366,11 @@ impl<'a> SuggestDao<'a> {
                   s.provider = :provider
                   AND k.keyword = :keyword
                   {where_extra}
-                  AND NOT EXISTS (
-                    -- For AMP suggestions dismissed with the deprecated URL-based dismissal API,
-                    -- `dismissed_suggestions.url` will be the suggestion URL. With the new
-                    -- `Suggestion`-based API, it will be the full keyword.
-                    SELECT 1 FROM dismissed_suggestions WHERE url IN (fk.full_keyword, s.url)
-                  )
+                  {dismissal_filter}
                 "#
             ),
             named_params! {
-                ":keyword": keyword_lowercased,
+                ":keyword": normalized_keyword,
                 ":provider": SuggestionProvider::Amp,
             },
             |row| -> Result<Suggestion> {
@@ -373,6 +428,17 @@ impl<'a> SuggestDao<'a> {
                 )
             },
         )?;
+        if suggestions.is_empty() {
+            if let Some(corrected) = self.typo_corrected_query(query, |keyword| {
+                self.keyword_exists(SuggestionProvider::Amp, keyword)
+            })? {
+                return Ok(self
+                    .fetch_amp_suggestions_using_keywords(&corrected, allow_keyword_expansion)?
+                    .into_iter()
+                    .map(Suggestion::with_typo_correction)
+                    .collect());
+            }
+        }
         Ok(suggestions)
     }
 
@@ -383,6 +449,9 @@ impl<'a> SuggestDao<'a> {
     ) -> Result<Vec<Suggestion>> {
         let fts_query = query.fts_query();
         let match_arg = &fts_query.match_arg;
+        let dismissal_filter = self.dismissal_filter(
+            "AND NOT EXISTS (SELECT 1 FROM dismissed_suggestions WHERE url=s.url)",
+        );
         let suggestions = self.conn.query_rows_and_then_cached(
             &format!(
                 r#"
@@ -400,7 +469,7 @@ impl<'a> SuggestDao<'a> {
                 WHERE
                   s.provider = :provider
                   AND amp_fts match '{fts_column}: {match_arg}'
-                AND NOT EXISTS (SELECT 1 FROM dismissed_suggestions WHERE url=s.url)
+                  {dismissal_filter}
                 ORDER BY rank
                 LIMIT 1
                 "#
@@ -511,6 +580,7 @@ impl<'a> SuggestDao<'a> {
         Ok(FtsMatchInfo {
             prefix,
             stemming: fts_query.match_required_stemming(&fts_content),
+            typo: false,
         })
     }
 
@@ -540,43 +610,61 @@ impl<'a> SuggestDao<'a> {
 
     /// Fetches Suggestions of type Wikipedia provider that match the given query
     pub fn fetch_wikipedia_suggestions(&self, query: &SuggestionQuery) -> Result<Vec<Suggestion>> {
-        let keyword_lowercased = &query.keyword.to_lowercase();
+        let normalized_keyword =
+            &self.normalize_query_keyword(SuggestionProvider::Wikipedia, query)?;
+        let dismissal_filter = self.dismissal_filter(
+            "AND NOT EXISTS (SELECT 1 FROM dismissed_suggestions WHERE url=s.url)",
+        );
         let suggestions = self.conn.query_rows_and_then_cached(
-            r#"
-            SELECT
-              s.id,
-              k.rank,
-              s.title,
-              s.url
-            FROM
-              suggestions s
-            JOIN
-              keywords k
-              ON k.suggestion_id = s.id
-            WHERE
-              s.provider = :provider
-              AND k.keyword = :keyword
-              AND NOT EXISTS (SELECT 1 FROM dismissed_suggestions WHERE url=s.url)
-            "#,
+            &format!(
+                r#"
+                SELECT
+                  s.id,
+                  k.rank,
+                  s.title,
+                  s.url,
+                  fk.full_keyword
+                FROM
+                  suggestions s
+                JOIN
+                  keywords k
+                  ON k.suggestion_id = s.id
+                LEFT JOIN
+                  full_keywords fk
+                  ON k.full_keyword_id = fk.id
+                WHERE
+                  s.provider = :provider
+                  AND k.keyword = :keyword
+                  {dismissal_filter}
+                "#
+            ),
             named_params! {
-                ":keyword": keyword_lowercased,
+                ":keyword": normalized_keyword,
                 ":provider": SuggestionProvider::Wikipedia
             },
             |row| -> Result<Suggestion> {
                 let suggestion_id: i64 = row.get("id")?;
                 let title = row.get("title")?;
                 let raw_url = row.get::<_, String>("url")?;
-
-                let keywords: Vec<String> = self.conn.query_rows_and_then_cached(
-                    "SELECT keyword FROM keywords
-                     WHERE suggestion_id = :suggestion_id AND rank >= :rank
-                     ORDER BY rank ASC",
-                    named_params! {
-                        ":suggestion_id": suggestion_id,
-                        ":rank": row.get::<_, i64>("rank")?,
-                    },
-                    |row| row.get(0),
-                )?;
+                // Keywords ingested with a non-default normalization keep
+                // their original text as the full keyword, since the stored
+                // keyword isn't fit to show.
+                let full_keyword = match row.get::<_, Option<String>>("full_keyword")? {
+                    Some(original_keyword) => original_keyword,
+                    None => {
+                        let keywords: Vec<String> = self.conn.query_rows_and_then_cached(
+                            "SELECT keyword FROM keywords
+                             WHERE suggestion_id = :suggestion_id AND rank >= :rank
+                             ORDER BY rank ASC",
+                            named_params! {
+                                ":suggestion_id": suggestion_id,
+                                ":rank": row.get::<_, i64>("rank")?,
+                            },
+                            |row| row.get(0),
+                        )?;
+                        full_keyword(normalized_keyword, &keywords)
+                    }
+                };
                 let (icon, icon_mimetype) = self
                     .conn
                     .try_query_row(
@@ -601,15 +689,262 @@ impl<'a> SuggestDao<'a> {
                 Ok(Suggestion::Wikipedia {
                     title,
                     url: raw_url,
-                    full_keyword: full_keyword(keyword_lowercased, &keywords),
+                    full_keyword,
                     icon,
                     icon_mimetype,
+                    fts_match_info: None,
                 })
             },
         )?;
+        if suggestions.is_empty() {
+            if let Some(corrected) = self.typo_corrected_query(query, |keyword| {
+                self.keyword_exists(SuggestionProvider::Wikipedia, keyword)
+            })? {
+                return Ok(self
+                    .fetch_wikipedia_suggestions(&corrected)?
+                    .into_iter()
+                    .map(Suggestion::with_typo_correction)
+                    .collect());
+            }
+        }
         Ok(suggestions)
     }
 
+    /// Returns whether any of the provider's suggestions have the keyword.
+    fn keyword_exists(&self, provider: SuggestionProvider, keyword: &str) -> Result<bool> {
+        let keyword = self.get_keyword_normalization(provider)?.normalize(keyword);
+        Ok(self
+            .conn
+            .try_query_one::<i64, _>(
+                "SELECT 1
+                 FROM keywords k
+                 JOIN suggestions s ON s.id = k.suggestion_id
+                 WHERE k.keyword = :keyword AND s.provider = :provider
+                 LIMIT 1",
+                named_params! {
+                    ":keyword": keyword,
+                    ":provider": provider,
+                },
+                true,
+            )?
+            .is_some())
+    }
+
+    /// Returns whether any of the provider's suggestions have a prefix keyword
+    /// starting with `keyword`.
+    fn prefix_keyword_exists(&self, provider: SuggestionProvider, keyword: &str) -> Result<bool> {
+        let keyword = self.get_keyword_normalization(provider)?.normalize(keyword);
+        let (keyword_prefix, keyword_suffix) = split_keyword(&keyword);
+        Ok(self
+            .conn
+            .try_query_one::<i64, _>(
+                "SELECT 1
+                 FROM prefix_keywords k
+                 JOIN suggestions s ON s.id = k.suggestion_id
+                 WHERE k.keyword_prefix = :keyword_prefix
+                   AND (k.keyword_suffix BETWEEN :keyword_suffix AND :keyword_suffix || x'FFFF')
+                   AND s.provider = :provider
+                 LIMIT 1",
+                named_params! {
+                    ":keyword_prefix": keyword_prefix,
+                    ":keyword_suffix": keyword_suffix,
+                    ":provider": provider,
+                },
+                true,
+            )?
+            .is_some())
+    }
+
+    /// Explains how a suggestion returned for `query` matched it.
+    pub fn explain_match(
+        &self,
+        query: &SuggestionQuery,
+        suggestion: &Suggestion,
+    ) -> Result<SuggestionExplanation> {
+        let provider = suggestion.provider();
+        let fts_match_info = suggestion.fts_match_info();
+        let typo = fts_match_info.is_some_and(|info| info.typo);
+        let uses_fts = provider == SuggestionProvider::Amp
+            && query
+                .provider_constraints
+                .as_ref()
+                .and_then(|c| c.amp_alternative_matching.as_ref())
+                .is_some_and(|strategy| strategy.uses_fts());
+
+        // Find the keyword row that matched, looking for the typo correction
+        // that the fetch used if the keyword itself didn't match.
+        let lookup = |keyword: &str| -> Result<Option<(String, i64)>> {
+            let keyword = self.get_keyword_normalization(provider)?.normalize(keyword);
+            match (provider, suggestion.raw_url()) {
+                (SuggestionProvider::Amp | SuggestionProvider::Wikipedia, Some(url)) => {
+                    self.keyword_row(provider, url, &keyword)
+                }
+                (SuggestionProvider::Amo | SuggestionProvider::Mdn, Some(url)) => {
+                    self.prefix_keyword_row(provider, url, &keyword)
+                }
+                _ => Ok(None),
+            }
+        };
+        let matched = if uses_fts {
+            None
+        } else if typo {
+            self.find_typo_correction(&query.keyword, lookup)?
+                .map(|(_, found)| found)
+        } else {
+            lookup(&query.keyword)?
+        };
+
+        let normalized_keyword = self.normalize_query_keyword(provider, query)?;
+        let match_type = match (suggestion, fts_match_info) {
+            (_, Some(info)) if info.typo => SuggestionMatchType::Typo,
+            (_, Some(info)) if info.stemming => SuggestionMatchType::Stemmed,
+            _ if uses_fts => SuggestionMatchType::Fts,
+            (_, Some(info)) if info.prefix => SuggestionMatchType::Prefix,
+            (
+                Suggestion::Amp { full_keyword, .. } | Suggestion::Wikipedia { full_keyword, .. },
+                _,
+            ) => {
+                let full_keyword = self
+                    .get_keyword_normalization(provider)?
+                    .normalize(full_keyword)
+                    .into_owned();
+                if matched.as_ref().map(|(keyword, _)| keyword) == Some(&full_keyword) {
+                    SuggestionMatchType::Exact
+                } else {
+                    SuggestionMatchType::Prefix
+                }
+            }
+            (Suggestion::Amo { .. } | Suggestion::Mdn { .. }, _) => {
+                if matched.as_ref().map(|(keyword, _)| keyword) == Some(&normalized_keyword) {
+                    SuggestionMatchType::Exact
+                } else {
+                    SuggestionMatchType::Prefix
+                }
+            }
+            (
+                Suggestion::Yelp {
+                    subject_exact_match: false,
+                    ..
+                },
+                _,
+            ) => SuggestionMatchType::Prefix,
+            _ => SuggestionMatchType::Exact,
+        };
+
+        let (matched_keyword, keyword_rank) = matched.unzip();
+        Ok(SuggestionExplanation {
+            suggestion: suggestion.clone(),
+            provider,
+            matched_keyword,
+            match_type,
+            score: suggestion.score(),
+            keyword_rank,
+        })
+    }
+
+    /// Returns the keyword row of the provider's suggestion with `url` that
+    /// equals `keyword`, along with its rank.
+    fn keyword_row(
+        &self,
+        provider: SuggestionProvider,
+        url: &str,
+        keyword: &str,
+    ) -> Result<Option<(String, i64)>> {
+        self.conn.try_query_row(
+            "SELECT k.keyword, k.rank
+             FROM keywords k
+             JOIN suggestions s ON s.id = k.suggestion_id
+             WHERE s.provider = :provider AND s.url = :url AND k.keyword = :keyword
+             LIMIT 1",
+            named_params! {
+                ":provider": provider,
+                ":url": url,
+                ":keyword": keyword,
+            },
+            |row| -> Result<_> { Ok((row.get(0)?, row.get(1)?)) },
+            true,
+        )
+    }
+
+    /// Returns the highest-ranked prefix keyword row of the provider's
+    /// suggestion with `url` that starts with `keyword`, joined back into one
+    /// keyword, along with its rank.
+    fn prefix_keyword_row(
+        &self,
+        provider: SuggestionProvider,
+        url: &str,
+        keyword: &str,
+    ) -> Result<Option<(String, i64)>> {
+        let (keyword_prefix, keyword_suffix) = split_keyword(keyword);
+        self.conn.try_query_row(
+            "SELECT k.keyword_prefix, k.keyword_suffix, k.rank
+             FROM prefix_keywords k
+             JOIN suggestions s ON s.id = k.suggestion_id
+             WHERE s.provider = :provider
+               AND s.url = :url
+               AND k.keyword_prefix = :keyword_prefix
+               AND (k.keyword_suffix BETWEEN :keyword_suffix AND :keyword_suffix || x'FFFF')
+             ORDER BY k.rank DESC
+             LIMIT 1",
+            named_params! {
+                ":provider": provider,
+                ":url": url,
+                ":keyword_prefix": keyword_prefix,
+                ":keyword_suffix": keyword_suffix,
+            },
+            |row| -> Result<_> {
+                let prefix: String = row.get(0)?;
+                let suffix: String = row.get(1)?;
+                let keyword = if suffix.is_empty() {
+                    prefix
+                } else {
+                    format!("{prefix} {suffix}")
+                };
+                Ok((keyword, row.get(2)?))
+            },
+            true,
+        )
+    }
+
+    /// Returns the first typo correction of `keyword` for which `lookup`
+    /// returns `Some`, along with what it returned. Since there are at most
+    /// `MAX_TYPO_CANDIDATES` corrections, this bounds the lookups.
+    pub(crate) fn find_typo_correction<T>(
+        &self,
+        keyword: &str,
+        mut lookup: impl FnMut(&str) -> Result<Option<T>>,
+    ) -> Result<Option<(String, T)>> {
+        for candidate in typo_candidates(&keyword.to_lowercase()) {
+            self.scope.err_if_interrupted()?;
+            if let Some(found) = lookup(&candidate)? {
+                return Ok(Some((candidate, found)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// If the query allows typos, returns a copy of it with the first typo
+    /// correction of its keyword that `matches`.
+    fn typo_corrected_query(
+        &self,
+        query: &SuggestionQuery,
+        mut matches: impl FnMut(&str) -> Result<bool>,
+    ) -> Result<Option<SuggestionQuery>> {
+        if !query.allow_typos {
+            return Ok(None);
+        }
+        Ok(self
+            .find_typo_correction(&query.keyword, |candidate| {
+                Ok(matches(candidate)?.then_some(()))
+            })?
+            .map(|(keyword, ())| SuggestionQuery {
+                keyword,
+                // Only correct one typo.
+                allow_typos: false,
+                ..query.clone()
+            }))
+    }
+
     /// Query for suggestions using the keyword prefix and provider
     fn map_prefix_keywords<T>(
         &self,
@@ -617,11 +952,15 @@ impl<'a> SuggestDao<'a> {
         provider: &SuggestionProvider,
         mut mapper: impl FnMut(&rusqlite::Row, &str) -> Result<T>,
     ) -> Result<Vec<T>> {
-        let keyword_lowercased = &query.keyword.to_lowercase();
-        let (keyword_prefix, keyword_suffix) = split_keyword(keyword_lowercased);
+        let normalized_keyword = &self.normalize_query_keyword(*provider, query)?;
+        let (keyword_prefix, keyword_suffix) = split_keyword(normalized_keyword);
         let suggestions_limit = query.limit.unwrap_or(-1);
+        let dismissal_filter = self.dismissal_filter(
+            "AND NOT EXISTS (SELECT 1 FROM dismissed_suggestions WHERE url=s.url)",
+        );
         self.conn.query_rows_and_then_cached(
-            r#"
+            &format!(
+                r#"
                 SELECT
                   s.id,
                   MAX(k.rank) AS rank,
@@ -639,7 +978,7 @@ impl<'a> SuggestDao<'a> {
                   k.keyword_prefix = :keyword_prefix
                   AND (k.keyword_suffix BETWEEN :keyword_suffix AND :keyword_suffix || x'FFFF')
                   AND s.provider = :provider
-                  AND NOT EXISTS (SELECT 1 FROM dismissed_suggestions WHERE url=s.url)
+                  {dismissal_filter}
                 GROUP BY
                   s.id
                 ORDER BY
@@ -647,7 +986,8 @@ impl<'a> SuggestDao<'a> {
                   rank DESC
                 LIMIT
                   :suggestions_limit
-                "#,
+                "#
+            ),
             &[
                 (":keyword_prefix", &keyword_prefix as &dyn ToSql),
                 (":keyword_suffix", &keyword_suffix as &dyn ToSql),
@@ -747,6 +1087,7 @@ impl<'a> SuggestDao<'a> {
                                         url: raw_url,
                                         description: row.get("description")?,
                                         score,
+                                        fts_match_info: None,
                                     })
                                 },
                             )
@@ -756,7 +1097,18 @@ impl<'a> SuggestDao<'a> {
             )?
             .into_iter()
             .flatten()
-            .collect();
+            .collect::<Vec<_>>();
+        if suggestions.is_empty() {
+            if let Some(corrected) = self.typo_corrected_query(query, |keyword| {
+                self.prefix_keyword_exists(SuggestionProvider::Mdn, keyword)
+            })? {
+                return Ok(self
+                    .fetch_mdn_suggestions(&corrected)?
+                    .into_iter()
+                    .map(Suggestion::with_typo_correction)
+                    .collect());
+            }
+        }
 
         Ok(suggestions)
     }
@@ -797,14 +1149,17 @@ impl<'a> SuggestDao<'a> {
                   s.provider = ?
                   AND k.keyword = ?
                   AND d.suggestion_type IN ({})
-                  AND NOT EXISTS (
-                    SELECT 1 FROM dismissed_dynamic_suggestions
-                    WHERE dismissal_key = s.url AND suggestion_type = d.suggestion_type
-                  )
+                  {}
                 ORDER BY
                   s.score ASC, d.suggestion_type ASC, s.id ASC
                 "#,
-                repeat_sql_vars(suggestion_types.len())
+                repeat_sql_vars(suggestion_types.len()),
+                self.dismissal_filter(
+                    "AND NOT EXISTS (
+                       SELECT 1 FROM dismissed_dynamic_suggestions
+                       WHERE dismissal_key = s.url AND suggestion_type = d.suggestion_type
+                     )"
+                ),
             ),
             params,
             |row| -> Result<Suggestion> {
@@ -823,6 +1178,15 @@ impl<'a> SuggestDao<'a> {
         )
     }
 
+    /// Returns the types of all ingested dynamic suggestions.
+    pub fn get_dynamic_suggestion_types(&self) -> Result<Vec<String>> {
+        self.conn.query_rows_and_then_cached(
+            "SELECT DISTINCT suggestion_type FROM dynamic_custom_details ORDER BY suggestion_type",
+            [],
+            |row| -> Result<String> { Ok(row.get(0)?) },
+        )
+    }
+
     pub fn are_suggestions_ingested_for_record(&self, record_id: &SuggestRecordId) -> Result<bool> {
         Ok(self.conn.exists(
             r#"
@@ -861,9 +1225,11 @@ impl<'a> SuggestDao<'a> {
         record_id: &SuggestRecordId,
         suggestions: &[DownloadedAmoSuggestion],
     ) -> Result<()> {
+        let normalization = self.get_keyword_normalization(SuggestionProvider::Amo)?;
         let mut suggestion_insert = SuggestionInsertStatement::new(self.conn)?;
         let mut amo_insert = AmoInsertStatement::new(self.conn)?;
-        let mut prefix_keyword_insert = PrefixKeywordInsertStatement::new(self.conn)?;
+        let mut prefix_keyword_insert =
+            PrefixKeywordInsertStatement::new(self.conn, normalization)?;
         for suggestion in suggestions {
             self.scope.err_if_interrupted()?;
             let suggestion_id = suggestion_insert.execute(
@@ -875,7 +1241,8 @@ impl<'a> SuggestDao<'a> {
             )?;
             amo_insert.execute(suggestion_id, suggestion)?;
             for (index, keyword) in suggestion.keywords.iter().enumerate() {
-                let (keyword_prefix, keyword_suffix) = split_keyword(keyword);
+                let keyword = normalize_ingested_keyword(normalization, keyword);
+                let (keyword_prefix, keyword_suffix) = split_keyword(&keyword);
                 prefix_keyword_insert.execute(
                     suggestion_id,
                     None,
@@ -897,9 +1264,11 @@ impl<'a> SuggestDao<'a> {
     ) -> Result<()> {
         // Prepare statements outside of the loop.  This results in a large performance
         // improvement on a fresh ingest, since there are so many rows.
+        let normalization = self.get_keyword_normalization(SuggestionProvider::Amp)?;
         let mut suggestion_insert = SuggestionInsertStatement::new(self.conn)?;
         let mut amp_insert = AmpInsertStatement::new(self.conn)?;
-        let mut keyword_insert = KeywordInsertStatement::new(self.conn)?;
+        let mut keyword_insert =
+            KeywordInsertStatement::for_normalization(self.conn, normalization)?;
         let mut fts_insert = AmpFtsInsertStatement::new(self.conn)?;
         let mut category_insert = CategoryInsertStatement::new(self.conn)?;
         for suggestion in suggestions {
@@ -928,7 +1297,7 @@ impl<'a> SuggestDao<'a> {
                 };
                 keyword_insert.execute(
                     suggestion_id,
-                    keyword.keyword,
+                    &normalize_ingested_keyword(normalization, keyword.keyword),
                     full_keyword_id,
                     keyword.rank,
                 )?;
@@ -951,9 +1320,11 @@ impl<'a> SuggestDao<'a> {
     ) -> Result<()> {
         // Prepare statements outside of the loop.  This results in a large performance
         // improvement on a fresh ingest, since there are so many rows.
+        let normalization = self.get_keyword_normalization(SuggestionProvider::Wikipedia)?;
         let mut suggestion_insert = SuggestionInsertStatement::new(self.conn)?;
         let mut wiki_insert = WikipediaInsertStatement::new(self.conn)?;
-        let mut keyword_insert = KeywordInsertStatement::new(self.conn)?;
+        let mut keyword_insert =
+            KeywordInsertStatement::for_normalization(self.conn, normalization)?;
         for suggestion in suggestions {
             self.scope.err_if_interrupted()?;
             let suggestion_id = suggestion_insert.execute(
@@ -964,9 +1335,22 @@ impl<'a> SuggestDao<'a> {
                 SuggestionProvider::Wikipedia,
             )?;
             wiki_insert.execute(suggestion_id, suggestion)?;
+            let mut full_keyword_inserter = FullKeywordInserter::new(self.conn, suggestion_id);
             for keyword in suggestion.keywords() {
-                // Don't update `full_keywords`, see bug 1876217.
-                keyword_insert.execute(suggestion_id, keyword.keyword, None, keyword.rank)?;
+                // Don't update `full_keywords`, see bug 1876217. The exception
+                // is normalized keywords, which can be folded or stemmed, so
+                // we keep the original to show instead.
+                let full_keyword_id = if normalization == KeywordNormalization::Lowercase {
+                    None
+                } else {
+                    Some(full_keyword_inserter.maybe_insert(keyword.keyword)?)
+                };
+                keyword_insert.execute(
+                    suggestion_id,
+                    &normalize_ingested_keyword(normalization, keyword.keyword),
+                    full_keyword_id,
+                    keyword.rank,
+                )?;
             }
         }
         Ok(())
@@ -979,9 +1363,11 @@ impl<'a> SuggestDao<'a> {
         record_id: &SuggestRecordId,
         suggestions: &[DownloadedMdnSuggestion],
     ) -> Result<()> {
+        let normalization = self.get_keyword_normalization(SuggestionProvider::Mdn)?;
         let mut suggestion_insert = SuggestionInsertStatement::new(self.conn)?;
         let mut mdn_insert = MdnInsertStatement::new(self.conn)?;
-        let mut prefix_keyword_insert = PrefixKeywordInsertStatement::new(self.conn)?;
+        let mut prefix_keyword_insert =
+            PrefixKeywordInsertStatement::new(self.conn, normalization)?;
         for suggestion in suggestions {
             self.scope.err_if_interrupted()?;
             let suggestion_id = suggestion_insert.execute(
@@ -993,7 +1379,8 @@ impl<'a> SuggestDao<'a> {
             )?;
             mdn_insert.execute(suggestion_id, suggestion)?;
             for (index, keyword) in suggestion.keywords.iter().enumerate() {
-                let (keyword_prefix, keyword_suffix) = split_keyword(keyword);
+                let keyword = normalize_ingested_keyword(normalization, keyword);
+                let (keyword_prefix, keyword_suffix) = split_keyword(&keyword);
                 prefix_keyword_insert.execute(
                     suggestion_id,
                     None,
@@ -1099,6 +1486,23 @@ impl<'a> SuggestDao<'a> {
         Ok(())
     }
 
+    /// Returns whether a suggestion has been dismissed by its dismissal key,
+    /// or, for AMP suggestions, by its URL with the deprecated URL-based API.
+    pub fn is_suggestion_dismissed(&self, suggestion: &Suggestion) -> Result<bool> {
+        let Some(key) = suggestion.dismissal_key() else {
+            return Ok(false);
+        };
+        match suggestion {
+            Suggestion::Dynamic {
+                suggestion_type, ..
+            } => self.has_dynamic_dismissal(suggestion_type, key),
+            Suggestion::Amp { raw_url, .. } => {
+                Ok(self.has_dismissal(key)? || self.has_dismissal(raw_url)?)
+            }
+            _ => self.has_dismissal(key),
+        }
+    }
+
     pub fn has_dismissal(&self, key: &str) -> Result<bool> {
         Ok(self.conn.exists(
             "SELECT 1 FROM dismissed_suggestions WHERE url = :url",
@@ -1131,6 +1535,133 @@ impl<'a> SuggestDao<'a> {
         )?)
     }
 
+    /// Records an impression, click, or dismissal of a suggestion at `now`, in
+    /// seconds since the epoch.
+    ///
+    /// This also drops rows that can no longer count toward a cap: impressions
+    /// and clicks older than a day, and all but the provider's latest
+    /// dismissal.
+    pub fn insert_interaction(
+        &self,
+        provider: SuggestionProvider,
+        key: &str,
+        kind: InteractionKind,
+        now: u64,
+    ) -> Result<()> {
+        if kind == InteractionKind::Dismissal {
+            self.conn.execute_cached(
+                "DELETE FROM suggestion_interactions
+                 WHERE provider = :provider AND kind = :kind",
+                named_params! {
+                    ":provider": provider,
+                    ":kind": kind,
+                },
+            )?;
+        }
+        self.conn.execute_cached(
+            "DELETE FROM suggestion_interactions
+             WHERE kind != :dismissal AND timestamp <= :day_ago",
+            named_params! {
+                ":dismissal": InteractionKind::Dismissal,
+                ":day_ago": now.saturating_sub(DAY_SECONDS),
+            },
+        )?;
+        self.conn.execute_cached(
+            "INSERT INTO suggestion_interactions(provider, suggestion_key, kind, timestamp)
+             VALUES(:provider, :key, :kind, :timestamp)",
+            named_params! {
+                ":provider": provider,
+                ":key": key,
+                ":kind": kind,
+                ":timestamp": now,
+            },
+        )?;
+        Ok(())
+    }
+
+    /// Counts a suggestion's impressions and clicks in the hour and day before
+    /// `now`.
+    pub fn get_interaction_counts(
+        &self,
+        provider: SuggestionProvider,
+        key: &str,
+        now: u64,
+    ) -> Result<SuggestInteractionCounts> {
+        Ok(self.conn.query_row_and_then_cachable(
+            "SELECT
+               count(*) FILTER (WHERE kind = :impression AND timestamp > :hour_ago),
+               count(*) FILTER (WHERE kind = :impression AND timestamp > :day_ago),
+               count(*) FILTER (WHERE kind = :click AND timestamp > :day_ago)
+             FROM suggestion_interactions
+             WHERE provider = :provider AND suggestion_key = :key",
+            named_params! {
+                ":impression": InteractionKind::Impression,
+                ":click": InteractionKind::Click,
+                ":hour_ago": now.saturating_sub(HOUR_SECONDS),
+                ":day_ago": now.saturating_sub(DAY_SECONDS),
+                ":provider": provider,
+                ":key": key,
+            },
+            |row| -> Result<_> {
+                Ok(SuggestInteractionCounts {
+                    impressions_last_hour: row.get(0)?,
+                    impressions_last_day: row.get(1)?,
+                    clicks_last_day: row.get(2)?,
+                })
+            },
+            true,
+        )?)
+    }
+
+    /// Returns when one of the provider's suggestions was last dismissed, in
+    /// seconds since the epoch.
+    pub fn get_last_dismissal_time(&self, provider: SuggestionProvider) -> Result<Option<u64>> {
+        Ok(self.conn.try_query_one(
+            "SELECT max(timestamp)
+             FROM suggestion_interactions
+             WHERE provider = :provider AND kind = :dismissal",
+            named_params! {
+                ":provider": provider,
+                ":dismissal": InteractionKind::Dismissal,
+            },
+            true,
+        )?)
+    }
+
+    pub fn clear_interactions(&self) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM suggestion_interactions", ())?;
+        Ok(())
+    }
+
+    /// Removes the suggestions that `cap` currently hides.
+    pub fn remove_capped_suggestions(
+        &self,
+        suggestions: Vec<Suggestion>,
+        cap: &SuggestFrequencyCap,
+        now: u64,
+    ) -> Result<Vec<Suggestion>> {
+        if let Some(cooldown) = cap.dismissal_cooldown_seconds {
+            let last_dismissal = self.get_last_dismissal_time(cap.provider)?;
+            if last_dismissal.is_some_and(|t| now.saturating_sub(t) < cooldown) {
+                return Ok(vec![]);
+            }
+        }
+        let mut uncapped = Vec::with_capacity(suggestions.len());
+        for suggestion in suggestions {
+            let is_capped = match suggestion.frequency_cap_key() {
+                Some(key) => {
+                    cap.is_reached(&self.get_interaction_counts(cap.provider, &key, now)?)
+                }
+                None => false,
+            };
+            if !is_capped {
+                uncapped.push(suggestion);
+            }
+        }
+        Ok(uncapped)
+    }
+
     /// Deletes all suggestions associated with a Remote Settings record from
     /// the database.
     pub fn drop_suggestions(&mut self, record_id: &SuggestRecordId) -> Result<()> {
@@ -1283,6 +1814,54 @@ impl<'a> SuggestDao<'a> {
             .map_or_else(|| Ok(None), |json| Ok(serde_json::from_str(&json)?))
     }
 
+    /// Gets the normalization that the provider's keywords were ingested with.
+    pub fn get_keyword_normalization(
+        &self,
+        provider: SuggestionProvider,
+    ) -> Result<KeywordNormalization> {
+        self.get_meta::<String>(&keyword_normalization_meta_key(provider))?
+            .map_or_else(
+                || Ok(KeywordNormalization::default()),
+                |json| Ok(serde_json::from_str(&json)?),
+            )
+    }
+
+    /// Sets the normalization to ingest the provider's keywords with.
+    ///
+    /// Returns true if it changed, in which case the keywords that were
+    /// already ingested need to be re-ingested to match.
+    pub fn put_keyword_normalization(
+        &mut self,
+        provider: SuggestionProvider,
+        normalization: KeywordNormalization,
+    ) -> Result<bool> {
+        if self.get_keyword_normalization(provider)? == normalization {
+            return Ok(false);
+        }
+        let key = keyword_normalization_meta_key(provider);
+        if normalization == KeywordNormalization::default() {
+            self.conn.execute_cached(
+                "DELETE FROM meta WHERE key = :key",
+                named_params! { ":key": key },
+            )?;
+        } else {
+            self.put_meta(&key, serde_json::to_string(&normalization)?)?;
+        }
+        Ok(true)
+    }
+
+    /// Normalizes a query's keyword the same way as the provider's keywords.
+    fn normalize_query_keyword(
+        &self,
+        provider: SuggestionProvider,
+        query: &SuggestionQuery,
+    ) -> Result<String> {
+        Ok(self
+            .get_keyword_normalization(provider)?
+            .normalize(&query.keyword)
+            .into_owned())
+    }
+
     /// Gets keywords metrics for a record type.
     pub fn get_keywords_metrics(&self, record_type: SuggestRecordType) -> Result<KeywordsMetrics> {
         let data = self.conn.try_query_row(
@@ -1578,8 +2157,18 @@ impl<'conn> DynamicInsertStatement<'conn> {
 pub(crate) struct KeywordInsertStatement<'conn>(rusqlite::Statement<'conn>);
 
 impl<'conn> KeywordInsertStatement<'conn> {
-    pub(crate) fn new(conn: &'conn Connection) -> Result<Self> {
-        Self::with_details(conn, "keywords", None)
+    /// Prepares a statement for inserting keywords normalized with
+    /// `normalization`. Normalizing can make two of a suggestion's keywords the
+    /// same, so duplicates are ignored unless the keywords are stored as-is.
+    pub(crate) fn for_normalization(
+        conn: &'conn Connection,
+        normalization: KeywordNormalization,
+    ) -> Result<Self> {
+        Self::with_details(
+            conn,
+            "keywords",
+            normalization_conflict_resolution(normalization),
+        )
     }
 
     pub(crate) fn with_details(
@@ -1664,9 +2253,9 @@ impl InsertConflictResolution {
 struct PrefixKeywordInsertStatement<'conn>(rusqlite::Statement<'conn>);
 
 impl<'conn> PrefixKeywordInsertStatement<'conn> {
-    fn new(conn: &'conn Connection) -> Result<Self> {
-        Ok(Self(conn.prepare(
-            "INSERT INTO prefix_keywords(
+    fn new(conn: &'conn Connection, normalization: KeywordNormalization) -> Result<Self> {
+        Ok(Self(conn.prepare(&format!(
+            "INSERT {} INTO prefix_keywords(
                  suggestion_id,
                  confidence,
                  keyword_prefix,
@@ -1675,7 +2264,11 @@ impl<'conn> PrefixKeywordInsertStatement<'conn> {
              )
              VALUES(?, ?, ?, ?, ?)
              ",
-        )?))
+            normalization_conflict_resolution(normalization)
+                .as_ref()
+                .map(|r| r.as_str())
+                .unwrap_or_default(),
+        ))?))
     }
 
     fn execute(
@@ -1816,6 +2409,28 @@ fn provider_config_meta_key(provider: SuggestionProvider) -> String {
     format!("{}{}", PROVIDER_CONFIG_META_KEY_PREFIX, provider as u8)
 }
 
+fn keyword_normalization_meta_key(provider: SuggestionProvider) -> String {
+    format!(
+        "{}{}",
+        KEYWORD_NORMALIZATION_META_KEY_PREFIX, provider as u8
+    )
+}
+
+/// Normalizes a keyword for storing. With the default `Lowercase`
+/// normalization, keywords are stored as they are.
+fn normalize_ingested_keyword(normalization: KeywordNormalization, keyword: &str) -> Cow<'_, str> {
+    match normalization {
+        KeywordNormalization::Lowercase => Cow::from(keyword),
+        _ => normalization.normalize(keyword),
+    }
+}
+
+fn normalization_conflict_resolution(
+    normalization: KeywordNormalization,
+) -> Option<InsertConflictResolution> {
+    (normalization != KeywordNormalization::Lowercase).then_some(InsertConflictResolution::Ignore)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -29,6 +29,9 @@ pub enum Error {
     #[error("Remote settings record is missing an attachment (id: u64)")]
     MissingAttachment(String),
 
+    #[error("Error reading Suggest snapshot: {0}")]
+    Snapshot(String),
+
     #[error("Operation interrupted")]
     Interrupted(#[from] interrupt_support::Interrupted),
 
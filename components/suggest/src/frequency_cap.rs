@@ -0,0 +1,111 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/.
+ */
+
+//! Frequency capping for suggestions.
+//!
+//! The application records impressions and clicks with
+//! [`crate::SuggestStore::record_impression()`] and
+//! [`crate::SuggestStore::record_click()`], and dismissals are recorded by the
+//! dismissal methods. Queries leave out suggestions that have reached one of
+//! their provider's [`SuggestFrequencyCap`]s, which come from the global
+//! config record.
+
+use rusqlite::types::{ToSql, ToSqlOutput};
+use serde::{Deserialize, Serialize};
+
+use crate::provider::SuggestionProvider;
+
+/// Seconds in an hour, the window for hourly caps.
+pub(crate) const HOUR_SECONDS: u64 = 60 * 60;
+/// Seconds in a day, the window for daily caps.
+pub(crate) const DAY_SECONDS: u64 = 24 * HOUR_SECONDS;
+
+/// Limits how often a provider's suggestions are shown.
+///
+/// Impressions and clicks are counted per suggestion: by block ID for AMP
+/// suggestions and by dismissal key for everything else. A suggestion that
+/// reaches any of its caps is left out of query results until enough time has
+/// passed. Caps that aren't set aren't enforced.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, uniffi::Record)]
+pub struct SuggestFrequencyCap {
+    pub provider: SuggestionProvider,
+    /// Maximum number of impressions for a suggestion in the last hour.
+    pub max_impressions_per_hour: Option<u32>,
+    /// Maximum number of impressions for a suggestion in the last day.
+    pub max_impressions_per_day: Option<u32>,
+    /// Maximum number of clicks on a suggestion in the last day.
+    pub max_clicks_per_day: Option<u32>,
+    /// After any of the provider's suggestions is dismissed, none of its
+    /// suggestions are shown for this many seconds.
+    pub dismissal_cooldown_seconds: Option<u64>,
+}
+
+impl SuggestFrequencyCap {
+    /// Returns true if a suggestion with these counts has reached any of the
+    /// caps.
+    pub(crate) fn is_reached(&self, counts: &SuggestInteractionCounts) -> bool {
+        let reached = |max: Option<u32>, count: u32| max.is_some_and(|max| count >= max);
+        reached(self.max_impressions_per_hour, counts.impressions_last_hour)
+            || reached(self.max_impressions_per_day, counts.impressions_last_day)
+            || reached(self.max_clicks_per_day, counts.clicks_last_day)
+    }
+}
+
+/// How many times a suggestion was recently shown and clicked, as counted for
+/// frequency caps.
+#[derive(Clone, Debug, Default, PartialEq, Eq, uniffi::Record)]
+pub struct SuggestInteractionCounts {
+    pub impressions_last_hour: u32,
+    pub impressions_last_day: u32,
+    pub clicks_last_day: u32,
+}
+
+/// A kind of interaction stored in the `suggestion_interactions` table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub(crate) enum InteractionKind {
+    Impression = 1,
+    Click = 2,
+    Dismissal = 3,
+}
+
+impl ToSql for InteractionKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(*self as u8))
+    }
+}
+
+/// Returns the current time in seconds since the epoch.
+pub(crate) fn now_seconds() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_reached() {
+        let cap = SuggestFrequencyCap {
+            provider: SuggestionProvider::Amp,
+            max_impressions_per_hour: Some(2),
+            max_impressions_per_day: Some(5),
+            max_clicks_per_day: None,
+            dismissal_cooldown_seconds: None,
+        };
+        let counts = |hour, day, clicks| SuggestInteractionCounts {
+            impressions_last_hour: hour,
+            impressions_last_day: day,
+            clicks_last_day: clicks,
+        };
+        assert!(!cap.is_reached(&counts(0, 0, 0)));
+        assert!(!cap.is_reached(&counts(1, 4, 100)));
+        assert!(cap.is_reached(&counts(2, 2, 0)));
+        assert!(cap.is_reached(&counts(0, 5, 0)));
+    }
+}
@@ -8,6 +8,7 @@ pub mod benchmarks;
 mod config;
 mod db;
 mod error;
+mod frequency_cap;
 mod geoname;
 mod metrics;
 mod provider;
@@ -24,12 +25,16 @@ mod yelp;
 
 pub use config::{SuggestGlobalConfig, SuggestProviderConfig};
 pub use error::{Error, SuggestApiError};
+pub use frequency_cap::{SuggestFrequencyCap, SuggestInteractionCounts};
 pub use geoname::{
     AlternateNames, Geoname, GeonameAlternates, GeonameMatch, GeonameMatchType, GeonameType,
 };
-pub use metrics::{LabeledTimingSample, SuggestIngestionMetrics};
+pub use metrics::{IngestedRecordCount, LabeledTimingSample, SuggestIngestionMetrics};
 pub use provider::{AmpMatchingStrategy, SuggestionProvider, SuggestionProviderConstraints};
-pub use query::{QueryWithMetricsResult, SuggestionQuery};
+pub use query::{
+    FilteredSuggestion, QueryExplainResult, QueryWithMetricsResult, SuggestionExplanation,
+    SuggestionFilterReason, SuggestionMatchType, SuggestionQuery,
+};
 pub use store::{InterruptKind, SuggestIngestionConstraints, SuggestStore, SuggestStoreBuilder};
 pub use suggestion::{raw_suggestion_url_matches, FtsMatchInfo, Suggestion, YelpSubjectType};
 
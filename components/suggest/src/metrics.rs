@@ -18,6 +18,13 @@ impl LabeledTimingSample {
     }
 }
 
+/// Number of records ingested for a record type
+#[derive(Debug, PartialEq, Eq, uniffi::Record)]
+pub struct IngestedRecordCount {
+    pub record_type: String,
+    pub count: u64,
+}
+
 /// Ingestion metrics
 ///
 /// These are recorded during [crate::Store::ingest] and returned to the consumer to record.
@@ -27,6 +34,8 @@ pub struct SuggestIngestionMetrics {
     pub ingestion_times: Vec<LabeledTimingSample>,
     /// Samples for the `suggest.ingestion_download_time` metric
     pub download_times: Vec<LabeledTimingSample>,
+    /// Number of new or updated records ingested, for each record type that had any
+    pub record_counts: Vec<IngestedRecordCount>,
 }
 
 impl SuggestIngestionMetrics {
@@ -55,6 +64,27 @@ impl SuggestIngestionMetrics {
         }
         result
     }
+
+    /// Adds `count` ingested records to the count for `record_type`.
+    ///
+    /// Some record types, like icons, are ingested from more than one collection, so this
+    /// accumulates rather than pushing a new entry each time.
+    pub fn record_ingested(&mut self, record_type: impl Into<String>, count: u64) {
+        if count == 0 {
+            return;
+        }
+        let record_type = record_type.into();
+        match self
+            .record_counts
+            .iter_mut()
+            .find(|c| c.record_type == record_type)
+        {
+            Some(c) => c.count += count,
+            None => self
+                .record_counts
+                .push(IngestedRecordCount { record_type, count }),
+        }
+    }
 }
 
 /// Context for a ingestion measurement
@@ -13,6 +13,8 @@ use rusqlite::{
     Result as RusqliteResult,
 };
 
+use serde::{Deserialize, Serialize};
+
 use crate::rs::{Collection, SuggestRecordType};
 
 #[cfg(test)]
@@ -34,7 +36,8 @@ pub(crate) const DEFAULT_INGEST_PROVIDERS: [SuggestionProvider; 5] = [
 /// A provider is a source of search suggestions.
 /// Please preserve the integer values after removing or adding providers.
 /// Provider configs are associated with integer keys stored in the database.
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, uniffi::Enum)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash, Deserialize, Serialize, uniffi::Enum)]
+#[serde(rename_all = "lowercase")]
 #[repr(u8)]
 pub enum SuggestionProvider {
     Amp = 1,
@@ -15,6 +15,12 @@ pub struct SuggestionQuery {
     pub provider_constraints: Option<SuggestionProviderConstraints>,
     #[uniffi(default = None)]
     pub limit: Option<i32>,
+    /// If nothing matches the keyword exactly, also match keywords one typo
+    /// away: with a character added, removed, replaced, or swapped with its
+    /// neighbor. This applies to AMP, Wikipedia, MDN, and Yelp suggestions,
+    /// and matches found this way have `FtsMatchInfo::typo` set.
+    #[uniffi(default = false)]
+    pub allow_typos: bool,
 }
 
 #[derive(uniffi::Record)]
@@ -24,6 +30,71 @@ pub struct QueryWithMetricsResult {
     pub query_times: Vec<LabeledTimingSample>,
 }
 
+/// The result of [crate::SuggestStore::query_explain]
+#[derive(uniffi::Record)]
+pub struct QueryExplainResult {
+    /// The suggestions that the query returns, in the same order, along with
+    /// how each one matched
+    pub explanations: Vec<SuggestionExplanation>,
+    /// Suggestions that matched the query's keyword but weren't returned
+    pub filtered: Vec<FilteredSuggestion>,
+    /// Samples for the `suggest.query_time` metric
+    pub query_times: Vec<LabeledTimingSample>,
+}
+
+/// Explains why a suggestion was returned for a query.
+#[derive(Clone, Debug, PartialEq, uniffi::Record)]
+pub struct SuggestionExplanation {
+    pub suggestion: Suggestion,
+    pub provider: SuggestionProvider,
+    /// The stored keyword that matched the query, normalized the same way as
+    /// the provider's keywords. This is `None` for providers that don't match
+    /// against keyword rows, and for FTS matches.
+    pub matched_keyword: Option<String>,
+    pub match_type: SuggestionMatchType,
+    /// The score that suggestions are sorted by. Wikipedia suggestions don't
+    /// have their own score, so this is the default score for them.
+    pub score: f64,
+    /// The rank of the matched keyword among the suggestion's keywords. This
+    /// breaks ties between MDN and AMO suggestions with the same score.
+    pub keyword_rank: Option<i64>,
+}
+
+/// How a suggestion matched the query's keyword
+#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum SuggestionMatchType {
+    /// The keyword is the suggestion's full keyword
+    Exact,
+    /// The keyword is a prefix of the suggestion's full keyword
+    Prefix,
+    /// The keyword matched a full-text search
+    Fts,
+    /// The keyword matched a full-text search after stemming
+    Stemmed,
+    /// The keyword matched after correcting a typo
+    Typo,
+}
+
+/// A suggestion that matched a query's keyword but was left out of the results
+#[derive(Clone, Debug, PartialEq, uniffi::Record)]
+pub struct FilteredSuggestion {
+    pub suggestion: Suggestion,
+    pub reason: SuggestionFilterReason,
+}
+
+/// Why a suggestion was left out of a query's results
+#[derive(Clone, Copy, Debug, Eq, PartialEq, uniffi::Enum)]
+pub enum SuggestionFilterReason {
+    /// The suggestion was dismissed
+    Dismissed,
+    /// The provider's frequency cap or dismissal cooldown was reached
+    FrequencyCapped,
+    /// The query's provider constraints excluded the suggestion
+    ProviderConstraints,
+    /// The suggestion was cut by the query's limit
+    Limit,
+}
+
 impl SuggestionQuery {
     // Builder style methods for creating queries (mostly used by the test code)
 
@@ -122,6 +193,13 @@ impl SuggestionQuery {
         }
     }
 
+    pub fn allow_typos(self) -> Self {
+        Self {
+            allow_typos: true,
+            ..self
+        }
+    }
+
     /// Create an FTS query term for our keyword(s)
     pub(crate) fn fts_query(&self) -> FtsQuery<'_> {
         FtsQuery::new(&self.keyword)
@@ -204,6 +282,76 @@ impl<'a> FtsQuery<'a> {
     }
 }
 
+/// Typo correction is only tried for keywords with at least this many
+/// characters. Shorter keywords are one edit away from too many others.
+const MIN_TYPO_KEYWORD_LENGTH: usize = 4;
+
+/// Typo correction isn't tried for keywords with more than this many
+/// characters, which bounds the number of candidates we look up.
+const MAX_TYPO_KEYWORD_LENGTH: usize = 24;
+
+/// At most this many typo corrections are looked up per query. Long keywords
+/// have more candidates than this, so their least likely typos aren't tried.
+const MAX_TYPO_CANDIDATES: usize = 512;
+
+/// Characters tried when replacing or adding a character, besides those
+/// already in the keyword.
+const TYPO_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyz0123456789 ";
+
+/// Returns the keywords one edit away from `keyword`, which should already be
+/// lowercased. The most likely typos come first: swapped neighbors, then
+/// extra, replaced, and missing characters. At most [MAX_TYPO_CANDIDATES]
+/// are returned.
+pub(crate) fn typo_candidates(keyword: &str) -> Vec<String> {
+    let chars: Vec<char> = keyword.chars().collect();
+    if !(MIN_TYPO_KEYWORD_LENGTH..=MAX_TYPO_KEYWORD_LENGTH).contains(&chars.len()) {
+        return vec![];
+    }
+    let mut alphabet: Vec<char> = TYPO_ALPHABET.chars().collect();
+    for c in &chars {
+        if !alphabet.contains(c) {
+            alphabet.push(*c);
+        }
+    }
+
+    let mut candidates: Vec<Vec<char>> = Vec::new();
+    for i in 0..chars.len() - 1 {
+        let mut candidate = chars.clone();
+        candidate.swap(i, i + 1);
+        candidates.push(candidate);
+    }
+    for i in 0..chars.len() {
+        let mut candidate = chars.clone();
+        candidate.remove(i);
+        candidates.push(candidate);
+    }
+    for i in 0..chars.len() {
+        for &c in &alphabet {
+            let mut candidate = chars.clone();
+            candidate[i] = c;
+            candidates.push(candidate);
+        }
+    }
+    for i in 0..=chars.len() {
+        for &c in &alphabet {
+            let mut candidate = chars.clone();
+            candidate.insert(i, c);
+            candidates.push(candidate);
+        }
+    }
+
+    let mut seen = HashSet::new();
+    candidates
+        .into_iter()
+        .map(String::from_iter)
+        .filter(|candidate| {
+            // Keywords never start or end with whitespace.
+            candidate != keyword && candidate.trim() == candidate && seen.insert(candidate.clone())
+        })
+        .take(MAX_TYPO_CANDIDATES)
+        .collect()
+}
+
 /// Given a list of full keywords, create an FTS string to match against.
 ///
 /// Creates a string with de-duped keywords.
@@ -298,6 +446,29 @@ mod test {
         assert!(FtsQuery::new("run").match_required_stemming("running shoes"));
     }
 
+    #[test]
+    fn test_typo_candidates() {
+        let candidates = typo_candidates("raemn");
+        // Swapped, replaced, missing, and extra characters.
+        for expected in ["ramen", "raemm", "remn", "raemen"] {
+            assert!(candidates.iter().any(|c| c == expected), "{expected}");
+        }
+        // Swapped neighbors come first, then extra characters.
+        assert_eq!(candidates[0], "aremn");
+        assert_eq!(candidates[4], "aemn");
+        assert!(!candidates.iter().any(|c| c == "raemn"));
+        assert!(!candidates.iter().any(|c| c.starts_with(' ')));
+        // Characters from the keyword are tried, too.
+        assert!(typo_candidates("cafés").iter().any(|c| c == "céfés"));
+        // Short and long keywords aren't corrected.
+        assert!(typo_candidates("abc").is_empty());
+        assert!(typo_candidates(&"a".repeat(MAX_TYPO_KEYWORD_LENGTH + 1)).is_empty());
+        // Long keywords have their candidates capped.
+        let candidates = typo_candidates(&"abcdef".repeat(4));
+        assert_eq!(candidates.len(), MAX_TYPO_CANDIDATES);
+        assert_eq!(candidates[0], "bacdef".to_owned() + &"abcdef".repeat(3));
+    }
+
     #[test]
     fn test_full_keywords_to_fts_content() {
         check_full_keywords_to_fts_content(["a", "b", "c"], "a b c");
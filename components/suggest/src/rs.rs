@@ -31,7 +31,13 @@
 //!     the new suggestion in their results, and return `Suggestion::T` variants
 //!     as needed.
 
-use std::{fmt, sync::Arc};
+use std::{
+    fmt, fs, io,
+    path::{Component, Path, PathBuf},
+    sync::Arc,
+};
+
+use error_support::warn;
 
 use remote_settings::{
     Attachment, RemoteSettingsClient, RemoteSettingsError, RemoteSettingsRecord,
@@ -105,10 +111,7 @@ impl Client for SuggestRemoteSettingsClient {
         client.sync()?;
         let response = client.get_records(false);
         match response {
-            Some(r) => Ok(r
-                .into_iter()
-                .filter_map(|r| Record::new(r, collection).ok())
-                .collect()),
+            Some(r) => Ok(parse_records(r, collection)),
             None => Err(Error::RemoteSettings(RemoteSettingsError::Other {
                 reason: "Unable to get records".to_owned(),
             })),
@@ -126,6 +129,114 @@ impl Client for SuggestRemoteSettingsClient {
     }
 }
 
+/// Implements the [Client] trait by reading a local snapshot directory
+/// instead of talking to Remote Settings.
+///
+/// The directory holds one `<collection name>.json` file per collection, for
+/// example `quicksuggest-amp.json`, containing the collection's records as a
+/// JSON array (a Remote Settings `{"data": [...]}` response body is accepted
+/// too). Attachments are read from `attachments/<location>`, where `location`
+/// is the attachment's `location` field. A collection without a file is
+/// treated as empty.
+pub struct SuggestSnapshotClient {
+    directory: PathBuf,
+}
+
+/// The contents of a snapshot collection file.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SnapshotRecords {
+    Records(Vec<RemoteSettingsRecord>),
+    Response { data: Vec<RemoteSettingsRecord> },
+}
+
+impl SuggestSnapshotClient {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            directory: directory.into(),
+        }
+    }
+}
+
+impl Client for SuggestSnapshotClient {
+    fn get_records(&self, collection: Collection) -> Result<Vec<Record>> {
+        let file_name = format!("{}.json", collection.name());
+        let data = match fs::read(self.directory.join(&file_name)) {
+            Ok(data) => data,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(Error::Snapshot(format!("{file_name}: {e}"))),
+        };
+        let records = match serde_json::from_slice(&data)? {
+            SnapshotRecords::Records(records) => records,
+            SnapshotRecords::Response { data } => data,
+        };
+        Ok(parse_records(
+            records.into_iter().filter(|r| !r.deleted),
+            collection,
+        ))
+    }
+
+    fn download_attachment(&self, record: &Record) -> Result<Vec<u8>> {
+        let Some(a) = &record.attachment else {
+            return Err(Error::MissingAttachment(record.id.to_string()));
+        };
+        // Only allow plain relative paths, so that a snapshot can't read files
+        // outside of its directory.
+        let location = Path::new(&a.location);
+        if !location
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(Error::Snapshot(format!(
+                "invalid attachment location for record {}: {}",
+                record.id, a.location
+            )));
+        }
+        fs::read(self.directory.join("attachments").join(location))
+            .map_err(|_| Error::MissingAttachment(record.id.to_string()))
+    }
+}
+
+/// Parses Remote Settings records for Suggest, logging and skipping the ones
+/// that can't be parsed.
+fn parse_records(
+    records: impl IntoIterator<Item = RemoteSettingsRecord>,
+    collection: Collection,
+) -> Vec<Record> {
+    records
+        .into_iter()
+        .filter_map(|r| {
+            let id = r.id.clone();
+            Record::new(r, collection)
+                .inspect_err(|e| warn!("Skipping invalid record {id}: {e}"))
+                .ok()
+        })
+        .collect()
+}
+
+/// The [Client] used by [crate::SuggestStore], which ingests either from
+/// Remote Settings or from a local snapshot.
+pub enum SuggestStoreClient {
+    RemoteSettings(SuggestRemoteSettingsClient),
+    Snapshot(SuggestSnapshotClient),
+}
+
+impl Client for SuggestStoreClient {
+    fn get_records(&self, collection: Collection) -> Result<Vec<Record>> {
+        match self {
+            Self::RemoteSettings(client) => client.get_records(collection),
+            Self::Snapshot(client) => client.get_records(collection),
+        }
+    }
+
+    fn download_attachment(&self, record: &Record) -> Result<Vec<u8>> {
+        match self {
+            Self::RemoteSettings(client) => client.download_attachment(record),
+            Self::Snapshot(client) => client.download_attachment(record),
+        }
+    }
+}
+
 /// Remote settings record for suggest.
 ///
 /// This is a `remote_settings::RemoteSettingsRecord` parsed for suggest.
@@ -577,6 +688,9 @@ pub(crate) struct DownloadedGlobalConfigInner {
     /// The maximum number of times the user can click "Show less frequently"
     /// for a suggestion in the UI.
     pub show_less_frequently_cap: i32,
+    /// Frequency caps, parsed into `SuggestFrequencyCap`s.
+    #[serde(default)]
+    pub frequency_caps: Vec<Value>,
 }
 
 #[cfg(test)]
@@ -809,4 +923,43 @@ mod test {
             ],
         );
     }
+
+    #[test]
+    fn test_snapshot_attachment_location() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir_all(dir.path().join("attachments/amp")).unwrap();
+        fs::write(dir.path().join("attachments/amp/data-1.json"), "[]").unwrap();
+        fs::write(dir.path().join("secret"), "secret").unwrap();
+        let client = SuggestSnapshotClient::new(dir.path());
+        let record = |location: &str| Record {
+            id: SuggestRecordId::new("data-1".into()),
+            last_modified: 1,
+            attachment: Some(Attachment {
+                filename: "data-1.json".into(),
+                mimetype: "application/json".into(),
+                location: location.into(),
+                hash: "".into(),
+                size: 0,
+            }),
+            payload: SuggestRecord::Amp,
+            collection: Collection::Amp,
+        };
+
+        assert_eq!(
+            client
+                .download_attachment(&record("amp/data-1.json"))
+                .unwrap(),
+            b"[]"
+        );
+        // Locations that could escape the snapshot directory are rejected.
+        for location in ["../secret", "amp/../../secret", "/etc/passwd"] {
+            assert!(
+                matches!(
+                    client.download_attachment(&record(location)),
+                    Err(Error::Snapshot(_))
+                ),
+                "{location}"
+            );
+        }
+    }
 }
@@ -23,7 +23,7 @@ use sql_support::{
 ///     `clear_database()` by adding their names to `conditional_tables`, unless
 ///     they are cleared via a deletion trigger or there's some other good
 ///     reason not to do so.
-pub const VERSION: u32 = 45;
+pub const VERSION: u32 = 46;
 
 /// The current Suggest database schema.
 pub const SQL: &str = "
@@ -241,6 +241,19 @@ CREATE TABLE dismissed_dynamic_suggestions (
     dismissal_key TEXT NOT NULL,
     PRIMARY KEY(suggestion_type, dismissal_key)
 ) WITHOUT ROWID;
+
+-- Impressions, clicks, and dismissals used to enforce frequency caps.
+-- `suggestion_key` is the block ID for AMP suggestions and the dismissal key
+-- for everything else.
+CREATE TABLE suggestion_interactions (
+    provider INTEGER NOT NULL,
+    suggestion_key TEXT NOT NULL,
+    kind INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL
+);
+
+CREATE INDEX suggestion_interactions_index
+    ON suggestion_interactions(provider, suggestion_key, kind, timestamp);
 ";
 
 /// Initializes an SQLite connection to the Suggest database, performing
@@ -835,6 +848,21 @@ impl ConnectionInitializer for SuggestConnectionInitializer<'_> {
                 )?;
                 Ok(())
             }
+            45 => {
+                tx.execute_batch(
+                    "
+                    CREATE TABLE suggestion_interactions (
+                        provider INTEGER NOT NULL,
+                        suggestion_key TEXT NOT NULL,
+                        kind INTEGER NOT NULL,
+                        timestamp INTEGER NOT NULL
+                    );
+                    CREATE INDEX suggestion_interactions_index
+                        ON suggestion_interactions(provider, suggestion_key, kind, timestamp);
+                    ",
+                )?;
+                Ok(())
+            }
 
             _ => Err(open_database::Error::IncompatibleVersion(version)),
         }
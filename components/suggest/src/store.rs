@@ -20,14 +20,18 @@ use crate::{
     config::{SuggestGlobalConfig, SuggestProviderConfig},
     db::{ConnectionType, IngestedRecord, Sqlite3Extension, SuggestDao, SuggestDb},
     error::Error,
+    frequency_cap::{now_seconds, InteractionKind, SuggestInteractionCounts},
     geoname::{Geoname, GeonameAlternates, GeonameMatch},
     metrics::{MetricsContext, SuggestIngestionMetrics, SuggestQueryMetrics},
     provider::{SuggestionProvider, SuggestionProviderConstraints, DEFAULT_INGEST_PROVIDERS},
     rs::{
         Client, Collection, DownloadedDynamicRecord, Record, SuggestAttachment, SuggestRecord,
-        SuggestRecordId, SuggestRecordType, SuggestRemoteSettingsClient,
+        SuggestRecordId, SuggestRecordType, SuggestRemoteSettingsClient, SuggestSnapshotClient,
+        SuggestStoreClient,
     },
-    QueryWithMetricsResult, Result, SuggestApiResult, Suggestion, SuggestionQuery,
+    util::KeywordNormalization,
+    FilteredSuggestion, QueryExplainResult, QueryWithMetricsResult, Result, SuggestApiResult,
+    Suggestion, SuggestionFilterReason, SuggestionQuery,
 };
 
 /// Builder for [SuggestStore]
@@ -43,7 +47,9 @@ struct SuggestStoreBuilderInner {
     remote_settings_server: Option<RemoteSettingsServer>,
     remote_settings_service: Option<Arc<RemoteSettingsService>>,
     remote_settings_bucket_name: Option<String>,
+    snapshot_directory: Option<String>,
     extensions_to_load: Vec<Sqlite3Extension>,
+    keyword_normalizations: HashMap<SuggestionProvider, KeywordNormalization>,
 }
 
 impl Default for SuggestStoreBuilder {
@@ -88,6 +94,35 @@ impl SuggestStoreBuilder {
         self
     }
 
+    /// Ingest from a local snapshot directory instead of Remote Settings.
+    ///
+    /// The directory holds a `<collection name>.json` file of records for each collection, for
+    /// example `quicksuggest-amp.json`, and an `attachments` directory with each attachment
+    /// stored at its `location`. A store built with this never touches the network, and doesn't
+    /// need a `remote_settings_service`.
+    pub fn snapshot_directory(self: Arc<Self>, path: String) -> Arc<Self> {
+        self.0.lock().snapshot_directory = Some(path);
+        self
+    }
+
+    /// Set how a provider's keywords are normalized for matching. Providers
+    /// use `KeywordNormalization::Lowercase` by default.
+    ///
+    /// Changing a provider's normalization re-ingests all suggestions on the
+    /// next [SuggestStore::ingest] of that provider, since keywords are stored
+    /// normalized. Until then, the provider keeps its old normalization.
+    pub fn keyword_normalization(
+        self: Arc<Self>,
+        provider: SuggestionProvider,
+        normalization: KeywordNormalization,
+    ) -> Arc<Self> {
+        self.0
+            .lock()
+            .keyword_normalizations
+            .insert(provider, normalization);
+        self
+    }
+
     /// Add an sqlite3 extension to load
     ///
     /// library_name should be the name of the library without any extension, for example `libmozsqlite3`.
@@ -113,17 +148,20 @@ impl SuggestStoreBuilder {
             .data_path
             .clone()
             .ok_or_else(|| Error::SuggestStoreBuilder("data_path not specified".to_owned()))?;
-        let rs_service = inner.remote_settings_service.clone().ok_or_else(|| {
-            Error::RemoteSettings(RemoteSettingsError::Other {
-                reason: "remote_settings_service_not_specified".to_string(),
-            })
-        })?;
+        let client = match &inner.snapshot_directory {
+            Some(directory) => SuggestStoreClient::Snapshot(SuggestSnapshotClient::new(directory)),
+            None => {
+                let rs_service = inner.remote_settings_service.clone().ok_or_else(|| {
+                    Error::RemoteSettings(RemoteSettingsError::Other {
+                        reason: "remote_settings_service_not_specified".to_string(),
+                    })
+                })?;
+                SuggestStoreClient::RemoteSettings(SuggestRemoteSettingsClient::new(&rs_service))
+            }
+        };
         Ok(Arc::new(SuggestStore {
-            inner: SuggestStoreInner::new(
-                data_path,
-                extensions_to_load,
-                SuggestRemoteSettingsClient::new(&rs_service),
-            ),
+            inner: SuggestStoreInner::new(data_path, extensions_to_load, client)
+                .with_keyword_normalizations(inner.keyword_normalizations.clone()),
         }))
     }
 }
@@ -170,7 +208,7 @@ pub enum InterruptKind {
 ///    on the first launch.
 #[derive(uniffi::Object)]
 pub struct SuggestStore {
-    inner: SuggestStoreInner<SuggestRemoteSettingsClient>,
+    inner: SuggestStoreInner<SuggestStoreClient>,
 }
 
 #[uniffi::export]
@@ -178,7 +216,9 @@ impl SuggestStore {
     /// Creates a Suggest store.
     #[uniffi::constructor()]
     pub fn new(path: &str, remote_settings_service: Arc<RemoteSettingsService>) -> Self {
-        let client = SuggestRemoteSettingsClient::new(&remote_settings_service);
+        let client = SuggestStoreClient::RemoteSettings(SuggestRemoteSettingsClient::new(
+            &remote_settings_service,
+        ));
         Self {
             inner: SuggestStoreInner::new(path.to_owned(), vec![], client),
         }
@@ -199,6 +239,15 @@ impl SuggestStore {
         self.inner.query(query)
     }
 
+    /// Queries the database for suggestions, and explains how each one
+    /// matched and which matching suggestions were filtered out.
+    ///
+    /// This is slower than [SuggestStore::query] and is meant for debugging.
+    #[handle_error(Error)]
+    pub fn query_explain(&self, query: SuggestionQuery) -> SuggestApiResult<QueryExplainResult> {
+        self.inner.query_explain(query)
+    }
+
     /// Dismiss a suggestion.
     ///
     /// Dismissed suggestions cannot be fetched again.
@@ -273,7 +322,8 @@ impl SuggestStore {
         self.inner.interrupt(kind)
     }
 
-    /// Ingests new suggestions from Remote Settings.
+    /// Ingests new suggestions from Remote Settings, or from the snapshot directory if the store
+    /// was built with one.
     #[handle_error(Error)]
     pub fn ingest(
         &self,
@@ -282,6 +332,38 @@ impl SuggestStore {
         self.inner.ingest(constraints)
     }
 
+    /// Records that a suggestion was shown to the user, for frequency capping.
+    #[handle_error(Error)]
+    pub fn record_impression(&self, suggestion: &Suggestion) -> SuggestApiResult<()> {
+        self.inner
+            .record_interaction(suggestion, InteractionKind::Impression, now_seconds())
+    }
+
+    /// Records that the user clicked a suggestion, for frequency capping.
+    #[handle_error(Error)]
+    pub fn record_click(&self, suggestion: &Suggestion) -> SuggestApiResult<()> {
+        self.inner
+            .record_interaction(suggestion, InteractionKind::Click, now_seconds())
+    }
+
+    /// Returns how many times a suggestion was recently shown and clicked, as
+    /// counted for frequency caps.
+    #[handle_error(Error)]
+    pub fn fetch_interaction_counts(
+        &self,
+        suggestion: &Suggestion,
+    ) -> SuggestApiResult<SuggestInteractionCounts> {
+        self.inner
+            .fetch_interaction_counts(suggestion, now_seconds())
+    }
+
+    /// Forgets all recorded impressions, clicks, and dismissal cooldowns.
+    /// Dismissed suggestions stay dismissed.
+    #[handle_error(Error)]
+    pub fn clear_interactions(&self) -> SuggestApiResult<()> {
+        self.inner.clear_interactions()
+    }
+
     /// Removes all content from the database.
     #[handle_error(Error)]
     pub fn clear(&self) -> SuggestApiResult<()> {
@@ -411,6 +493,9 @@ pub(crate) struct SuggestStoreInner<S> {
     data_path: PathBuf,
     dbs: OnceCell<SuggestStoreDbs>,
     extensions_to_load: Vec<Sqlite3Extension>,
+    /// How each provider's keywords are normalized. Providers that aren't in
+    /// the map use the default normalization.
+    keyword_normalizations: HashMap<SuggestionProvider, KeywordNormalization>,
     settings_client: S,
 }
 
@@ -424,10 +509,19 @@ impl<S> SuggestStoreInner<S> {
             data_path: data_path.into(),
             extensions_to_load,
             dbs: OnceCell::new(),
+            keyword_normalizations: HashMap::new(),
             settings_client,
         }
     }
 
+    pub fn with_keyword_normalizations(
+        mut self,
+        keyword_normalizations: HashMap<SuggestionProvider, KeywordNormalization>,
+    ) -> Self {
+        self.keyword_normalizations = keyword_normalizations;
+        self
+    }
+
     /// Returns this store's database connections, initializing them if
     /// they're not already open.
     fn dbs(&self) -> Result<&SuggestStoreDbs> {
@@ -441,16 +535,16 @@ impl<S> SuggestStoreInner<S> {
 
         let unique_providers = query.providers.iter().collect::<HashSet<_>>();
         let reader = &self.dbs()?.reader;
+        let global_config = reader.read(|dao| dao.get_global_config())?;
+        let now = now_seconds();
         for provider in unique_providers {
             let new_suggestions = metrics.measure_query(provider.to_string(), || {
-                reader.read(|dao| match provider {
-                    SuggestionProvider::Amp => dao.fetch_amp_suggestions(&query),
-                    SuggestionProvider::Wikipedia => dao.fetch_wikipedia_suggestions(&query),
-                    SuggestionProvider::Amo => dao.fetch_amo_suggestions(&query),
-                    SuggestionProvider::Yelp => dao.fetch_yelp_suggestions(&query),
-                    SuggestionProvider::Mdn => dao.fetch_mdn_suggestions(&query),
-                    SuggestionProvider::Weather => dao.fetch_weather_suggestions(&query),
-                    SuggestionProvider::Dynamic => dao.fetch_dynamic_suggestions(&query),
+                reader.read(|dao| {
+                    let suggestions = dao.fetch_provider_suggestions(*provider, &query)?;
+                    match global_config.frequency_cap(*provider) {
+                        Some(cap) => dao.remove_capped_suggestions(suggestions, cap, now),
+                        None => Ok(suggestions),
+                    }
                 })
             })?;
             suggestions.extend(new_suggestions);
@@ -467,6 +561,87 @@ impl<S> SuggestStoreInner<S> {
         })
     }
 
+    fn query_explain(&self, query: SuggestionQuery) -> Result<QueryExplainResult> {
+        let QueryWithMetricsResult {
+            suggestions,
+            query_times,
+        } = self.query(query.clone())?;
+        let dbs = self.dbs()?;
+        let explanations = dbs.reader.read(|dao| {
+            suggestions
+                .iter()
+                .map(|suggestion| dao.explain_match(&query, suggestion))
+                .collect::<Result<Vec<_>>>()
+        })?;
+
+        // Fetch the suggestions again including dismissed ones, as if the
+        // query had no constraints, to find the ones that were filtered out.
+        let unconstrained = dbs.reader.read(|dao| {
+            let dao = dao.including_dismissed();
+            let unconstrained_query = SuggestionQuery {
+                provider_constraints: Some(SuggestionProviderConstraints {
+                    dynamic_suggestion_types: Some(dao.get_dynamic_suggestion_types()?),
+                    amp_alternative_matching: query
+                        .provider_constraints
+                        .as_ref()
+                        .and_then(|c| c.amp_alternative_matching.clone())
+                        .filter(|strategy| strategy.uses_fts()),
+                }),
+                limit: None,
+                ..query.clone()
+            };
+            let mut unconstrained = vec![];
+            for provider in query.providers.iter().collect::<HashSet<_>>() {
+                unconstrained
+                    .extend(dao.fetch_provider_suggestions(*provider, &unconstrained_query)?);
+            }
+            Ok(unconstrained)
+        })?;
+        let unlimited = match query.limit {
+            Some(_) => {
+                self.query(SuggestionQuery {
+                    limit: None,
+                    ..query.clone()
+                })?
+                .suggestions
+            }
+            None => vec![],
+        };
+        let global_config = dbs.reader.read(|dao| dao.get_global_config())?;
+        let now = now_seconds();
+        let filtered = dbs.reader.read(|dao| {
+            let mut filtered = vec![];
+            for suggestion in unconstrained {
+                if suggestions.contains(&suggestion) {
+                    continue;
+                }
+                let is_capped = match global_config.frequency_cap(suggestion.provider()) {
+                    Some(cap) => dao
+                        .remove_capped_suggestions(vec![suggestion.clone()], cap, now)?
+                        .is_empty(),
+                    None => false,
+                };
+                let reason = if dao.is_suggestion_dismissed(&suggestion)? {
+                    SuggestionFilterReason::Dismissed
+                } else if is_capped {
+                    SuggestionFilterReason::FrequencyCapped
+                } else if unlimited.contains(&suggestion) {
+                    SuggestionFilterReason::Limit
+                } else {
+                    SuggestionFilterReason::ProviderConstraints
+                };
+                filtered.push(FilteredSuggestion { suggestion, reason });
+            }
+            Ok(filtered)
+        })?;
+
+        Ok(QueryExplainResult {
+            explanations,
+            filtered,
+            query_times,
+        })
+    }
+
     fn dismiss_by_suggestion(&self, suggestion: &Suggestion) -> Result<()> {
         if let Some(key) = suggestion.dismissal_key() {
             match suggestion {
@@ -478,10 +653,44 @@ impl<S> SuggestStoreInner<S> {
                     .write(|dao| dao.insert_dynamic_dismissal(suggestion_type, key))?,
                 _ => self.dismiss_by_key(key)?,
             }
+            // Start the provider's dismissal cooldown, if it has one.
+            self.record_interaction(suggestion, InteractionKind::Dismissal, now_seconds())?;
+        }
+        Ok(())
+    }
+
+    fn record_interaction(
+        &self,
+        suggestion: &Suggestion,
+        kind: InteractionKind,
+        now: u64,
+    ) -> Result<()> {
+        if let Some(key) = suggestion.frequency_cap_key() {
+            self.dbs()?
+                .writer
+                .write(|dao| dao.insert_interaction(suggestion.provider(), &key, kind, now))?;
         }
         Ok(())
     }
 
+    fn fetch_interaction_counts(
+        &self,
+        suggestion: &Suggestion,
+        now: u64,
+    ) -> Result<SuggestInteractionCounts> {
+        match suggestion.frequency_cap_key() {
+            Some(key) => self
+                .dbs()?
+                .reader
+                .read(|dao| dao.get_interaction_counts(suggestion.provider(), &key, now)),
+            None => Ok(SuggestInteractionCounts::default()),
+        }
+    }
+
+    fn clear_interactions(&self) -> Result<()> {
+        self.dbs()?.writer.write(|dao| dao.clear_interactions())
+    }
+
     fn dismiss_by_key(&self, key: &str) -> Result<()> {
         self.dbs()?.writer.write(|dao| dao.insert_dismissal(key))
     }
@@ -607,12 +816,11 @@ where
             Collection::Other,
             HashSet::from([SuggestRecordType::GlobalConfig]),
         )]);
-        for provider in constraints
+        let providers = constraints
             .providers
-            .as_ref()
-            .unwrap_or(&DEFAULT_INGEST_PROVIDERS.to_vec())
-            .iter()
-        {
+            .clone()
+            .unwrap_or_else(|| DEFAULT_INGEST_PROVIDERS.to_vec());
+        for provider in &providers {
             for (collection, provider_rts) in provider.record_types_by_collection() {
                 record_types_by_collection
                     .entry(collection)
@@ -624,6 +832,26 @@ where
         // Create a single write scope for all DB operations
         let mut write_scope = writer.write_scope()?;
 
+        // Keywords are stored normalized, so if any provider's normalization
+        // changed, re-ingest everything to match the new one. Providers that
+        // we aren't ingesting keep their old normalization, since their
+        // keywords are still stored that way.
+        write_scope.write(|dao| {
+            let mut normalization_changed = false;
+            for &provider in &providers {
+                let normalization = self
+                    .keyword_normalizations
+                    .get(&provider)
+                    .copied()
+                    .unwrap_or_default();
+                normalization_changed |= dao.put_keyword_normalization(provider, normalization)?;
+            }
+            if normalization_changed {
+                dao.force_reingest()?;
+            }
+            Ok(())
+        })?;
+
         // Read the previously ingested records.  We use this to calculate what's changed
         let ingested_records = write_scope.read(|dao| dao.get_ingested_records())?;
 
@@ -646,11 +874,13 @@ where
                     }),
                 );
                 has_changes |= changes.has_changes();
+                let ingested_count = (changes.new.len() + changes.updated.len()) as u64;
                 metrics.measure_ingest(record_type.to_string(), |context| {
                     write_scope.write(|dao| {
                         self.process_changes(dao, collection, changes, &constraints, context)
                     })
                 })?;
+                metrics.record_ingested(record_type.to_string(), ingested_count);
                 write_scope.err_if_interrupted()?;
             }
         }
@@ -995,8 +1225,13 @@ pub(crate) mod tests {
     use std::sync::atomic::{AtomicUsize, Ordering};
 
     use crate::{
-        db::DEFAULT_SUGGESTION_SCORE, provider::AmpMatchingStrategy, suggestion::FtsMatchInfo,
-        testing::*, SuggestionProvider,
+        db::DEFAULT_SUGGESTION_SCORE,
+        frequency_cap::{SuggestFrequencyCap, HOUR_SECONDS},
+        metrics::IngestedRecordCount,
+        provider::AmpMatchingStrategy,
+        suggestion::FtsMatchInfo,
+        testing::*,
+        SuggestionExplanation, SuggestionMatchType, SuggestionProvider,
     };
 
     // Extra methods for the tests
@@ -1182,6 +1417,82 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    /// Tests ingesting from a local snapshot directory.
+    #[test]
+    fn ingest_snapshot() -> anyhow::Result<()> {
+        before_each();
+
+        let tempdir = tempfile::tempdir()?;
+        let dir = tempdir.path();
+        std::fs::create_dir_all(dir.join("attachments"))?;
+        std::fs::write(
+            dir.join("quicksuggest-amp.json"),
+            json!([
+                {
+                    "id": "data-1",
+                    "type": "amp",
+                    "last_modified": 15,
+                    "attachment": {
+                        "filename": "data-1.json",
+                        "mimetype": "application/json",
+                        "location": "data-1.json",
+                        "hash": "",
+                        "size": 0,
+                    },
+                },
+                {
+                    "id": "icon-los-pollos-favicon",
+                    "type": "icon",
+                    "last_modified": 15,
+                    "attachment": {
+                        "filename": "los-pollos-favicon.png",
+                        "mimetype": "image/png",
+                        "location": "los-pollos-favicon.png",
+                        "hash": "",
+                        "size": 0,
+                    },
+                },
+            ])
+            .to_string(),
+        )?;
+        std::fs::write(
+            dir.join("attachments").join("data-1.json"),
+            json!([los_pollos_amp()]).to_string(),
+        )?;
+        std::fs::write(
+            dir.join("attachments").join("los-pollos-favicon.png"),
+            "los-pollos-icon-data",
+        )?;
+
+        let store = SuggestStoreInner::new(
+            "file:ingest_snapshot?mode=memory&cache=shared",
+            vec![],
+            SuggestSnapshotClient::new(dir),
+        );
+        let metrics = store.ingest(SuggestIngestionConstraints::amp_without_fts())?;
+
+        let mut record_counts = metrics.record_counts;
+        record_counts.sort_by(|a, b| a.record_type.cmp(&b.record_type));
+        assert_eq!(
+            record_counts,
+            vec![
+                IngestedRecordCount {
+                    record_type: "amp".into(),
+                    count: 1,
+                },
+                IngestedRecordCount {
+                    record_type: "icon".into(),
+                    count: 1,
+                },
+            ]
+        );
+        assert_eq!(
+            store.query(SuggestionQuery::amp("lo"))?.suggestions,
+            vec![los_pollos_suggestion("los pollos", None)],
+        );
+        Ok(())
+    }
+
     #[test]
     fn ingest_amp_full_keywords() -> anyhow::Result<()> {
         before_each();
@@ -1419,6 +1730,7 @@ pub(crate) mod tests {
                 Some(FtsMatchInfo {
                     prefix: false,
                     stemming: false,
+                    typo: false,
                 })
             )],
         );
@@ -1450,6 +1762,7 @@ pub(crate) mod tests {
                 Some(FtsMatchInfo {
                     prefix: false,
                     stemming: false,
+                    typo: false,
                 })
             )],
         );
@@ -1559,6 +1872,7 @@ pub(crate) mod tests {
                 Some(FtsMatchInfo {
                     prefix: false,
                     stemming: false,
+                    typo: false,
                 }),
             )],
         );
@@ -2485,6 +2799,82 @@ pub(crate) mod tests {
         Ok(())
     }
 
+    #[test]
+    fn query_with_typos() -> anyhow::Result<()> {
+        before_each();
+
+        let store = TestStore::new(
+            MockRemoteSettingsClient::default()
+                .with_record(SuggestionProvider::Amp.record("data-1", json!([los_pollos_amp()])))
+                .with_record(SuggestionProvider::Amp.icon(los_pollos_icon()))
+                .with_record(
+                    SuggestionProvider::Wikipedia.record("wikipedia-1", json!([california_wiki()])),
+                )
+                .with_record(SuggestionProvider::Wikipedia.icon(california_icon()))
+                .with_record(SuggestionProvider::Mdn.record("mdn-1", json!([array_mdn()])))
+                .with_record(SuggestionProvider::Yelp.record("yelp-1", json!([ramen_yelp()])))
+                .with_record(SuggestionProvider::Yelp.icon(yelp_favicon())),
+        );
+        store.ingest(SuggestIngestionConstraints::all_providers());
+
+        // Typos are only corrected if the query allows it.
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("lso pollos")),
+            vec![]
+        );
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("lso pollos").allow_typos()),
+            vec![los_pollos_suggestion(
+                "los pollos",
+                Some(FtsMatchInfo {
+                    prefix: false,
+                    stemming: false,
+                    typo: true,
+                })
+            )]
+        );
+        // Exact matches don't need correcting.
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("los pollos").allow_typos()),
+            vec![los_pollos_suggestion("los pollos", None)]
+        );
+        // Short keywords aren't corrected.
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("lso").allow_typos()),
+            vec![]
+        );
+        // Nor are keywords more than one typo away.
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("lsopollos").allow_typos()),
+            vec![]
+        );
+
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::wikipedia("califronia").allow_typos()),
+            vec![california_suggestion("california").with_typo_correction()]
+        );
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::mdn("arary").allow_typos()),
+            vec![array_suggestion().with_typo_correction()]
+        );
+        // The corrected MDN keyword can still be a prefix.
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::mdn("arary java").allow_typos()),
+            vec![array_suggestion().with_typo_correction()]
+        );
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::yelp("raemn in tokyo").allow_typos()),
+            vec![ramen_suggestion(
+                "ramen in tokyo",
+                "https://www.yelp.com/search?find_desc=ramen&find_loc=tokyo"
+            )
+            .subject_exact_match(false)
+            .with_typo_correction()],
+        );
+
+        Ok(())
+    }
+
     #[test]
     fn query_no_yelp_icon_data() -> anyhow::Result<()> {
         before_each();
@@ -2522,6 +2912,7 @@ pub(crate) mod tests {
             store.fetch_global_config(),
             SuggestGlobalConfig {
                 show_less_frequently_cap: 3,
+                frequency_caps: vec![],
             }
         );
 
@@ -2538,12 +2929,400 @@ pub(crate) mod tests {
             store.fetch_global_config(),
             SuggestGlobalConfig {
                 show_less_frequently_cap: 0,
+                frequency_caps: vec![],
             }
         );
 
         Ok(())
     }
 
+    /// Tests ingesting and querying with a keyword normalization, and
+    /// re-ingesting when it changes.
+    #[test]
+    fn keyword_normalization() -> anyhow::Result<()> {
+        before_each();
+
+        let mut store = TestStore::new(MockRemoteSettingsClient::default().with_record(
+            SuggestionProvider::Wikipedia.record(
+                "data-1",
+                json!([{
+                    "keywords": ["münchen", "münchner"],
+                    "title": "München",
+                    "url": "https://wikipedia.org/München",
+                    "icon": "muenchen-favicon",
+                }]),
+            ),
+        ));
+        let titles = |store: &TestStore, keyword: &str| {
+            store
+                .fetch_suggestions(SuggestionQuery::wikipedia(keyword))
+                .iter()
+                .map(|s| s.title().to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        store.ingest(SuggestIngestionConstraints::all_providers());
+        assert_eq!(titles(&store, "München"), vec!["München"]);
+        assert_eq!(titles(&store, "munchen"), Vec::<String>::new());
+
+        // Changing the normalization should re-ingest the keywords normalized.
+        store.inner.keyword_normalizations =
+            HashMap::from([(SuggestionProvider::Wikipedia, KeywordNormalization::German)]);
+        store.ingest(SuggestIngestionConstraints::all_providers());
+        assert_eq!(
+            store.read(|dao| dao.get_keyword_normalization(SuggestionProvider::Wikipedia))?,
+            KeywordNormalization::German
+        );
+        for keyword in ["München", "MÜNCHEN", "munchen", "münchner"] {
+            assert_eq!(titles(&store, keyword), vec!["München"], "{keyword}");
+        }
+        // The full keyword is the original keyword, not the normalized one.
+        let suggestions = store.fetch_suggestions(SuggestionQuery::wikipedia("munchner"));
+        assert!(
+            matches!(
+                suggestions.as_slice(),
+                [Suggestion::Wikipedia { full_keyword, .. }] if full_keyword == "münchner"
+            ),
+            "{suggestions:?}"
+        );
+
+        // Ingesting other providers shouldn't change it, since the keywords
+        // aren't re-ingested.
+        store.inner.keyword_normalizations = HashMap::new();
+        store.ingest(SuggestIngestionConstraints {
+            providers: Some(vec![SuggestionProvider::Amp]),
+            ..SuggestIngestionConstraints::default()
+        });
+        assert_eq!(
+            store.read(|dao| dao.get_keyword_normalization(SuggestionProvider::Wikipedia))?,
+            KeywordNormalization::German
+        );
+        assert_eq!(titles(&store, "munchen"), vec!["München"]);
+
+        // Changing it back should work the same way.
+        store.ingest(SuggestIngestionConstraints::all_providers());
+        assert_eq!(titles(&store, "München"), vec!["München"]);
+        assert_eq!(titles(&store, "munchen"), Vec::<String>::new());
+
+        Ok(())
+    }
+
+    /// Returns a global config record with frequency caps.
+    fn frequency_caps_record(caps: serde_json::Value) -> MockRecord {
+        MockRecord {
+            collection: Collection::Other,
+            record_type: SuggestRecordType::GlobalConfig,
+            id: "configuration-1".to_string(),
+            inline_data: Some(json!({
+                "configuration": {
+                    "show_less_frequently_cap": 3,
+                    "frequency_caps": caps,
+                },
+            })),
+            attachment: None,
+        }
+    }
+
+    #[test]
+    fn fetch_global_config_frequency_caps() -> anyhow::Result<()> {
+        before_each();
+
+        let store = TestStore::new(MockRemoteSettingsClient::default().with_record(
+            frequency_caps_record(json!([
+                {
+                    "provider": "amp",
+                    "max_impressions_per_day": 10,
+                    "dismissal_cooldown_seconds": 3600,
+                },
+                // Caps for unknown providers should be skipped.
+                { "provider": "not-a-provider", "max_impressions_per_day": 1 },
+            ])),
+        ));
+        store.ingest(SuggestIngestionConstraints::all_providers());
+        assert_eq!(
+            store.fetch_global_config(),
+            SuggestGlobalConfig {
+                show_less_frequently_cap: 3,
+                frequency_caps: vec![SuggestFrequencyCap {
+                    provider: SuggestionProvider::Amp,
+                    max_impressions_per_hour: None,
+                    max_impressions_per_day: Some(10),
+                    max_clicks_per_day: None,
+                    dismissal_cooldown_seconds: Some(3600),
+                }],
+            }
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn impression_and_click_caps() -> anyhow::Result<()> {
+        before_each();
+
+        let store = TestStore::new(
+            MockRemoteSettingsClient::default()
+                .with_record(frequency_caps_record(json!([{
+                    "provider": "amp",
+                    "max_impressions_per_hour": 2,
+                    "max_clicks_per_day": 1,
+                }])))
+                .with_record(SuggestionProvider::Amp.record("1234", json![los_pollos_amp()]))
+                .with_record(SuggestionProvider::Amp.icon(los_pollos_icon())),
+        );
+        store.ingest(SuggestIngestionConstraints::all_providers());
+        let suggestion = los_pollos_suggestion("los pollos", None);
+        let now = now_seconds();
+
+        // Impressions from more than an hour ago don't count toward the hourly
+        // cap.
+        for _ in 0..2 {
+            store.inner.record_interaction(
+                &suggestion,
+                InteractionKind::Impression,
+                now - HOUR_SECONDS - 1,
+            )?;
+        }
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("lo")),
+            vec![suggestion.clone()],
+        );
+
+        store
+            .inner
+            .record_interaction(&suggestion, InteractionKind::Impression, now)?;
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("lo")),
+            vec![suggestion.clone()],
+        );
+        store
+            .inner
+            .record_interaction(&suggestion, InteractionKind::Impression, now)?;
+        assert_eq!(
+            store.inner.fetch_interaction_counts(&suggestion, now)?,
+            SuggestInteractionCounts {
+                impressions_last_hour: 2,
+                impressions_last_day: 4,
+                clicks_last_day: 0,
+            }
+        );
+        assert_eq!(store.fetch_suggestions(SuggestionQuery::amp("lo")), vec![]);
+
+        // Resetting the counts should show the suggestion again, until it's
+        // clicked.
+        store.inner.clear_interactions()?;
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("lo")),
+            vec![suggestion.clone()],
+        );
+        store
+            .inner
+            .record_interaction(&suggestion, InteractionKind::Click, now)?;
+        assert_eq!(store.fetch_suggestions(SuggestionQuery::amp("lo")), vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn dismissal_cooldown() -> anyhow::Result<()> {
+        before_each();
+
+        let store = TestStore::new(
+            MockRemoteSettingsClient::default()
+                .with_record(frequency_caps_record(json!([{
+                    "provider": "amp",
+                    "dismissal_cooldown_seconds": 3600,
+                }])))
+                .with_record(
+                    SuggestionProvider::Amp
+                        .record("1234", json!([los_pollos_amp(), good_place_eats_amp()])),
+                )
+                .with_record(SuggestionProvider::Amp.icon(los_pollos_icon()))
+                .with_record(SuggestionProvider::Amp.icon(good_place_eats_icon())),
+        );
+        store.ingest(SuggestIngestionConstraints::all_providers());
+
+        // Dismissing one AMP suggestion should hide the others during the
+        // cooldown.
+        store
+            .inner
+            .dismiss_by_suggestion(&los_pollos_suggestion("los pollos", None))?;
+        assert_eq!(store.fetch_suggestions(SuggestionQuery::amp("la")), vec![]);
+
+        // After the cooldown, they should be shown again.
+        store.inner.record_interaction(
+            &los_pollos_suggestion("los pollos", None),
+            InteractionKind::Dismissal,
+            now_seconds() - 3600,
+        )?;
+        assert_eq!(
+            store.fetch_suggestions(SuggestionQuery::amp("la")),
+            vec![good_place_eats_suggestion("lasagna", None)],
+        );
+        assert_eq!(store.fetch_suggestions(SuggestionQuery::amp("lo")), vec![]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_explain() -> anyhow::Result<()> {
+        before_each();
+
+        let store = TestStore::new(
+            MockRemoteSettingsClient::default()
+                .with_record(SuggestionProvider::Amp.record("data-1", json!([los_pollos_amp()])))
+                .with_record(SuggestionProvider::Amp.icon(los_pollos_icon()))
+                .with_record(
+                    SuggestionProvider::Wikipedia
+                        .record("wikipedia-1", json!([california_wiki(), caltech_wiki()])),
+                )
+                .with_record(SuggestionProvider::Wikipedia.icon(california_icon()))
+                .with_record(SuggestionProvider::Wikipedia.icon(caltech_icon()))
+                .with_record(SuggestionProvider::Mdn.record("mdn-1", json!([array_mdn()])))
+                .with_record(SuggestionProvider::Dynamic.full_record(
+                    "dynamic-0",
+                    Some(json!({
+                        "suggestion_type": "aaa",
+                    })),
+                    Some(MockAttachment::Json(json!({
+                        "keywords": ["array"],
+                    }))),
+                )),
+        );
+        store.ingest(SuggestIngestionConstraints {
+            provider_constraints: Some(SuggestionProviderConstraints {
+                dynamic_suggestion_types: Some(vec!["aaa".to_string()]),
+                ..SuggestionProviderConstraints::default()
+            }),
+            ..SuggestIngestionConstraints::all_providers()
+        });
+
+        // A keyword that's a prefix of the full keyword, and the full keyword
+        // itself.
+        let result = store.inner.query_explain(SuggestionQuery::amp("lo"))?;
+        assert_eq!(
+            result.explanations,
+            vec![SuggestionExplanation {
+                suggestion: los_pollos_suggestion("los pollos", None),
+                provider: SuggestionProvider::Amp,
+                matched_keyword: Some("lo".into()),
+                match_type: SuggestionMatchType::Prefix,
+                score: 0.3,
+                keyword_rank: Some(0),
+            }],
+        );
+        assert_eq!(result.filtered, vec![]);
+        let result = store
+            .inner
+            .query_explain(SuggestionQuery::amp("los pollos"))?;
+        assert_eq!(
+            result.explanations,
+            vec![SuggestionExplanation {
+                suggestion: los_pollos_suggestion("los pollos", None),
+                provider: SuggestionProvider::Amp,
+                matched_keyword: Some("los pollos".into()),
+                match_type: SuggestionMatchType::Exact,
+                score: 0.3,
+                keyword_rank: Some(3),
+            }],
+        );
+
+        // A typo correction.
+        let typo_suggestion = los_pollos_suggestion(
+            "los pollos",
+            Some(FtsMatchInfo {
+                prefix: false,
+                stemming: false,
+                typo: true,
+            }),
+        );
+        let result = store
+            .inner
+            .query_explain(SuggestionQuery::amp("lso pollos").allow_typos())?;
+        assert_eq!(
+            result.explanations,
+            vec![SuggestionExplanation {
+                suggestion: typo_suggestion,
+                provider: SuggestionProvider::Amp,
+                matched_keyword: Some("los pollos".into()),
+                match_type: SuggestionMatchType::Typo,
+                score: 0.3,
+                keyword_rank: Some(3),
+            }],
+        );
+
+        // A prefix keyword match, and a dynamic suggestion that the provider
+        // constraints leave out.
+        let result = store.inner.query_explain(SuggestionQuery::with_providers(
+            "array",
+            vec![SuggestionProvider::Mdn, SuggestionProvider::Dynamic],
+        ))?;
+        assert_eq!(
+            result.explanations,
+            vec![SuggestionExplanation {
+                suggestion: array_suggestion(),
+                provider: SuggestionProvider::Mdn,
+                matched_keyword: Some("array javascript".into()),
+                match_type: SuggestionMatchType::Prefix,
+                score: 0.24,
+                keyword_rank: Some(0),
+            }],
+        );
+        assert_eq!(
+            result.filtered,
+            vec![FilteredSuggestion {
+                suggestion: Suggestion::Dynamic {
+                    suggestion_type: "aaa".into(),
+                    data: None,
+                    dismissal_key: None,
+                    score: DEFAULT_SUGGESTION_SCORE,
+                },
+                reason: SuggestionFilterReason::ProviderConstraints,
+            }],
+        );
+
+        // A suggestion cut by the limit.
+        let result = store
+            .inner
+            .query_explain(SuggestionQuery::wikipedia("cal").limit(1))?;
+        assert_eq!(
+            result.explanations,
+            vec![SuggestionExplanation {
+                suggestion: california_suggestion("california"),
+                provider: SuggestionProvider::Wikipedia,
+                matched_keyword: Some("cal".into()),
+                match_type: SuggestionMatchType::Prefix,
+                score: DEFAULT_SUGGESTION_SCORE,
+                keyword_rank: Some(0),
+            }],
+        );
+        assert_eq!(
+            result.filtered,
+            vec![FilteredSuggestion {
+                suggestion: caltech_suggestion("california"),
+                reason: SuggestionFilterReason::Limit,
+            }],
+        );
+
+        // A dismissed suggestion. Explaining the query shouldn't undo the
+        // dismissal.
+        store
+            .inner
+            .dismiss_by_suggestion(&los_pollos_suggestion("los pollos", None))?;
+        let result = store.inner.query_explain(SuggestionQuery::amp("lo"))?;
+        assert_eq!(result.explanations, vec![]);
+        assert_eq!(
+            result.filtered,
+            vec![FilteredSuggestion {
+                suggestion: los_pollos_suggestion("los pollos", None),
+                reason: SuggestionFilterReason::Dismissed,
+            }],
+        );
+        assert_eq!(store.fetch_suggestions(SuggestionQuery::amp("lo")), vec![]);
+
+        Ok(())
+    }
+
     #[test]
     fn fetch_provider_config_none() -> anyhow::Result<()> {
         before_each();
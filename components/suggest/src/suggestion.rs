@@ -5,7 +5,9 @@
 
 use chrono::Local;
 
-use crate::{db::DEFAULT_SUGGESTION_SCORE, geoname::Geoname, JsonValue};
+use crate::{
+    db::DEFAULT_SUGGESTION_SCORE, geoname::Geoname, provider::SuggestionProvider, JsonValue,
+};
 
 /// The template parameter for a timestamp in a "raw" sponsored suggestion URL.
 const TIMESTAMP_TEMPLATE: &str = "%YYYYMMDDHH%";
@@ -52,6 +54,7 @@ pub enum Suggestion {
         icon: Option<Vec<u8>>,
         icon_mimetype: Option<String>,
         full_keyword: String,
+        fts_match_info: Option<FtsMatchInfo>,
     },
     Amo {
         title: String,
@@ -73,12 +76,14 @@ pub enum Suggestion {
         subject_exact_match: bool,
         subject_type: YelpSubjectType,
         location_param: String,
+        fts_match_info: Option<FtsMatchInfo>,
     },
     Mdn {
         title: String,
         url: String,
         description: String,
         score: f64,
+        fts_match_info: Option<FtsMatchInfo>,
     },
     Weather {
         city: Option<Geoname>,
@@ -96,13 +101,16 @@ pub enum Suggestion {
     },
 }
 
-/// Additional data about how an FTS match was made
+/// Additional data about how an FTS or typo-corrected match was made
 #[derive(Debug, Clone, PartialEq, uniffi::Record)]
 pub struct FtsMatchInfo {
     /// Was this a prefix match (`water b` matched against `water bottle`)
     pub prefix: bool,
     /// Did the match require stemming? (`run shoes` matched against `running shoes`)
     pub stemming: bool,
+    /// Was a typo in the keyword corrected? (`lso pollos` matched against `los pollos`)
+    #[uniffi(default = false)]
+    pub typo: bool,
 }
 
 impl PartialOrd for Suggestion {
@@ -142,6 +150,30 @@ impl Suggestion {
         }
     }
 
+    /// Get the provider that returned this suggestion.
+    pub(crate) fn provider(&self) -> SuggestionProvider {
+        match self {
+            Self::Amp { .. } => SuggestionProvider::Amp,
+            Self::Wikipedia { .. } => SuggestionProvider::Wikipedia,
+            Self::Amo { .. } => SuggestionProvider::Amo,
+            Self::Yelp { .. } => SuggestionProvider::Yelp,
+            Self::Mdn { .. } => SuggestionProvider::Mdn,
+            Self::Weather { .. } => SuggestionProvider::Weather,
+            Self::Dynamic { .. } => SuggestionProvider::Dynamic,
+        }
+    }
+
+    /// Get the key that impressions and clicks on this suggestion are counted
+    /// under for frequency capping. This is the block ID for AMP suggestions,
+    /// so that all keywords for the same sponsored suggestion share one count,
+    /// and the dismissal key for everything else.
+    pub(crate) fn frequency_cap_key(&self) -> Option<String> {
+        match self {
+            Self::Amp { block_id, .. } => Some(block_id.to_string()),
+            _ => self.dismissal_key().map(str::to_owned),
+        }
+    }
+
     /// Get the URL for this suggestion, if present
     pub fn url(&self) -> Option<&str> {
         match self {
@@ -203,7 +235,31 @@ impl Suggestion {
     }
 
     pub fn fts_match_info(&self) -> Option<&FtsMatchInfo> {
-        None
+        match self {
+            Self::Amp { fts_match_info, .. }
+            | Self::Wikipedia { fts_match_info, .. }
+            | Self::Yelp { fts_match_info, .. }
+            | Self::Mdn { fts_match_info, .. } => fts_match_info.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Marks the suggestion as matched by correcting a typo in the keyword.
+    pub(crate) fn with_typo_correction(mut self) -> Self {
+        if let Self::Amp { fts_match_info, .. }
+        | Self::Wikipedia { fts_match_info, .. }
+        | Self::Yelp { fts_match_info, .. }
+        | Self::Mdn { fts_match_info, .. } = &mut self
+        {
+            fts_match_info
+                .get_or_insert(FtsMatchInfo {
+                    prefix: false,
+                    stemming: false,
+                    typo: false,
+                })
+                .typo = true;
+        }
+        self
     }
 }
 
@@ -129,6 +129,7 @@ pub fn california_suggestion(full_keyword: &str) -> Suggestion {
         icon: Some("california-icon-data".as_bytes().to_vec()),
         icon_mimetype: Some("image/png".into()),
         full_keyword: full_keyword.into(),
+        fts_match_info: None,
     }
 }
 
@@ -159,6 +160,7 @@ pub fn caltech_suggestion(full_keyword: &str) -> Suggestion {
         icon: Some("caltech-icon-data".as_bytes().to_vec()),
         icon_mimetype: Some("image/png".into()),
         full_keyword: full_keyword.into(),
+        fts_match_info: None,
     }
 }
 
@@ -303,6 +305,7 @@ pub fn ramen_suggestion(title: &str, url: &str) -> Suggestion {
         subject_exact_match: true,
         subject_type: YelpSubjectType::Service,
         location_param: "find_loc".into(),
+        fts_match_info: None,
     }
 }
 
@@ -332,6 +335,7 @@ pub fn array_suggestion() -> Suggestion {
                 .into(),
         description: "Javascript Array".into(),
         score: 0.24,
+        fts_match_info: None,
     }
 }
 
@@ -389,5 +393,6 @@ pub fn multimatch_wiki_suggestion() -> Suggestion {
         icon: Some("multimatch-wiki-icon-data".as_bytes().to_vec()),
         icon_mimetype: Some("image/png".into()),
         full_keyword: "multimatch".into(),
+        fts_match_info: None,
     }
 }
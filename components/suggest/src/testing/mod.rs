@@ -80,6 +80,7 @@ impl Suggestion {
                 subject_exact_match,
                 subject_type,
                 location_param,
+                fts_match_info,
                 ..
             } => Self::Yelp {
                 title,
@@ -91,6 +92,7 @@ impl Suggestion {
                 subject_type,
                 location_param,
                 has_location_sign,
+                fts_match_info,
             },
             _ => panic!("has_location_sign only valid for yelp suggestions"),
         }
@@ -107,6 +109,7 @@ impl Suggestion {
                 subject_type,
                 has_location_sign,
                 location_param,
+                fts_match_info,
                 ..
             } => Self::Yelp {
                 title,
@@ -118,6 +121,7 @@ impl Suggestion {
                 subject_type,
                 location_param,
                 has_location_sign,
+                fts_match_info,
             },
             _ => panic!("subject_exact_match only valid for yelp suggestions"),
         }
@@ -134,6 +138,7 @@ impl Suggestion {
                 subject_exact_match,
                 has_location_sign,
                 location_param,
+                fts_match_info,
                 ..
             } => Self::Yelp {
                 title,
@@ -145,6 +150,7 @@ impl Suggestion {
                 subject_type,
                 location_param,
                 has_location_sign,
+                fts_match_info,
             },
             _ => panic!("subject_type only valid for yelp suggestions"),
         }
@@ -4,15 +4,17 @@
  */
 
 use icu_casemap::CaseMapperBorrowed;
-use icu_normalizer::DecomposingNormalizerBorrowed;
+use icu_normalizer::{ComposingNormalizerBorrowed, DecomposingNormalizerBorrowed};
 use icu_properties::props::GeneralCategory;
 use icu_properties::props::GeneralCategoryGroup;
 use icu_properties::CodePointMapDataBorrowed;
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
 
 use crate::Result;
 
 static NFKD: DecomposingNormalizerBorrowed = DecomposingNormalizerBorrowed::new_nfkd();
+static NFC: ComposingNormalizerBorrowed = ComposingNormalizerBorrowed::new_nfc();
 static GENERAL_CATEGORY: CodePointMapDataBorrowed<'static, GeneralCategory> =
     icu_properties::CodePointMapData::<GeneralCategory>::new();
 static CASE_MAPPER: CaseMapperBorrowed = CaseMapperBorrowed::new();
@@ -332,6 +334,113 @@ pub fn i18n_transform(s: &str) -> Cow<'_, str> {
     }
 }
 
+/// How keywords are normalized, both when they're ingested and when a query is
+/// matched against them, so that a query matches the keywords it would have
+/// been ingested as.
+///
+/// Set per provider with `SuggestStoreBuilder::keyword_normalization()`. Only
+/// AMP, Wikipedia, AMO and MDN keywords are normalized; Yelp and weather
+/// keywords are already matched with `i18n_collate`. Normalizations other than
+/// `Lowercase` are also applied to the stored keywords, so Wikipedia
+/// suggestions' `full_keyword` is the normalized keyword.
+#[derive(
+    Clone, Copy, Debug, Default, Eq, PartialEq, Hash, Deserialize, Serialize, uniffi::Enum,
+)]
+pub enum KeywordNormalization {
+    /// Lowercases queries. Keywords are stored as they are. This is the
+    /// default, and how keywords were always matched.
+    #[default]
+    Lowercase,
+    /// Locale-independent Unicode normalization: case folding, removing
+    /// diacritics, and folding full-width and half-width forms.
+    Unicode,
+    /// `Unicode`, plus light German stemming, so inflected forms like
+    /// "schuhe" match "schuh".
+    German,
+    /// `Unicode`, plus light French stemming, so plural and feminine forms
+    /// like "voitures" match "voiture".
+    French,
+    /// `Unicode`, plus folding katakana into hiragana, so a query typed in
+    /// either script matches keywords in the other.
+    Japanese,
+}
+
+/// Stems shorter than this are left unstemmed, so that short words aren't
+/// reduced to meaningless fragments.
+const MIN_STEM_LENGTH: usize = 3;
+
+/// German suffixes removed by stemming, longest first.
+const GERMAN_SUFFIXES: &[&str] = &["ern", "em", "en", "er", "es", "e", "s"];
+
+/// French suffixes removed by stemming, longest first. "aux" and "al" both
+/// appear so that plurals like "chevaux" stem the same as "cheval".
+const FRENCH_SUFFIXES: &[&str] = &["aux", "es", "al", "e", "s", "x"];
+
+impl KeywordNormalization {
+    /// Normalizes a keyword or query.
+    pub fn normalize<'a>(&self, keyword: &'a str) -> Cow<'a, str> {
+        match self {
+            Self::Lowercase => {
+                if keyword.chars().any(char::is_uppercase) {
+                    Cow::from(keyword.to_lowercase())
+                } else {
+                    Cow::from(keyword)
+                }
+            }
+            Self::Unicode => Cow::from(unicode_fold(keyword)),
+            Self::German => Cow::from(stem_words(&unicode_fold(keyword), GERMAN_SUFFIXES)),
+            Self::French => Cow::from(stem_words(&unicode_fold(keyword), FRENCH_SUFFIXES)),
+            Self::Japanese => Cow::from(
+                unicode_fold(keyword)
+                    .chars()
+                    .map(katakana_to_hiragana)
+                    .collect::<String>(),
+            ),
+        }
+    }
+}
+
+/// Case folds `s`, removes its diacritics, and folds full-width and half-width
+/// forms into their usual forms.
+fn unicode_fold(s: &str) -> String {
+    // NFKD folds compatibility forms, like full-width Latin letters and
+    // half-width katakana, and splits diacritics into combining marks. Keep the
+    // kana voicing marks, since "が" and "か" are different letters, and
+    // recompose them with NFC.
+    let without_marks = NFKD
+        .normalize_iter(s.chars())
+        .filter(|&c| !is_combining_mark(c) || is_kana_voicing_mark(c))
+        .collect::<String>();
+    NFC.normalize(&CASE_MAPPER.fold_string(&without_marks))
+        .into_owned()
+}
+
+fn is_kana_voicing_mark(c: char) -> bool {
+    matches!(c, '\u{3099}' | '\u{309A}')
+}
+
+fn katakana_to_hiragana(c: char) -> char {
+    match c {
+        // Katakana "ァ" through "ヶ" are offset from their hiragana by 0x60.
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+/// Removes the first matching suffix from each space-separated word in `s`.
+fn stem_words(s: &str, suffixes: &[&str]) -> String {
+    s.split(' ')
+        .map(|word| {
+            suffixes
+                .iter()
+                .filter_map(|suffix| word.strip_suffix(suffix))
+                .find(|stem| stem.chars().count() >= MIN_STEM_LENGTH)
+                .unwrap_or(word)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1795,4 +1904,59 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn keyword_normalization() {
+        let tests: &[(KeywordNormalization, &[(&str, &str)])] = &[
+            (
+                KeywordNormalization::Lowercase,
+                &[("Los Pollos", "los pollos"), ("québec", "québec")],
+            ),
+            (
+                KeywordNormalization::Unicode,
+                &[
+                    ("Québec", "quebec"),
+                    ("STRAẞE", "strasse"),
+                    ("Ｆｉｒｅｆｏｘ", "firefox"),
+                    ("ｶﾞｲﾄﾞ", "ガイド"),
+                ],
+            ),
+            (
+                KeywordNormalization::German,
+                &[
+                    ("Schuhe", "schuh"),
+                    ("Häuser kaufen", "haus kauf"),
+                    ("Bahn", "bahn"),
+                    ("die", "die"),
+                ],
+            ),
+            (
+                KeywordNormalization::French,
+                &[
+                    ("Voitures", "voitur"),
+                    ("voiture", "voitur"),
+                    ("chevaux", "chev"),
+                    ("cheval", "chev"),
+                    ("Élève", "elev"),
+                ],
+            ),
+            (
+                KeywordNormalization::Japanese,
+                &[
+                    ("ガイド", "がいど"),
+                    ("ｶﾞｲﾄﾞ", "がいど"),
+                    ("がいど", "がいど"),
+                ],
+            ),
+        ];
+        for (normalization, cases) in tests {
+            for (input, expected) in *cases {
+                assert_eq!(
+                    normalization.normalize(input),
+                    *expected,
+                    "{normalization:?}: {input}"
+                );
+            }
+        }
+    }
 }
@@ -205,8 +205,17 @@ impl SuggestDao<'_> {
             query_words = rest;
         }
 
-        let Some(subject_data) = self.find_subject(query_words)? else {
-            return Ok(vec![]);
+        let mut subject_typo = false;
+        let subject_data = match self.find_subject(query_words)? {
+            Some(subject_data) => subject_data,
+            None if query.allow_typos => {
+                let Some(subject_data) = self.find_subject_with_typo(query_words)? else {
+                    return Ok(vec![]);
+                };
+                subject_typo = true;
+                subject_data
+            }
+            None => return Ok(vec![]),
         };
         query_words = subject_data.rest;
 
@@ -239,6 +248,7 @@ impl SuggestDao<'_> {
             subject: &subject_data.subject,
             subject_exact_match: subject_data.exact_match,
             subject_type: subject_data.subject_type,
+            subject_typo,
             pre_modifier: pre_modifier_tuple.map(|(words, _)| words.to_string()),
             post_modifier: post_modifier_tuple.map(|(words, _)| words.to_string()),
             location_sign: location_sign_tuple.map(|(words, _)| words.to_string()),
@@ -382,6 +392,39 @@ impl SuggestDao<'_> {
         Ok(None)
     }
 
+    /// Find a subject for the given query by correcting a typo in it. Like
+    /// `find_subject()`, the subject is taken from the start of the query,
+    /// but it must be complete. Each prefix gets its own lookup budget, so
+    /// the longer ones can't use it up before the subject alone is tried.
+    /// Only prefixes short enough to have typo corrections are looked up.
+    fn find_subject_with_typo<'a>(
+        &self,
+        query_words: &'a [&'a str],
+    ) -> Result<Option<FindSubjectData<'a>>> {
+        for n in (1..=query_words.len()).rev() {
+            let (candidate_chunk, rest) = query_words.split_at(n);
+            if let Some((subject, subject_type)) =
+                self.find_typo_correction(&candidate_chunk.join(" "), |candidate| {
+                    Ok(self.conn.try_query_one::<YelpSubjectType, _>(
+                        "SELECT subject_type FROM yelp_subjects WHERE keyword = :keyword",
+                        named_params! {
+                            ":keyword": candidate,
+                        },
+                        true,
+                    )?)
+                })?
+            {
+                return Ok(Some(FindSubjectData {
+                    subject,
+                    exact_match: false,
+                    subject_type,
+                    rest,
+                }));
+            }
+        }
+        Ok(None)
+    }
+
     /// Fetch the custom details for Yelp suggestions.
     /// It returns the location tuple as follows:
     /// (
@@ -427,6 +470,7 @@ struct SuggestionBuilder<'a> {
     subject: &'a str,
     subject_exact_match: bool,
     subject_type: YelpSubjectType,
+    subject_typo: bool,
     pre_modifier: Option<String>,
     post_modifier: Option<String>,
     location_sign: Option<String>,
@@ -471,7 +515,7 @@ impl<'a> From<SuggestionBuilder<'a>> for Suggestion {
         .collect::<Vec<_>>()
         .join(" ");
 
-        Suggestion::Yelp {
+        let suggestion = Suggestion::Yelp {
             url,
             title,
             icon: builder.icon,
@@ -481,6 +525,12 @@ impl<'a> From<SuggestionBuilder<'a>> for Suggestion {
             subject_exact_match: builder.subject_exact_match,
             subject_type: builder.subject_type,
             location_param: "find_loc".to_string(),
+            fts_match_info: None,
+        };
+        if builder.subject_typo {
+            suggestion.with_typo_correction()
+        } else {
+            suggestion
         }
     }
 }
@@ -202,6 +202,8 @@ fn main() -> Result<()> {
         server: None,
         bucket_name: None,
         app_context: None,
+        dumps_directory: None,
+        attachment_cache_max_bytes: None,
     };
 
     let remote_settings_services = RemoteSettingsService::new("nimbus".to_owned(), config);
@@ -147,6 +147,12 @@ fn run_ios_import_history(conn: &PlacesDb, filename: String) -> Result<()> {
     Ok(())
 }
 
+fn run_chromium_import_history(conn: &PlacesDb, filename: String) -> Result<()> {
+    let res = places::import::import_chromium_history(conn, filename)?;
+    println!("Import finished!, results: {:?}", res);
+    Ok(())
+}
+
 fn run_native_import(db: &PlacesDb, filename: String) -> Result<()> {
     println!("import from {}", filename);
 
@@ -409,6 +415,13 @@ enum Command {
         input_file: String,
     },
 
+    /// Import history from a Chrome/Chromium `History` database
+    ImportChromiumHistory {
+        #[arg(name = "input-file", long, short = 'i')]
+        /// The name of the file to read
+        input_file: String,
+    },
+
     /// Import bookmarks from JSON file exported by desktop Firefox
     ImportDesktopBookmarks {
         #[arg(name = "input-file", long, short = 'i')]
@@ -478,6 +491,9 @@ fn main() -> Result<()> {
         Command::ImportBookmarks { input_file } => run_native_import(&db, input_file),
         Command::ImportDesktopBookmarks { input_file } => run_desktop_import(&db, input_file),
         Command::ImportIosHistory { input_file } => run_ios_import_history(&db, input_file),
+        Command::ImportChromiumHistory { input_file } => {
+            run_chromium_import_history(&db, input_file)
+        }
         Command::CreateFakeVisits {
             num_sites,
             num_visits,
@@ -116,6 +116,8 @@ fn build_service(cli: &Cli) -> Result<RemoteSettingsService> {
         }),
         bucket_name: cli.bucket.clone(),
         app_context: None,
+        dumps_directory: None,
+        attachment_cache_max_bytes: None,
     };
     cli_support::ensure_cli_data_dir_exists();
     let storage_dir = cli
@@ -152,6 +152,8 @@ fn build_remote_settings_service(cli: &Cli) -> Arc<RemoteSettingsService> {
         }),
         bucket_name: cli.remote_settings_bucket.clone(),
         app_context: None,
+        dumps_directory: None,
+        attachment_cache_max_bytes: None,
     };
     let storage_dir = cli_support::cli_data_subdir("remote-settings-data");
     Arc::new(RemoteSettingsService::new(storage_dir, config))
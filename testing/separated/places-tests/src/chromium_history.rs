@@ -0,0 +1,184 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use places::{
+    api::places_api::{ConnectionType, PlacesApi},
+    apply_observation,
+    storage::{fetch_page_info, history::get_visit_infos},
+    Result, VisitObservation, VisitTransitionSet, VisitType,
+};
+use rusqlite::Connection;
+use std::path::Path;
+use std::time::Duration;
+use tempfile::tempdir;
+use types::Timestamp;
+use url::Url;
+
+// Microseconds between 1601-01-01, Chromium's epoch, and 1970-01-01.
+const WEBKIT_EPOCH_OFFSET_US: i64 = 11_644_473_600_000_000;
+
+fn empty_chromium_db(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.execute_batch(include_str!("./chromium_schema.sql"))?;
+    Ok(conn)
+}
+
+fn add_url(conn: &Connection, id: i64, url: &str, title: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO urls(id, url, title, last_visit_time) VALUES (?, ?, ?, 0)",
+        rusqlite::params![id, url, title],
+    )?;
+    Ok(())
+}
+
+fn add_visit(conn: &Connection, url_id: i64, at: Timestamp, transition: i64) -> Result<()> {
+    conn.execute(
+        "INSERT INTO visits(url, visit_time, transition) VALUES (?, ?, ?)",
+        rusqlite::params![
+            url_id,
+            at.as_millis_i64() * 1000 + WEBKIT_EPOCH_OFFSET_US,
+            transition
+        ],
+    )?;
+    Ok(())
+}
+
+// We subtract a bit because our sanitization logic is smart and rejects
+// visits that have a future timestamp.
+fn a_month_ago() -> Timestamp {
+    Timestamp::now()
+        .checked_sub(Duration::from_secs(30 * 24 * 60 * 60))
+        .unwrap()
+}
+
+#[test]
+fn test_import_empty() -> Result<()> {
+    let tmpdir = tempdir().unwrap();
+    let chromium_path = tmpdir.path().join("History");
+    empty_chromium_db(&chromium_path)?;
+    let places_api = PlacesApi::new(tmpdir.path().join("places.sqlite"))?;
+    let conn = places_api.open_connection(ConnectionType::ReadWrite)?;
+    let results = places::import::import_chromium_history(&conn, &chromium_path)?;
+    assert_eq!(results.num_total, 0);
+    assert_eq!(results.num_succeeded, 0);
+    Ok(())
+}
+
+#[test]
+fn test_import_basic() -> Result<()> {
+    let tmpdir = tempdir().unwrap();
+    let chromium_path = tmpdir.path().join("History");
+    let chromium_db = empty_chromium_db(&chromium_path)?;
+    let first_visit_ts = a_month_ago();
+    let second_visit_ts = first_visit_ts
+        .checked_add(Duration::from_secs(100))
+        .unwrap();
+
+    add_url(&chromium_db, 1, "https://example.com/", "Example")?;
+    add_url(&chromium_db, 2, "https://www.mozilla.org/", "Mozilla")?;
+    add_url(&chromium_db, 3, "https://ads.example.com/frame", "")?;
+    add_url(&chromium_db, 4, "not a url", "Invalid")?;
+    // A typed visit, with the CHAIN_START and CHAIN_END qualifiers.
+    add_visit(&chromium_db, 1, first_visit_ts, 0x3000_0001)?;
+    // A link which was the target of a server redirect.
+    add_visit(&chromium_db, 2, second_visit_ts, 0x8000_0000)?;
+    // An AUTO_SUBFRAME visit, which isn't imported or counted.
+    add_visit(&chromium_db, 3, second_visit_ts, 3)?;
+    add_visit(&chromium_db, 4, second_visit_ts, 0)?;
+
+    let places_api = PlacesApi::new(tmpdir.path().join("places.sqlite"))?;
+    let conn = places_api.open_connection(ConnectionType::ReadWrite)?;
+    let results = places::import::import_chromium_history(&conn, &chromium_path)?;
+    assert_eq!(results.num_total, 3);
+    assert_eq!(results.num_succeeded, 2);
+    assert_eq!(results.num_failed, 1);
+
+    let visit_infos = get_visit_infos(
+        &conn,
+        Timestamp::EARLIEST,
+        Timestamp::now(),
+        VisitTransitionSet::empty(),
+    )?;
+    assert_eq!(visit_infos.len(), 2);
+    assert_eq!(visit_infos[0].url.as_str(), "https://example.com/");
+    assert_eq!(visit_infos[0].title, Some("Example".to_owned()));
+    assert_eq!(visit_infos[0].timestamp, first_visit_ts);
+    assert_eq!(visit_infos[0].visit_type, VisitType::Typed);
+    assert!(!visit_infos[0].is_remote);
+    assert_eq!(visit_infos[1].url.as_str(), "https://www.mozilla.org/");
+    assert_eq!(visit_infos[1].timestamp, second_visit_ts);
+    assert_eq!(visit_infos[1].visit_type, VisitType::RedirectTemporary);
+
+    // Frecencies are calculated for the new pages.
+    let page = fetch_page_info(&conn, &Url::parse("https://example.com/").unwrap())?
+        .expect("page should exist")
+        .page;
+    assert!(page.frecency > 0);
+    Ok(())
+}
+
+#[test]
+fn test_import_dedupes_and_keeps_titles() -> Result<()> {
+    let tmpdir = tempdir().unwrap();
+    let places_api = PlacesApi::new(tmpdir.path().join("places.sqlite"))?;
+    let mut conn = places_api.open_connection(ConnectionType::ReadWrite)?;
+    let visit_ts = a_month_ago();
+
+    // We already know about one of the visits, and both pages.
+    apply_observation(
+        &mut conn,
+        VisitObservation::new(Url::parse("https://example.com/").unwrap())
+            .with_visit_type(VisitType::Link)
+            .with_at(visit_ts),
+    )?;
+    apply_observation(
+        &mut conn,
+        VisitObservation::new(Url::parse("https://www.mozilla.org/").unwrap())
+            .with_title("Our Mozilla".to_string())
+            .with_visit_type(VisitType::Link),
+    )?;
+
+    let chromium_path = tmpdir.path().join("History");
+    let chromium_db = empty_chromium_db(&chromium_path)?;
+    add_url(&chromium_db, 1, "https://example.com/", "Example")?;
+    add_url(&chromium_db, 2, "https://www.mozilla.org/", "Their Mozilla")?;
+    add_visit(&chromium_db, 1, visit_ts, 0)?;
+    add_visit(&chromium_db, 2, visit_ts, 0)?;
+
+    let results = places::import::import_chromium_history(&conn, &chromium_path)?;
+    assert_eq!(results.num_total, 2);
+    assert_eq!(results.num_succeeded, 2);
+    assert_eq!(results.num_failed, 0);
+
+    let visit_infos = get_visit_infos(
+        &conn,
+        Timestamp::EARLIEST,
+        Timestamp::now(),
+        VisitTransitionSet::empty(),
+    )?;
+    // The visit to example.com wasn't duplicated.
+    assert_eq!(visit_infos.len(), 3);
+    for visit in visit_infos {
+        match visit.url.as_str() {
+            // A missing title is filled in...
+            "https://example.com/" => assert_eq!(visit.title, Some("Example".to_owned())),
+            // ...but an existing one is kept.
+            "https://www.mozilla.org/" => assert_eq!(visit.title, Some("Our Mozilla".to_owned())),
+            url => panic!("Unexpected visit: {}", url),
+        }
+    }
+
+    // Importing again doesn't add anything.
+    places::import::import_chromium_history(&conn, &chromium_path)?;
+    assert_eq!(
+        get_visit_infos(
+            &conn,
+            Timestamp::EARLIEST,
+            Timestamp::now(),
+            VisitTransitionSet::empty(),
+        )?
+        .len(),
+        3
+    );
+    Ok(())
+}
@@ -3,4 +3,5 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 mod check_coop_tx;
+mod chromium_history;
 mod ios_history;